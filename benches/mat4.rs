@@ -0,0 +1,76 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use math::{Mat4, Vec3, Vec4};
+
+// About what a frame of a large scene does
+const TRANSFORMS: usize = 10_000;
+
+fn transforms() -> Vec<(Vec3, Vec3, Vec3, Vec3)> {
+    (0..TRANSFORMS)
+        .map(|i| {
+            let f = i as f32;
+            (
+                Vec3::new(f * 0.1, 0.5, -f * 0.2),
+                Vec3::new(f, f * 0.5, -f),
+                Vec3::new(1. + f * 0.001, 1., 2.),
+                Vec3::new(f * 0.01, f * 0.02, f * 0.03),
+            )
+        })
+        .collect()
+}
+
+fn multiply(c: &mut Criterion) {
+    let matrices: Vec<Mat4> = transforms()
+        .iter()
+        .map(|(_, translation, _, rotation)| {
+            Mat4::rotate(*rotation) * Mat4::translate(*translation)
+        })
+        .collect();
+    let view_projection = Mat4::rotate(Vec3::new(0.1, 0.2, 0.3)) * Mat4::translate(Vec3::one());
+
+    c.bench_function("multiply", |b| {
+        b.iter(|| {
+            for matrix in &matrices {
+                black_box(*black_box(matrix) * view_projection);
+            }
+        })
+    });
+}
+
+// Pivot, rotation, scale and translation composed as Transform::mat did
+// before building the matrix in place
+fn compose_trs(c: &mut Criterion) {
+    let transforms = transforms();
+
+    c.bench_function("compose_trs", |b| {
+        b.iter(|| {
+            for (pivot, translation, scale, rotation) in black_box(&transforms) {
+                black_box(
+                    Mat4::translate(*pivot * -1.)
+                        * Mat4::rotate(*rotation)
+                        * Mat4::translate(*pivot)
+                        * Mat4::scale(*scale)
+                        * Mat4::translate(*translation - (*pivot * *scale)),
+                );
+            }
+        })
+    });
+}
+
+fn transform_point(c: &mut Criterion) {
+    let matrix = Mat4::rotate(Vec3::new(0.1, 0.2, 0.3)) * Mat4::translate(Vec3::one());
+    let points: Vec<Vec4> = transforms()
+        .iter()
+        .map(|(_, t, _, _)| Vec4::new(t.x, t.y, t.z, 1.))
+        .collect();
+
+    c.bench_function("transform_point", |b| {
+        b.iter(|| {
+            for point in black_box(&points) {
+                black_box(matrix * *point);
+            }
+        })
+    });
+}
+
+criterion_group!(benches, multiply, compose_trs, transform_point);
+criterion_main!(benches);
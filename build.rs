@@ -0,0 +1,43 @@
+use std::{env, fs, path::PathBuf, process::Command};
+
+// Shaders embedded in the binary, used when their .spv file cannot be found
+const EMBEDDED_SHADERS: [&str; 8] = [
+    "default.vert",
+    "default.frag",
+    "debug.vert",
+    "debug.frag",
+    "overlay.vert",
+    "skinned.vert",
+    "background.vert",
+    "background.frag",
+];
+
+fn main() {
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    let shaders_dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap()).join("shaders");
+
+    for shader in EMBEDDED_SHADERS {
+        let source = shaders_dir.join(shader);
+        let compiled = shaders_dir.join(format!("{}.spv", shader));
+        let output = out_dir.join(format!("{}.spv", shader));
+        println!("cargo:rerun-if-changed={}", source.display());
+        println!("cargo:rerun-if-changed={}", compiled.display());
+
+        let glslc = Command::new("glslc")
+            .arg(&source)
+            .arg("-o")
+            .arg(&output)
+            .status()
+            .is_ok_and(|status| status.success());
+
+        // Falls back on the output of `make`, then on nothing
+        if !glslc && fs::copy(&compiled, &output).is_err() {
+            println!(
+                "cargo:warning=glslc not found and {} missing, {} is not embedded",
+                compiled.display(),
+                shader
+            );
+            fs::write(&output, []).unwrap();
+        }
+    }
+}
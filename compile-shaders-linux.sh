@@ -1,2 +1,8 @@
 glslc shaders/default.vert -o shaders/default.vert.spv
-glslc shaders/default.frag -o shaders/default.frag.spv
\ No newline at end of file
+glslc shaders/default.frag -o shaders/default.frag.spv
+glslc shaders/debug.vert -o shaders/debug.vert.spv
+glslc shaders/debug.frag -o shaders/debug.frag.spv
+glslc shaders/overlay.vert -o shaders/overlay.vert.spv
+glslc shaders/skinned.vert -o shaders/skinned.vert.spv
+glslc shaders/background.vert -o shaders/background.vert.spv
+glslc shaders/background.frag -o shaders/background.frag.spv
\ No newline at end of file
@@ -1,2 +1,8 @@
 glslc.exe shaders/default.vert -o shaders/default.vert.spv
-glslc.exe shaders/default.frag -o shaders/default.frag.spv
\ No newline at end of file
+glslc.exe shaders/default.frag -o shaders/default.frag.spv
+glslc.exe shaders/debug.vert -o shaders/debug.vert.spv
+glslc.exe shaders/debug.frag -o shaders/debug.frag.spv
+glslc.exe shaders/overlay.vert -o shaders/overlay.vert.spv
+glslc.exe shaders/skinned.vert -o shaders/skinned.vert.spv
+glslc.exe shaders/background.vert -o shaders/background.vert.spv
+glslc.exe shaders/background.frag -o shaders/background.frag.spv
\ No newline at end of file
@@ -0,0 +1,127 @@
+use std::fmt::{Display, Formatter, Result};
+use std::ops::{Index, IndexMut, Mul, MulAssign};
+
+use crate::macros::{forward_ref_binop, forward_ref_op_assign};
+use crate::{DVec3, Mat4};
+
+// Double precision Mat4, same layout and product order. Meant to compose
+// transforms far from the origin, see `DVec3` for the way back to f32.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DMat4([[f64; 4]; 4]);
+
+impl DMat4 {
+    pub fn identity() -> Self {
+        Self([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn translate(translation: DVec3) -> Self {
+        let mut mat = Self::identity();
+        mat[3] = [translation.x, translation.y, translation.z, 1.0];
+        mat
+    }
+
+    pub fn transform_point(&self, point: DVec3) -> DVec3 {
+        let [c0, c1, c2, c3] = self.0;
+        DVec3::new(
+            c0[0] * point.x + c1[0] * point.y + c2[0] * point.z + c3[0],
+            c0[1] * point.x + c1[1] * point.y + c2[1] * point.z + c3[1],
+            c0[2] * point.x + c1[2] * point.y + c2[2] * point.z + c3[2],
+        )
+    }
+
+    pub fn transform_vector(&self, vector: DVec3) -> DVec3 {
+        let [c0, c1, c2, _] = self.0;
+        DVec3::new(
+            c0[0] * vector.x + c1[0] * vector.y + c2[0] * vector.z,
+            c0[1] * vector.x + c1[1] * vector.y + c2[1] * vector.z,
+            c0[2] * vector.x + c1[2] * vector.y + c2[2] * vector.z,
+        )
+    }
+
+    // Rounded to the nearest f32
+    pub fn as_mat4(&self) -> Mat4 {
+        Mat4::from(self.0.map(|column| column.map(|v| v as f32)))
+    }
+
+    // The transform seen from `origin`, its translation is small enough for
+    // f32 when `origin` is close, typically the camera position
+    pub fn relative_to(&self, origin: &DVec3) -> Mat4 {
+        (*self * Self::translate(-*origin)).as_mat4()
+    }
+}
+
+// `a * b` applies `a` then `b`
+impl Mul for DMat4 {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        let [b0, b1, b2, b3] = other.0;
+        Self(self.0.map(|a| {
+            [
+                a[0] * b0[0] + a[1] * b1[0] + a[2] * b2[0] + a[3] * b3[0],
+                a[0] * b0[1] + a[1] * b1[1] + a[2] * b2[1] + a[3] * b3[1],
+                a[0] * b0[2] + a[1] * b1[2] + a[2] * b2[2] + a[3] * b3[2],
+                a[0] * b0[3] + a[1] * b1[3] + a[2] * b2[3] + a[3] * b3[3],
+            ]
+        }))
+    }
+}
+
+impl MulAssign for DMat4 {
+    fn mul_assign(&mut self, other: Self) {
+        *self = *self * other;
+    }
+}
+
+impl Display for DMat4 {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        for i in 0..4 {
+            for j in 0..4 {
+                write!(f, "{} ", self.0[i][j])?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Index<usize> for DMat4 {
+    type Output = [f64; 4];
+
+    fn index(&self, i: usize) -> &[f64; 4] {
+        &self.0[i]
+    }
+}
+
+impl IndexMut<usize> for DMat4 {
+    fn index_mut(&mut self, i: usize) -> &mut [f64; 4] {
+        &mut self.0[i]
+    }
+}
+
+impl From<[[f64; 4]; 4]> for DMat4 {
+    fn from(content: [[f64; 4]; 4]) -> Self {
+        Self(content)
+    }
+}
+
+// Lossless, every f32 is exactly representable as f64
+impl From<Mat4> for DMat4 {
+    fn from(mat: Mat4) -> Self {
+        let mut result = Self::identity();
+        for column in 0..4 {
+            result[column] = mat[column].map(f64::from);
+        }
+        result
+    }
+}
+
+forward_ref_binop!(impl Mul, mul for DMat4, DMat4);
+forward_ref_op_assign!(impl MulAssign, mul_assign for DMat4, DMat4);
@@ -0,0 +1,138 @@
+use std::fmt::{self, Display, Formatter};
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+use crate::macros::{forward_ref_binop, forward_ref_op_assign};
+use crate::Vec3;
+
+// Double precision position for CPU side work on large coordinates. It never
+// goes to the GPU as is: subtract a nearby origin then convert with
+// `relative_to`, the small result fits in f32 without losing precision.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DVec3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl DVec3 {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn cross(&self, other: &Self) -> Self {
+        Self {
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
+    }
+
+    pub fn length_squared(&self) -> f64 {
+        self.dot(self)
+    }
+
+    pub fn length(&self) -> f64 {
+        self.length_squared().sqrt()
+    }
+
+    pub fn normalized(&self) -> Self {
+        *self / self.length()
+    }
+
+    pub fn dot(&self, other: &Self) -> f64 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn lerp(&self, other: &Self, t: f64) -> Self {
+        *self + (*other - *self) * t
+    }
+
+    // Rounded to the nearest f32
+    pub fn as_vec3(&self) -> Vec3 {
+        Vec3::new(self.x as f32, self.y as f32, self.z as f32)
+    }
+
+    pub fn relative_to(&self, origin: &Self) -> Vec3 {
+        (*self - *origin).as_vec3()
+    }
+}
+
+impl Add for DVec3 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl AddAssign for DVec3 {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for DVec3 {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl SubAssign for DVec3 {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+impl Mul<f64> for DVec3 {
+    type Output = Self;
+
+    fn mul(self, scalar: f64) -> Self {
+        Self::new(self.x * scalar, self.y * scalar, self.z * scalar)
+    }
+}
+
+impl Div<f64> for DVec3 {
+    type Output = Self;
+
+    fn div(self, scalar: f64) -> Self {
+        Self::new(self.x / scalar, self.y / scalar, self.z / scalar)
+    }
+}
+
+impl Neg for DVec3 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl Display for DVec3 {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "[{}, {}, {}]", self.x, self.y, self.z)
+    }
+}
+
+impl From<[f64; 3]> for DVec3 {
+    fn from(content: [f64; 3]) -> Self {
+        Self::new(content[0], content[1], content[2])
+    }
+}
+
+// Lossless, every f32 is exactly representable as f64
+impl From<Vec3> for DVec3 {
+    fn from(v: Vec3) -> Self {
+        Self::new(v.x.into(), v.y.into(), v.z.into())
+    }
+}
+
+forward_ref_binop!(impl Add, add for DVec3, DVec3);
+forward_ref_binop!(impl Sub, sub for DVec3, DVec3);
+forward_ref_binop!(impl Mul, mul for DVec3, f64);
+forward_ref_binop!(impl Div, div for DVec3, f64);
+forward_ref_op_assign!(impl AddAssign, add_assign for DVec3, DVec3);
+forward_ref_op_assign!(impl SubAssign, sub_assign for DVec3, DVec3);
@@ -0,0 +1,26 @@
+// The only math library of the project, shared by the engine and the
+// renderer. Every type is repr(C) with f32 fields in x, y, z, w order so it can
+// be written to GPU buffers. Matrices are column-major and `a * b` applies `a`
+// first, so the GLSL `P * V * M` is written `m * v * p` here. DVec3 and DMat4
+// are f64 versions for CPU work only.
+//
+// Directions are right-handed with +y up, +x right and -z forward.
+
+mod macros;
+mod vec2;
+mod vec3;
+mod vec4;
+mod mat3;
+mod mat4;
+mod quat;
+mod dvec3;
+mod dmat4;
+
+pub use vec2::Vec2;
+pub use vec3::Vec3;
+pub use vec4::Vec4;
+pub use mat3::Mat3;
+pub use mat4::Mat4;
+pub use quat::Quat;
+pub use dvec3::DVec3;
+pub use dmat4::DMat4;
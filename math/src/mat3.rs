@@ -1,7 +1,12 @@
 use std::fmt::{Display, Formatter, Result};
 use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Sub, SubAssign};
 
+// Column-major like Mat4, but GLSL pads each mat3 column to 16 bytes in
+// uniform and push constant blocks, so it cannot be uploaded as is: the
+// renderer converts it to a GpuMat3 first
+#[repr(C)]
 #[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Mat3([[f32; 3]; 3]);
 
 impl Mat3 {
@@ -2,9 +2,12 @@ use std::fmt::{Display, Formatter, Result};
 use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Sub, SubAssign};
 
 use crate::macros::{forward_ref_binop, forward_ref_op_assign};
-use crate::Vec3;
+use crate::{Mat3, Quat, Vec3, Vec4};
 
+// Column-major, `m[column][row]`, same memory layout as a GLSL mat4
+#[repr(C)]
 #[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Mat4([[f32; 4]; 4]);
 
 impl Mat4 {
@@ -137,6 +140,69 @@ impl Mat4 {
         ])
     }
 
+    pub fn rotation_from_quaternion(q: Quat) -> Mat4 {
+        let (x, y, z, w) = (q.x, q.y, q.z, q.w);
+
+        Mat4::from([
+            [
+                1. - 2. * (y * y + z * z),
+                2. * (x * y + w * z),
+                2. * (x * z - w * y),
+                0.,
+            ],
+            [
+                2. * (x * y - w * z),
+                1. - 2. * (x * x + z * z),
+                2. * (y * z + w * x),
+                0.,
+            ],
+            [
+                2. * (x * z + w * y),
+                2. * (y * z - w * x),
+                1. - 2. * (x * x + y * y),
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    // Scale, then rotation, then translation, like a glTF node
+    pub fn from_trs(translation: Vec3, rotation: Quat, scale: Vec3) -> Mat4 {
+        let mut mat = Mat4::rotation_from_quaternion(rotation);
+        for (i, factor) in [scale.x, scale.y, scale.z].into_iter().enumerate() {
+            for row in 0..3 {
+                mat[i][row] *= factor;
+            }
+        }
+        mat[3] = [translation.x, translation.y, translation.z, 1.];
+        mat
+    }
+
+    // Inverse of `from_trs` for affine matrices without shear. A mirroring
+    // matrix gets a negative x scale.
+    pub fn decompose(&self) -> (Vec3, Quat, Vec3) {
+        let translation = Vec3::new(self[3][0], self[3][1], self[3][2]);
+        let mut scale = Vec3::new(
+            Vec3::new(self[0][0], self[0][1], self[0][2]).length(),
+            Vec3::new(self[1][0], self[1][1], self[1][2]).length(),
+            Vec3::new(self[2][0], self[2][1], self[2][2]).length(),
+        );
+        if self.determinant() < 0. {
+            scale.x = -scale.x;
+        }
+
+        let mut rotation = Mat4::identity();
+        for (i, factor) in [scale.x, scale.y, scale.z].into_iter().enumerate() {
+            if factor != 0. {
+                for row in 0..3 {
+                    rotation[i][row] = self[i][row] / factor;
+                }
+            }
+        }
+
+        (translation, Quat::from_rotation_matrix(&rotation), scale)
+    }
+
     pub fn scale(factor: Vec3) -> Mat4 {
         Mat4::from([
             [factor.x, 0.0f32, 0.0f32, 0.0f32],
@@ -146,6 +212,32 @@ impl Mat4 {
         ])
     }
 
+    // Inverse transpose of the upper left 3x3, normals transformed by it stay
+    // perpendicular to the surface with a non-uniform scale. Its columns are
+    // the cross products of the matrix columns, divided by the determinant.
+    pub fn normal_matrix(&self) -> Mat3 {
+        let column = |i: usize| Vec3::new(self[i][0], self[i][1], self[i][2]);
+        let (c0, c1, c2) = (column(0), column(1), column(2));
+        let mut cofactors = [c1.cross(&c2), c2.cross(&c0), c0.cross(&c1)];
+
+        // Flattened matrices keep the cofactors, normals are normalized anyway
+        let det = c0.dot(&cofactors[0]);
+        if det != 0. {
+            cofactors = cofactors.map(|c| c / det);
+        }
+        Mat3::from(cofactors.map(|c| [c.x, c.y, c.z]))
+    }
+
+    #[inline]
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        Vec3::from(*self * Vec4::new(point.x, point.y, point.z, 1.))
+    }
+
+    #[inline]
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        Vec3::from(*self * Vec4::new(vector.x, vector.y, vector.z, 0.))
+    }
+
     pub fn translate(translation: Vec3) -> Mat4 {
         Mat4::from([
             [1.0f32, 0.0f32, 0.0f32, 0.0f32],
@@ -204,18 +296,41 @@ impl SubAssign for Mat4 {
     }
 }
 
+// `a * b` applies `a` then `b`. Each column of the result combines the
+// columns of `b`, written out so it compiles to a few vector operations.
 impl Mul for Mat4 {
     type Output = Self;
+
+    #[inline]
     fn mul(self, other: Self) -> Self {
-        let mut result = Self::new();
-        for i in 0..4 {
-            for j in 0..4 {
-                for k in 0..4 {
-                    result[i][j] += self[i][k] * other[k][j];
-                }
-            }
-        }
-        result
+        let [b0, b1, b2, b3] = other.0;
+        let column = |a: [f32; 4]| {
+            [
+                a[0] * b0[0] + a[1] * b1[0] + a[2] * b2[0] + a[3] * b3[0],
+                a[0] * b0[1] + a[1] * b1[1] + a[2] * b2[1] + a[3] * b3[1],
+                a[0] * b0[2] + a[1] * b1[2] + a[2] * b2[2] + a[3] * b3[2],
+                a[0] * b0[3] + a[1] * b1[3] + a[2] * b2[3] + a[3] * b3[3],
+            ]
+        };
+
+        let [a0, a1, a2, a3] = self.0;
+        Self([column(a0), column(a1), column(a2), column(a3)])
+    }
+}
+
+// Columns are stored contiguously, as in GLSL, so `m * v` matches the shaders
+impl Mul<Vec4> for Mat4 {
+    type Output = Vec4;
+
+    #[inline]
+    fn mul(self, v: Vec4) -> Vec4 {
+        let [c0, c1, c2, c3] = self.0;
+        Vec4::new(
+            c0[0] * v.x + c1[0] * v.y + c2[0] * v.z + c3[0] * v.w,
+            c0[1] * v.x + c1[1] * v.y + c2[1] * v.z + c3[1] * v.w,
+            c0[2] * v.x + c1[2] * v.y + c2[2] * v.z + c3[2] * v.w,
+            c0[3] * v.x + c1[3] * v.y + c2[3] * v.z + c3[3] * v.w,
+        )
     }
 }
 
@@ -292,12 +407,14 @@ impl Display for Mat4 {
 impl Index<usize> for Mat4 {
     type Output = [f32; 4];
 
+    #[inline]
     fn index(&self, index: usize) -> &Self::Output {
         &self.0[index]
     }
 }
 
 impl IndexMut<usize> for Mat4 {
+    #[inline]
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
         &mut self.0[index]
     }
@@ -312,6 +429,7 @@ impl From<[[f32; 4]; 4]> for Mat4 {
 forward_ref_binop!(impl Add, add for Mat4, Mat4);
 forward_ref_binop!(impl Sub, sub for Mat4, Mat4);
 forward_ref_binop!(impl Mul, mul for Mat4, Mat4);
+forward_ref_binop!(impl Mul, mul for Mat4, Vec4);
 forward_ref_binop!(impl Mul, mul for Mat4, f32);
 forward_ref_binop!(impl Mul, mul for f32, Mat4);
 forward_ref_binop!(impl Div, div for Mat4, f32);
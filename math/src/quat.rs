@@ -0,0 +1,176 @@
+use std::fmt::{self, Display, Formatter};
+use std::ops::{Mul, MulAssign, Neg};
+
+use crate::macros::{forward_ref_binop, forward_ref_op_assign};
+use crate::{Mat4, Vec3};
+
+// Unit quaternion, same x, y, z, w order as glTF. Like matrices, `a * b`
+// applies `a` first, which is the Hamilton product `b * a`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quat {
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Self { x, y, z, w }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(0., 0., 0., 1.)
+    }
+
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Self {
+        let axis = axis.normalized() * (angle / 2.).sin();
+        Self::new(axis.x, axis.y, axis.z, (angle / 2.).cos())
+    }
+
+    // Rotation part of `m`, its columns must be orthonormal
+    pub fn from_rotation_matrix(m: &Mat4) -> Self {
+        let (m00, m11, m22) = (m[0][0], m[1][1], m[2][2]);
+        let trace = m00 + m11 + m22;
+
+        let q = if trace > 0. {
+            let s = (trace + 1.).sqrt() * 2.;
+            Self::new(
+                (m[1][2] - m[2][1]) / s,
+                (m[2][0] - m[0][2]) / s,
+                (m[0][1] - m[1][0]) / s,
+                s / 4.,
+            )
+        } else if m00 > m11 && m00 > m22 {
+            let s = (1. + m00 - m11 - m22).sqrt() * 2.;
+            Self::new(
+                s / 4.,
+                (m[1][0] + m[0][1]) / s,
+                (m[2][0] + m[0][2]) / s,
+                (m[1][2] - m[2][1]) / s,
+            )
+        } else if m11 > m22 {
+            let s = (1. + m11 - m00 - m22).sqrt() * 2.;
+            Self::new(
+                (m[1][0] + m[0][1]) / s,
+                s / 4.,
+                (m[2][1] + m[1][2]) / s,
+                (m[2][0] - m[0][2]) / s,
+            )
+        } else {
+            let s = (1. + m22 - m00 - m11).sqrt() * 2.;
+            Self::new(
+                (m[2][0] + m[0][2]) / s,
+                (m[2][1] + m[1][2]) / s,
+                s / 4.,
+                (m[0][1] - m[1][0]) / s,
+            )
+        };
+        q.normalized()
+    }
+
+    pub fn length(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalized(&self) -> Self {
+        let length = self.length();
+        Self::new(
+            self.x / length,
+            self.y / length,
+            self.z / length,
+            self.w / length,
+        )
+    }
+
+    pub fn dot(&self, other: &Self) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    // Inverse rotation of a unit quaternion
+    pub fn conjugate(&self) -> Self {
+        Self::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    pub fn rotate(&self, v: Vec3) -> Vec3 {
+        let u = Vec3::new(self.x, self.y, self.z);
+        let t = u.cross(&v) * 2.;
+        v + t * self.w + u.cross(&t)
+    }
+
+    // Shortest path, falls back on a normalized lerp when both are close
+    pub fn slerp(&self, other: &Self, t: f32) -> Self {
+        let mut other = *other;
+        let mut cos = self.dot(&other);
+        if cos < 0. {
+            other = -other;
+            cos = -cos;
+        }
+
+        let (a, b) = if cos > 0.9995 {
+            (1. - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1. - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        Self::new(
+            self.x * a + other.x * b,
+            self.y * a + other.y * b,
+            self.z * a + other.z * b,
+            self.w * a + other.w * b,
+        )
+        .normalized()
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Mul for Quat {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let (p, q) = (rhs, self);
+        Self::new(
+            p.w * q.x + p.x * q.w + p.y * q.z - p.z * q.y,
+            p.w * q.y - p.x * q.z + p.y * q.w + p.z * q.x,
+            p.w * q.z + p.x * q.y - p.y * q.x + p.z * q.w,
+            p.w * q.w - p.x * q.x - p.y * q.y - p.z * q.z,
+        )
+    }
+}
+
+impl MulAssign for Quat {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+impl Neg for Quat {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.x, -self.y, -self.z, -self.w)
+    }
+}
+
+impl Display for Quat {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "[{}, {}, {}, {}]", self.x, self.y, self.z, self.w)
+    }
+}
+
+impl From<[f32; 4]> for Quat {
+    fn from(q: [f32; 4]) -> Self {
+        Self::new(q[0], q[1], q[2], q[3])
+    }
+}
+
+forward_ref_binop!(impl Mul, mul for Quat, Quat);
+forward_ref_op_assign!(impl MulAssign, mul_assign for Quat, Quat);
@@ -8,7 +8,9 @@ use crate::{
     Vec3, Vec4,
 };
 
+#[repr(C)]
 #[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vec2 {
     pub x: f32,
     pub y: f32,
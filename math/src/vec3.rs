@@ -4,7 +4,9 @@ use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign,
 use crate::macros::{forward_ref_binop, forward_ref_op_assign};
 use crate::{Vec2, Vec4};
 
+#[repr(C)]
 #[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vec3 {
     pub x: f32,
     pub y: f32,
@@ -56,11 +58,12 @@ impl Vec3 {
         }
     }
 
+    // -z, where the camera looks at rest, like `Vec4::forward`
     pub fn forward() -> Self {
         Self {
             x: 0.,
             y: 0.,
-            z: 1.,
+            z: -1.,
         }
     }
 
@@ -68,7 +71,7 @@ impl Vec3 {
         Self {
             x: 0.,
             y: 0.,
-            z: -1.,
+            z: 1.,
         }
     }
 
@@ -159,6 +162,23 @@ impl Vec3 {
     pub fn dot(&self, other: &Self) -> f32 {
         self.x * other.x + self.y * other.y + self.z * other.z
     }
+
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        *self + (*other - *self) * t
+    }
+
+    // Both vectors must be normalized
+    pub fn slerp(&self, other: &Self, t: f32) -> Self {
+        let cos_angle = self.dot(other).clamp(-1., 1.);
+        let relative = *other - *self * cos_angle;
+        if relative.length_squared() < f32::EPSILON {
+            // Parallel or opposite, there is no single arc to follow
+            return if t < 0.5 { *self } else { *other };
+        }
+
+        let angle = cos_angle.acos() * t;
+        *self * angle.cos() + relative.normalized() * angle.sin()
+    }
 }
 
 impl Add for Vec3 {
@@ -4,7 +4,9 @@ use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign,
 use crate::macros::{forward_ref_binop, forward_ref_op_assign};
 use crate::{Vec2, Vec3};
 
+#[repr(C)]
 #[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vec4 {
     pub x: f32,
     pub y: f32,
@@ -0,0 +1,65 @@
+#version 450
+
+layout (location = 0) in vec2 i_ndc;
+
+layout (location = 0) out vec4 o_color;
+
+layout (set = 0, binding = 0) uniform Camera {
+    mat4 projection;
+    mat4 view;
+    mat4 inverse_view;
+    vec4 clip_plane;
+    vec2 viewport_size;
+    float time;
+    float delta_time;
+    uint color_flags;
+} camera;
+
+layout (push_constant) uniform Push {
+    vec4 color_a;
+    vec4 color_b;
+    vec4 color_c;
+    uint mode;
+    float depth;
+} push;
+
+const uint MODE_SOLID = 0u;
+const uint MODE_GRADIENT = 1u;
+const uint MODE_ENVIRONMENT = 2u;
+const uint MODE_CHECKER = 3u;
+
+const float CHECKER_SIZE = 32.0;
+
+const uint COLOR_ENCODE_SRGB = 1u;
+
+vec3 unproject(vec2 ndc, float depth) {
+    vec4 position = inverse(camera.projection) * vec4(ndc, depth, 1.0);
+    return position.xyz / position.w;
+}
+
+void main() {
+    vec3 color = push.color_a.rgb;
+    if (push.mode == MODE_GRADIENT) {
+        // Vulkan y points down, -1 is the top of the screen
+        color = mix(push.color_a.rgb, push.color_b.rgb, i_ndc.y * 0.5 + 0.5);
+    } else if (push.mode == MODE_ENVIRONMENT) {
+        // World direction of the pixel, works for both projections
+        vec3 view_direction = unproject(i_ndc, push.depth) - unproject(i_ndc, 1.0 - push.depth);
+        vec3 direction = normalize(mat3(camera.inverse_view) * view_direction);
+        float elevation = direction.y;
+        if (elevation >= 0.0) {
+            color = mix(push.color_b.rgb, push.color_a.rgb, sqrt(elevation));
+        } else {
+            color = mix(push.color_b.rgb, push.color_c.rgb, sqrt(-elevation));
+        }
+    } else if (push.mode == MODE_CHECKER) {
+        vec2 cell = floor(gl_FragCoord.xy / CHECKER_SIZE);
+        color = mod(cell.x + cell.y, 2.0) < 1.0 ? push.color_a.rgb : push.color_b.rgb;
+    }
+
+    // Colors are given in sRGB, like the debug lines
+    if ((camera.color_flags & COLOR_ENCODE_SRGB) == 0u) {
+        color = mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), greaterThan(color, vec3(0.04045)));
+    }
+    o_color = vec4(color, 1.0);
+}
@@ -0,0 +1,19 @@
+#version 450
+
+layout (push_constant) uniform Push {
+    vec4 color_a;
+    vec4 color_b;
+    vec4 color_c;
+    uint mode;
+    float depth;
+} push;
+
+layout (location = 0) out vec2 o_ndc;
+
+// One triangle covering the screen at the far plane, drawn after the scene
+// so only the pixels nothing was drawn on pass the depth test
+void main() {
+    vec2 ndc = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2) * 2.0 - 1.0;
+    gl_Position = vec4(ndc, push.depth, 1.0);
+    o_ndc = ndc;
+}
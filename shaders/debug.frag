@@ -0,0 +1,27 @@
+#version 450
+
+layout (location = 0) in vec3 i_color;
+
+layout (location = 0) out vec4 o_color;
+
+layout (set = 0, binding = 0) uniform Camera {
+    mat4 projection;
+    mat4 view;
+    mat4 inverse_view;
+    vec4 clip_plane;
+    vec2 viewport_size;
+    float time;
+    float delta_time;
+    uint color_flags;
+} camera;
+
+const uint COLOR_ENCODE_SRGB = 1u;
+
+void main() {
+    // Line colors are given in sRGB, the swapchain encodes them back on write
+    vec3 color = i_color;
+    if ((camera.color_flags & COLOR_ENCODE_SRGB) == 0u) {
+        color = mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), greaterThan(color, vec3(0.04045)));
+    }
+    o_color = vec4(color, 1.0);
+}
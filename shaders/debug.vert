@@ -0,0 +1,22 @@
+#version 450
+
+layout (location = 0) in vec3 i_pos;
+layout (location = 1) in vec3 i_color;
+
+layout (location = 0) out vec3 o_color;
+
+layout (set = 0, binding = 0) uniform Camera {
+    mat4 projection;
+    mat4 view;
+    mat4 inverse_view;
+    vec4 clip_plane;
+    vec2 viewport_size;
+    float time;
+    float delta_time;
+    uint color_flags;
+} camera;
+
+void main() {
+    gl_Position = camera.projection * camera.view * vec4(i_pos, 1.0);
+    o_color = i_color;
+}
@@ -2,17 +2,107 @@
 
 layout (location = 0) flat in vec3 i_color;
 layout (location = 1) in vec2 i_uv;
+layout (location = 2) in vec3 i_normal;
+layout (location = 3) in vec3 i_position_world;
 
 layout (location = 0) out vec4 o_color;
 
+layout (set = 0, binding = 0) uniform Camera {
+    mat4 projection;
+    mat4 view;
+    mat4 inverse_view;
+    vec4 clip_plane;
+    vec2 viewport_size;
+    float time;
+    float delta_time;
+    uint color_flags;
+    float exposure;
+    float sun_intensity;
+    float ambient_intensity;
+    vec4 sun_direction;
+} camera;
+
 layout (set = 1, binding = 0) uniform sampler2D texSampler;
+layout (set = 1, binding = 1) uniform sampler2D secondaryTexSampler;
+
+layout (set = 2, binding = 0) uniform Params {
+    vec4 tint;
+    float texture_blend;
+} params;
 
 layout (push_constant) uniform Push {
     mat4 model_matrix;
     mat3 normal_matrix;
     float flat_texture_interpolation;
+    uint debug_color;
 } push;
 
+const uint COLOR_ENCODE_SRGB = 1u;
+const uint COLOR_NAIVE_GAMMA = 2u;
+const uint COLOR_TEXEL_DENSITY = 4u;
+const uint COLOR_DISTINCT = 8u;
+const uint COLOR_WINDING = 16u;
+
+vec3 srgb_to_linear(vec3 color) {
+    return mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), greaterThan(color, vec3(0.04045)));
+}
+
+vec3 linear_to_srgb(vec3 color) {
+    return mix(color * 12.92, 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, greaterThan(color, vec3(0.0031308)));
+}
+
+// Mip level the texture lookup would use, from the screen derivatives of the
+// texel coordinates. 0 is one texel per pixel, negative levels stretch texels
+// over several pixels, positive ones skip texels.
+float texel_lod(vec2 uv) {
+    vec2 texels = uv * vec2(textureSize(texSampler, 0));
+    vec2 dx = dFdx(texels);
+    vec2 dy = dFdy(texels);
+    return 0.5 * log2(max(dot(dx, dx), dot(dy, dy)));
+}
+
+// Blue where the texture is too small, green around one texel per pixel, red
+// where its resolution is wasted, saturated 3 levels away
+vec3 texel_density_color(float lod) {
+    float t = clamp(abs(lod) / 3.0, 0.0, 1.0);
+    return mix(vec3(0.0, 1.0, 0.0), lod < 0.0 ? vec3(0.0, 0.2, 1.0) : vec3(1.0, 0.0, 0.0), t);
+}
+
 void main() {
-    o_color = mix(vec4(i_color, 1.0), texture(texSampler, i_uv), push.flat_texture_interpolation);
+    // Derivatives before any discard
+    float lod = texel_lod(i_uv);
+
+    if (dot(camera.clip_plane.xyz, i_position_world) + camera.clip_plane.w < 0.0) {
+        discard;
+    }
+
+    // Meshes without normals are lit as if facing the sun
+    float light = camera.ambient_intensity + camera.sun_intensity;
+    if (dot(i_normal, i_normal) > 0.0) {
+        // Back faces are only rasterized by double-sided materials
+        vec3 normal = normalize(gl_FrontFacing ? i_normal : -i_normal);
+        light = camera.ambient_intensity + camera.sun_intensity * max(dot(normal, -camera.sun_direction.xyz), 0.0);
+    }
+
+    // Vertex colors are authored in sRGB, textures are decoded by their sampler
+    vec4 texture_color = mix(texture(texSampler, i_uv), texture(secondaryTexSampler, i_uv), params.texture_blend);
+    vec4 color = mix(vec4(srgb_to_linear(i_color), 1.0), texture_color, push.flat_texture_interpolation) * params.tint;
+    if ((camera.color_flags & COLOR_DISTINCT) != 0u) {
+        color = vec4(srgb_to_linear(unpackUnorm4x8(push.debug_color).rgb), 1.0);
+    }
+    if ((camera.color_flags & COLOR_TEXEL_DENSITY) != 0u) {
+        color = vec4(texel_density_color(lod), 1.0);
+    }
+    if ((camera.color_flags & COLOR_WINDING) != 0u) {
+        color = gl_FrontFacing ? vec4(0.1, 0.3, 1.0, 1.0) : vec4(1.0, 0.1, 0.1, 1.0);
+    }
+    vec3 lit = color.rgb * light;
+    if ((camera.color_flags & COLOR_NAIVE_GAMMA) != 0u) {
+        lit = srgb_to_linear(linear_to_srgb(color.rgb) * light);
+    }
+    lit *= camera.exposure;
+    if ((camera.color_flags & COLOR_ENCODE_SRGB) != 0u) {
+        lit = linear_to_srgb(lit);
+    }
+    o_color = vec4(lit, color.a);
 }
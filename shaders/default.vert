@@ -7,10 +7,18 @@ layout (location = 3) in vec2 i_uv;
 
 layout (location = 0) flat out vec3 o_color;
 layout (location = 1) out vec2 o_uv;
+layout (location = 2) out vec3 o_normal;
+layout (location = 3) out vec3 o_position_world;
 
 layout (set = 0, binding = 0) uniform Camera {
     mat4 projection;
     mat4 view;
+    mat4 inverse_view;
+    vec4 clip_plane;
+    vec2 viewport_size;
+    float time;
+    float delta_time;
+    uint color_flags;
 } camera;
 
 layout (push_constant) uniform Push {
@@ -26,4 +34,6 @@ void main() {
     float grey = (mod(gl_VertexIndex * 9, 60.) + 20) / 100.;
     o_color = vec3(grey, grey, grey);
     o_uv = i_uv;
+    o_normal = push.normal_matrix * i_normal;
+    o_position_world = position_world.xyz;
 }
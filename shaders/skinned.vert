@@ -0,0 +1,57 @@
+#version 450
+
+layout (location = 0) in vec3 i_pos;
+layout (location = 1) in vec3 i_color;
+layout (location = 2) in vec3 i_normal;
+layout (location = 3) in vec2 i_uv;
+layout (location = 4) in uvec4 i_joints;
+layout (location = 5) in vec4 i_weights;
+
+layout (location = 0) flat out vec3 o_color;
+layout (location = 1) out vec2 o_uv;
+layout (location = 2) out vec3 o_normal;
+layout (location = 3) out vec3 o_position_world;
+
+layout (set = 0, binding = 0) uniform Camera {
+    mat4 projection;
+    mat4 view;
+    mat4 inverse_view;
+    vec4 clip_plane;
+    vec2 viewport_size;
+    float time;
+    float delta_time;
+    uint color_flags;
+} camera;
+
+// After the texture and parameter sets of the default material
+layout (set = 3, binding = 0) uniform Joints {
+    mat4 matrices[128];
+} joints;
+
+layout (push_constant) uniform Push {
+    mat4 model_matrix;
+    mat3 normal_matrix;
+    float flat_texture_interpolation;
+} push;
+
+void main() {
+    float total_weight = dot(i_weights, vec4(1.0));
+    // Vertices without weights follow the object only
+    mat4 skin_matrix = mat4(1.0);
+    if (total_weight > 0.0) {
+        skin_matrix = (i_weights.x * joints.matrices[i_joints.x]
+            + i_weights.y * joints.matrices[i_joints.y]
+            + i_weights.z * joints.matrices[i_joints.z]
+            + i_weights.w * joints.matrices[i_joints.w]) / total_weight;
+    }
+
+    vec4 position_world = push.model_matrix * skin_matrix * vec4(i_pos, 1.0);
+    gl_Position = camera.projection * camera.view * position_world;
+
+    float grey = (mod(gl_VertexIndex * 9, 60.) + 20) / 100.;
+    o_color = vec3(grey, grey, grey);
+    o_uv = i_uv;
+    // Joints are expected to scale uniformly, the normal matrix is left out
+    o_normal = push.normal_matrix * mat3(skin_matrix) * i_normal;
+    o_position_world = position_world.xyz;
+}
@@ -0,0 +1,50 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    parsing::{
+        import_obj_file, print_obj_warnings, resolve_asset, simplify_mesh, write_mesh_file,
+        CancelToken, ObjImportOptions, ProgressBar,
+    },
+    utils::Result,
+};
+
+// Imports a model like the viewer, without opening a window, optionally
+// simplifies it to `simplify` of its triangles, then writes it in the mesh
+// cache layout. The viewer opens the `.mesh` file like an OBJ file.
+pub fn convert_obj_file(
+    input: &str,
+    output: Option<&str>,
+    options: &ObjImportOptions,
+    simplify: Option<f32>,
+) -> Result<()> {
+    let source = resolve_asset(input)?;
+    let output = match output {
+        Some(output) => PathBuf::from(output),
+        None => Path::new(input).with_extension("mesh"),
+    };
+
+    let progress = ProgressBar::new(input);
+    let on_progress = |stage, fraction| progress.update(stage, fraction);
+    let (mut elements, import_scale) =
+        import_obj_file(input, options, &on_progress, &CancelToken::default())?;
+    print_obj_warnings(input, &elements.warnings);
+
+    if let Some(ratio) = simplify {
+        let triangles = elements.indices.len() / 3;
+        simplify_mesh(&mut elements, ratio)?;
+        println!(
+            "Simplified from {} to {} triangles",
+            triangles,
+            elements.indices.len() / 3
+        );
+    }
+
+    write_mesh_file(&output, &source, options, &elements, import_scale)?;
+    println!(
+        "{}: {} vertices and {} triangles written",
+        output.display(),
+        elements.vertices.len(),
+        elements.indices.len() / 3
+    );
+    Ok(())
+}
@@ -1,164 +1,706 @@
-use ash::vk;
-use math::Vec3;
-use winit::keyboard::{Key, KeyCode, NamedKey};
+use std::{path::Path, rc::Rc, time::Duration};
+
+use math::{Vec2, Vec3, Vec4};
+use winit::{event::MouseButton, keyboard::KeyCode};
 
 use crate::{
-    engine::{camera::Camera, Engine, GameObject, Transform},
-    parsing::{read_frag_spv_file, read_obj_file, read_tga_r8g8b8a8_srgb_file, read_vert_spv_file},
-    renderer::{Material, MaterialInstance, ScopDescriptorSetLayout}, utils::Result,
+    engine::{
+        axis_gizmo::AxisGizmo,
+        camera::Camera,
+        input::{FrameInput, InputExt},
+        loading::LoadingSpinner,
+        mesh::{mirror_x, Mesh},
+        physics::{Collider, Physics, RigidBody},
+        Engine, EngineConfig, GameObject, Transform,
+    },
+    parsing::{
+        print_obj_warnings, read_frag_spv_file, read_obj_file_reported, read_obj_materials,
+        read_tga_r8g8b8a8_srgb_file, read_tga_r8g8b8a8_srgb_file_with, read_vert_spv_file,
+        user_path, FileWatcher, ObjImportOptions, ObjLoad, ObjWarning, ProgressBar, UpAxis,
+    },
+    renderer::{Background, DebugLines, MaterialInstance, ScopTexture2D},
+    utils::Result,
 };
 
-#[derive(Default)]
+use super::showcase::ShowcaseApp;
+
+// Looked for next to the model, `model.obj` uses `model.tga`
+const TEXTURE_EXTENSIONS: [&str; 4] = ["tga", "TGA", "png", "PNG"];
+// Shown when the model has no texture of its own
+const FALLBACK_CHECKER: [Vec3; 2] = [
+    Vec3 {
+        x: 0.8,
+        y: 0.8,
+        z: 0.8,
+    },
+    Vec3 {
+        x: 0.4,
+        y: 0.4,
+        z: 0.4,
+    },
+];
+
+// Cycled through with `background_key`
+fn backgrounds() -> [(&'static str, Background); 5] {
+    [
+        ("white", Background::Solid(Vec3::new(1., 1., 1.))),
+        ("black", Background::Solid(Vec3::new(0.02, 0.02, 0.02))),
+        (
+            "gradient",
+            Background::Gradient(Vec3::new(0.35, 0.45, 0.6), Vec3::new(0.9, 0.9, 0.88)),
+        ),
+        (
+            "environment",
+            Background::Environment {
+                sky: Vec3::new(0.3, 0.5, 0.85),
+                horizon: Vec3::new(0.85, 0.88, 0.9),
+                ground: Vec3::new(0.35, 0.3, 0.25),
+            },
+        ),
+        (
+            "checker",
+            Background::Checker(Vec3::new(0.6, 0.6, 0.6), Vec3::new(0.4, 0.4, 0.4)),
+        ),
+    ]
+}
+
+// The first diffuse map of the model materials, or a file named after the
+// model when it has none
+fn model_texture(path: &str) -> Option<String> {
+    let materials = read_obj_materials(path).unwrap_or_else(|e| {
+        eprintln!("{}: materials ignored, {}", path, e);
+        vec![]
+    });
+    let Some((material, diffuse_map)) = materials
+        .iter()
+        .find_map(|material| Some((&material.name, material.diffuse_map.as_ref()?)))
+    else {
+        return find_model_texture(path, "");
+    };
+
+    if !diffuse_map.is_file() {
+        eprintln!(
+            "{}: texture of material {} not found",
+            diffuse_map.display(),
+            material
+        );
+        return None;
+    }
+    Some(diffuse_map.to_string_lossy().into_owned())
+}
+
+// `<model><suffix>.<extension>` in the directory of the model
+fn find_model_texture(model_path: &str, suffix: &str) -> Option<String> {
+    let model_path = Path::new(model_path);
+    let stem = model_path.file_stem()?.to_string_lossy();
+    TEXTURE_EXTENSIONS
+        .iter()
+        .map(|extension| model_path.with_file_name(format!("{}{}.{}", stem, suffix, extension)))
+        .find(|candidate| candidate.is_file())
+        .map(|candidate| candidate.to_string_lossy().into_owned())
+}
+
+// The checkerboard stands in for a missing or unreadable texture
+fn load_model_texture(engine: &Engine, path: Option<&str>) -> Result<ScopTexture2D> {
+    match path {
+        Some(path) if path.to_ascii_lowercase().ends_with(".tga") => {
+            let progress = ProgressBar::new(path);
+            let on_progress = |stage, fraction| progress.update(stage, fraction);
+            match read_tga_r8g8b8a8_srgb_file_with(engine, path, &on_progress) {
+                Ok(texture) => return Ok(texture),
+                Err(e) => eprintln!("{}: {}, using a checkerboard", path, e),
+            }
+        }
+        Some(path) => eprintln!(
+            "{}: only TGA textures can be read, using a checkerboard",
+            path
+        ),
+        None => println!("No texture for the model, using a checkerboard"),
+    }
+    ScopTexture2D::checkerboard(
+        engine.renderer.main_device.clone(),
+        &mut engine.renderer.staging_ring.borrow_mut(),
+        256,
+        FALLBACK_CHECKER,
+    )
+}
+
 pub struct AppCustom {
-    last_frame_move: u32,
-    texture_target_fade: f32,
-    texture_change_frame: u32,
+    // Reads the model file again, to follow an export without restarting
+    pub reload_key: KeyCode,
+    // Mirrors the model on x, for files exported with the other handedness
+    pub mirror_key: KeyCode,
+    // Lifts the model and lets it fall back on the ground under it
+    pub drop_key: KeyCode,
+    // Cycles through the backgrounds, silhouettes stand out on some of them
+    pub background_key: KeyCode,
+    // Reloads the model and the texture when their files change
+    pub watch: bool,
+    // Also used when reloading, so the model keeps its size
+    pub import_options: ObjImportOptions,
+    // Reload in progress, the current model stays shown until it is done
+    loading: Option<ObjLoad>,
+    measuring: bool,
+    // Picked points, in model space so they follow the object
+    measure_points: Vec<Vec3>,
+    clipping: bool,
+    clip_yaw: f32,
+    clip_pitch: f32,
+    clip_offset: f32,
+    background: usize,
+}
+
+impl Default for AppCustom {
+    fn default() -> Self {
+        Self {
+            reload_key: KeyCode::F5,
+            mirror_key: KeyCode::KeyH,
+            drop_key: KeyCode::KeyP,
+            background_key: KeyCode::KeyB,
+            watch: false,
+            import_options: ObjImportOptions {
+                up_axis: UpAxis::Auto,
+                normalize_size: Some(10.),
+                ..Default::default()
+            },
+            loading: None,
+            measuring: false,
+            measure_points: vec![],
+            clipping: false,
+            clip_yaw: 0.,
+            clip_pitch: 0.,
+            clip_offset: 0.,
+            background: 0,
+        }
+    }
 }
 
 impl AppCustom {
-    pub fn start<'a>(&mut self, path: &'a str) -> Result<()> {
-        let mut engine = Engine::new()?;
+    pub fn start<'a>(&mut self, path: &'a str, config: EngineConfig) -> Result<()> {
+        let mut app = ShowcaseApp::builder(config).name(path).build()?;
+        let engine = &mut app.engine;
+        let path = user_path(path);
+        let path = path.as_str();
 
         // --------------------
         // Meshs
         // --------------------
 
-        let mesh_custom = read_obj_file(&engine, path)?;
+        let progress = ProgressBar::new(path);
+        let on_progress = |stage, fraction| progress.update(stage, fraction);
+        let (mesh_custom, warnings) =
+            read_obj_file_reported(engine, path, &self.import_options, &on_progress)?;
+        print_obj_warnings(path, &warnings);
+        if mesh_custom.import_scale != 1. {
+            println!("{}: scaled by {} to fit", path, mesh_custom.import_scale);
+        }
+
+        let inconsistent_winding = mesh_custom.inconsistent_winding_ratio();
+        if inconsistent_winding > 0. {
+            println!(
+                "{}: {:.1}% of faces have inconsistent winding, rendering double-sided",
+                path,
+                inconsistent_winding * 100.
+            );
+        }
+        // Clockwise faces are the front ones of a left-handed world
+        if let Some(mut inverted_winding) = mesh_custom.inverted_winding_ratio() {
+            if engine.renderer.config.left_handed {
+                inverted_winding = 1. - inverted_winding;
+            }
+            if inverted_winding > 0.5 {
+                println!(
+                    "{}: {:.1}% of faces turn against their normals, the mesh may be \
+                     mirrored or exported for the other handedness (F8 shows the winding)",
+                    path,
+                    inverted_winding * 100.
+                );
+            }
+        }
 
         // --------------------
         // Textures
         // --------------------
 
-        let mut texture_ponies = read_tga_r8g8b8a8_srgb_file(&engine, "./textures/ponies.tga")?;
+        let texture_path = model_texture(path);
+        let mut texture = load_model_texture(engine, texture_path.as_deref())?;
+        let normal_path = find_model_texture(path, "_normal");
+        let mut normal_texture = match &normal_path {
+            Some(normal_path) => match read_tga_r8g8b8a8_srgb_file(engine, normal_path) {
+                Ok(normal_texture) => {
+                    // Bound as the secondary texture, the default shader does
+                    // not shade with it yet
+                    println!("{}: normal map found", normal_path);
+                    Some(normal_texture)
+                }
+                Err(e) => {
+                    eprintln!("{}: ignored, {}", normal_path, e);
+                    None
+                }
+            },
+            None => None,
+        };
 
         // --------------------
         // Shaders
         // --------------------
 
-        let vert_shader = read_vert_spv_file(&engine, "./shaders/default.vert.spv")?;
+        let debug_vert_shader = read_vert_spv_file(engine, "./shaders/debug.vert.spv")?;
+
+        let debug_frag_shader = read_frag_spv_file(engine, "./shaders/debug.frag.spv")?;
 
-        let frag_shader = read_frag_spv_file(&engine, "./shaders/default.frag.spv")?;
+        let overlay_vert_shader = read_vert_spv_file(engine, "./shaders/overlay.vert.spv")?;
+
+        let overlay_frag_shader = read_frag_spv_file(engine, "./shaders/debug.frag.spv")?;
 
         // --------------------
         // Materials
         // --------------------
 
-        let set_layouts = vec![
-            ScopDescriptorSetLayout::builder(&engine.renderer.main_device)
-                .add_texture_binding(0, vk::ShaderStageFlags::FRAGMENT)
-                .build()?,
-        ];
+        if inconsistent_winding > 0. {
+            app.material = ShowcaseApp::default_material(engine, true)?;
+        }
 
-        let material = Material::new(&engine.renderer, set_layouts, &vert_shader, &frag_shader)?;
+        engine.renderer.debug_lines = Some(DebugLines::new(
+            &engine.renderer,
+            &debug_vert_shader,
+            &debug_frag_shader,
+        )?);
+        engine.renderer.overlay_lines = Some(DebugLines::new(
+            &engine.renderer,
+            &overlay_vert_shader,
+            &overlay_frag_shader,
+        )?);
 
         // --------------------
         // Material instances
         // --------------------
 
-        let material_instance_ponies =
-            MaterialInstance::instanciate(&engine.renderer, material.clone())?;
-        material_instance_ponies
-            .writer(0)
-            .set_texture2d(0, &texture_ponies)
-            .write();
+        let material_instance =
+            MaterialInstance::instanciate(&engine.renderer, app.material.clone())?;
+        material_instance.set_textures(&texture, normal_texture.as_ref().unwrap_or(&texture))?;
 
         // --------------------
         // GameObjects
         // --------------------
 
-        let go = GameObject::builder(&mut engine)
+        let go = GameObject::builder(engine)
             .name("Custom")
             .mesh(mesh_custom.clone())
             .transform(Transform {
                 pivot: mesh_custom.bounding_box.get_middle_point(),
                 ..Default::default()
             })
-            .material(material_instance_ponies.clone())
-            .build();
-        go.borrow_mut().transform.translation = Vec3::from([0., 0., 0.]);
+            .material(material_instance.clone())
+            .build()?;
+        go.borrow_mut().transform_mut().translation = Vec3::from([0., 0., 0.]);
 
         // --------------------
         // Logic
         // --------------------
 
-        let mut camera = Camera::empty();
-        let aspect = engine.renderer.window.window.inner_size().width as f32
-            / engine.renderer.window.window.inner_size().height as f32;
-        camera.set_perspective_projection(60.0, aspect, 1.0, 100.0);
-        camera.set_view_target([0.0, 0.0, 20.0].into(), Vec3::default(), Vec3::up());
-        
-        engine.run(&camera, |engine, input, _image_index| {
-            let mut movement = Vec3::default();
-            let mut rotation = Vec3::default();
-            if input.key_held_logical(Key::Named(NamedKey::ArrowLeft)) {
-                rotation.y -= 0.02;
-                self.last_frame_move = engine.renderer.frame_count;
+        engine.title = Self::title(path, &warnings);
+
+        let camera = &mut app.camera;
+        let aspect = engine.renderer.window.aspect_ratio();
+        // Any model size, large scenes are not clipped in the distance
+        camera.set_perspective_infinite(60.0, aspect, 1.0);
+        let bounding_sphere = mesh_custom
+            .bounding_sphere
+            .transform(&go.borrow().local_matrix());
+        camera.frame_sphere(
+            bounding_sphere.center,
+            bounding_sphere.radius,
+            Vec3::forward(),
+            Vec3::up(),
+        );
+        engine.axis_gizmo = Some(AxisGizmo::new(bounding_sphere.center));
+        // Owned by the game object from now on, a reload releases it
+        drop(mesh_custom);
+
+        let watcher = self
+            .watch
+            .then(|| FileWatcher::new(Duration::from_millis(500)));
+        if let Some(watcher) = &watcher {
+            // Only a TGA texture can be reloaded
+            let texture_watched = texture_path
+                .as_deref()
+                .filter(|p| p.to_ascii_lowercase().ends_with(".tga"));
+            for watched in [Some(path), texture_watched].into_iter().flatten() {
+                if let Err(e) = watcher.watch(watched) {
+                    eprintln!("{}: not watched: {}", watched, e);
+                }
             }
-            if input.key_held_logical(Key::Named(NamedKey::ArrowRight)) {
-                rotation.y += 0.02;
-                self.last_frame_move = engine.renderer.frame_count;
+        }
+
+        app.run(|engine, camera, input, controls| {
+            if input.toggled(KeyCode::KeyM, &mut self.measuring) {
+                self.measure_points.clear();
+                println!(
+                    "Measure mode {}",
+                    if self.measuring {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    }
+                );
             }
-            if input.key_held_logical(Key::Named(NamedKey::ArrowUp)) {
-                rotation.z += 0.02;
-                self.last_frame_move = engine.renderer.frame_count;
+
+            if self.measuring {
+                // Keep the model still while picking
+                controls.hold(engine);
+                self.measure(engine, input, camera, &go.borrow());
             }
-            if input.key_held_logical(Key::Named(NamedKey::ArrowDown)) {
-                rotation.z -= 0.02;
-                self.last_frame_move = engine.renderer.frame_count;
+
+            let changed = watcher
+                .as_ref()
+                .map(FileWatcher::changed)
+                .unwrap_or_default();
+            if input.key_pressed(self.reload_key) || changed.iter().any(|p| p == path) {
+                self.reload(engine, path);
             }
-            if input.key_held(KeyCode::KeyA) {
-                movement.x -= 0.084;
+            self.update_loading(engine, &mut go.borrow_mut());
+            if input.key_pressed(self.mirror_key) {
+                self.mirror(engine, &mut go.borrow_mut());
             }
-            if input.key_held(KeyCode::KeyD) {
-                movement.x += 0.084;
+            if input.key_pressed(self.drop_key) {
+                Self::drop_model(engine, &mut go.borrow_mut());
             }
-            if input.key_held(KeyCode::KeyW) {
-                movement.z -= 0.084;
+            if input.key_pressed(self.background_key) {
+                let backgrounds = backgrounds();
+                self.background = (self.background + 1) % backgrounds.len();
+                let (name, background) = backgrounds[self.background];
+                engine.renderer.set_background(background);
+                println!("Background: {}", name);
             }
-            if input.key_held(KeyCode::KeyS) {
-                movement.z += 0.084;
+            if let Some(texture_path) = &texture_path {
+                if changed.iter().any(|p| p == texture_path) {
+                    Self::reload_texture(
+                        engine,
+                        texture_path,
+                        &material_instance,
+                        &mut texture,
+                        normal_texture.as_ref(),
+                    );
+                }
             }
-            if input.key_held(KeyCode::KeyQ) {
-                movement.y -= 0.084;
+
+            Self::draw_wires(engine, &go.borrow());
+
+            input.toggled(KeyCode::KeyC, &mut self.clipping);
+            if self.clipping {
+                self.update_clip_plane(engine, input, &go.borrow());
+            } else {
+                engine.renderer.clip_plane = Vec4::default();
             }
-            if input.key_held(KeyCode::KeyE) {
-                movement.y += 0.084;
+
+            controls.move_objects(engine);
+        })?;
+
+        app.engine.renderer.wait_gpu();
+        texture.cleanup();
+        if let Some(normal_texture) = &mut normal_texture {
+            normal_texture.cleanup();
+        }
+
+        Ok(())
+    }
+
+    // The file name, with the warning count of the last load
+    fn title(path: &str, warnings: &[ObjWarning]) -> String {
+        let name = Path::new(path)
+            .file_name()
+            .map_or(path.to_string(), |name| name.to_string_lossy().into_owned());
+        match warnings.len() {
+            0 => name,
+            count => format!("{} ({} warnings)", name, count),
+        }
+    }
+
+    // Parses the file in the background, a reload still running is canceled
+    fn reload(&mut self, engine: &mut Engine, path: &str) {
+        let load = ObjLoad::start(path, self.import_options);
+        if let Some(previous) = self.loading.replace(load) {
+            println!("{}: previous reload canceled", previous.path());
+        }
+        engine.loading = Some(LoadingSpinner::new(path));
+    }
+
+    // Swaps the mesh once the pending reload is done. Keeps the material, the
+    // old mesh is released once no frame uses it.
+    fn update_loading(&mut self, engine: &mut Engine, game_object: &mut GameObject) {
+        let Some(load) = &self.loading else {
+            return;
+        };
+        let Some(loaded) = load.poll(engine) else {
+            if let Some(spinner) = &mut engine.loading {
+                spinner.progress = load.progress();
             }
+            return;
+        };
+        let path = load.path().to_string();
+        self.loading = None;
+        engine.loading = None;
 
-            if input.key_pressed_logical(Key::Character(&"t")) {
-                self.texture_target_fade = if self.texture_target_fade == 1. {
-                    0.
-                } else {
-                    1.
-                };
-                self.texture_change_frame = engine.renderer.frame_count;
+        let mesh = match loaded {
+            Ok((mesh, warnings)) => {
+                print_obj_warnings(&path, &warnings);
+                engine.title = Self::title(&path, &warnings);
+                mesh
             }
+            Err(e) => {
+                eprintln!("{}: could not reload: {}", path, e);
+                return;
+            }
+        };
+        println!("{}: reloaded, {} triangles", path, mesh.triangle_count());
+
+        game_object.transform_mut().pivot = mesh.bounding_box.get_middle_point();
+        if let Some(old_mesh) = game_object.mesh.replace(mesh) {
+            engine.renderer.retire(old_mesh);
+        }
+        // They were picked on the old model
+        self.measure_points.clear();
+    }
+
+    // Mirrors the loaded mesh instead of reading the file again, reloads keep
+    // the choice through the import options
+    fn mirror(&mut self, engine: &mut Engine, game_object: &mut GameObject) {
+        let Some(mesh) = game_object.mesh.clone() else {
+            return;
+        };
+        let mut vertices = mesh.vertices.clone();
+        let mut indices = mesh.indices.clone();
+        mirror_x(&mut vertices, &mut indices);
 
-            if self.last_frame_move == 0 || engine.renderer.frame_count - self.last_frame_move > 200
-            {
-                rotation.y += 0.02;
+        let mirrored = Mesh::builder(engine.renderer.main_device.clone())
+            .staging_ring(&mut engine.renderer.staging_ring.borrow_mut())
+            .vertices(&vertices)
+            .indices(&indices)
+            .bvh(true)
+            .import_scale(mesh.import_scale)
+            .vertex_layout(mesh.vertex_layout)
+            .build();
+        let mirrored = match mirrored {
+            Ok(mirrored) => Rc::new(mirrored),
+            Err(e) => {
+                eprintln!("Could not mirror the model: {}", e);
+                return;
             }
+        };
 
-            if engine.renderer.flat_texture_interpolation < self.texture_target_fade {
-                engine.renderer.flat_texture_interpolation =
-                    (engine.renderer.flat_texture_interpolation + 0.016).clamp(0., 1.);
-            } else if engine.renderer.flat_texture_interpolation > self.texture_target_fade {
-                engine.renderer.flat_texture_interpolation =
-                    (engine.renderer.flat_texture_interpolation - 0.016).clamp(0., 1.);
+        self.import_options.mirror_x = !self.import_options.mirror_x;
+        println!(
+            "Mirror {}",
+            if self.import_options.mirror_x {
+                "enabled"
+            } else {
+                "disabled"
             }
+        );
 
-            engine.game_objects.values_mut().for_each(|e| {
-                e.borrow_mut().transform.rotation += rotation;
-                e.borrow_mut().transform.translation += movement;
-            });
-        })?;
+        game_object.transform_mut().pivot = mirrored.bounding_box.get_middle_point();
+        if let Some(old_mesh) = game_object.mesh.replace(mirrored) {
+            engine.renderer.retire(old_mesh);
+        }
+        self.measure_points.clear();
+    }
 
+    // The ground is set where the model stood on the first drop
+    fn drop_model(engine: &mut Engine, game_object: &mut GameObject) {
+        let Some(bounds) = game_object.world_bounds() else {
+            return;
+        };
+        let physics = engine
+            .physics
+            .get_or_insert_with(|| Physics::new(bounds.min.y));
+
+        let height = bounds.max.y - bounds.min.y;
+        game_object.transform_mut().translation.y += physics.ground_height - bounds.min.y + height;
+        game_object.body = Some(RigidBody::new(Collider::Aabb));
+    }
+
+    fn reload_texture(
+        engine: &mut Engine,
+        path: &str,
+        material_instance: &MaterialInstance,
+        texture: &mut ScopTexture2D,
+        normal_texture: Option<&ScopTexture2D>,
+    ) {
+        let mut new_texture = match read_tga_r8g8b8a8_srgb_file(engine, path) {
+            Ok(texture) => texture,
+            Err(e) => {
+                eprintln!("{}: could not reload: {}", path, e);
+                return;
+            }
+        };
+
+        // The descriptor sets of the frames in flight cannot be written, and
+        // the recorded command buffers using them become invalid
         engine.renderer.wait_gpu();
+        let written =
+            material_instance.set_textures(&new_texture, normal_texture.unwrap_or(&new_texture));
+        if let Err(e) = written {
+            eprintln!("{}: could not reload: {}", path, e);
+            new_texture.cleanup();
+            return;
+        }
+        engine.renderer.invalidate_recordings();
 
-        texture_ponies.cleanup();
+        std::mem::replace(texture, new_texture).cleanup();
+        println!("{}: reloaded", path);
+    }
 
-        engine.game_objects.clear();
+    fn measure(
+        &mut self,
+        engine: &mut Engine,
+        input: &FrameInput,
+        camera: &Camera,
+        game_object: &GameObject,
+    ) {
+        let Some(mesh) = &game_object.mesh else {
+            return;
+        };
+        let model_matrix = game_object.local_matrix();
 
-        Ok(())
+        if input.mouse_pressed(MouseButton::Left) {
+            if let Some((x, y)) = input.cursor() {
+                if let Some((origin, direction)) =
+                    camera.screen_to_ray(Vec2::new(x, y), engine.renderer.window.viewport_size())
+                {
+                    if let Some(hit) =
+                        mesh.raycast(origin, direction, &game_object.world_transform())
+                    {
+                        if self.measure_points.len() == 2 {
+                            self.measure_points.clear();
+                        }
+                        let point = origin + direction * hit.distance;
+                        if let Some(inverse_model_matrix) = model_matrix.inverse() {
+                            self.measure_points
+                                .push(inverse_model_matrix.transform_point(point));
+                        }
+
+                        if let [a, b] = self.measure_points[..] {
+                            let distance = (model_matrix.transform_point(b)
+                                - model_matrix.transform_point(a))
+                            .length();
+                            let size = (mesh.bounding_box.max - mesh.bounding_box.min)
+                                * game_object.transform().scale;
+                            println!(
+                                "Distance: {:.4}  (bounding box {:.4} x {:.4} x {:.4})",
+                                distance, size.x, size.y, size.z
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        let Some(debug_lines) = &mut engine.renderer.debug_lines else {
+            return;
+        };
+        let points: Vec<Vec3> = self
+            .measure_points
+            .iter()
+            .map(|p| model_matrix.transform_point(*p))
+            .collect();
+        let marker = mesh.bounding_sphere.transform(&model_matrix).radius * 0.02;
+        let color = Vec3::new(1., 0.8, 0.);
+        for point in &points {
+            for axis in [Vec3::right(), Vec3::up(), Vec3::forward()] {
+                debug_lines.line(*point - axis * marker, *point + axis * marker, color);
+            }
+        }
+        if let [a, b] = points[..] {
+            debug_lines.line(a, b, color);
+        }
+    }
+
+    // Line and point elements of the file, points are drawn as small crosses
+    fn draw_wires(engine: &mut Engine, game_object: &GameObject) {
+        let Some(mesh) = &game_object.mesh else {
+            return;
+        };
+        if mesh.line_indices.is_empty() && mesh.point_indices.is_empty() {
+            return;
+        }
+        let Some(debug_lines) = &mut engine.renderer.debug_lines else {
+            return;
+        };
+
+        let model_matrix = game_object.local_matrix();
+        let position =
+            |index: &u32| model_matrix.transform_point(mesh.vertices[*index as usize].position);
+        let color = Vec3::new(0.9, 0.3, 0.9);
+        for segment in mesh.line_indices.chunks_exact(2) {
+            debug_lines.line(position(&segment[0]), position(&segment[1]), color);
+        }
+        let marker = mesh.bounding_sphere.transform(&model_matrix).radius * 0.005;
+        for point in mesh.point_indices.iter().map(position) {
+            for axis in [Vec3::right(), Vec3::up(), Vec3::forward()] {
+                debug_lines.line(point - axis * marker, point + axis * marker, color);
+            }
+        }
+    }
+
+    fn update_clip_plane(
+        &mut self,
+        engine: &mut Engine,
+        input: &FrameInput,
+        game_object: &GameObject,
+    ) {
+        let Some(mesh) = &game_object.mesh else {
+            return;
+        };
+        let bounding_sphere = mesh.bounding_sphere.transform(&game_object.local_matrix());
+
+        if input.key_held(KeyCode::KeyZ) {
+            self.clip_offset -= bounding_sphere.radius * 0.01;
+        }
+        if input.key_held(KeyCode::KeyX) {
+            self.clip_offset += bounding_sphere.radius * 0.01;
+        }
+        if input.key_held(KeyCode::KeyJ) {
+            self.clip_yaw -= 0.02;
+        }
+        if input.key_held(KeyCode::KeyL) {
+            self.clip_yaw += 0.02;
+        }
+        if input.key_held(KeyCode::KeyI) {
+            self.clip_pitch += 0.02;
+        }
+        if input.key_held(KeyCode::KeyK) {
+            self.clip_pitch -= 0.02;
+        }
+        self.clip_offset = self
+            .clip_offset
+            .clamp(-bounding_sphere.radius, bounding_sphere.radius);
+
+        let normal = Vec3::new(
+            self.clip_pitch.cos() * self.clip_yaw.sin(),
+            self.clip_pitch.sin(),
+            self.clip_pitch.cos() * self.clip_yaw.cos(),
+        );
+        let origin = bounding_sphere.center + normal * self.clip_offset;
+        engine.renderer.clip_plane = Vec4::new(normal.x, normal.y, normal.z, -normal.dot(&origin));
+
+        // Outline the cut
+        if let Some(debug_lines) = &mut engine.renderer.debug_lines {
+            let tangent = if normal.cross(&Vec3::up()).length_squared() > 0.01 {
+                normal.cross(&Vec3::up()).normalized()
+            } else {
+                normal.cross(&Vec3::right()).normalized()
+            };
+            let bitangent = normal.cross(&tangent);
+            let corners = [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)]
+                .map(|(u, v)| origin + (tangent * u + bitangent * v) * bounding_sphere.radius);
+            for i in 0..corners.len() {
+                debug_lines.line(
+                    corners[i],
+                    corners[(i + 1) % corners.len()],
+                    Vec3::new(0., 0.8, 1.),
+                );
+            }
+        }
     }
 }
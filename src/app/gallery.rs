@@ -0,0 +1,358 @@
+use std::{cell::RefCell, collections::HashMap, fs, path::Path, rc::Rc};
+
+use ash::vk;
+use math::{Vec2, Vec3, Vec4};
+use winit::{event::MouseButton, keyboard::KeyCode};
+
+use crate::{
+    engine::{
+        bookmarks::CameraBookmarks,
+        camera::Camera,
+        input::FrameInput,
+        mesh::{Mesh, Vertex},
+        Engine, EngineConfig, GameObject, Transform,
+    },
+    ensure,
+    parsing::{
+        read_frag_spv_file, read_obj_files_with, read_tga_r8g8b8a8_srgb_file, read_vert_spv_file,
+        resolve_asset, ObjImportOptions, ProgressBar,
+    },
+    renderer::{
+        Material, MaterialInstance, MaterialInstanceRef, MaterialParams, ScopDescriptorSetLayout,
+        ScopRenderTarget,
+    },
+    utils::Result,
+};
+
+const RESOURCES_DIRECTORY: &str = "./resources";
+const THUMBNAIL_SIZE: u32 = 256;
+// Distance between two thumbnail centers, quads are 2 units wide
+const GRID_SPACING: f32 = 2.2;
+
+struct GalleryEntry {
+    name: String,
+    mesh: Rc<Mesh>,
+    thumbnail: ScopRenderTarget,
+    thumbnail_material: MaterialInstanceRef,
+    grid_position: Vec3,
+}
+
+#[derive(Default)]
+pub struct AppGallery {
+    // Model shown in full, the grid is shown when None
+    selected: Option<usize>,
+}
+
+impl AppGallery {
+    pub fn start(&mut self, config: EngineConfig) -> Result<()> {
+        let mut engine = Engine::new(config)?;
+
+        // --------------------
+        // Meshs
+        // --------------------
+
+        let mut paths: Vec<String> = fs::read_dir(resolve_asset(RESOURCES_DIRECTORY)?)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case("obj"))
+            })
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        paths.sort();
+
+        let mut meshes = Vec::with_capacity(paths.len());
+        let progress = ProgressBar::new("models");
+        let loaded = read_obj_files_with(
+            &engine,
+            &paths,
+            &ObjImportOptions::default(),
+            &|stage, fraction| progress.update(stage, fraction),
+        );
+        for (path, mesh) in paths.iter().zip(loaded) {
+            match mesh {
+                Ok(mesh) => meshes.push((path, mesh)),
+                Err(e) => eprintln!("{}: skipped, {}", path, e),
+            }
+        }
+        ensure!(
+            !meshes.is_empty(),
+            "No model could be loaded from ./resources"
+        );
+
+        // --------------------
+        // Textures
+        // --------------------
+
+        let mut texture_ponies = read_tga_r8g8b8a8_srgb_file(&engine, "./textures/ponies.tga")?;
+
+        // --------------------
+        // Shaders
+        // --------------------
+
+        let vert_shader = read_vert_spv_file(&engine, "./shaders/default.vert.spv")?;
+
+        let frag_shader = read_frag_spv_file(&engine, "./shaders/default.frag.spv")?;
+
+        let thumbnail_vert_shader = read_vert_spv_file(&engine, "./shaders/default.vert.spv")?;
+
+        let thumbnail_frag_shader = read_frag_spv_file(&engine, "./shaders/default.frag.spv")?;
+
+        // --------------------
+        // Materials
+        // --------------------
+
+        let material = Material::new(
+            &engine.renderer,
+            vec![
+                ScopDescriptorSetLayout::builder(&engine.renderer.main_device)
+                    .add_texture_binding(0, vk::ShaderStageFlags::FRAGMENT)
+                    .add_texture_binding(1, vk::ShaderStageFlags::FRAGMENT)
+                    .build()?,
+            ],
+            MaterialParams::builder()
+                .vec4("tint", Vec4::one())
+                .float("texture_blend", 0.)
+                .build()?,
+            &vert_shader,
+            &frag_shader,
+            false,
+        )?;
+
+        let thumbnail_material = Material::new(
+            &engine.renderer,
+            vec![
+                ScopDescriptorSetLayout::builder(&engine.renderer.main_device)
+                    .add_texture_binding(0, vk::ShaderStageFlags::FRAGMENT)
+                    .add_texture_binding(1, vk::ShaderStageFlags::FRAGMENT)
+                    .build()?,
+            ],
+            MaterialParams::builder()
+                .vec4("tint", Vec4::one())
+                .float("texture_blend", 0.)
+                .build()?,
+            &thumbnail_vert_shader,
+            &thumbnail_frag_shader,
+            true,
+        )?;
+
+        let material_instance_ponies =
+            MaterialInstance::instanciate(&engine.renderer, material.clone())?;
+        material_instance_ponies.set_textures(&texture_ponies, &texture_ponies)?;
+
+        // --------------------
+        // Grid
+        // --------------------
+
+        let columns = (meshes.len() as f32).sqrt().ceil() as usize;
+        let rows = meshes.len().div_ceil(columns);
+
+        let mut grid_camera = Camera::empty();
+        let aspect = engine.renderer.window.aspect_ratio();
+        grid_camera.set_perspective_projection(60.0, aspect, 1.0, 100.0);
+        let grid_radius = Vec2::new(columns as f32, rows as f32).length() * GRID_SPACING / 2.;
+        grid_camera.frame_sphere(Vec3::default(), grid_radius, Vec3::forward(), Vec3::up());
+
+        // Quads face the camera, right and down follow the screen axes
+        let inverse_view = grid_camera.get_inverse_view();
+        let right = Vec3::new(inverse_view[0][0], inverse_view[0][1], inverse_view[0][2]);
+        let down = Vec3::new(inverse_view[1][0], inverse_view[1][1], inverse_view[1][2]);
+
+        let quad_vertices: Vec<Vertex> = [(0., 0.), (1., 0.), (1., 1.), (0., 1.)]
+            .into_iter()
+            .map(|(u, v)| Vertex {
+                position: right * (u * 2. - 1.) + down * (v * 2. - 1.),
+                color: Vec3::one(),
+                uv: Vec2::new(u, v),
+                ..Default::default()
+            })
+            .collect();
+        let quad_mesh = Rc::new(
+            Mesh::builder(engine.renderer.main_device.clone())
+                .staging_ring(&mut engine.renderer.staging_ring.borrow_mut())
+                .vertices(&quad_vertices)
+                .indices(&[0, 1, 2, 2, 3, 0])
+                .build()?,
+        );
+
+        // --------------------
+        // Thumbnails
+        // --------------------
+
+        let mut entries = Vec::with_capacity(meshes.len());
+        for (i, (path, mesh)) in meshes.into_iter().enumerate() {
+            let mut thumbnail =
+                ScopRenderTarget::new(&engine.renderer, THUMBNAIL_SIZE, THUMBNAIL_SIZE)?;
+            let game_object = Rc::new(RefCell::new(Self::model_object(
+                &mesh,
+                &material_instance_ponies,
+            )));
+
+            let mut camera = Camera::empty();
+            camera.set_reverse_z(engine.renderer.config.reverse_z);
+            camera.set_handedness(engine.renderer.handedness());
+            let bounding_sphere = mesh
+                .bounding_sphere
+                .transform(&game_object.borrow().local_matrix());
+            let radius = bounding_sphere.radius.max(f32::EPSILON);
+            camera.set_perspective_projection(45.0, 1.0, radius * 0.1, radius * 10.);
+            camera.frame_sphere(bounding_sphere.center, radius, Vec3::forward(), Vec3::up());
+
+            engine.renderer.flat_texture_interpolation = 0.;
+            engine.renderer.render_to_target(
+                &camera,
+                &HashMap::from([(0, game_object)]),
+                &mut thumbnail,
+            )?;
+
+            let thumbnail_material =
+                MaterialInstance::instanciate(&engine.renderer, thumbnail_material.clone())?;
+            thumbnail_material.set_textures(&thumbnail.texture, &thumbnail.texture)?;
+
+            let (column, row) = (i % columns, i / columns);
+            entries.push(GalleryEntry {
+                name: Path::new(path)
+                    .file_name()
+                    .map_or(path.clone(), |name| name.to_string_lossy().into_owned()),
+                mesh,
+                thumbnail,
+                thumbnail_material,
+                grid_position: right * ((column as f32 - (columns - 1) as f32 / 2.) * GRID_SPACING)
+                    + down * ((row as f32 - (rows - 1) as f32 / 2.) * GRID_SPACING),
+            });
+        }
+
+        // --------------------
+        // Logic
+        // --------------------
+
+        engine.bookmarks = Some(CameraBookmarks::load("gallery")?);
+
+        let grid_pose = grid_camera.get_pose();
+        let mut camera = grid_camera;
+        self.show_grid(&mut engine, &entries, &quad_mesh);
+
+        engine.run(
+            &mut camera,
+            |engine, camera, input, _image_index| match self.selected {
+                None => {
+                    if let Some(selected) = Self::pick(engine, camera, input, &entries) {
+                        let entry = &entries[selected];
+                        println!("{}", entry.name);
+
+                        self.selected = Some(selected);
+                        let go = Self::show_model(engine, entry, &material_instance_ponies);
+                        let bounding_sphere = entry
+                            .mesh
+                            .bounding_sphere
+                            .transform(&go.borrow().local_matrix());
+                        camera.frame_sphere(
+                            bounding_sphere.center,
+                            bounding_sphere.radius,
+                            Vec3::forward(),
+                            Vec3::up(),
+                        );
+                    }
+                }
+                Some(_) => {
+                    if input.key_pressed(KeyCode::Backspace) {
+                        self.selected = None;
+                        camera.set_pose(&grid_pose);
+                        self.show_grid(engine, &entries, &quad_mesh);
+                    } else {
+                        engine.game_objects.values_mut().for_each(|e| {
+                            e.borrow_mut().transform_mut().rotation.y += 0.02;
+                        });
+                    }
+                }
+            },
+        )?;
+
+        engine.renderer.wait_gpu();
+
+        engine.game_objects.clear();
+        for entry in &mut entries {
+            entry.thumbnail.cleanup();
+        }
+        texture_ponies.cleanup();
+
+        Ok(())
+    }
+
+    fn model_object(mesh: &Rc<Mesh>, material: &MaterialInstanceRef) -> GameObject {
+        let mut game_object = GameObject::default();
+        game_object.mesh = Some(mesh.clone());
+        game_object.material = Some(material.clone());
+        game_object.set_transform(Transform {
+            pivot: mesh.bounding_box.get_middle_point(),
+            ..Default::default()
+        });
+        game_object
+    }
+
+    fn show_grid(&self, engine: &mut Engine, entries: &[GalleryEntry], quad_mesh: &Rc<Mesh>) {
+        engine.game_objects.clear();
+        for entry in entries {
+            let mut game_object = GameObject::default();
+            game_object.name = Some(entry.name.clone());
+            game_object.mesh = Some(quad_mesh.clone());
+            game_object.material = Some(entry.thumbnail_material.clone());
+            game_object.set_transform(Transform {
+                translation: entry.grid_position,
+                ..Default::default()
+            });
+            engine.register(game_object);
+        }
+
+        // Thumbnails are unlit and only show their texture
+        engine.renderer.flat_texture_interpolation = 1.;
+        engine.title = String::from("gallery");
+    }
+
+    fn show_model(
+        engine: &mut Engine,
+        entry: &GalleryEntry,
+        material: &MaterialInstanceRef,
+    ) -> Rc<RefCell<GameObject>> {
+        engine.game_objects.clear();
+        let mut game_object = Self::model_object(&entry.mesh, material);
+        game_object.name = Some(entry.name.clone());
+
+        engine.renderer.flat_texture_interpolation = 0.;
+        engine.title = entry.name.clone();
+        engine.register(game_object)
+    }
+
+    // Index of the thumbnail under the cursor when clicked
+    fn pick(
+        engine: &Engine,
+        camera: &Camera,
+        input: &FrameInput,
+        entries: &[GalleryEntry],
+    ) -> Option<usize> {
+        if !input.mouse_pressed(MouseButton::Left) {
+            return None;
+        }
+        let (x, y) = input.cursor()?;
+        let (origin, direction) =
+            camera.screen_to_ray(Vec2::new(x, y), engine.renderer.window.viewport_size())?;
+
+        let (material, _) = engine
+            .game_objects
+            .values()
+            .filter_map(|go| {
+                let go = go.borrow();
+                let hit = go
+                    .mesh
+                    .as_ref()?
+                    .raycast(origin, direction, &go.world_transform())?;
+                Some((go.material.clone()?, hit.distance))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))?;
+
+        entries
+            .iter()
+            .position(|entry| Rc::ptr_eq(&entry.thumbnail_material, &material))
+    }
+}
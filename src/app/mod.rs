@@ -1,3 +1,8 @@
 pub mod samourai;
 pub mod objects;
 pub mod custom;
+pub mod convert;
+pub mod gallery;
+pub mod preview;
+pub mod showcase;
+pub mod test_scene;
@@ -1,118 +1,169 @@
-use ash::vk;
-use math::Vec3;
-use winit::keyboard::{Key, KeyCode, NamedKey};
+use std::rc::Rc;
+
+use math::{Vec2, Vec3, Vec4};
+use winit::{event::MouseButton, keyboard::KeyCode};
 
 use crate::{
-    engine::{camera::Camera, Engine, GameObject, Transform},
-    parsing::{read_frag_spv_file, read_obj_file, read_tga_r8g8b8a8_srgb_file, read_vert_spv_file},
-    renderer::{Material, MaterialInstance, ScopDescriptorSetLayout}, utils::Result,
+    engine::{
+        camera::{Camera, CameraPose},
+        input::{FrameInput, InputExt},
+        mesh::{uv_sphere, Mesh, MorphTarget},
+        motion::{Orbit, Spin},
+        Engine, EngineConfig, GameObject, GameObjectId, Transform,
+    },
+    parsing::read_obj_file,
+    renderer::{Background, MaterialInstanceRef, Morph, PictureInPicture, PipClear},
+    utils::Result,
 };
 
+use super::showcase::ShowcaseApp;
+
 #[derive(Default)]
 pub struct AppObjects {
-    last_frame_move: u32,
-    texture_target_fade: f32,
-    texture_change_frame: u32,
+    // Earth and Mars trade their textures
+    planets_swapped: bool,
+    planet_blend: f32,
+    // Clicked object, Tab changes its material. Also shown alone in a
+    // corner of the screen.
+    selected: Option<GameObjectId>,
+    // Toggled by M
+    material_preview: Option<MaterialPreview>,
 }
 
-impl AppObjects {
-    pub fn start(&mut self) -> Result<()> {
-        let mut engine = Engine::new()?;
-
-        // --------------------
-        // Meshs
-        // --------------------
-
-        let mesh_sphere = read_obj_file(&engine, "./resources/sphere.obj")?;
-
-        let mesh_42 = read_obj_file(&engine, "./resources/42.obj")?;
-
-        let mesh_teapot_1 = read_obj_file(&engine, "./resources/teapot.obj")?;
-
-        let mesh_teapot_2 = read_obj_file(&engine, "./resources/teapot2.obj")?;
-
-        // --------------------
-        // Textures
-        // --------------------
-
-        let mut texture_earth = read_tga_r8g8b8a8_srgb_file(&engine, "./textures/earth.tga")?;
-
-        let mut texture_mars = read_tga_r8g8b8a8_srgb_file(&engine, "./textures/mars.tga")?;
-
-        let mut texture_ponies = read_tga_r8g8b8a8_srgb_file(&engine, "./textures/ponies.tga")?;
+// Only a smooth sphere wearing the material of the selected object is shown,
+// in front of a neutral background, so the material is judged apart from the
+// uvs of the models. What it hides is restored when it ends.
+struct MaterialPreview {
+    background: Background,
+    camera: CameraPose,
+    hidden: Vec<GameObjectId>,
+}
 
-        // --------------------
-        // Shaders
-        // --------------------
+const PREVIEW_SPHERE: &str = "Material preview";
+const PREVIEW_BACKGROUND: Background = Background::Environment {
+    sky: Vec3 {
+        x: 0.55,
+        y: 0.55,
+        z: 0.57,
+    },
+    horizon: Vec3 {
+        x: 0.42,
+        y: 0.42,
+        z: 0.43,
+    },
+    ground: Vec3 {
+        x: 0.22,
+        y: 0.22,
+        z: 0.22,
+    },
+};
 
-        let vert_shader = read_vert_spv_file(&engine, "./shaders/default.vert.spv")?;
+impl AppObjects {
+    pub fn start(&mut self, config: EngineConfig) -> Result<()> {
+        let mut app = ShowcaseApp::builder(config).name("objects").build()?;
+        let engine = &mut app.engine;
+
+        let mesh_sphere = read_obj_file(engine, "./resources/sphere.obj")?;
+
+        // Flattened at the poles, Mars breathes through it
+        let squash = MorphTarget {
+            name: String::from("squash"),
+            position_deltas: mesh_sphere
+                .vertices
+                .iter()
+                .map(|v| {
+                    Vec3::new(
+                        v.position.x * 0.15,
+                        v.position.y * -0.3,
+                        v.position.z * 0.15,
+                    )
+                })
+                .collect(),
+            normal_deltas: vec![],
+        };
+        let mesh_sphere_squash = Rc::new(
+            Mesh::builder(engine.renderer.main_device.clone())
+                .staging_ring(&mut engine.renderer.staging_ring.borrow_mut())
+                .vertices(&mesh_sphere.vertices)
+                .indices(&mesh_sphere.indices)
+                .bvh(true)
+                .morph_targets(&[squash])
+                .build()?,
+        );
 
-        let frag_shader = read_frag_spv_file(&engine, "./shaders/default.frag.spv")?;
+        let mesh_42 = read_obj_file(engine, "./resources/42.obj")?;
 
-        // --------------------
-        // Materials
-        // --------------------
-
-        let set_layouts = vec![
-            ScopDescriptorSetLayout::builder(&engine.renderer.main_device)
-                .add_texture_binding(0, vk::ShaderStageFlags::FRAGMENT)
+        let (preview_vertices, preview_indices) = uv_sphere(1., 128, 64);
+        let mesh_preview_sphere = Rc::new(
+            Mesh::builder(engine.renderer.main_device.clone())
+                .staging_ring(&mut engine.renderer.staging_ring.borrow_mut())
+                .vertices(&preview_vertices)
+                .indices(&preview_indices)
                 .build()?,
-        ];
+        );
 
-        let material = Material::new(&engine.renderer, set_layouts, &vert_shader, &frag_shader)?;
+        let mesh_teapot_1 = read_obj_file(engine, "./resources/teapot.obj")?;
 
-        // --------------------
-        // Material instances
-        // --------------------
+        let mesh_teapot_2 = read_obj_file(engine, "./resources/teapot2.obj")?;
 
         let material_instance_earth =
-            MaterialInstance::instanciate(&engine.renderer, material.clone())?;
-        material_instance_earth
-            .writer(0)
-            .set_texture2d(0, &texture_earth)
-            .write();
-
+            app.material_instance("./textures/earth.tga", "./textures/mars.tga")?;
         let material_instance_ponies =
-            MaterialInstance::instanciate(&engine.renderer, material.clone())?;
-        material_instance_ponies
-            .writer(0)
-            .set_texture2d(0, &texture_ponies)
-            .write();
-
+            app.material_instance("./textures/ponies.tga", "./textures/ponies.tga")?;
         let material_instance_mars =
-            MaterialInstance::instanciate(&engine.renderer, material.clone())?;
-        material_instance_mars
-            .writer(0)
-            .set_texture2d(0, &texture_mars)
-            .write();
+            app.material_instance("./textures/mars.tga", "./textures/earth.tga")?;
+        let engine = &mut app.engine;
+
+        // Cycled through by Tab on the selected object
+        let material_instances = [
+            ("earth", material_instance_earth.clone()),
+            ("mars", material_instance_mars.clone()),
+            ("ponies", material_instance_ponies.clone()),
+        ];
 
         // --------------------
         // GameObjects
         // --------------------
 
-        let go = GameObject::builder(&mut engine)
+        // The planets face each other across the orbit and spin on themselves
+        let planets_orbit = Orbit::new(Vec3::new(0., -7., 0.), 7., 0.3);
+        let planets_spin = Spin::new(Vec3::up() * 0.6);
+
+        GameObject::builder(engine)
             .name("Earth")
+            .tag("planet")
             .mesh(mesh_sphere.clone())
             .material(material_instance_earth.clone())
             .transform(Transform {
+                translation: planets_orbit.position(),
                 scale: Vec3::one() * 2.,
                 ..Default::default()
             })
-            .build();
-        go.borrow_mut().transform.translation = Vec3::from([7., -7., 0.]);
-
-        let go = GameObject::builder(&mut engine)
+            .component(planets_orbit)
+            .component(planets_spin)
+            .build()?;
+
+        let morph_mars = Morph::new(&engine.renderer, &mesh_sphere_squash)?;
+        let mars_orbit = Orbit {
+            angle: std::f32::consts::PI,
+            ..planets_orbit
+        };
+        let mars = GameObject::builder(engine)
             .name("Mars")
-            .mesh(mesh_sphere.clone())
+            .tag("planet")
+            .mesh(mesh_sphere_squash.clone())
             .material(material_instance_mars.clone())
+            .morph(morph_mars)
             .transform(Transform {
+                translation: mars_orbit.position(),
                 scale: Vec3::one() * 1.5,
                 ..Default::default()
             })
-            .build();
-        go.borrow_mut().transform.translation = Vec3::from([-7., -7., 0.]);
+            .component(mars_orbit)
+            .component(planets_spin)
+            .build()?;
 
-        let go = GameObject::builder(&mut engine)
+        let go = GameObject::builder(engine)
             .name("42")
             .mesh(mesh_42.clone())
             .material(material_instance_ponies.clone())
@@ -122,10 +173,10 @@ impl AppObjects {
                 rotation: Vec3::up() * std::f32::consts::PI * 1.5,
                 ..Default::default()
             })
-            .build();
-        go.borrow_mut().transform.translation = Vec3::from([0., 0., 0.]);
+            .build()?;
+        go.borrow_mut().transform_mut().translation = Vec3::from([0., 0., 0.]);
 
-        let go = GameObject::builder(&mut engine)
+        let go = GameObject::builder(engine)
             .name("Teapot 1")
             .mesh(mesh_teapot_1.clone())
             .material(material_instance_ponies.clone())
@@ -133,10 +184,10 @@ impl AppObjects {
                 pivot: mesh_teapot_1.bounding_box.get_middle_point(),
                 ..Default::default()
             })
-            .build();
-        go.borrow_mut().transform.translation = Vec3::from([7., 7., 0.]);
+            .build()?;
+        go.borrow_mut().transform_mut().translation = Vec3::from([7., 7., 0.]);
 
-        let go = GameObject::builder(&mut engine)
+        let go = GameObject::builder(engine)
             .name("Teapot 2")
             .mesh(mesh_teapot_2.clone())
             .material(material_instance_ponies.clone())
@@ -144,93 +195,217 @@ impl AppObjects {
                 pivot: mesh_teapot_2.bounding_box.get_middle_point(),
                 ..Default::default()
             })
-            .build();
-        go.borrow_mut().transform.translation = Vec3::from([-7., 7., 0.]);
+            .build()?;
+        go.borrow_mut().transform_mut().translation = Vec3::from([-7., 7., 0.]);
 
-        // --------------------
-        // Logic
-        // --------------------
-
-        let mut camera = Camera::empty();
-        let aspect = engine.renderer.window.window.inner_size().width as f32
-            / engine.renderer.window.window.inner_size().height as f32;
-        camera.set_perspective_projection(60.0, aspect, 1.0, 100.0);
-        camera.set_view_target([0.0, 0.0, 20.0].into(), Vec3::default(), Vec3::up());
-        
-        engine.run(&camera, |engine, input, _image_index| {
-            let mut movement = Vec3::default();
-            let mut rotation = Vec3::default();
-            if input.key_held_logical(Key::Named(NamedKey::ArrowLeft)) {
-                rotation.y -= 0.02;
-                self.last_frame_move = engine.renderer.frame_count;
-            }
-            if input.key_held_logical(Key::Named(NamedKey::ArrowRight)) {
-                rotation.y += 0.02;
-                self.last_frame_move = engine.renderer.frame_count;
-            }
-            if input.key_held_logical(Key::Named(NamedKey::ArrowUp)) {
-                rotation.z += 0.02;
-                self.last_frame_move = engine.renderer.frame_count;
-            }
-            if input.key_held_logical(Key::Named(NamedKey::ArrowDown)) {
-                rotation.z -= 0.02;
-                self.last_frame_move = engine.renderer.frame_count;
-            }
-            if input.key_held(KeyCode::KeyA) {
-                movement.x -= 0.084;
-            }
-            if input.key_held(KeyCode::KeyD) {
-                movement.x += 0.084;
-            }
-            if input.key_held(KeyCode::KeyW) {
-                movement.z -= 0.084;
-            }
-            if input.key_held(KeyCode::KeyS) {
-                movement.z += 0.084;
-            }
-            if input.key_held(KeyCode::KeyQ) {
-                movement.y -= 0.084;
+        GameObject::builder(engine)
+            .name(PREVIEW_SPHERE)
+            .mesh(mesh_preview_sphere)
+            .material(material_instance_earth.clone())
+            .enabled(false)
+            .build()?;
+
+        app.camera
+            .set_view_target([0.0, 0.0, 20.0].into(), Vec3::default(), Vec3::up());
+
+        let mut inspection_camera = Camera::empty();
+        inspection_camera.set_reverse_z(app.engine.renderer.config.reverse_z);
+        inspection_camera.set_handedness(app.engine.renderer.handedness());
+        let mut inspection = PictureInPicture::new(&app.engine.renderer, inspection_camera)?;
+        inspection.enabled = false;
+        inspection.clear = PipClear::Color(Vec3::new(0.1, 0.1, 0.12));
+        app.engine.renderer.pictures_in_picture.push(inspection);
+
+        app.run(|engine, camera, input, controls| {
+            self.update_selection(engine, camera, input, &material_instances);
+            if input.key_pressed(KeyCode::KeyM) {
+                self.toggle_material_preview(engine, camera);
             }
-            if input.key_held(KeyCode::KeyE) {
-                movement.y += 0.084;
+            self.update_material_preview(engine);
+            self.update_inspection(engine, camera);
+
+            input.toggled(KeyCode::KeyB, &mut self.planets_swapped);
+            let blend_target = if self.planets_swapped { 1. } else { 0. };
+            if self.planet_blend != blend_target {
+                self.planet_blend = (self.planet_blend
+                    + (blend_target - self.planet_blend).signum() * 0.016)
+                    .clamp(0., 1.);
+                for planet in [&material_instance_earth, &material_instance_mars] {
+                    if let Err(e) = planet.set_param("texture_blend", self.planet_blend) {
+                        eprintln!("{}", e);
+                    }
+                }
             }
 
-            if input.key_pressed_logical(Key::Character(&"t")) {
-                self.texture_target_fade = if self.texture_target_fade == 1. {
-                    0.
-                } else {
-                    1.
-                };
-                self.texture_change_frame = engine.renderer.frame_count;
-            }
+            controls.move_objects(engine);
 
-            if self.last_frame_move == 0 || engine.renderer.frame_count - self.last_frame_move > 200
-            {
-                rotation.y += 0.02;
+            if let Some(morph) = &mut mars.borrow_mut().morph {
+                morph.set_weight(0, 0.5 + 0.5 * (engine.renderer.time() * 1.5).sin());
             }
 
-            if engine.renderer.flat_texture_interpolation < self.texture_target_fade {
-                engine.renderer.flat_texture_interpolation =
-                    (engine.renderer.flat_texture_interpolation + 0.016).clamp(0., 1.);
-            } else if engine.renderer.flat_texture_interpolation > self.texture_target_fade {
-                engine.renderer.flat_texture_interpolation =
-                    (engine.renderer.flat_texture_interpolation - 0.016).clamp(0., 1.);
+            // Mars slowly glows, the other instances keep their tint
+            let glow = 0.8 + 0.2 * (engine.renderer.frame_count as f32 * 0.03).sin();
+            if let Err(e) = material_instance_mars.set_param("tint", Vec4::new(1., glow, glow, 1.))
+            {
+                eprintln!("{}", e);
             }
+        })
+    }
 
-            engine.game_objects.values_mut().for_each(|e| {
-                e.borrow_mut().transform.rotation += rotation;
-                e.borrow_mut().transform.translation += movement;
-            });
-        })?;
+    fn toggle_material_preview(&mut self, engine: &mut Engine, camera: &mut Camera) {
+        let Some(sphere_id) = engine.find_by_name(PREVIEW_SPHERE) else {
+            return;
+        };
 
-        engine.renderer.wait_gpu();
+        if let Some(preview) = self.material_preview.take() {
+            for id in preview.hidden {
+                if let Some(go) = engine.game_objects.get(&id) {
+                    go.borrow_mut().enabled = true;
+                }
+            }
+            if let Some(sphere) = engine.game_objects.get(&sphere_id) {
+                sphere.borrow_mut().enabled = false;
+            }
+            engine.renderer.set_background(preview.background);
+            camera.set_pose(&preview.camera);
+            println!("Material preview disabled");
+            return;
+        }
+
+        let hidden = engine
+            .game_objects
+            .iter()
+            .filter(|(_, go)| go.borrow().enabled)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in &hidden {
+            engine.game_objects[id].borrow_mut().enabled = false;
+        }
+        let sphere = &engine.game_objects[&sphere_id];
+        {
+            let mut sphere = sphere.borrow_mut();
+            sphere.enabled = true;
+            *sphere.transform_mut() = Transform::default();
+        }
+        self.material_preview = Some(MaterialPreview {
+            background: engine.renderer.background(),
+            camera: camera.get_pose(),
+            hidden,
+        });
+        engine.renderer.set_background(PREVIEW_BACKGROUND);
+        camera.frame_sphere(Vec3::default(), 1.2, Vec3::forward(), Vec3::up());
+        println!("Material preview enabled, Tab changes the material of the selected object");
+    }
 
-        texture_earth.cleanup();
-        texture_mars.cleanup();
-        texture_ponies.cleanup();
+    // The sphere follows the material of the selection
+    fn update_material_preview(&self, engine: &Engine) {
+        if self.material_preview.is_none() {
+            return;
+        }
+        let material = self
+            .selected
+            .and_then(|id| engine.game_objects.get(&id))
+            .and_then(|go| go.borrow().material.clone());
+        if let (Some(material), Some(sphere)) = (
+            material,
+            engine
+                .find_by_name(PREVIEW_SPHERE)
+                .and_then(|id| engine.game_objects.get(&id)),
+        ) {
+            sphere.borrow_mut().material = Some(material);
+        }
+    }
 
-        engine.game_objects.clear();
+    // Frames the selected object, seen from the main camera
+    fn update_inspection(&self, engine: &mut Engine, camera: &Camera) {
+        let extent = engine.renderer.render_extent();
+        let Some(inspection) = engine.renderer.pictures_in_picture.first_mut() else {
+            return;
+        };
+        let sphere = self
+            .selected
+            .and_then(|id| engine.game_objects.get(&id))
+            .and_then(|go| {
+                let go = go.borrow();
+                let sphere = go.mesh.as_ref()?.bounding_sphere;
+                Some(sphere.transform(&go.world_transform()))
+            });
+        inspection.enabled = sphere.is_some() && self.material_preview.is_none();
+        let (Some(id), Some(sphere)) = (self.selected, sphere) else {
+            return;
+        };
+
+        inspection.objects = vec![id];
+        let radius = sphere.radius.max(f32::EPSILON);
+        inspection.camera.set_perspective_projection(
+            40.,
+            inspection.aspect_ratio(extent),
+            radius * 0.05,
+            radius * 10.,
+        );
+        inspection.camera.frame_sphere(
+            sphere.center,
+            radius,
+            sphere.center - camera.get_position(),
+            Vec3::up(),
+        );
+    }
 
-        Ok(())
+    // A click selects the object under the cursor, or nothing in the void
+    fn update_selection(
+        &mut self,
+        engine: &Engine,
+        camera: &Camera,
+        input: &FrameInput,
+        material_instances: &[(&str, MaterialInstanceRef)],
+    ) {
+        // The material preview shows the selection, clicks keep it
+        if input.mouse_pressed(MouseButton::Left) && self.material_preview.is_none() {
+            let ray = input.cursor().and_then(|(x, y)| {
+                camera.screen_to_ray(Vec2::new(x, y), engine.renderer.window.viewport_size())
+            });
+            self.selected = ray.and_then(|(origin, direction)| {
+                engine
+                    .game_objects
+                    .iter()
+                    .filter_map(|(id, go)| {
+                        let go = go.borrow();
+                        let hit =
+                            go.mesh
+                                .as_ref()?
+                                .raycast(origin, direction, &go.world_transform())?;
+                        Some((*id, hit.distance))
+                    })
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|(id, _)| id)
+            });
+            if let Some(go) = self.selected.and_then(|id| engine.game_objects.get(&id)) {
+                println!(
+                    "Selected {}, Tab changes its material",
+                    go.borrow().name.as_deref().unwrap_or("object")
+                );
+            }
+        }
+
+        if !input.key_pressed(KeyCode::Tab) {
+            return;
+        }
+        let Some(go) = self.selected.and_then(|id| engine.game_objects.get(&id)) else {
+            return;
+        };
+        let mut go = go.borrow_mut();
+        let current = material_instances.iter().position(|(_, instance)| {
+            go.material
+                .as_ref()
+                .is_some_and(|material| Rc::ptr_eq(material, instance))
+        });
+        let (name, instance) =
+            &material_instances[current.map_or(0, |i| (i + 1) % material_instances.len())];
+        go.material = Some(instance.clone());
+        println!(
+            "{}: {} material",
+            go.name.as_deref().unwrap_or("object"),
+            name
+        );
     }
 }
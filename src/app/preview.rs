@@ -0,0 +1,107 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use ash::vk;
+use math::{Vec3, Vec4};
+
+use crate::{
+    engine::{camera::Camera, Engine, EngineConfig, GameObject, Transform},
+    ensure,
+    parsing::{read_frag_spv_file, read_obj_file, read_tga_r8g8b8a8_srgb_file, read_vert_spv_file},
+    renderer::{
+        Background, Material, MaterialInstance, MaterialParams, ScopDescriptorSetLayout,
+        ScopRenderTarget, ScopTexture2D,
+    },
+    utils::Result,
+};
+
+// Neutral gray, does not tint the model colors
+const STUDIO_BACKGROUND: Vec3 = Vec3 {
+    x: 0.3,
+    y: 0.3,
+    z: 0.32,
+};
+// Space left around the model, relative to its bounding sphere
+const FRAMING_MARGIN: f32 = 1.1;
+
+// RGBA8 image of `size` by `size` pixels, rows from top to bottom, showing
+// the model from slightly above. Without texture, the vertex colors are shown.
+// Opens a hidden window, the renderer cannot run without a surface.
+pub fn render_preview(obj_path: &str, texture_path: Option<&str>, size: u32) -> Result<Vec<u8>> {
+    ensure!(size > 0, "The preview size must not be 0");
+
+    let mut config = EngineConfig::default();
+    config.renderer.hidden = true;
+    let mut engine = Engine::new(config)?;
+
+    let mesh = read_obj_file(&engine, obj_path)?;
+    let mut texture = match texture_path {
+        Some(path) => read_tga_r8g8b8a8_srgb_file(&engine, path)?,
+        None => ScopTexture2D::checkerboard(
+            engine.renderer.main_device.clone(),
+            &mut engine.renderer.staging_ring.borrow_mut(),
+            8,
+            [Vec3::one(), Vec3::one()],
+        )?,
+    };
+
+    let vert_shader = read_vert_spv_file(&engine, "./shaders/default.vert.spv")?;
+    let frag_shader = read_frag_spv_file(&engine, "./shaders/default.frag.spv")?;
+    let material = Material::new(
+        &engine.renderer,
+        vec![
+            ScopDescriptorSetLayout::builder(&engine.renderer.main_device)
+                .add_texture_binding(0, vk::ShaderStageFlags::FRAGMENT)
+                .add_texture_binding(1, vk::ShaderStageFlags::FRAGMENT)
+                .build()?,
+        ],
+        MaterialParams::builder()
+            .vec4("tint", Vec4::one())
+            .float("texture_blend", 0.)
+            .build()?,
+        &vert_shader,
+        &frag_shader,
+        mesh.inconsistent_winding_ratio() > 0.,
+    )?;
+    let material_instance = MaterialInstance::instanciate(&engine.renderer, material)?;
+    material_instance.set_textures(&texture, &texture)?;
+
+    let mut game_object = GameObject::default();
+    game_object.mesh = Some(mesh.clone());
+    game_object.material = Some(material_instance);
+    game_object.set_transform(Transform {
+        pivot: mesh.bounding_box.get_middle_point(),
+        ..Default::default()
+    });
+
+    let mut camera = Camera::empty();
+    camera.set_reverse_z(engine.renderer.config.reverse_z);
+    camera.set_handedness(engine.renderer.handedness());
+    let bounding_sphere = mesh.bounding_sphere.transform(&game_object.local_matrix());
+    let radius = bounding_sphere.radius.max(f32::EPSILON) * FRAMING_MARGIN;
+    camera.set_perspective_projection(45.0, 1.0, radius * 0.1, radius * 10.);
+    camera.frame_sphere(
+        bounding_sphere.center,
+        radius,
+        Vec3::forward() + Vec3::down() * 0.35,
+        Vec3::up(),
+    );
+
+    engine.renderer.offscreen_render_pass.clear_color =
+        Background::Solid(STUDIO_BACKGROUND).clear_value(engine.renderer.swapchain.is_srgb());
+    engine.renderer.flat_texture_interpolation = if texture_path.is_some() { 1. } else { 0. };
+
+    let mut target = ScopRenderTarget::new(&engine.renderer, size, size)?;
+    let pixels = engine
+        .renderer
+        .render_to_target(
+            &camera,
+            &HashMap::from([(0, Rc::new(RefCell::new(game_object)))]),
+            &mut target,
+        )
+        .and_then(|_| target.read_pixels(&engine.renderer));
+
+    engine.renderer.wait_gpu();
+    target.cleanup();
+    texture.cleanup();
+    pixels
+}
@@ -1,176 +1,105 @@
-use ash::vk;
 use math::Vec3;
-use winit::keyboard::{Key, KeyCode, NamedKey};
+use winit::keyboard::{Key, KeyCode};
 
 use crate::{
-    engine::{camera::Camera, Engine, GameObject, Transform},
-    parsing::{read_frag_spv_file, read_obj_file, read_tga_r8g8b8a8_srgb_file, read_vert_spv_file},
-    renderer::{Material, MaterialInstance, ScopDescriptorSetLayout}, utils::Result,
+    engine::{EngineConfig, GameObject, Transform},
+    parsing::read_obj_file,
+    utils::Result,
 };
 
+use super::showcase::ShowcaseApp;
+
 #[derive(Default)]
 pub struct AppSamourai {
-    texture_target_fade: f32,
-    texture_change_frame: u32,
+    explode_factor: f32,
+    explode_target: f32,
 }
 
 impl AppSamourai {
-    pub fn start(&mut self) -> Result<()> {
-        let mut engine = Engine::new()?;
-
-        // --------------------
-        // Meshs
-        // --------------------
-
-        let mesh_samourai = read_obj_file(&engine, "./resources/samourai2.obj")?;
-
-        let mesh_socle = read_obj_file(&engine, "./resources/socle_samourai.obj")?;
-
-        let mesh_katana = read_obj_file(&engine, "./resources/katana.obj")?;
-
-        // --------------------
-        // Textures
-        // --------------------
-
-        let mut texture_samourai = read_tga_r8g8b8a8_srgb_file(&engine, "./textures/samourai.tga")?;
-
-        let mut texture_katana = read_tga_r8g8b8a8_srgb_file(&engine, "./textures/katana.tga")?;
+    pub fn start(&mut self, config: EngineConfig) -> Result<()> {
+        let mut app = ShowcaseApp::builder(config)
+            .name("samourai")
+            .idle_spin(false)
+            .build()?;
 
-        // --------------------
-        // Shaders
-        // --------------------
-
-        let vert_shader = read_vert_spv_file(&engine, "./shaders/default.vert.spv")?;
-
-        let frag_shader = read_frag_spv_file(&engine, "./shaders/default.frag.spv")?;
-
-        // --------------------
-        // Materials
-        // --------------------
-
-        let set_layouts = vec![
-            ScopDescriptorSetLayout::builder(&engine.renderer.main_device)
-                .add_texture_binding(0, vk::ShaderStageFlags::FRAGMENT)
-                .build()?,
-        ];
-
-        let material = Material::new(&engine.renderer, set_layouts, &vert_shader, &frag_shader)?;
-
-        // --------------------
-        // Material instances
-        // --------------------
+        let mesh_samourai = read_obj_file(&app.engine, "./resources/samourai2.obj")?;
+        let mesh_socle = read_obj_file(&app.engine, "./resources/socle_samourai.obj")?;
+        let mesh_katana = read_obj_file(&app.engine, "./resources/katana.obj")?;
 
         let material_instance_samourai =
-            MaterialInstance::instanciate(&engine.renderer, material.clone())?;
-        material_instance_samourai
-            .writer(0)
-            .set_texture2d(0, &texture_samourai)
-            .write();
-
+            app.material_instance("./textures/samourai.tga", "./textures/samourai.tga")?;
         let material_instance_katana =
-            MaterialInstance::instanciate(&engine.renderer, material.clone())?;
-        material_instance_katana
-            .writer(0)
-            .set_texture2d(0, &texture_katana)
-            .write();
+            app.material_instance("./textures/katana.tga", "./textures/katana.tga")?;
 
-        // --------------------
-        // GameObjects
-        // --------------------
-
-        GameObject::builder(&mut engine)
+        let samourai = GameObject::builder(&mut app.engine)
             .name("Samourai")
             .mesh(mesh_samourai.clone())
             .material(material_instance_samourai.clone())
-            .build();
+            .build()?;
 
-        GameObject::builder(&mut engine)
+        let socle = GameObject::builder(&mut app.engine)
             .name("Socle Samourai")
             .mesh(mesh_socle.clone())
             .material(material_instance_samourai.clone())
-            .build();
+            .build()?;
 
-        GameObject::builder(&mut engine)
+        let katana = GameObject::builder(&mut app.engine)
             .name("Katana")
             .mesh(mesh_katana.clone())
             .material(material_instance_katana.clone())
-            .build();
+            .build()?;
+
+        // Exploded view, each part moves away from the center of the assembly
+        let scene_center = app
+            .engine
+            .world_bounds()
+            .unwrap_or_default()
+            .get_middle_point();
+        let mut parts = [
+            (samourai, mesh_samourai.clone(), Vec3::default()),
+            (socle, mesh_socle.clone(), Vec3::default()),
+            (katana, mesh_katana.clone(), Vec3::default()),
+        ];
 
-        // --------------------
-        // Logic
-        // --------------------
+        app.camera
+            .set_view_direction([0.0, 10.0, 25.0].into(), Vec3::forward(), Vec3::up());
 
-        let mut camera = Camera::empty();
-        let aspect = engine.renderer.window.window.inner_size().width as f32
-            / engine.renderer.window.window.inner_size().height as f32;
-        camera.set_perspective_projection(60.0, aspect, 1.0, 100.0);
-        camera.set_view_direction([0.0, 10.0, 25.0].into(), Vec3::backward(), Vec3::up());
-        
-        engine.run(&camera, |engine, input, _image_index| {
-            let mut movement = Vec3::default();
-            let mut rotation = Vec3::default();
-            if input.key_held_logical(Key::Named(NamedKey::ArrowLeft)) {
-                rotation.y -= 0.02;
-            }
-            if input.key_held_logical(Key::Named(NamedKey::ArrowRight)) {
-                rotation.y += 0.02;
-            }
-            if input.key_held(KeyCode::KeyA) {
-                movement.x -= 0.084;
-            }
-            if input.key_held(KeyCode::KeyD) {
-                movement.x += 0.084;
-            }
-            if input.key_held(KeyCode::KeyW) {
-                movement.z -= 0.084;
-            }
-            if input.key_held(KeyCode::KeyS) {
-                movement.z += 0.084;
-            }
-            if input.key_held(KeyCode::KeyQ) {
-                movement.y -= 0.084;
+        app.run(|engine, _camera, input, controls| {
+            if input.key_pressed_logical(Key::Character("x")) {
+                self.explode_target = if self.explode_target > 0. { 0. } else { 1. };
             }
-            if input.key_held(KeyCode::KeyE) {
-                movement.y += 0.084;
+            if input.key_held(KeyCode::Equal) {
+                self.explode_target = (self.explode_target + 0.02).min(5.);
             }
-
-            if input.key_pressed_logical(Key::Character(&"t")) {
-                self.texture_target_fade = if self.texture_target_fade == 1. {
-                    0.
-                } else {
-                    1.
-                };
-                self.texture_change_frame = engine.renderer.frame_count;
-            }
-
-            if engine.renderer.flat_texture_interpolation < self.texture_target_fade {
-                engine.renderer.flat_texture_interpolation =
-                    (engine.renderer.flat_texture_interpolation + 0.016).clamp(0., 1.);
-            } else if engine.renderer.flat_texture_interpolation > self.texture_target_fade {
-                engine.renderer.flat_texture_interpolation =
-                    (engine.renderer.flat_texture_interpolation - 0.016).clamp(0., 1.);
+            if input.key_held(KeyCode::Minus) {
+                self.explode_target = (self.explode_target - 0.02).max(0.);
             }
+            self.explode_factor += (self.explode_target - self.explode_factor) * 0.1;
 
             if input.key_pressed_logical(Key::Character(&"r")) {
                 engine.game_objects.values_mut().for_each(|e| {
-                    e.borrow_mut().transform = Transform::default();
+                    e.borrow_mut().set_transform(Transform::default());
                 });
+                parts
+                    .iter_mut()
+                    .for_each(|(_, _, offset)| *offset = Vec3::default());
+                self.explode_factor = 0.;
+                self.explode_target = 0.;
                 return;
             }
 
-            engine.game_objects.values_mut().for_each(|e| {
-                e.borrow_mut().transform.rotation += rotation;
-                e.borrow_mut().transform.translation += movement;
-            });
-        })?;
-
-        engine.renderer.wait_gpu();
-
-        texture_samourai.cleanup();
-        texture_katana.cleanup();
-
-        engine.game_objects.clear();
-
-        Ok(())
+            controls.move_objects(engine);
+
+            for (game_object, mesh, offset) in &mut parts {
+                let mut game_object = game_object.borrow_mut();
+                let direction = mesh.bounding_box.get_middle_point() - scene_center;
+                let new_offset = game_object
+                    .transform()
+                    .rotation_matrix()
+                    .transform_vector(direction * self.explode_factor);
+                game_object.transform_mut().translation += new_offset - *offset;
+                *offset = new_offset;
+            }
+        })
     }
 }
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use ash::vk;
+use math::{Vec3, Vec4};
+use winit::keyboard::Key;
+
+use crate::{
+    engine::{
+        bookmarks::CameraBookmarks,
+        camera::Camera,
+        input::{Axis, FrameInput, InputExt},
+        motion::Orbit,
+        Engine, EngineConfig,
+    },
+    parsing::{read_frag_spv_file, read_tga_r8g8b8a8_srgb_file, read_vert_spv_file},
+    renderer::{
+        Material, MaterialInstance, MaterialInstanceRef, MaterialParams, MaterialRef,
+        ScopDescriptorSetLayout, ScopTexture2D,
+    },
+    utils::Result,
+};
+
+// Frames without rotation from the player before the objects spin alone
+const IDLE_FRAMES: u32 = 200;
+const IDLE_SPIN: f32 = 0.02;
+// Per frame, the fade to the flat colors takes about a second
+const TEXTURE_FADE_STEP: f32 = 0.016;
+
+// What the demo scenes share: the engine, the default material, the camera,
+// and the keys they all answer to. A scene adds its meshes and objects, then
+// runs its own logic after the common one.
+pub struct ShowcaseApp {
+    pub camera: Camera,
+    // The default shaders, two textures, a `tint` and a `texture_blend`
+    pub material: MaterialRef,
+    // By path, released with the app
+    textures: HashMap<String, ScopTexture2D>,
+    controls: ShowcaseControls,
+    // Last, the fields above are released before the device
+    pub engine: Engine,
+}
+
+pub struct ShowcaseAppBuilder<'a> {
+    config: EngineConfig,
+    name: &'a str,
+    idle_spin: bool,
+}
+
+// Computed from the input before the scene logic of each frame
+pub struct ShowcaseControls {
+    pub rotation: Vec3,
+    pub movement: Vec3,
+    idle_spin: bool,
+    spinning: bool,
+    last_frame_move: u32,
+    // T fades the textures to flat colors and back
+    texture_target_fade: f32,
+}
+
+impl ShowcaseApp {
+    pub fn builder(config: EngineConfig) -> ShowcaseAppBuilder<'static> {
+        ShowcaseAppBuilder {
+            config,
+            name: "scop",
+            idle_spin: true,
+        }
+    }
+
+    // Made by the builder, a model with inconsistent winding needs another
+    pub fn default_material(engine: &Engine, double_sided: bool) -> Result<MaterialRef> {
+        let vert_shader = read_vert_spv_file(engine, "./shaders/default.vert.spv")?;
+        let frag_shader = read_frag_spv_file(engine, "./shaders/default.frag.spv")?;
+        let set_layouts = vec![
+            ScopDescriptorSetLayout::builder(&engine.renderer.main_device)
+                .add_texture_binding(0, vk::ShaderStageFlags::FRAGMENT)
+                .add_texture_binding(1, vk::ShaderStageFlags::FRAGMENT)
+                .build()?,
+        ];
+        Material::new(
+            &engine.renderer,
+            set_layouts,
+            MaterialParams::builder()
+                .vec4("tint", Vec4::one())
+                .float("texture_blend", 0.)
+                .build()?,
+            &vert_shader,
+            &frag_shader,
+            double_sided,
+        )
+    }
+
+    pub fn texture(&mut self, path: &str) -> Result<&ScopTexture2D> {
+        if !self.textures.contains_key(path) {
+            let texture = read_tga_r8g8b8a8_srgb_file(&self.engine, path)?;
+            self.textures.insert(path.to_string(), texture);
+        }
+        Ok(&self.textures[path])
+    }
+
+    // An instance of `material`, the textures are read once whatever the
+    // number of instances using them
+    pub fn material_instance(
+        &mut self,
+        texture_path: &str,
+        secondary_texture_path: &str,
+    ) -> Result<MaterialInstanceRef> {
+        self.texture(texture_path)?;
+        self.texture(secondary_texture_path)?;
+        let instance = MaterialInstance::instanciate(&self.engine.renderer, self.material.clone())?;
+        instance.set_textures(
+            &self.textures[texture_path],
+            &self.textures[secondary_texture_path],
+        )?;
+        Ok(instance)
+    }
+
+    pub fn run<F: FnMut(&mut Engine, &mut Camera, &FrameInput, &mut ShowcaseControls)>(
+        &mut self,
+        mut on_update: F,
+    ) -> Result<()> {
+        let controls = &mut self.controls;
+        self.engine
+            .run(&mut self.camera, |engine, camera, input, _image_index| {
+                controls.update(engine, input);
+                on_update(engine, camera, input, controls);
+            })
+    }
+}
+
+impl Drop for ShowcaseApp {
+    fn drop(&mut self) {
+        self.engine.renderer.wait_gpu();
+        for texture in self.textures.values_mut() {
+            texture.cleanup();
+        }
+        self.engine.game_objects.clear();
+    }
+}
+
+impl<'a> ShowcaseAppBuilder<'a> {
+    // The window title, and the name the camera bookmarks are saved under
+    pub fn name<'b>(self, name: &'b str) -> ShowcaseAppBuilder<'b> {
+        ShowcaseAppBuilder {
+            config: self.config,
+            name,
+            idle_spin: self.idle_spin,
+        }
+    }
+
+    // The objects spin on themselves when left alone for a while
+    pub fn idle_spin(mut self, idle_spin: bool) -> Self {
+        self.idle_spin = idle_spin;
+        self
+    }
+
+    pub fn build(self) -> Result<ShowcaseApp> {
+        let mut engine = Engine::new(self.config)?;
+        engine.title = self.name.to_string();
+        engine.bookmarks = Some(CameraBookmarks::load(self.name)?);
+
+        let material = ShowcaseApp::default_material(&engine, false)?;
+
+        let mut camera = Camera::empty();
+        let aspect = engine.renderer.window.aspect_ratio();
+        camera.set_perspective_projection(60.0, aspect, 1.0, 100.0);
+
+        Ok(ShowcaseApp {
+            camera,
+            material,
+            textures: HashMap::new(),
+            controls: ShowcaseControls {
+                rotation: Vec3::default(),
+                movement: Vec3::default(),
+                idle_spin: self.idle_spin,
+                spinning: false,
+                last_frame_move: 0,
+                texture_target_fade: 0.,
+            },
+            engine,
+        })
+    }
+}
+
+impl ShowcaseControls {
+    fn update(&mut self, engine: &mut Engine, input: &FrameInput) {
+        let frame_count = engine.renderer.frame_count;
+        self.rotation = Vec3::new(0., input.axis(Axis::Yaw), input.axis(Axis::Tilt)) * 0.02;
+        if self.rotation != Vec3::default() {
+            self.last_frame_move = frame_count;
+        }
+        self.movement = Vec3::new(
+            input.axis(Axis::Horizontal),
+            input.axis(Axis::Vertical),
+            input.axis(Axis::Depth),
+        ) * engine.movement_step(input);
+
+        self.spinning = self.idle_spin
+            && (self.last_frame_move == 0 || frame_count - self.last_frame_move > IDLE_FRAMES);
+        if self.spinning {
+            self.rotation.y += IDLE_SPIN;
+        }
+
+        if input.key_pressed_logical(Key::Character("t")) {
+            self.texture_target_fade = if self.texture_target_fade == 1. {
+                0.
+            } else {
+                1.
+            };
+        }
+        let fade = &mut engine.renderer.flat_texture_interpolation;
+        if *fade != self.texture_target_fade {
+            *fade = (*fade + (self.texture_target_fade - *fade).signum() * TEXTURE_FADE_STEP)
+                .clamp(0., 1.);
+        }
+    }
+
+    // Keeps the objects from spinning alone, while the scene needs them still
+    pub fn hold(&mut self, engine: &Engine) {
+        self.last_frame_move = engine.renderer.frame_count;
+        if self.spinning {
+            self.rotation.y -= IDLE_SPIN;
+            self.spinning = false;
+        }
+    }
+
+    // Rotates and moves every object. Orbits own the translation, their
+    // center moves instead.
+    pub fn move_objects(&self, engine: &mut Engine) {
+        engine.game_objects.values_mut().for_each(|e| {
+            let mut e = e.borrow_mut();
+            e.transform_mut().rotation += self.rotation;
+            e.transform_mut().translation += self.movement;
+            if let Some(orbit) = e.component_mut::<Orbit>() {
+                orbit.center += self.movement;
+            }
+        });
+    }
+}
@@ -0,0 +1,265 @@
+use std::rc::Rc;
+
+use ash::vk;
+use math::{Vec2, Vec3, Vec4};
+
+use crate::{
+    engine::{
+        bookmarks::CameraBookmarks,
+        camera::Camera,
+        mesh::{Mesh, Vertex},
+        Engine, EngineConfig, GameObject, Transform,
+    },
+    ensure,
+    parsing::{read_frag_spv_file, read_vert_spv_file},
+    renderer::{
+        Material, MaterialInstance, MaterialParams, RendererDebug, ScopDescriptorSetLayout,
+        ScopTexture2D,
+    },
+    utils::Result,
+};
+
+const WHITE: Vec3 = Vec3 {
+    x: 0.73,
+    y: 0.73,
+    z: 0.73,
+};
+const RED: Vec3 = Vec3 {
+    x: 0.65,
+    y: 0.05,
+    z: 0.05,
+};
+const GREEN: Vec3 = Vec3 {
+    x: 0.12,
+    y: 0.45,
+    z: 0.15,
+};
+
+#[derive(Default)]
+struct MeshData {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+impl MeshData {
+    // Square of side 2 * half_size, front facing along `normal`. Unlit quads
+    // keep a zero normal.
+    fn quad(
+        &mut self,
+        center: Vec3,
+        normal: Vec3,
+        u: Vec3,
+        half_size: f32,
+        color: Vec3,
+        lit: bool,
+    ) {
+        let u = u.normalized() * half_size;
+        let v = normal.cross(&u).normalized() * half_size;
+        let first = self.vertices.len() as u32;
+
+        for (corner, uv) in [
+            (center - u - v, Vec2::new(0., 0.)),
+            (center + u - v, Vec2::new(1., 0.)),
+            (center + u + v, Vec2::new(1., 1.)),
+            (center - u + v, Vec2::new(0., 1.)),
+        ] {
+            self.vertices.push(Vertex {
+                position: corner,
+                color,
+                normal: if lit { normal } else { Vec3::default() },
+                uv,
+                ..Default::default()
+            });
+        }
+        self.indices
+            .extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    fn build(&self, engine: &Engine) -> Result<Rc<Mesh>> {
+        Mesh::builder(engine.renderer.main_device.clone())
+            .staging_ring(&mut engine.renderer.staging_ring.borrow_mut())
+            .vertices(&self.vertices)
+            .indices(&self.indices)
+            .bvh(true)
+            .build()
+            .map(Rc::new)
+    }
+}
+
+// Cornell box: a 2 units room open toward +z, red on the left, green on the
+// right, two white boxes and the light panel on the ceiling. Everything is
+// generated so the scene is identical on every run.
+#[derive(Default)]
+pub struct AppTestScene;
+
+impl AppTestScene {
+    pub fn start(&mut self, config: EngineConfig) -> Result<()> {
+        let mut engine = Engine::new(config)?;
+
+        // --------------------
+        // Meshs
+        // --------------------
+
+        let mut room = MeshData::default();
+        let walls = [
+            (Vec3::left(), Vec3::right(), Vec3::backward(), RED),
+            (Vec3::right(), Vec3::left(), Vec3::backward(), GREEN),
+            (Vec3::down(), Vec3::up(), Vec3::right(), WHITE),
+            (Vec3::up(), Vec3::down(), Vec3::right(), WHITE),
+            (Vec3::forward(), Vec3::backward(), Vec3::right(), WHITE),
+        ];
+        for (position, inward, u, color) in walls {
+            room.quad(position, inward, u, 1., color, true);
+        }
+        let mesh_room = room.build(&engine)?;
+
+        let mut cube = MeshData::default();
+        for normal in [
+            Vec3::left(),
+            Vec3::right(),
+            Vec3::down(),
+            Vec3::up(),
+            Vec3::backward(),
+            Vec3::forward(),
+        ] {
+            let u = if normal.y == 0. {
+                Vec3::up()
+            } else {
+                Vec3::right()
+            };
+            cube.quad(normal * 0.5, normal, u, 0.5, WHITE, true);
+        }
+        let mesh_cube = cube.build(&engine)?;
+
+        let mut light = MeshData::default();
+        light.quad(
+            Vec3::new(0., 0.999, 0.),
+            Vec3::down(),
+            Vec3::right(),
+            0.25,
+            Vec3::one(),
+            false,
+        );
+        let mesh_light = light.build(&engine)?;
+
+        // --------------------
+        // Textures
+        // --------------------
+
+        let mut texture_grid = ScopTexture2D::uv_grid(
+            engine.renderer.main_device.clone(),
+            &mut engine.renderer.staging_ring.borrow_mut(),
+            256,
+        )?;
+
+        // --------------------
+        // Shaders
+        // --------------------
+
+        let vert_shader = read_vert_spv_file(&engine, "./shaders/default.vert.spv")?;
+
+        let frag_shader = read_frag_spv_file(&engine, "./shaders/default.frag.spv")?;
+
+        // --------------------
+        // Materials
+        // --------------------
+
+        let set_layouts = vec![
+            ScopDescriptorSetLayout::builder(&engine.renderer.main_device)
+                .add_texture_binding(0, vk::ShaderStageFlags::FRAGMENT)
+                .add_texture_binding(1, vk::ShaderStageFlags::FRAGMENT)
+                .build()?,
+        ];
+
+        let material = Material::new(
+            &engine.renderer,
+            set_layouts,
+            MaterialParams::builder()
+                .vec4("tint", Vec4::one())
+                .float("texture_blend", 0.)
+                .build()?,
+            &vert_shader,
+            &frag_shader,
+            false,
+        )?;
+
+        let material_instance_grid =
+            MaterialInstance::instanciate(&engine.renderer, material.clone())?;
+        material_instance_grid.set_textures(&texture_grid, &texture_grid)?;
+
+        // --------------------
+        // GameObjects
+        // --------------------
+
+        GameObject::builder(&mut engine)
+            .name("Room")
+            .mesh(mesh_room)
+            .material(material_instance_grid.clone())
+            .build()?;
+
+        GameObject::builder(&mut engine)
+            .name("Light")
+            .mesh(mesh_light)
+            .material(material_instance_grid.clone())
+            // The emitter, it would shade the whole room
+            .casts_shadows(false)
+            .build()?;
+
+        GameObject::builder(&mut engine)
+            .name("Tall box")
+            .mesh(mesh_cube.clone())
+            .material(material_instance_grid.clone())
+            .transform(Transform {
+                translation: Vec3::new(-0.35, -0.4, -0.35),
+                scale: Vec3::new(0.6, 1.2, 0.6),
+                rotation: Vec3::up() * 0.3,
+                ..Default::default()
+            })
+            .build()?;
+
+        GameObject::builder(&mut engine)
+            .name("Short box")
+            .mesh(mesh_cube.clone())
+            .material(material_instance_grid.clone())
+            .transform(Transform {
+                translation: Vec3::new(0.35, -0.7, 0.3),
+                scale: Vec3::one() * 0.6,
+                rotation: Vec3::up() * -0.3,
+                ..Default::default()
+            })
+            .build()?;
+
+        // --------------------
+        // Logic
+        // --------------------
+
+        engine.title = String::from("test scene");
+        engine.bookmarks = Some(CameraBookmarks::load("testscene")?);
+
+        let mut camera = Camera::empty();
+        let aspect = engine.renderer.window.aspect_ratio();
+        camera.set_perspective_projection(40.0, aspect, 0.1, 100.0);
+        camera.set_view_direction(Vec3::new(0., 0., 3.9), Vec3::forward(), Vec3::up());
+
+        engine.run(&mut camera, |_engine, _camera, _input, _image_index| {})?;
+
+        engine.renderer.wait_gpu();
+
+        // Run with --gpu-validation, the scene is a regression test for the
+        // hazards the validation layer finds
+        let validation_errors = RendererDebug::error_count();
+        if engine.renderer.config.gpu_validation {
+            println!("GPU validation: {} errors", validation_errors);
+        }
+
+        texture_grid.cleanup();
+
+        engine.game_objects.clear();
+
+        ensure!(
+            validation_errors == 0,
+            "The validation layer reported errors"
+        );
+        Ok(())
+    }
+}
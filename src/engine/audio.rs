@@ -0,0 +1,85 @@
+#[cfg(feature = "audio")]
+use std::{fs::File, io::BufReader};
+
+use crate::utils::Result;
+
+// Sounds played on the default output device, with the "audio" feature.
+// Without it, or without a device, sounds are skipped silently.
+pub struct Audio {
+    #[cfg(feature = "audio")]
+    output: Option<(rodio::OutputStream, rodio::OutputStreamHandle)>,
+    // Applied to the sounds played afterwards
+    pub volume: f32,
+    // Sounds waiting for the clock, by start time
+    cues: Vec<(f32, String)>,
+}
+
+impl Audio {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "audio")]
+            output: rodio::OutputStream::try_default()
+                .inspect_err(|e| eprintln!("No audio output: {}", e))
+                .ok(),
+            volume: 1.,
+            cues: vec![],
+        }
+    }
+
+    #[cfg(feature = "audio")]
+    pub fn is_available(&self) -> bool {
+        self.output.is_some()
+    }
+
+    #[cfg(not(feature = "audio"))]
+    pub fn is_available(&self) -> bool {
+        false
+    }
+
+    // Starts the sound and returns, it plays until its end
+    #[cfg(feature = "audio")]
+    pub fn play(&self, path: &str) -> Result<()> {
+        use rodio::Source;
+
+        let Some((_, handle)) = &self.output else {
+            return Ok(());
+        };
+        let file = File::open(crate::parsing::resolve_asset(path)?)?;
+        let source = rodio::Decoder::new(BufReader::new(file))?;
+        handle.play_raw(source.amplify(self.volume).convert_samples())?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "audio"))]
+    pub fn play(&self, _path: &str) -> Result<()> {
+        Ok(())
+    }
+
+    // Plays `path` once the renderer clock reaches `time`, in seconds, so
+    // sounds follow a scripted scene even with a fixed time step
+    pub fn schedule(&mut self, time: f32, path: &str) {
+        let index = self.cues.partition_point(|(cue_time, _)| *cue_time <= time);
+        self.cues.insert(index, (time, path.to_owned()));
+    }
+
+    // Drops the scheduled sounds not played yet
+    pub fn clear_schedule(&mut self) {
+        self.cues.clear();
+    }
+
+    // Called by the engine every frame with the renderer clock
+    pub fn update(&mut self, time: f32) {
+        let due = self.cues.partition_point(|(cue_time, _)| *cue_time <= time);
+        for (_, path) in self.cues.drain(..due).collect::<Vec<_>>() {
+            if let Err(e) = self.play(&path) {
+                eprintln!("{}: could not play: {}", path, e);
+            }
+        }
+    }
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,118 @@
+use math::{Vec2, Vec3};
+
+use super::camera::{Camera, CameraPose};
+use crate::renderer::DebugLines;
+
+// Radius of the clickable end of an axis, in pixels
+const TIP_RADIUS: f32 = 6.;
+
+// World axes as seen by the camera, drawn in the top right corner through
+// overlay lines. Clicking the end of an axis looks at `target` along it, from
+// the same distance.
+pub struct AxisGizmo {
+    pub target: Vec3,
+    // Length of the axes in physical pixels
+    pub radius: f32,
+    // Space between the gizmo and the corner
+    pub margin: f32,
+}
+
+struct AxisTip {
+    axis: Vec3,
+    color: Vec3,
+    position: Vec2,
+    // Toward the screen is negative
+    depth: f32,
+}
+
+impl AxisGizmo {
+    pub fn new(target: Vec3) -> Self {
+        Self {
+            target,
+            radius: 40.,
+            margin: 16.,
+        }
+    }
+
+    fn center(&self, viewport_size: Vec2) -> Vec2 {
+        Vec2::new(
+            viewport_size.x - self.margin - self.radius,
+            self.margin + self.radius,
+        )
+    }
+
+    // Ends of the 6 half axes in pixels, the farthest first. The rotation is
+    // the one of the view, the projection is left out.
+    fn tips(&self, camera: &Camera, viewport_size: Vec2) -> Vec<AxisTip> {
+        let center = self.center(viewport_size);
+        let axes = [
+            (Vec3::right(), Vec3::new(0.9, 0.2, 0.2)),
+            (Vec3::up(), Vec3::new(0.3, 0.8, 0.2)),
+            (Vec3::backward(), Vec3::new(0.2, 0.4, 0.9)),
+        ];
+
+        let mut tips: Vec<AxisTip> = axes
+            .into_iter()
+            .flat_map(|(axis, color)| [(axis, color), (axis * -1., color * 0.4)])
+            .map(|(axis, color)| {
+                let view = camera.get_view().transform_vector(axis);
+                AxisTip {
+                    axis,
+                    color,
+                    position: center + Vec2::new(view.x, view.y) * self.radius,
+                    depth: view.z,
+                }
+            })
+            .collect();
+        tips.sort_by(|a, b| b.depth.total_cmp(&a.depth));
+        tips
+    }
+
+    pub fn draw(&self, lines: &mut DebugLines, camera: &Camera, viewport_size: Vec2) {
+        let to_ndc = |position: Vec2| {
+            Vec3::new(
+                position.x * 2. / viewport_size.x - 1.,
+                position.y * 2. / viewport_size.y - 1.,
+                0.5,
+            )
+        };
+        let center = self.center(viewport_size);
+
+        // Lines are drawn in order, the closest axes end up on top
+        for tip in self.tips(camera, viewport_size) {
+            if tip.axis.x + tip.axis.y + tip.axis.z > 0. {
+                lines.line(to_ndc(center), to_ndc(tip.position), tip.color);
+            }
+            let corners = [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)]
+                .map(|(x, y)| to_ndc(tip.position + Vec2::new(x, y) * (TIP_RADIUS / 2.)));
+            for i in 0..corners.len() {
+                lines.line(corners[i], corners[(i + 1) % corners.len()], tip.color);
+            }
+        }
+    }
+
+    // Half axis whose end is under the cursor, the closest to the camera
+    // when they overlap
+    pub fn hit(&self, camera: &Camera, cursor: Vec2, viewport_size: Vec2) -> Option<Vec3> {
+        self.tips(camera, viewport_size)
+            .into_iter()
+            .rev()
+            .find(|tip| (tip.position - cursor).length() <= TIP_RADIUS)
+            .map(|tip| tip.axis)
+    }
+
+    // Front, top or side view, looking toward -axis
+    pub fn snap_pose(&self, camera: &Camera, axis: Vec3) -> CameraPose {
+        let distance = (camera.get_position() - self.target).length();
+        let up = if axis.y.abs() > 0.5 {
+            Vec3::forward() * axis.y
+        } else {
+            Vec3::up()
+        };
+        CameraPose {
+            position: self.target + axis * distance,
+            direction: axis * -1.,
+            up,
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::{fs, path::PathBuf};
+
+use math::Vec3;
+use winit::keyboard::KeyCode;
+
+use super::{
+    camera::{Camera, CameraPose},
+    input::FrameInput,
+};
+use crate::{
+    ensure,
+    utils::{Context, Result},
+};
+
+const BOOKMARKS_DIRECTORY: &str = "./.scop/bookmarks";
+const SLOT_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+// Ctrl+1..9 stores the camera pose, 1..9 flies back to it
+pub struct CameraBookmarks {
+    path: PathBuf,
+    slots: [Option<CameraPose>; 9],
+}
+
+impl CameraBookmarks {
+    pub fn load(scene: &str) -> Result<Self> {
+        let file_name: String = scene
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let mut bookmarks = Self {
+            path: PathBuf::from(BOOKMARKS_DIRECTORY).join(file_name),
+            slots: [None; 9],
+        };
+
+        if !bookmarks.path.exists() {
+            return Ok(bookmarks);
+        }
+
+        for line in fs::read_to_string(&bookmarks.path)?.lines() {
+            let mut values = line.split_whitespace();
+            let slot = values.next().context("Empty bookmark")?.parse::<usize>()?;
+            ensure!((1..=9).contains(&slot), "Bookmark slot out of range");
+
+            let values = values
+                .map(str::parse::<f32>)
+                .collect::<core::result::Result<Vec<_>, _>>()?;
+            ensure!(values.len() == 9, "A bookmark needs 9 values");
+            bookmarks.slots[slot - 1] = Some(CameraPose {
+                position: Vec3::new(values[0], values[1], values[2]),
+                direction: Vec3::new(values[3], values[4], values[5]),
+                up: Vec3::new(values[6], values[7], values[8]),
+            });
+        }
+
+        Ok(bookmarks)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let mut content = String::new();
+        for (slot, pose) in self.slots.iter().enumerate() {
+            if let Some(pose) = pose {
+                let [p, d, u] = [pose.position, pose.direction, pose.up];
+                content += &format!(
+                    "{} {} {} {} {} {} {} {} {} {}\n",
+                    slot + 1,
+                    p.x,
+                    p.y,
+                    p.z,
+                    d.x,
+                    d.y,
+                    d.z,
+                    u.x,
+                    u.y,
+                    u.z
+                );
+            }
+        }
+
+        fs::create_dir_all(BOOKMARKS_DIRECTORY)?;
+        fs::write(&self.path, content)?;
+        Ok(())
+    }
+
+    // The pose to fly to when a bookmark is recalled
+    pub fn update(&mut self, camera: &Camera, input: &FrameInput) -> Option<CameraPose> {
+        let mut recalled = None;
+        for (slot, key) in SLOT_KEYS.iter().enumerate() {
+            if !input.key_pressed(*key) {
+                continue;
+            }
+
+            if input.held_control() {
+                self.slots[slot] = Some(camera.get_pose());
+                match self.save() {
+                    Ok(()) => println!("Camera bookmark {} saved", slot + 1),
+                    Err(e) => eprintln!("Could not save camera bookmarks: {}", e),
+                }
+            } else if self.slots[slot].is_some() {
+                recalled = self.slots[slot];
+            }
+        }
+        recalled
+    }
+}
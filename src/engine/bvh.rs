@@ -0,0 +1,195 @@
+use math::Vec3;
+
+use super::mesh::{triangle, triangles, BoundingBox, Vertex};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Hit {
+    pub distance: f32,
+    pub triangle: usize,
+    pub barycentric: Vec3,
+}
+
+enum BvhNode {
+    Leaf {
+        bounding_box: BoundingBox,
+        first: usize,
+        count: usize,
+    },
+    Branch {
+        bounding_box: BoundingBox,
+        left: usize,
+        right: usize,
+    },
+}
+
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    // Triangle indices, reordered so every leaf owns a contiguous range
+    triangles: Vec<usize>,
+}
+
+impl Bvh {
+    const MAX_LEAF_SIZE: usize = 4;
+
+    pub fn new(vertices: &[Vertex], indices: &[u32]) -> Self {
+        let corners: Vec<[Vec3; 3]> = triangles(vertices, indices)
+            .map(|t| t.map(|i| vertices[i as usize].position))
+            .collect();
+
+        let mut bvh = Self {
+            nodes: vec![],
+            triangles: (0..corners.len()).collect(),
+        };
+        if !corners.is_empty() {
+            bvh.build(&corners, 0, corners.len());
+        }
+        bvh
+    }
+
+    fn build(&mut self, corners: &[[Vec3; 3]], first: usize, count: usize) -> usize {
+        let mut bounding_box = BoundingBox {
+            min: Vec3::positive_infinity(),
+            max: Vec3::negative_infinity(),
+        };
+        for &triangle in &self.triangles[first..first + count] {
+            for corner in corners[triangle] {
+                bounding_box.extend(corner);
+            }
+        }
+
+        let index = self.nodes.len();
+        if count <= Self::MAX_LEAF_SIZE {
+            self.nodes.push(BvhNode::Leaf {
+                bounding_box,
+                first,
+                count,
+            });
+            return index;
+        }
+
+        // Median split on the longest axis
+        let size = bounding_box.max - bounding_box.min;
+        let axis = if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        };
+        let centroid =
+            |t: usize| (corners[t][0][axis] + corners[t][1][axis] + corners[t][2][axis]) / 3.;
+        self.triangles[first..first + count]
+            .sort_unstable_by(|a, b| centroid(*a).total_cmp(&centroid(*b)));
+
+        // Reserve the slot, children are pushed after their parent
+        self.nodes.push(BvhNode::Leaf {
+            bounding_box,
+            first,
+            count,
+        });
+        let half = count / 2;
+        let left = self.build(corners, first, half);
+        let right = self.build(corners, first + half, count - half);
+        self.nodes[index] = BvhNode::Branch {
+            bounding_box,
+            left,
+            right,
+        };
+        index
+    }
+
+    pub fn raycast(
+        &self,
+        vertices: &[Vertex],
+        indices: &[u32],
+        origin: Vec3,
+        direction: Vec3,
+    ) -> Option<Hit> {
+        let corners = |t: usize| triangle(indices, t).map(|i| vertices[i as usize].position);
+
+        let mut closest: Option<Hit> = None;
+        let mut stack = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        };
+
+        while let Some(node) = stack.pop() {
+            match &self.nodes[node] {
+                BvhNode::Leaf {
+                    bounding_box,
+                    first,
+                    count,
+                } => {
+                    if !bounding_box.intersects_ray(origin, direction, closest.map(|h| h.distance))
+                    {
+                        continue;
+                    }
+                    for &triangle in &self.triangles[*first..*first + *count] {
+                        if let Some(hit) =
+                            ray_triangle(origin, direction, corners(triangle), triangle)
+                        {
+                            if closest.is_none_or(|c| hit.distance < c.distance) {
+                                closest = Some(hit);
+                            }
+                        }
+                    }
+                }
+                BvhNode::Branch {
+                    bounding_box,
+                    left,
+                    right,
+                } => {
+                    if bounding_box.intersects_ray(origin, direction, closest.map(|h| h.distance)) {
+                        stack.push(*left);
+                        stack.push(*right);
+                    }
+                }
+            }
+        }
+
+        closest
+    }
+}
+
+// Möller–Trumbore, both faces are hit
+// https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
+pub fn ray_triangle(
+    origin: Vec3,
+    direction: Vec3,
+    corners: [Vec3; 3],
+    triangle: usize,
+) -> Option<Hit> {
+    let [a, b, c] = corners;
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = direction.cross(&edge2);
+    let det = edge1.dot(&p);
+    if det == 0. {
+        return None;
+    }
+
+    let inv_det = 1. / det;
+    let t_vec = origin - a;
+    let u = t_vec.dot(&p) * inv_det;
+    if !(0. ..=1.).contains(&u) {
+        return None;
+    }
+
+    let q = t_vec.cross(&edge1);
+    let v = direction.dot(&q) * inv_det;
+    if v < 0. || u + v > 1. {
+        return None;
+    }
+
+    let distance = edge2.dot(&q) * inv_det;
+    if distance <= 0. {
+        return None;
+    }
+
+    Some(Hit {
+        distance,
+        triangle,
+        barycentric: Vec3::new(1. - u - v, u, v),
+    })
+}
@@ -1,9 +1,58 @@
-use math::{Mat4, Vec3};
+use math::{Mat4, Quat, Vec2, Vec3, Vec4};
 
+use super::mesh::BoundingBox;
+
+// Lowest near / far ratio given by `fit_depth_range`, the depth precision
+// degrades with it
+const MIN_NEAR_FAR_RATIO: f32 = 1e-4;
+
+// Right-handed world with +y up by default, see `Handedness`. The view space
+// has x right, y down and z forward, the projection maps it to the Vulkan clip
+// space (y down, depth in 0..1) so the viewport keeps a positive height. Both
+// handednesses share that view space and so the same projections.
 pub struct Camera {
     projection_matrix: Mat4,
     view_matrix: Mat4,
     inverse_view_matrix: Mat4,
+    fovy: f32,
+    aspect: f32,
+    reverse_z: bool,
+    handedness: Handedness,
+    // In stops, each one doubles the light reaching the screen
+    pub exposure: f32,
+}
+
+// A left-handed world has +x on the right when looking toward +z. Its view
+// matrix mirrors x, which also turns counter-clockwise triangles clockwise
+// on screen.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Handedness {
+    #[default]
+    Right,
+    Left,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CameraPose {
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub up: Vec3,
+}
+
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+// Moves a camera to a pose over `duration` seconds, easing in and out. With
+// a target projection, the projection matrices are blended along, which also
+// goes between perspective and orthographic.
+pub struct CameraTransition {
+    from: CameraPose,
+    to: CameraPose,
+    from_projection: Mat4,
+    to_projection: Mat4,
+    duration: f32,
+    elapsed: f32,
 }
 
 impl Camera {
@@ -12,31 +61,175 @@ impl Camera {
             projection_matrix: Mat4::identity(),
             view_matrix: Mat4::identity(),
             inverse_view_matrix: Mat4::identity(),
+            fovy: 0.,
+            aspect: 1.,
+            reverse_z: false,
+            handedness: Handedness::Right,
+            exposure: 0.,
         }
     }
 
+    // Bounds in view space, where y goes down: top is usually the negative one
     pub fn set_orthographic_projection(
         &mut self,
-        _left: f32,
-        _right: f32,
-        _top: f32,
-        _bottom: f32,
-        _near: f32,
-        _far: f32,
+        left: f32,
+        right: f32,
+        top: f32,
+        bottom: f32,
+        near: f32,
+        far: f32,
     ) {
-        unimplemented!()
+        self.projection_matrix = Mat4::identity();
+        self.projection_matrix[0][0] = 2. / (right - left);
+        self.projection_matrix[1][1] = 2. / (bottom - top);
+        self.projection_matrix[2][2] = 1. / (far - near);
+        self.projection_matrix[3][0] = -(right + left) / (right - left);
+        self.projection_matrix[3][1] = -(bottom + top) / (bottom - top);
+        self.projection_matrix[3][2] = -near / (far - near);
+
+        if self.reverse_z {
+            self.flip_depth();
+        }
+    }
+
+    // Orthographic projection showing the plane at `distance` like the
+    // current perspective does
+    pub fn orthographic_at(&self, distance: f32) -> Mat4 {
+        let half_height = distance * ((std::f32::consts::PI / 180.) * self.fovy / 2.).tan();
+        let half_width = half_height * self.aspect;
+
+        let mut orthographic = Self::empty();
+        orthographic.reverse_z = self.reverse_z;
+        // Everything around the target stays in the depth range
+        orthographic.set_orthographic_projection(
+            -half_width,
+            half_width,
+            -half_height,
+            half_height,
+            -distance,
+            distance * 100.,
+        );
+        orthographic.projection_matrix
+    }
+
+    pub fn is_orthographic(&self) -> bool {
+        self.projection_matrix[2][3] == 0.
+    }
+
+    // Set by `set_perspective_infinite`. Read from the matrix like
+    // `is_orthographic`, a projection given back to `set_projection` keeps it.
+    pub fn is_infinite_far(&self) -> bool {
+        let depth_scale = if self.reverse_z { 0. } else { 1. };
+        !self.is_orthographic() && self.projection_matrix[2][2] == depth_scale
+    }
+
+    pub fn set_projection(&mut self, projection: Mat4) {
+        self.projection_matrix = projection;
     }
 
     pub fn set_perspective_projection(&mut self, fovy: f32, aspect: f32, near: f32, far: f32) {
+        self.set_perspective(fovy, aspect);
+        self.projection_matrix[2][2] = far / (far - near);
+        self.projection_matrix[3][2] = -(far * near) / (far - near);
+
+        if self.reverse_z {
+            self.flip_depth();
+        }
+    }
+
+    // Limit of the perspective projection when far goes to infinity, nothing
+    // is clipped in the distance. Best used with reverse-Z, depth then goes
+    // from 1 at near to 0 at infinity.
+    pub fn set_perspective_infinite(&mut self, fovy: f32, aspect: f32, near: f32) {
+        self.set_perspective(fovy, aspect);
+        self.projection_matrix[2][2] = 1f32;
+        self.projection_matrix[3][2] = -near;
+
+        if self.reverse_z {
+            self.flip_depth();
+        }
+    }
+
+    fn set_perspective(&mut self, fovy: f32, aspect: f32) {
         assert!((aspect - f32::EPSILON).abs() > 0f32);
 
         let fovy_rad = (std::f32::consts::PI / 180.) * fovy;
         let tan_half_fovy = (fovy_rad / 2f32).tan();
+        self.projection_matrix = Mat4::new();
         self.projection_matrix[0][0] = 1f32 / (aspect * tan_half_fovy);
         self.projection_matrix[1][1] = 1f32 / (tan_half_fovy);
-        self.projection_matrix[2][2] = far / (far - near);
         self.projection_matrix[2][3] = 1f32;
-        self.projection_matrix[3][2] = -(far * near) / (far - near);
+        self.fovy = fovy;
+        self.aspect = aspect;
+    }
+
+    // Near and far planes of the perspective tight around a world space box,
+    // with a margin so moving objects are not clipped before the next fit.
+    // Orthographic projections and boxes behind the camera are left alone,
+    // an infinite far plane stays infinite and only the near one moves.
+    pub fn fit_depth_range(&mut self, bounds: &BoundingBox) {
+        if self.fovy == 0. || self.is_orthographic() {
+            return;
+        }
+
+        // View space z goes forward
+        let view_bounds = bounds.transform(&self.view_matrix);
+        if view_bounds.max.z <= 0. {
+            return;
+        }
+        let far = view_bounds.max.z * 1.1;
+        let near = (view_bounds.min.z * 0.9).max(far * MIN_NEAR_FAR_RATIO);
+        if self.is_infinite_far() {
+            self.set_perspective_infinite(self.fovy, self.aspect, near);
+        } else {
+            self.set_perspective_projection(self.fovy, self.aspect, near, far);
+        }
+    }
+
+    // Matches the renderer depth buffer, set by the engine before running
+    pub fn set_reverse_z(&mut self, reverse_z: bool) {
+        if self.reverse_z != reverse_z {
+            self.reverse_z = reverse_z;
+            self.flip_depth();
+        }
+    }
+
+    pub fn is_reverse_z(&self) -> bool {
+        self.reverse_z
+    }
+
+    // Matches the renderer front face, set by the engine before running
+    pub fn set_handedness(&mut self, handedness: Handedness) {
+        if self.handedness != handedness {
+            self.handedness = handedness;
+            for i in 0..4 {
+                self.view_matrix[i][0] = -self.view_matrix[i][0];
+            }
+            for i in 0..3 {
+                self.inverse_view_matrix[0][i] = -self.inverse_view_matrix[0][i];
+            }
+        }
+    }
+
+    pub fn handedness(&self) -> Handedness {
+        self.handedness
+    }
+
+    // Normalized depth of the near plane
+    pub fn near_depth(&self) -> f32 {
+        if self.reverse_z {
+            1.
+        } else {
+            0.
+        }
+    }
+
+    // depth = 1 - depth, applying it twice gives back the projection
+    fn flip_depth(&mut self) {
+        for i in 0..4 {
+            self.projection_matrix[i][2] =
+                self.projection_matrix[i][3] - self.projection_matrix[i][2];
+        }
     }
 
     pub fn get_projection(&self) -> &Mat4 {
@@ -53,16 +246,68 @@ impl Camera {
 
     pub fn get_position(&self) -> Vec3 {
         Vec3::from([
-            self.inverse_view_matrix[0][0],
-            self.inverse_view_matrix[1][0],
-            self.inverse_view_matrix[2][0],
+            self.inverse_view_matrix[3][0],
+            self.inverse_view_matrix[3][1],
+            self.inverse_view_matrix[3][2],
         ])
     }
 
+    // World space ray under the cursor, given in physical pixels from the top
+    // left corner. The origin is on the near plane, the direction goes away from the
+    // camera. None while the projection cannot be inverted.
+    pub fn screen_to_ray(&self, cursor: Vec2, viewport_size: Vec2) -> Option<(Vec3, Vec3)> {
+        let near = self.screen_to_world(cursor, self.near_depth(), viewport_size)?;
+        // A point further along, parallel rays of orthographic projections
+        // do not go through the camera position
+        let further = self.screen_to_world(cursor, 0.5, viewport_size)?;
+
+        Some((near, (further - near).normalized()))
+    }
+
+    // Point under the cursor at a normalized depth, as read from the depth
+    // buffer
+    pub fn screen_to_world(&self, cursor: Vec2, depth: f32, viewport_size: Vec2) -> Option<Vec3> {
+        let inverse_projection = self.projection_matrix.inverse()?;
+        let ndc = Vec4::new(
+            2. * cursor.x / viewport_size.x - 1.,
+            2. * cursor.y / viewport_size.y - 1.,
+            depth,
+            1.,
+        );
+        let point = inverse_projection * ndc;
+        Some(
+            self.inverse_view_matrix
+                .transform_point(Vec3::from(point) / point.w),
+        )
+    }
+
+    pub fn get_pose(&self) -> CameraPose {
+        CameraPose {
+            position: self.get_position(),
+            direction: Vec3::from([
+                self.inverse_view_matrix[2][0],
+                self.inverse_view_matrix[2][1],
+                self.inverse_view_matrix[2][2],
+            ]),
+            up: Vec3::from([
+                -self.inverse_view_matrix[1][0],
+                -self.inverse_view_matrix[1][1],
+                -self.inverse_view_matrix[1][2],
+            ]),
+        }
+    }
+
+    pub fn set_pose(&mut self, pose: &CameraPose) {
+        self.set_view_direction(pose.position, pose.direction, pose.up);
+    }
+
     pub fn set_view_direction(&mut self, position: Vec3, direction: Vec3, up: Vec3) {
         let w = direction.normalized();
-        let u = w.cross(&up).normalized();
+        let mut u = w.cross(&up).normalized();
         let v = w.cross(&u);
+        if self.handedness == Handedness::Left {
+            u *= -1.;
+        }
 
         self.view_matrix[0][0] = u.x;
         self.view_matrix[1][0] = u.y;
@@ -91,46 +336,329 @@ impl Camera {
         self.inverse_view_matrix[3][2] = position.z;
     }
 
+    // Moves the camera back along `direction` until the sphere fits in both
+    // the vertical and horizontal field of view
+    pub fn frame_sphere(&mut self, center: Vec3, radius: f32, direction: Vec3, up: Vec3) {
+        let half_fovy = (std::f32::consts::PI / 180.) * self.fovy / 2.;
+        let half_fovx = (half_fovy.tan() * self.aspect).atan();
+        let distance = radius / half_fovy.min(half_fovx).sin();
+
+        self.set_view_direction(center - direction.normalized() * distance, direction, up);
+    }
+
+    pub fn get_frustum(&self) -> Frustum {
+        Frustum::from(self.view_matrix * self.projection_matrix)
+    }
+
     pub fn set_view_target(&mut self, position: Vec3, target: Vec3, up: Vec3) {
         self.set_view_direction(position, target - position, up);
     }
 
+    // Euler angles in the Transform order, zero looks toward -z with +y up
+    // like `set_view_direction(position, Vec3::forward(), Vec3::up())`
     pub fn set_view_yxz(&mut self, position: Vec3, rotation: Vec3) {
-        let c3 = rotation.z.cos();
-        let s3 = rotation.z.sin();
-        let c2 = rotation.x.cos();
-        let s2 = rotation.x.sin();
-        let c1 = rotation.y.cos();
-        let s1 = rotation.y.sin();
+        let rotation = Mat4::rotate(rotation);
+        self.set_view_direction(
+            position,
+            rotation.transform_vector(Vec3::forward()),
+            rotation.transform_vector(Vec3::up()),
+        );
+    }
+}
 
-        let u = Vec3::from([c1 * c3 + s1 * s2 * s3, c2 * s3, c1 * s2 * s3 - c3 * s1]);
-        let v = Vec3::from([c3 * s1 * s2 - c1 * s3, c2 * c3, c1 * c3 * s2 + s1 * s3]);
-        let w = Vec3::from([c2 * s1, -s2, c1 * c2]);
+impl CameraPose {
+    // Takes the shortest rotation, direction and up stay perpendicular
+    pub fn interpolate(&self, other: &Self, t: f32) -> Self {
+        let rotation = self.rotation().slerp(&other.rotation(), t);
+        Self {
+            position: self.position.lerp(&other.position, t),
+            // The view space z and -y axes
+            direction: rotation.rotate(Vec3::new(0., 0., 1.)),
+            up: rotation.rotate(Vec3::down()),
+        }
+    }
 
-        self.view_matrix[0][0] = u.x;
-        self.view_matrix[1][0] = u.y;
-        self.view_matrix[2][0] = u.z;
-        self.view_matrix[0][1] = v.x;
-        self.view_matrix[1][1] = v.y;
-        self.view_matrix[2][1] = v.z;
-        self.view_matrix[0][2] = w.x;
-        self.view_matrix[1][2] = w.y;
-        self.view_matrix[2][2] = w.z;
-        self.view_matrix[3][0] = -u.dot(&position);
-        self.view_matrix[3][1] = -v.dot(&position);
-        self.view_matrix[3][2] = -w.dot(&position);
+    // From the view space axes to the world, like the inverse view
+    fn rotation(&self) -> Quat {
+        let w = self.direction.normalized();
+        let u = w.cross(&self.up).normalized();
+        let v = w.cross(&u);
+        Quat::from_rotation_matrix(&Mat4::from([
+            [u.x, u.y, u.z, 0.],
+            [v.x, v.y, v.z, 0.],
+            [w.x, w.y, w.z, 0.],
+            [0., 0., 0., 1.],
+        ]))
+    }
+}
 
-        self.inverse_view_matrix[0][0] = u.x;
-        self.inverse_view_matrix[0][1] = u.y;
-        self.inverse_view_matrix[0][2] = u.z;
-        self.inverse_view_matrix[1][0] = v.x;
-        self.inverse_view_matrix[1][1] = v.y;
-        self.inverse_view_matrix[1][2] = v.z;
-        self.inverse_view_matrix[2][0] = w.x;
-        self.inverse_view_matrix[2][1] = w.y;
-        self.inverse_view_matrix[2][2] = w.z;
-        self.inverse_view_matrix[3][0] = position.x;
-        self.inverse_view_matrix[3][1] = position.y;
-        self.inverse_view_matrix[3][2] = position.z;
+impl CameraTransition {
+    pub fn new(camera: &Camera, to: CameraPose, duration: f32) -> Self {
+        Self {
+            from: camera.get_pose(),
+            to,
+            from_projection: camera.projection_matrix,
+            to_projection: camera.projection_matrix,
+            duration,
+            elapsed: 0.,
+        }
+    }
+
+    pub fn with_projection(mut self, projection: Mat4) -> Self {
+        self.to_projection = projection;
+        self
+    }
+
+    pub fn target_pose(&self) -> CameraPose {
+        self.to
+    }
+
+    pub fn target_projection(&self) -> Mat4 {
+        self.to_projection
+    }
+
+    // Tells if the transition is over
+    pub fn update(&mut self, camera: &mut Camera, delta_time: f32) -> bool {
+        self.elapsed += delta_time;
+        let t = if self.duration > 0. {
+            (self.elapsed / self.duration).min(1.)
+        } else {
+            1.
+        };
+        let t = t * t * (3. - 2. * t);
+
+        camera.set_pose(&self.from.interpolate(&self.to, t));
+        for column in 0..4 {
+            for row in 0..4 {
+                camera.projection_matrix[column][row] = self.from_projection[column][row]
+                    * (1. - t)
+                    + self.to_projection[column][row] * t;
+            }
+        }
+
+        t >= 1.
+    }
+}
+
+impl Frustum {
+    pub fn intersects_sphere(&self, center: Vec3, radius: f32) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = Vec3::from(*plane);
+            normal.dot(&center) + plane.w >= -radius * normal.length()
+        })
+    }
+}
+
+// Gribb/Hartmann plane extraction, with the 0..1 Vulkan depth range. With a
+// reversed depth the near and far planes swap, the set stays the same.
+impl From<Mat4> for Frustum {
+    fn from(view_projection: Mat4) -> Self {
+        let row = |r: usize| {
+            Vec4::new(
+                view_projection[0][r],
+                view_projection[1][r],
+                view_projection[2][r],
+                view_projection[3][r],
+            )
+        };
+
+        Self {
+            planes: [
+                row(3) + row(0),
+                row(3) - row(0),
+                row(3) + row(1),
+                row(3) - row(1),
+                row(2),
+                row(3) - row(2),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    // At (0, 0, 5) looking toward -z with +y up, 90 degrees fov
+    fn test_camera(reverse_z: bool) -> Camera {
+        let mut camera = Camera::empty();
+        camera.set_reverse_z(reverse_z);
+        camera.set_view_direction(Vec3::new(0., 0., 5.), Vec3::forward(), Vec3::up());
+        camera.set_perspective_projection(90., 1., 1., 10.);
+        camera
+    }
+
+    fn to_ndc(camera: &Camera, point: Vec3) -> Vec3 {
+        let view = *camera.get_view() * Vec4::new(point.x, point.y, point.z, 1.);
+        let clip = *camera.get_projection() * view;
+        Vec3::from(clip) / clip.w
+    }
+
+    fn assert_near(actual: Vec3, expected: Vec3) {
+        assert!(
+            (actual - expected).length() < EPSILON,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    fn assert_matrix(actual: &Mat4, expected: &Mat4) {
+        for column in 0..4 {
+            for row in 0..4 {
+                assert!(
+                    (actual[column][row] - expected[column][row]).abs() < EPSILON,
+                    "[{}][{}] is {}, expected {}",
+                    column,
+                    row,
+                    actual[column][row],
+                    expected[column][row]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn perspective_projection_matrix() {
+        let mut camera = Camera::empty();
+        camera.set_perspective_projection(90., 2., 1., 10.);
+        assert_matrix(
+            camera.get_projection(),
+            &Mat4::from([
+                [0.5, 0., 0., 0.],
+                [0., 1., 0., 0.],
+                [0., 0., 10. / 9., 1.],
+                [0., 0., -10. / 9., 0.],
+            ]),
+        );
+    }
+
+    #[test]
+    fn view_matrix() {
+        let camera = test_camera(false);
+        // x right, y down, z forward
+        assert_matrix(
+            camera.get_view(),
+            &Mat4::from([
+                [1., 0., 0., 0.],
+                [0., -1., 0., 0.],
+                [0., 0., -1., 0.],
+                [0., 0., 5., 1.],
+            ]),
+        );
+        assert_matrix(
+            &(*camera.get_view() * *camera.get_inverse_view()),
+            &Mat4::identity(),
+        );
+        assert_near(camera.get_position(), Vec3::new(0., 0., 5.));
+    }
+
+    #[test]
+    fn world_axes_in_ndc() {
+        let camera = test_camera(false);
+        assert_near(
+            to_ndc(&camera, Vec3::new(1., 0., 0.)),
+            Vec3::new(0.2, 0., 8. / 9.),
+        );
+        // Vulkan y goes down, +y is on the top half of the screen
+        assert_near(
+            to_ndc(&camera, Vec3::new(0., 1., 0.)),
+            Vec3::new(0., -0.2, 8. / 9.),
+        );
+    }
+
+    #[test]
+    fn depth_range() {
+        let near = Vec3::new(0., 0., 4.);
+        let middle = Vec3::new(0., 0., 0.);
+        let far = Vec3::new(0., 0., -5.);
+
+        let camera = test_camera(false);
+        assert!(to_ndc(&camera, near).z.abs() < EPSILON);
+        assert!((to_ndc(&camera, far).z - 1.).abs() < EPSILON);
+        assert!((0f32..1.).contains(&to_ndc(&camera, middle).z));
+
+        let camera = test_camera(true);
+        assert!((to_ndc(&camera, near).z - 1.).abs() < EPSILON);
+        assert!(to_ndc(&camera, far).z.abs() < EPSILON);
+        assert!((0f32..1.).contains(&to_ndc(&camera, middle).z));
+    }
+
+    #[test]
+    fn flip_depth_is_its_own_inverse() {
+        let mut camera = test_camera(false);
+        let projection = *camera.get_projection();
+        camera.set_reverse_z(true);
+        camera.set_reverse_z(false);
+        assert_matrix(camera.get_projection(), &projection);
+    }
+
+    #[test]
+    fn infinite_depth_range() {
+        for reverse_z in [false, true] {
+            let mut camera = test_camera(reverse_z);
+            camera.set_perspective_infinite(90., 1., 1.);
+
+            let near = to_ndc(&camera, Vec3::new(0., 0., 4.)).z;
+            let distant = to_ndc(&camera, Vec3::new(0., 0., -1e6)).z;
+            assert!((near - camera.near_depth()).abs() < EPSILON);
+            assert!((0f32..=1.).contains(&distant));
+            // The far end of the range is only reached at infinity
+            assert!((distant - (1. - camera.near_depth())).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn fitted_infinite_projection_stays_infinite() {
+        // 2 to 8 units in front of the camera at (0, 0, 5)
+        let bounds = BoundingBox {
+            min: Vec3::new(-1., -1., -3.),
+            max: Vec3::new(1., 1., 3.),
+        };
+        for reverse_z in [false, true] {
+            let mut camera = test_camera(reverse_z);
+            camera.set_perspective_infinite(90., 1., 1.);
+            camera.fit_depth_range(&bounds);
+            assert!(camera.is_infinite_far());
+
+            // The near plane moved to the box, with its margin
+            let near = to_ndc(&camera, Vec3::new(0., 0., 5. - 1.8)).z;
+            assert!((near - camera.near_depth()).abs() < EPSILON);
+            for distance in [10., 1e3, 1e6] {
+                let depth = to_ndc(&camera, Vec3::new(0., 0., 5. - distance)).z;
+                assert!((0f32..=1.).contains(&depth), "{} at {}", depth, distance);
+            }
+        }
+
+        let mut camera = test_camera(false);
+        camera.fit_depth_range(&bounds);
+        assert!(!camera.is_infinite_far());
+    }
+
+    #[test]
+    fn view_yxz_at_rest_looks_forward() {
+        let position = Vec3::new(1., 2., 3.);
+        let mut expected = Camera::empty();
+        expected.set_view_direction(position, Vec3::forward(), Vec3::up());
+
+        let mut camera = Camera::empty();
+        camera.set_view_yxz(position, Vec3::default());
+        assert_matrix(camera.get_view(), expected.get_view());
+        assert_matrix(camera.get_inverse_view(), expected.get_inverse_view());
+    }
+
+    #[test]
+    fn view_yxz_yaw_keeps_up() {
+        let mut camera = Camera::empty();
+        camera.set_view_yxz(
+            Vec3::default(),
+            Vec3::new(0., std::f32::consts::FRAC_PI_2, 0.),
+        );
+        let pose = camera.get_pose();
+        assert!(pose.direction.y.abs() < EPSILON);
+        assert!((pose.direction.x.abs() - 1.).abs() < EPSILON);
+        assert_near(pose.up, Vec3::up());
     }
 }
@@ -0,0 +1,123 @@
+use std::{
+    error::Error,
+    ffi::CStr,
+    fmt::Write as _,
+    fs,
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{camera::Camera, Engine};
+
+impl Engine {
+    // Written in the working directory when a frame fails, so a bug report
+    // can come with what the renderer was doing. Returns the file name.
+    pub fn write_crash_report(&self, error: &dyn Error, camera: &Camera) -> Option<String> {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let path = format!("scop-crash-{}.txt", seconds);
+
+        match fs::write(&path, self.crash_report(error, camera)) {
+            Ok(()) => {
+                eprintln!("Crash report written to {}", path);
+                Some(path)
+            }
+            Err(e) => {
+                eprintln!("{}: could not write the crash report: {}", path, e);
+                None
+            }
+        }
+    }
+
+    pub fn crash_report(&self, error: &dyn Error, camera: &Camera) -> String {
+        let mut report = String::new();
+        // Writing to a String does not fail
+        let _ = self.write_report(&mut report, error, camera);
+        report
+    }
+
+    fn write_report(
+        &self,
+        out: &mut String,
+        error: &dyn Error,
+        camera: &Camera,
+    ) -> std::fmt::Result {
+        let renderer = &self.renderer;
+
+        writeln!(out, "Error:      {}", error)?;
+        let mut source = error.source();
+        while let Some(cause) = source {
+            writeln!(out, "Caused by:  {}", cause)?;
+            source = cause.source();
+        }
+        writeln!(out, "Frame:      {}", renderer.frame_count)?;
+        writeln!(out, "Time:       {:.3}s", renderer.time())?;
+        writeln!(out, "Config:     {:?}", renderer.config)?;
+
+        writeln!(out, "\n[Swapchain]")?;
+        let extent = renderer.swapchain.extent;
+        writeln!(out, "Extent:     {}x{}", extent.width, extent.height)?;
+        match renderer.capabilities() {
+            Ok(capabilities) => writeln!(out, "{}", capabilities)?,
+            Err(e) => writeln!(out, "Capabilities unavailable: {}", e)?,
+        }
+
+        writeln!(out, "\n[Device]")?;
+        let device = &renderer.main_device;
+        let properties = &device.properties;
+        let name = unsafe { CStr::from_ptr(properties.device_name.as_ptr()) };
+        writeln!(out, "Name:       {}", name.to_string_lossy())?;
+        writeln!(out, "Type:       {:?}", properties.device_type)?;
+        writeln!(
+            out,
+            "API:        {}.{}.{}",
+            ash::vk::api_version_major(properties.api_version),
+            ash::vk::api_version_minor(properties.api_version),
+            ash::vk::api_version_patch(properties.api_version)
+        )?;
+        writeln!(out, "Driver:     {:#x}", properties.driver_version)?;
+        writeln!(
+            out,
+            "VRAM:       {:.1} MiB allocated",
+            device.allocated_memory() as f64 / (1024. * 1024.)
+        )?;
+        writeln!(out, "Limits:     {:#?}", properties.limits)?;
+
+        writeln!(out, "\n[Camera]")?;
+        writeln!(out, "{:?}", camera.get_pose())?;
+
+        // Shared meshes and materials have the same address
+        writeln!(out, "\n[Game objects]")?;
+        for node in self.traverse() {
+            let game_object = node.game_object.borrow();
+            writeln!(
+                out,
+                "{} {:?}{} mesh={} material={} position={}",
+                node.id,
+                game_object.name.as_deref().unwrap_or(""),
+                if node.active { "" } else { " (inactive)" },
+                game_object
+                    .mesh
+                    .as_ref()
+                    .map_or("none".to_string(), |mesh| format!(
+                        "{:p} ({} triangles)",
+                        Rc::as_ptr(mesh),
+                        mesh.triangle_count()
+                    )),
+                game_object
+                    .material
+                    .as_ref()
+                    .map_or("none".to_string(), |material| format!(
+                        "{:p}",
+                        Rc::as_ptr(material)
+                    )),
+                node.world_transform.transform_point(math::Vec3::default()),
+            )?;
+        }
+
+        writeln!(out, "\n[Last frame]")?;
+        writeln!(out, "{}", renderer.stats)?;
+        write!(out, "{}", renderer.frame_breakdown())
+    }
+}
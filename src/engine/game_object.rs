@@ -1,22 +1,74 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    any::{Any, TypeId},
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::{Rc, Weak},
+};
 
-use crate::renderer::MaterialInstanceRef;
+use math::Mat4;
 
-use super::{mesh::Mesh, Engine, Transform};
+use crate::{
+    renderer::{MaterialInstanceRef, Morph, Skin},
+    utils::Result,
+};
 
+use super::{
+    jobs::Jobs,
+    mesh::{BoundingBox, Mesh},
+    physics::RigidBody,
+    Engine, GameObjectId, Transform,
+};
+
+// Below this many stale local matrices the job threads cost more than they save
+const PARALLEL_MATRICES_MIN_OBJECTS: usize = 512;
+
+// Without mesh the object is an anchor: an invisible transform that other
+// objects can be parented to, to group them or rotate them around a pivot
 pub struct GameObject {
     pub name: Option<String>,
-    pub transform: Transform,
+    // Free form labels to address groups of objects, see `Engine::with_tag`
+    pub tags: Vec<String>,
+    // Relative to the parent, if any. Private so every change goes through
+    // `transform_mut` and invalidates `local_matrix`.
+    transform: Transform,
+    local_matrix: Cell<Option<Mat4>>,
+    // Set with the transform changes, until the engine looks for changes
+    moved: Cell<bool>,
+    pub parent: Option<Weak<RefCell<GameObject>>>,
+    // Disabled objects and their children are not drawn
+    pub enabled: bool,
+    // For the shadow pass, which does not exist yet: whether the object is
+    // drawn in the shadow map, and whether its shading reads it. A ground
+    // grid receives without casting, UI props opt out of both.
+    pub casts_shadows: bool,
+    pub receives_shadows: bool,
     pub mesh: Option<Rc<Mesh>>,
     pub material: Option<MaterialInstanceRef>,
+    // Deforms the mesh when the material has a skinned vertex shader
+    pub skin: Option<Skin>,
+    // Blends the morph targets of the mesh, ignored when made for another mesh
+    pub morph: Option<Morph>,
+    // Moved by `Engine::physics`, when there is one
+    pub body: Option<RigidBody>,
+    // App state attached to the object, one value per type
+    components: HashMap<TypeId, Box<dyn Any>>,
 }
 
 pub struct GameObjectBuilder<'a> {
     engine: &'a mut Engine,
     name: Option<&'a str>,
+    tags: Vec<String>,
     transform: Option<Transform>,
+    parent: Option<Weak<RefCell<GameObject>>>,
+    enabled: bool,
+    casts_shadows: bool,
+    receives_shadows: bool,
     mesh: Option<Rc<Mesh>>,
     material: Option<MaterialInstanceRef>,
+    skin: Option<Skin>,
+    morph: Option<Morph>,
+    body: Option<RigidBody>,
+    components: HashMap<TypeId, Box<dyn Any>>,
 }
 
 impl GameObject {
@@ -24,9 +76,211 @@ impl GameObject {
         GameObjectBuilder {
             engine,
             name: None,
+            tags: vec![],
             transform: None,
+            parent: None,
+            enabled: true,
+            casts_shadows: true,
+            receives_shadows: true,
+            mesh: None,
+            material: None,
+            skin: None,
+            morph: None,
+            body: None,
+            components: HashMap::new(),
+        }
+    }
+
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn transform_mut(&mut self) -> &mut Transform {
+        self.local_matrix.set(None);
+        self.moved.set(true);
+        &mut self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        *self.transform_mut() = transform;
+    }
+
+    // `transform.mat()`, only recomputed after the transform changed
+    pub fn local_matrix(&self) -> Mat4 {
+        self.local_matrix.get().unwrap_or_else(|| {
+            let matrix = self.transform.mat();
+            self.local_matrix.set(Some(matrix));
+            matrix
+        })
+    }
+
+    // Whether the transform changed since the last call
+    pub fn take_moved(&self) -> bool {
+        self.moved.replace(false)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    // Replaces the component of the same type, returned
+    pub fn insert_component<T: Any>(&mut self, component: T) -> Option<T> {
+        self.components
+            .insert(TypeId::of::<T>(), Box::new(component))
+            .and_then(|previous| previous.downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    pub fn component<T: Any>(&self) -> Option<&T> {
+        self.components.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    pub fn component_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.components.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+
+    pub fn remove_component<T: Any>(&mut self) -> Option<T> {
+        self.components
+            .remove(&TypeId::of::<T>())
+            .and_then(|component| component.downcast().ok())
+            .map(|component| *component)
+    }
+
+    pub fn has_component<T: Any>(&self) -> bool {
+        self.components.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn is_anchor(&self) -> bool {
+        self.mesh.is_none()
+    }
+
+    pub fn parent(&self) -> Option<Rc<RefCell<GameObject>>> {
+        self.parent.as_ref()?.upgrade()
+    }
+
+    // Enabled along with all its parents
+    pub fn is_active(&self) -> bool {
+        self.enabled
+            && self
+                .parent()
+                .is_none_or(|parent| parent.borrow().is_active())
+    }
+
+    // Object to world, parents transforms are applied after its own
+    pub fn world_transform(&self) -> Mat4 {
+        match self.parent() {
+            Some(parent) => self.local_matrix() * parent.borrow().world_transform(),
+            None => self.local_matrix(),
+        }
+    }
+
+    // Mesh bounding box in world space, None for anchors
+    pub fn world_bounds(&self) -> Option<BoundingBox> {
+        Some(
+            self.mesh
+                .as_ref()?
+                .bounding_box
+                .transform(&self.world_transform()),
+        )
+    }
+}
+
+pub struct SceneNode {
+    pub id: GameObjectId,
+    pub game_object: Rc<RefCell<GameObject>>,
+    pub world_transform: Mat4,
+    // Enabled along with all its parents
+    pub active: bool,
+}
+
+// Computes the local matrices of the moved objects on the job threads, so
+// `traverse` only multiplies them. Small batches are left to `local_matrix`.
+pub fn update_local_matrices(
+    game_objects: &HashMap<GameObjectId, Rc<RefCell<GameObject>>>,
+    jobs: &Jobs,
+) {
+    let (stale, transforms): (Vec<_>, Vec<_>) = game_objects
+        .values()
+        .filter_map(|go| {
+            let game_object = go.borrow();
+            let stale = game_object.local_matrix.get().is_none();
+            stale.then(|| (go, game_object.transform))
+        })
+        .unzip();
+    if stale.len() < PARALLEL_MATRICES_MIN_OBJECTS {
+        return;
+    }
+
+    let matrices = jobs.map(&transforms, Transform::mat);
+    for (go, matrix) in stale.into_iter().zip(matrices) {
+        go.borrow().local_matrix.set(Some(matrix));
+    }
+}
+
+// Depth first, parents before their children and siblings by id. Each world
+// transform is computed once from the parent one.
+pub fn traverse(
+    game_objects: &HashMap<GameObjectId, Rc<RefCell<GameObject>>>,
+) -> impl Iterator<Item = SceneNode> + '_ {
+    let mut roots = vec![];
+    let mut children = HashMap::<*const RefCell<GameObject>, Vec<GameObjectId>>::new();
+    for (id, go) in game_objects {
+        match go.borrow().parent() {
+            Some(parent) => children.entry(Rc::as_ptr(&parent)).or_default().push(*id),
+            None => roots.push(*id),
+        }
+    }
+    roots.sort_unstable_by(|a, b| b.cmp(a));
+    children
+        .values_mut()
+        .for_each(|ids| ids.sort_unstable_by(|a, b| b.cmp(a)));
+
+    let mut stack: Vec<(GameObjectId, Option<(Mat4, bool)>)> =
+        roots.into_iter().map(|id| (id, None)).collect();
+    std::iter::from_fn(move || {
+        let (id, parent) = stack.pop()?;
+        let game_object = game_objects[&id].clone();
+        let (world_transform, active) = {
+            let go = game_object.borrow();
+            match parent {
+                Some((parent_transform, parent_active)) => (
+                    go.local_matrix() * parent_transform,
+                    go.enabled && parent_active,
+                ),
+                None => (go.local_matrix(), go.enabled),
+            }
+        };
+
+        if let Some(ids) = children.get(&Rc::as_ptr(&game_object)) {
+            stack.extend(ids.iter().map(|id| (*id, Some((world_transform, active)))));
+        }
+        Some(SceneNode {
+            id,
+            game_object,
+            world_transform,
+            active,
+        })
+    })
+}
+
+impl Default for GameObject {
+    fn default() -> Self {
+        Self {
+            name: None,
+            tags: vec![],
+            transform: Transform::default(),
+            local_matrix: Cell::new(None),
+            moved: Cell::new(false),
+            parent: None,
+            enabled: true,
+            casts_shadows: true,
+            receives_shadows: true,
             mesh: None,
             material: None,
+            skin: None,
+            morph: None,
+            body: None,
+            components: HashMap::new(),
         }
     }
 }
@@ -37,11 +291,36 @@ impl<'a> GameObjectBuilder<'a> {
         self
     }
 
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
     pub fn transform(mut self, transform: Transform) -> Self {
         self.transform = Some(transform);
         self
     }
 
+    pub fn parent(mut self, parent: &Rc<RefCell<GameObject>>) -> Self {
+        self.parent = Some(Rc::downgrade(parent));
+        self
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn casts_shadows(mut self, casts_shadows: bool) -> Self {
+        self.casts_shadows = casts_shadows;
+        self
+    }
+
+    pub fn receives_shadows(mut self, receives_shadows: bool) -> Self {
+        self.receives_shadows = receives_shadows;
+        self
+    }
+
     pub fn mesh(mut self, mesh: Rc<Mesh>) -> Self {
         self.mesh = Some(mesh);
         self
@@ -52,12 +331,74 @@ impl<'a> GameObjectBuilder<'a> {
         self
     }
 
-    pub fn build(self) -> Rc<RefCell<GameObject>> {
-        self.engine.register(GameObject {
+    pub fn skin(mut self, skin: Skin) -> Self {
+        self.skin = Some(skin);
+        self
+    }
+
+    pub fn morph(mut self, morph: Morph) -> Self {
+        self.morph = Some(morph);
+        self
+    }
+
+    pub fn body(mut self, body: RigidBody) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    pub fn component<T: Any>(mut self, component: T) -> Self {
+        self.components
+            .insert(TypeId::of::<T>(), Box::new(component));
+        self
+    }
+
+    // Objects without mesh nor material are valid and never drawn
+    pub fn build(self) -> Result<Rc<RefCell<GameObject>>> {
+        let name = self.name.unwrap_or("<unnamed>");
+        match (&self.mesh, &self.material) {
+            (Some(_), None) => {
+                return Err(format!("GameObjectBuilder: {}: mesh without material", name).into())
+            }
+            (None, Some(_)) => {
+                return Err(format!("GameObjectBuilder: {}: material without mesh", name).into())
+            }
+            _ => (),
+        }
+
+        let device = &self.engine.renderer.main_device;
+        if self
+            .mesh
+            .as_ref()
+            .is_some_and(|mesh| !Rc::ptr_eq(mesh.device(), device))
+        {
+            return Err(format!("GameObjectBuilder: {}: mesh from another device", name).into());
+        }
+        if self
+            .material
+            .as_ref()
+            .is_some_and(|material| !Rc::ptr_eq(&material.material.device, device))
+        {
+            return Err(
+                format!("GameObjectBuilder: {}: material from another device", name).into(),
+            );
+        }
+
+        Ok(self.engine.register(GameObject {
             name: self.name.map(|s| s.to_string()),
+            tags: self.tags,
             transform: self.transform.unwrap_or(Transform::default()),
+            local_matrix: Cell::new(None),
+            moved: Cell::new(false),
+            parent: self.parent,
+            enabled: self.enabled,
+            casts_shadows: self.casts_shadows,
+            receives_shadows: self.receives_shadows,
             mesh: self.mesh,
             material: self.material,
-        })
+            skin: self.skin,
+            morph: self.morph,
+            body: self.body,
+            components: self.components,
+        }))
     }
 }
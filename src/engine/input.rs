@@ -0,0 +1,672 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fs::{self, File},
+    io::{BufWriter, Write},
+};
+
+use winit::{
+    event::MouseButton,
+    keyboard::{Key, KeyCode},
+};
+
+use crate::{bail, ensure, utils::Result};
+
+// Movement axes shared by the apps, bound to physical keys so they stay in
+// place on any keyboard layout
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Axis {
+    // A / D
+    Horizontal,
+    // Q / E
+    Vertical,
+    // W / S, W moves toward -z like the camera
+    Depth,
+    // Left / Right
+    Yaw,
+    // Down / Up
+    Tilt,
+}
+
+impl Axis {
+    // Keys toward the negative and the positive direction
+    pub fn keys(self) -> (KeyCode, KeyCode) {
+        match self {
+            Axis::Horizontal => (KeyCode::KeyA, KeyCode::KeyD),
+            Axis::Vertical => (KeyCode::KeyQ, KeyCode::KeyE),
+            Axis::Depth => (KeyCode::KeyW, KeyCode::KeyS),
+            Axis::Yaw => (KeyCode::ArrowLeft, KeyCode::ArrowRight),
+            Axis::Tilt => (KeyCode::ArrowDown, KeyCode::ArrowUp),
+        }
+    }
+}
+
+pub trait InputExt {
+    // -1, 0 or 1, holding both keys cancels out
+    fn axis(&self, axis: Axis) -> f32;
+
+    // Flips `value` when `key` goes down and tells if it did. OS key repeats
+    // are not presses, holding the key flips it once.
+    fn toggled(&self, key: KeyCode, value: &mut bool) -> bool;
+
+    // Shift moves faster and Ctrl slower, both cancel out
+    fn speed_multiplier(&self) -> f32;
+}
+
+const FAST_MULTIPLIER: f32 = 4.;
+const SLOW_MULTIPLIER: f32 = 0.25;
+
+// Keyboard and mouse events of one frame, the part of the window events the
+// apps read. Kept apart so a frame can be recorded and fed back.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputEvent {
+    // OS key repeats are left out. `text` is the character the key types
+    // with the current layout, for layout dependent shortcuts.
+    KeyDown { key: KeyCode, text: Option<String> },
+    KeyUp(KeyCode),
+    MouseDown(MouseButton),
+    MouseUp(MouseButton),
+    // Physical pixels, only the last position of a frame is kept
+    CursorMoved(f32, f32),
+    CursorLeft,
+    // Wheel lines, positive away from the user. Summed over the frame.
+    Scroll(f32),
+}
+
+// Input state the apps read each frame, built from the events of the frame.
+// Answers like WinitInputHelper for the queries the apps use.
+#[derive(Clone, Debug, Default)]
+pub struct FrameInput {
+    held: HashSet<KeyCode>,
+    pressed: Vec<KeyCode>,
+    typed: Vec<String>,
+    mouse_held: HashSet<MouseButton>,
+    mouse_pressed: Vec<MouseButton>,
+    cursor: Option<(f32, f32)>,
+    scroll: f32,
+}
+
+impl FrameInput {
+    // Starts a frame, presses only last one frame
+    pub fn step(&mut self, events: &[InputEvent]) {
+        self.pressed.clear();
+        self.typed.clear();
+        self.mouse_pressed.clear();
+        self.scroll = 0.;
+
+        for event in events {
+            match event {
+                InputEvent::KeyDown { key, text } => {
+                    if self.held.insert(*key) {
+                        self.pressed.push(*key);
+                        self.typed.extend(text.clone());
+                    }
+                }
+                InputEvent::KeyUp(key) => {
+                    self.held.remove(key);
+                }
+                InputEvent::MouseDown(button) => {
+                    if self.mouse_held.insert(*button) {
+                        self.mouse_pressed.push(*button);
+                    }
+                }
+                InputEvent::MouseUp(button) => {
+                    self.mouse_held.remove(button);
+                }
+                InputEvent::CursorMoved(x, y) => self.cursor = Some((*x, *y)),
+                InputEvent::CursorLeft => self.cursor = None,
+                InputEvent::Scroll(lines) => self.scroll += lines,
+            }
+        }
+    }
+
+    pub fn key_pressed(&self, key: KeyCode) -> bool {
+        self.pressed.contains(&key)
+    }
+
+    pub fn key_held(&self, key: KeyCode) -> bool {
+        self.held.contains(&key)
+    }
+
+    // Only characters are recorded, named keys are matched physically
+    pub fn key_pressed_logical(&self, key: Key<&str>) -> bool {
+        match key {
+            Key::Character(character) => self.typed.iter().any(|typed| typed == character),
+            _ => false,
+        }
+    }
+
+    pub fn held_control(&self) -> bool {
+        self.key_held(KeyCode::ControlLeft) || self.key_held(KeyCode::ControlRight)
+    }
+
+    pub fn held_shift(&self) -> bool {
+        self.key_held(KeyCode::ShiftLeft) || self.key_held(KeyCode::ShiftRight)
+    }
+
+    pub fn mouse_pressed(&self, button: MouseButton) -> bool {
+        self.mouse_pressed.contains(&button)
+    }
+
+    pub fn mouse_held(&self, button: MouseButton) -> bool {
+        self.mouse_held.contains(&button)
+    }
+
+    pub fn cursor(&self) -> Option<(f32, f32)> {
+        self.cursor
+    }
+
+    pub fn scroll(&self) -> f32 {
+        self.scroll
+    }
+}
+
+impl InputExt for FrameInput {
+    fn axis(&self, axis: Axis) -> f32 {
+        let (negative, positive) = axis.keys();
+        let held = |key| if self.key_held(key) { 1. } else { 0. };
+        held(positive) - held(negative)
+    }
+
+    fn toggled(&self, key: KeyCode, value: &mut bool) -> bool {
+        if self.key_pressed(key) {
+            *value = !*value;
+            return true;
+        }
+        false
+    }
+
+    fn speed_multiplier(&self) -> f32 {
+        let mut multiplier = 1.;
+        if self.held_shift() {
+            multiplier *= FAST_MULTIPLIER;
+        }
+        if self.held_control() {
+            multiplier *= SLOW_MULTIPLIER;
+        }
+        multiplier
+    }
+}
+
+// Writes the events of every frame to a text file, one event per line
+// prefixed with its frame number. Frames without events take no line.
+pub struct InputRecorder {
+    file: BufWriter<File>,
+    frame: u64,
+}
+
+impl InputRecorder {
+    pub fn create(path: &str) -> Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "{}", INPUT_TRACE_HEADER)?;
+        Ok(Self { file, frame: 0 })
+    }
+
+    pub fn record(&mut self, events: &[InputEvent]) -> Result<()> {
+        for event in events {
+            // A skipped event would make the replay diverge
+            let Some(line) = event.to_line() else {
+                bail!(format!("Input recording: cannot record {:?}", event));
+            };
+            writeln!(self.file, "{} {}", self.frame, line)?;
+        }
+        // Kept on disk even if the app crashes on this frame
+        if !events.is_empty() {
+            self.file.flush()?;
+        }
+        self.frame += 1;
+        Ok(())
+    }
+}
+
+// Feeds a file written by InputRecorder back, frame by frame
+pub struct InputReplay {
+    events: VecDeque<(u64, InputEvent)>,
+    frame: u64,
+    last_frame: u64,
+}
+
+impl InputReplay {
+    pub fn open(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        // Only \n ends a record, `lines` would also eat a \r ending a key text
+        let mut lines = content.split('\n').enumerate();
+        ensure!(
+            lines
+                .next()
+                .is_some_and(|(_, line)| line == INPUT_TRACE_HEADER),
+            "Not an input recording"
+        );
+
+        let mut events = VecDeque::new();
+        for (number, line) in lines {
+            if line.is_empty() {
+                continue;
+            }
+            let parsed = line.split_once(' ').and_then(|(frame, event)| {
+                Some((frame.parse::<u64>().ok()?, InputEvent::parse(event)?))
+            });
+            let Some((frame, event)) = parsed else {
+                bail!(format!("{}:{}: invalid input event", path, number + 1));
+            };
+            ensure!(
+                events.back().is_none_or(|(last, _)| *last <= frame),
+                "Input recording frames must be in order"
+            );
+            events.push_back((frame, event));
+        }
+
+        Ok(Self {
+            last_frame: events.back().map_or(0, |(frame, _)| *frame),
+            events,
+            frame: 0,
+        })
+    }
+
+    // Events of the next frame, None once the recording is over
+    pub fn next_frame(&mut self) -> Option<Vec<InputEvent>> {
+        if self.frame > self.last_frame {
+            return None;
+        }
+        let mut events = vec![];
+        while self
+            .events
+            .front()
+            .is_some_and(|(frame, _)| *frame == self.frame)
+        {
+            events.extend(self.events.pop_front().map(|(_, event)| event));
+        }
+        self.frame += 1;
+        Some(events)
+    }
+}
+
+// 2 escapes the key texts
+const INPUT_TRACE_HEADER: &str = "scop input 2";
+
+impl InputEvent {
+    fn to_line(&self) -> Option<String> {
+        Some(match self {
+            InputEvent::KeyDown { key, text } => match text {
+                Some(text) => format!("key_down {} {}", key_code_name(*key)?, escape(text)),
+                None => format!("key_down {}", key_code_name(*key)?),
+            },
+            InputEvent::KeyUp(key) => format!("key_up {}", key_code_name(*key)?),
+            InputEvent::MouseDown(button) => format!("mouse_down {}", mouse_button_name(*button)),
+            InputEvent::MouseUp(button) => format!("mouse_up {}", mouse_button_name(*button)),
+            InputEvent::CursorMoved(x, y) => format!("cursor {} {}", x, y),
+            InputEvent::CursorLeft => "cursor_left".to_owned(),
+            InputEvent::Scroll(lines) => format!("scroll {}", lines),
+        })
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let (kind, args) = line.split_once(' ').unwrap_or((line, ""));
+        Some(match kind {
+            "key_down" => {
+                // The text is the rest of the line, it can be a space
+                let (key, text) = match args.split_once(' ') {
+                    Some((key, text)) => (key, Some(unescape(text)?)),
+                    None => (args, None),
+                };
+                InputEvent::KeyDown {
+                    key: key_code_from_name(key)?,
+                    text,
+                }
+            }
+            "key_up" => InputEvent::KeyUp(key_code_from_name(args)?),
+            "mouse_down" => InputEvent::MouseDown(mouse_button_from_name(args)?),
+            "mouse_up" => InputEvent::MouseUp(mouse_button_from_name(args)?),
+            "cursor" => {
+                let (x, y) = args.split_once(' ')?;
+                InputEvent::CursorMoved(x.parse().ok()?, y.parse().ok()?)
+            }
+            "cursor_left" => InputEvent::CursorLeft,
+            "scroll" => InputEvent::Scroll(args.parse().ok()?),
+            _ => return None,
+        })
+    }
+}
+
+// Key texts can be control characters, like \r for Enter. They are written
+// escaped so a record stays on one line.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(escaped: &str) -> Option<String> {
+    let mut text = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        text.push(match chars.next()? {
+            '\\' => '\\',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => {
+                let code = chars.as_str().strip_prefix('{')?.split_once('}')?.0;
+                let character = char::from_u32(u32::from_str_radix(code, 16).ok()?)?;
+                // The braces and the digits
+                chars.nth(code.len() + 1)?;
+                character
+            }
+            _ => return None,
+        });
+    }
+    Some(text)
+}
+
+fn mouse_button_name(button: MouseButton) -> String {
+    match button {
+        MouseButton::Left => "left".to_owned(),
+        MouseButton::Right => "right".to_owned(),
+        MouseButton::Middle => "middle".to_owned(),
+        MouseButton::Back => "back".to_owned(),
+        MouseButton::Forward => "forward".to_owned(),
+        MouseButton::Other(index) => index.to_string(),
+    }
+}
+
+fn mouse_button_from_name(name: &str) -> Option<MouseButton> {
+    Some(match name {
+        "left" => MouseButton::Left,
+        "right" => MouseButton::Right,
+        "middle" => MouseButton::Middle,
+        "back" => MouseButton::Back,
+        "forward" => MouseButton::Forward,
+        _ => MouseButton::Other(name.parse().ok()?),
+    })
+}
+
+// KeyCode has no stable number or name, its variants are listed by hand. The
+// list covers winit 0.29, a key added later fails the recording.
+macro_rules! key_code_names {
+    ($($key:ident),* $(,)?) => {
+        fn key_code_name(key: KeyCode) -> Option<&'static str> {
+            match key {
+                $(KeyCode::$key => Some(stringify!($key)),)*
+                _ => None,
+            }
+        }
+
+        fn key_code_from_name(name: &str) -> Option<KeyCode> {
+            match name {
+                $(stringify!($key) => Some(KeyCode::$key),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+key_code_names![
+    Backquote,
+    Backslash,
+    BracketLeft,
+    BracketRight,
+    Comma,
+    Digit0,
+    Digit1,
+    Digit2,
+    Digit3,
+    Digit4,
+    Digit5,
+    Digit6,
+    Digit7,
+    Digit8,
+    Digit9,
+    Equal,
+    IntlBackslash,
+    IntlRo,
+    IntlYen,
+    KeyA,
+    KeyB,
+    KeyC,
+    KeyD,
+    KeyE,
+    KeyF,
+    KeyG,
+    KeyH,
+    KeyI,
+    KeyJ,
+    KeyK,
+    KeyL,
+    KeyM,
+    KeyN,
+    KeyO,
+    KeyP,
+    KeyQ,
+    KeyR,
+    KeyS,
+    KeyT,
+    KeyU,
+    KeyV,
+    KeyW,
+    KeyX,
+    KeyY,
+    KeyZ,
+    Minus,
+    Period,
+    Quote,
+    Semicolon,
+    Slash,
+    AltLeft,
+    AltRight,
+    Backspace,
+    CapsLock,
+    ContextMenu,
+    ControlLeft,
+    ControlRight,
+    Enter,
+    SuperLeft,
+    SuperRight,
+    ShiftLeft,
+    ShiftRight,
+    Space,
+    Tab,
+    Convert,
+    KanaMode,
+    Lang1,
+    Lang2,
+    Lang3,
+    Lang4,
+    Lang5,
+    NonConvert,
+    Delete,
+    End,
+    Help,
+    Home,
+    Insert,
+    PageDown,
+    PageUp,
+    ArrowDown,
+    ArrowLeft,
+    ArrowRight,
+    ArrowUp,
+    NumLock,
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    NumpadAdd,
+    NumpadBackspace,
+    NumpadClear,
+    NumpadClearEntry,
+    NumpadComma,
+    NumpadDecimal,
+    NumpadDivide,
+    NumpadEnter,
+    NumpadEqual,
+    NumpadHash,
+    NumpadMemoryAdd,
+    NumpadMemoryClear,
+    NumpadMemoryRecall,
+    NumpadMemoryStore,
+    NumpadMemorySubtract,
+    NumpadMultiply,
+    NumpadParenLeft,
+    NumpadParenRight,
+    NumpadStar,
+    NumpadSubtract,
+    Escape,
+    Fn,
+    FnLock,
+    PrintScreen,
+    ScrollLock,
+    Pause,
+    BrowserBack,
+    BrowserFavorites,
+    BrowserForward,
+    BrowserHome,
+    BrowserRefresh,
+    BrowserSearch,
+    BrowserStop,
+    Eject,
+    LaunchApp1,
+    LaunchApp2,
+    LaunchMail,
+    MediaPlayPause,
+    MediaSelect,
+    MediaStop,
+    MediaTrackNext,
+    MediaTrackPrevious,
+    Power,
+    Sleep,
+    AudioVolumeDown,
+    AudioVolumeMute,
+    AudioVolumeUp,
+    WakeUp,
+    Meta,
+    Hyper,
+    Turbo,
+    Abort,
+    Resume,
+    Suspend,
+    Again,
+    Copy,
+    Cut,
+    Find,
+    Open,
+    Paste,
+    Props,
+    Select,
+    Undo,
+    Hiragana,
+    Katakana,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    F13,
+    F14,
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
+    F25,
+    F26,
+    F27,
+    F28,
+    F29,
+    F30,
+    F31,
+    F32,
+    F33,
+    F34,
+    F35,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(event: InputEvent) {
+        let line = event.to_line().unwrap();
+        assert!(!line.contains(['\n', '\r']), "{:?}", line);
+        assert_eq!(InputEvent::parse(&line), Some(event));
+    }
+
+    #[test]
+    fn key_texts_survive_a_round_trip() {
+        for text in ["\r", " ", "\\", "\\n", "\t", "\u{1b}", "\u{7f}x", "é"] {
+            round_trip(InputEvent::KeyDown {
+                key: KeyCode::Enter,
+                text: Some(text.to_owned()),
+            });
+        }
+        round_trip(InputEvent::KeyDown {
+            key: KeyCode::Escape,
+            text: None,
+        });
+    }
+
+    #[test]
+    fn every_listed_key_has_a_name() {
+        for key in [KeyCode::F35, KeyCode::NumpadEnter, KeyCode::IntlYen] {
+            round_trip(InputEvent::KeyUp(key));
+        }
+    }
+
+    #[test]
+    fn bad_escapes_are_rejected() {
+        for text in ["\\", "\\q", "\\u{110000}", "\\u{zz}", "\\u{41"] {
+            assert_eq!(unescape(text), None, "{:?}", text);
+        }
+        assert_eq!(unescape("a\\u{41}b").as_deref(), Some("aAb"));
+    }
+
+    #[test]
+    fn replay_keeps_a_carriage_return_text() {
+        let path = std::env::temp_dir().join(format!("scop-input-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        let events = [
+            InputEvent::KeyDown {
+                key: KeyCode::Enter,
+                text: Some("\r".to_owned()),
+            },
+            InputEvent::KeyUp(KeyCode::Enter),
+        ];
+
+        let mut recorder = InputRecorder::create(path).unwrap();
+        recorder.record(&events).unwrap();
+        drop(recorder);
+        let mut replay = InputReplay::open(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(replay.next_frame().as_deref(), Some(&events[..]));
+        assert_eq!(replay.next_frame(), None);
+    }
+}
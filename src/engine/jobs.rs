@@ -0,0 +1,274 @@
+use std::{
+    cell::Cell,
+    mem,
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Condvar, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+// Runs closures on worker threads started with the pool, they take the jobs
+// from a channel in spawn order. Jobs spawned in a scope may borrow from the
+// caller and are all done when `scope` returns. A scope with a single job, or
+// started from a job, runs on the calling thread.
+#[derive(Debug)]
+pub struct Jobs {
+    threads: usize,
+    // None once dropped, which stops the workers
+    sender: Option<Sender<Job<'static>>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+type Job<'scope> = Box<dyn FnOnce() + Send + 'scope>;
+
+pub struct JobScope<'scope> {
+    jobs: Mutex<Vec<Job<'scope>>>,
+}
+
+// Jobs of a scope not done yet, and whether one of them panicked
+#[derive(Default)]
+struct Latch {
+    state: Mutex<(usize, bool)>,
+    done: Condvar,
+}
+
+thread_local! {
+    static IN_JOB: Cell<bool> = const { Cell::new(false) };
+}
+
+impl Jobs {
+    // The calling thread only waits, a single thread pool has no worker
+    pub fn new(threads: usize) -> Self {
+        let threads = threads.max(1);
+        if threads == 1 {
+            return Self {
+                threads,
+                sender: None,
+                workers: vec![],
+            };
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads)
+            .map(|i| {
+                let receiver = receiver.clone();
+                thread::Builder::new()
+                    .name(format!("scop-job-{}", i))
+                    .spawn(move || Self::work(&receiver))
+                    .expect("Cannot start a job thread")
+            })
+            .collect();
+        Self {
+            threads,
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    // One thread per core
+    pub fn available() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, NonZeroUsize::get))
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn scope<'scope, R>(&self, f: impl FnOnce(&JobScope<'scope>) -> R) -> R {
+        let scope = JobScope {
+            jobs: Mutex::new(vec![]),
+        };
+        let result = f(&scope);
+        self.run(scope.jobs.into_inner().unwrap());
+        result
+    }
+
+    // One job per thread, each mapping a contiguous chunk of `items`. The
+    // results keep the order of the items.
+    pub fn map<T: Sync, R: Send>(&self, items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+        let chunk_size = items.len().div_ceil(self.threads).max(1);
+        let mut results: Vec<Vec<R>> = items.chunks(chunk_size).map(|_| vec![]).collect();
+        self.scope(|s| {
+            let f = &f;
+            for (chunk, out) in items.chunks(chunk_size).zip(results.iter_mut()) {
+                s.spawn(move || *out = chunk.iter().map(f).collect());
+            }
+        });
+        results.into_iter().flatten().collect()
+    }
+
+    fn run<'scope>(&self, jobs: Vec<Job<'scope>>) {
+        // Waiting on the workers from a job could take the last free one
+        let sender = match &self.sender {
+            Some(sender) if jobs.len() > 1 && !IN_JOB.get() => sender,
+            _ => {
+                jobs.into_iter().for_each(|job| job());
+                return;
+            }
+        };
+
+        let latch = Arc::new(Latch::default());
+        latch.state.lock().unwrap().0 = jobs.len();
+        for job in jobs {
+            let latch = latch.clone();
+            let job: Job<'scope> = Box::new(move || {
+                let panicked = panic::catch_unwind(AssertUnwindSafe(job)).is_err();
+                let mut state = latch.state.lock().unwrap();
+                state.0 -= 1;
+                state.1 |= panicked;
+                latch.done.notify_all();
+            });
+            // SAFETY: the borrows live for 'scope, which outlives this call,
+            // and every job is done before it returns. The jobs catch their
+            // panics so the count always reaches 0.
+            let job = unsafe { mem::transmute::<Job<'scope>, Job<'static>>(job) };
+            if let Err(mpsc::SendError(job)) = sender.send(job) {
+                job();
+            }
+        }
+
+        let mut state = latch.state.lock().unwrap();
+        while state.0 > 0 {
+            state = latch.done.wait(state).unwrap();
+        }
+        if state.1 {
+            panic!("A job panicked");
+        }
+    }
+
+    fn work(receiver: &Mutex<Receiver<Job<'static>>>) {
+        IN_JOB.set(true);
+        loop {
+            // The lock is released before running the job
+            let job = receiver.lock().unwrap().recv();
+            match job {
+                Ok(job) => job(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+impl Drop for Jobs {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl<'scope> JobScope<'scope> {
+    pub fn spawn(&self, job: impl FnOnce() + Send + 'scope) {
+        self.jobs.lock().unwrap().push(Box::new(job));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+
+    #[test]
+    fn map_keeps_the_order() {
+        let jobs = Jobs::new(4);
+        let items: Vec<usize> = (0..1000).collect();
+        for _ in 0..10 {
+            let doubled = jobs.map(&items, |i| i * 2);
+            assert_eq!(doubled, items.iter().map(|i| i * 2).collect::<Vec<_>>());
+        }
+        assert_eq!(jobs.map(&items[..3], |i| *i), [0, 1, 2]);
+        assert!(jobs.map(&[] as &[usize], |i| *i).is_empty());
+    }
+
+    #[test]
+    fn jobs_write_to_borrowed_locals() {
+        let jobs = Jobs::new(3);
+        let mut sums = [0; 8];
+        let values: Vec<u64> = (1..=100).collect();
+        jobs.scope(|s| {
+            for (i, sum) in sums.iter_mut().enumerate() {
+                let values = &values;
+                s.spawn(move || *sum = values.iter().map(|v| v * i as u64).sum());
+            }
+        });
+        assert_eq!(sums, [0, 5050, 10100, 15150, 20200, 25250, 30300, 35350]);
+    }
+
+    #[test]
+    fn workers_are_kept_between_scopes() {
+        let jobs = Jobs::new(2);
+        let threads = Mutex::new(HashSet::new());
+        for _ in 0..20 {
+            jobs.scope(|s| {
+                for _ in 0..4 {
+                    s.spawn(|| {
+                        threads.lock().unwrap().insert(thread::current().id());
+                    });
+                }
+            });
+        }
+        let threads = threads.into_inner().unwrap();
+        assert!(threads.len() <= 2);
+        assert!(!threads.contains(&thread::current().id()));
+    }
+
+    #[test]
+    fn scopes_started_from_jobs_finish() {
+        let jobs = Jobs::new(2);
+        let count = AtomicUsize::new(0);
+        jobs.scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    jobs.scope(|s| {
+                        for _ in 0..4 {
+                            s.spawn(|| {
+                                count.fetch_add(1, Ordering::Relaxed);
+                            });
+                        }
+                    })
+                });
+            }
+        });
+        assert_eq!(count.into_inner(), 16);
+    }
+
+    #[test]
+    fn a_single_thread_runs_on_the_caller() {
+        let jobs = Jobs::new(0);
+        assert_eq!(jobs.threads(), 1);
+        let caller = thread::current().id();
+        assert!(jobs
+            .map(&[1, 2, 3], |_| thread::current().id())
+            .iter()
+            .all(|id| *id == caller));
+    }
+
+    #[test]
+    fn job_panics_reach_the_caller_after_the_scope() {
+        let jobs = Jobs::new(2);
+        let finished = AtomicUsize::new(0);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            jobs.scope(|s| {
+                s.spawn(|| panic!("job"));
+                for _ in 0..3 {
+                    s.spawn(|| {
+                        finished.fetch_add(1, Ordering::Relaxed);
+                    });
+                }
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(finished.into_inner(), 3);
+        // The workers survived the panic
+        assert_eq!(jobs.map(&[1, 2], |i| i + 1), [2, 3]);
+    }
+}
@@ -0,0 +1,61 @@
+use std::f32::consts::TAU;
+
+use math::{Vec2, Vec3};
+
+use crate::renderer::DebugLines;
+
+const SEGMENTS: usize = 32;
+
+// Drawn in the middle of the screen through overlay lines while the engine
+// is in its loading state, see `Engine::loading`. An arc turns around a ring
+// filling up with the progress.
+pub struct LoadingSpinner {
+    pub label: String,
+    // From 0 to 1
+    pub progress: f32,
+    // In physical pixels
+    pub radius: f32,
+    pub color: Vec3,
+}
+
+impl LoadingSpinner {
+    pub fn new(label: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            progress: 0.,
+            radius: 24.,
+            color: Vec3::new(0.9, 0.9, 0.9),
+        }
+    }
+
+    pub fn draw(&self, lines: &mut DebugLines, time: f32, viewport_size: Vec2) {
+        let center = viewport_size / 2.;
+        let point = |angle: f32, radius: f32| {
+            let position = center + Vec2::new(angle.cos(), angle.sin()) * radius;
+            Vec3::new(
+                position.x * 2. / viewport_size.x - 1.,
+                position.y * 2. / viewport_size.y - 1.,
+                0.5,
+            )
+        };
+        let mut arc = |start: f32, length: f32, radius: f32, color: Vec3| {
+            let segments = ((SEGMENTS as f32 * length / TAU).ceil() as usize).max(1);
+            for i in 0..segments {
+                let from = start + length * i as f32 / segments as f32;
+                let to = start + length * (i + 1) as f32 / segments as f32;
+                lines.line(point(from, radius), point(to, radius), color);
+            }
+        };
+
+        // From the top, clockwise on screen
+        let top = -TAU / 4.;
+        arc(top, TAU, self.radius * 0.8, self.color * 0.3);
+        arc(
+            top,
+            TAU * self.progress.clamp(0., 1.),
+            self.radius * 0.8,
+            self.color,
+        );
+        arc(time * TAU, TAU / 4., self.radius, self.color);
+    }
+}
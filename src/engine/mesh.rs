@@ -1,18 +1,21 @@
 use std::{
-    mem::{self, offset_of, size_of},
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+    mem::{self, offset_of},
     rc::Rc,
 };
 
 use ash::vk::{
-    self, BufferUsageFlags, CommandBuffer, MemoryPropertyFlags, VertexInputAttributeDescription,
-    VertexInputBindingDescription, WHOLE_SIZE,
+    self, BufferUsageFlags, CommandBuffer, VertexInputAttributeDescription,
+    VertexInputBindingDescription,
 };
-use math::{Vec2, Vec3};
+use math::{Mat4, Vec2, Vec3, Vec4};
 
+use super::bvh::{ray_triangle, Bvh, Hit};
 use crate::{
     ensure,
-    renderer::{RendererDevice, ScopBuffer},
-    utils::{Context, Result},
+    renderer::{RendererDevice, ScopBuffer, ScopStagingRing},
+    utils::{any_as_u8_slice, Context, Result},
 };
 
 #[derive(Copy, Clone, Default, Debug, PartialEq)]
@@ -21,85 +24,476 @@ pub struct Vertex {
     pub color: Vec3,
     pub normal: Vec3,
     pub uv: Vec2,
+    // Up to 4 joints of the skeleton moving the vertex, weights summing to 1.
+    // Only read by the skinned vertex shader.
+    pub joints: [u16; 4],
+    pub weights: Vec4,
 }
 
 #[derive(Copy, Clone, Default, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BoundingBox {
     pub min: Vec3,
     pub max: Vec3,
 }
 
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+// Offsets from the base vertices, mixed in by the weights of a `Morph`
+#[derive(Clone, Debug, Default)]
+pub struct MorphTarget {
+    pub name: String,
+    // One per vertex of the mesh
+    pub position_deltas: Vec<Vec3>,
+    // Empty when the target keeps the normals
+    pub normal_deltas: Vec<Vec3>,
+}
+
 pub struct Mesh {
     device: Rc<RendererDevice>,
     pub bounding_box: BoundingBox,
-    // pub vertices: Vec<Vertex>,
+    pub bounding_sphere: BoundingSphere,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    // Pairs of vertices, not drawn with the triangles
+    pub line_indices: Vec<u32>,
+    pub point_indices: Vec<u32>,
+    // Factor the positions were multiplied by on import, a transform scale of
+    // its inverse shows the model in its original units
+    pub import_scale: f32,
+    pub morph_targets: Vec<MorphTarget>,
+    pub vertex_layout: VertexLayout,
+    bvh: Option<Bvh>,
     vertex_buffer: ScopBuffer,
+    // Where each binding starts in `vertex_buffer`, one per attribute when
+    // planar
+    binding_offsets: Vec<vk::DeviceSize>,
     index_buffer: Option<ScopBuffer>,
 }
 
 pub struct MeshBuilder<'a> {
     device: Rc<RendererDevice>,
+    staging_ring: Option<&'a mut ScopStagingRing>,
     vertices: Option<&'a [Vertex]>,
     indices: Option<&'a [u32]>,
+    line_indices: &'a [u32],
+    point_indices: &'a [u32],
+    bvh: bool,
+    import_scale: f32,
+    morph_targets: &'a [MorphTarget],
+    vertex_layout: VertexLayout,
 }
 
-impl Vertex {
-    pub fn get_vertex_input_attribute_descriptions() -> Vec<VertexInputAttributeDescription> {
-        vec![
-            vk::VertexInputAttributeDescription {
-                location: 0,
-                binding: 0,
-                format: vk::Format::R32G32B32_SFLOAT,
-                offset: offset_of!(Vertex, position) as u32,
-            },
-            vk::VertexInputAttributeDescription {
-                location: 1,
-                binding: 0,
-                format: vk::Format::R32G32B32_SFLOAT,
-                offset: offset_of!(Vertex, color) as u32,
-            },
-            vk::VertexInputAttributeDescription {
-                location: 2,
-                binding: 0,
-                format: vk::Format::R32G32B32_SFLOAT,
-                offset: offset_of!(Vertex, normal) as u32,
-            },
-            vk::VertexInputAttributeDescription {
-                location: 3,
+// How the attributes are laid out in the vertex buffer, chosen when the mesh
+// is built. Planar puts each attribute in its own stream of the buffer: depth
+// only passes, like the depth prepass, then fetch the positions alone instead
+// of whole vertices.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum VertexLayout {
+    #[default]
+    Interleaved,
+    Planar,
+}
+
+struct VertexAttribute {
+    format: vk::Format,
+    offset: usize,
+    size: usize,
+}
+
+// By shader location
+const VERTEX_ATTRIBUTES: [VertexAttribute; 6] = [
+    VertexAttribute {
+        format: vk::Format::R32G32B32_SFLOAT,
+        offset: offset_of!(Vertex, position),
+        size: mem::size_of::<Vec3>(),
+    },
+    VertexAttribute {
+        format: vk::Format::R32G32B32_SFLOAT,
+        offset: offset_of!(Vertex, color),
+        size: mem::size_of::<Vec3>(),
+    },
+    VertexAttribute {
+        format: vk::Format::R32G32B32_SFLOAT,
+        offset: offset_of!(Vertex, normal),
+        size: mem::size_of::<Vec3>(),
+    },
+    VertexAttribute {
+        format: vk::Format::R32G32_SFLOAT,
+        offset: offset_of!(Vertex, uv),
+        size: mem::size_of::<Vec2>(),
+    },
+    VertexAttribute {
+        format: vk::Format::R16G16B16A16_UINT,
+        offset: offset_of!(Vertex, joints),
+        size: mem::size_of::<[u16; 4]>(),
+    },
+    VertexAttribute {
+        format: vk::Format::R32G32B32A32_SFLOAT,
+        offset: offset_of!(Vertex, weights),
+        size: mem::size_of::<Vec4>(),
+    },
+];
+
+// Streams of a planar buffer start on this boundary
+const STREAM_ALIGNMENT: usize = 16;
+
+impl VertexLayout {
+    pub fn attribute_descriptions(self) -> Vec<VertexInputAttributeDescription> {
+        VERTEX_ATTRIBUTES
+            .iter()
+            .enumerate()
+            .map(|(location, attribute)| match self {
+                VertexLayout::Interleaved => vk::VertexInputAttributeDescription {
+                    location: location as u32,
+                    binding: 0,
+                    format: attribute.format,
+                    offset: attribute.offset as u32,
+                },
+                VertexLayout::Planar => vk::VertexInputAttributeDescription {
+                    location: location as u32,
+                    binding: location as u32,
+                    format: attribute.format,
+                    offset: 0,
+                },
+            })
+            .collect()
+    }
+
+    pub fn binding_descriptions(self) -> Vec<VertexInputBindingDescription> {
+        match self {
+            VertexLayout::Interleaved => vec![vk::VertexInputBindingDescription {
                 binding: 0,
-                format: vk::Format::R32G32_SFLOAT,
-                offset: offset_of!(Vertex, uv) as u32,
-            },
-        ]
+                stride: mem::size_of::<Vertex>() as u32,
+                input_rate: vk::VertexInputRate::VERTEX,
+            }],
+            VertexLayout::Planar => VERTEX_ATTRIBUTES
+                .iter()
+                .enumerate()
+                .map(|(binding, attribute)| vk::VertexInputBindingDescription {
+                    binding: binding as u32,
+                    stride: attribute.size as u32,
+                    input_rate: vk::VertexInputRate::VERTEX,
+                })
+                .collect(),
+        }
+    }
+
+}
+
+// The bytes of a planar vertex buffer, and where each binding starts in it
+fn planar_streams(vertices: &[Vertex]) -> (Vec<u8>, Vec<vk::DeviceSize>) {
+    let mut bytes = vec![];
+    let mut offsets = Vec::with_capacity(VERTEX_ATTRIBUTES.len());
+    for attribute in &VERTEX_ATTRIBUTES {
+        bytes.resize(bytes.len().next_multiple_of(STREAM_ALIGNMENT), 0);
+        offsets.push(bytes.len() as vk::DeviceSize);
+        for vertex in vertices {
+            let vertex = unsafe { any_as_u8_slice(vertex) };
+            bytes.extend_from_slice(&vertex[attribute.offset..attribute.offset + attribute.size]);
+        }
+    }
+    (bytes, offsets)
+}
+
+pub struct MeshStats {
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+    pub surface_area: f32,
+    pub degenerate_triangle_count: usize,
+    pub duplicate_vertex_count: usize,
+    pub uv_coverage: f32,
+    pub has_normals: bool,
+    pub inconsistent_winding_ratio: f32,
+    pub inverted_winding_ratio: Option<f32>,
+    pub bounding_box: BoundingBox,
+    pub bounding_sphere: BoundingSphere,
+}
+
+pub fn triangles<'a>(
+    vertices: &'a [Vertex],
+    indices: &'a [u32],
+) -> impl Iterator<Item = [u32; 3]> + 'a {
+    let count = if indices.is_empty() {
+        vertices.len() / 3
+    } else {
+        indices.len() / 3
+    };
+
+    (0..count).map(move |i| triangle(indices, i))
+}
+
+// Non-indexed meshes store their triangles as consecutive vertices
+pub fn triangle(indices: &[u32], i: usize) -> [u32; 3] {
+    if indices.is_empty() {
+        [i as u32 * 3, i as u32 * 3 + 1, i as u32 * 3 + 2]
+    } else {
+        [indices[i * 3], indices[i * 3 + 1], indices[i * 3 + 2]]
+    }
+}
+
+// No area at all, its corners are on one line. Shared by the OBJ parser,
+// which drops them, and `MeshStats`, which counts them.
+pub fn is_degenerate_triangle(a: Vec3, b: Vec3, c: Vec3) -> bool {
+    (b - a).cross(&(c - a)) == Vec3::default()
+}
+
+// Negates x and swaps two corners of every triangle so they keep facing out
+pub fn mirror_x(vertices: &mut [Vertex], indices: &mut [u32]) {
+    for vertex in vertices.iter_mut() {
+        vertex.position.x = -vertex.position.x;
+        vertex.normal.x = -vertex.normal.x;
+    }
+
+    if indices.is_empty() {
+        vertices
+            .chunks_exact_mut(3)
+            .for_each(|triangle| triangle.swap(1, 2));
+    } else {
+        indices
+            .chunks_exact_mut(3)
+            .for_each(|triangle| triangle.swap(1, 2));
+    }
+}
+
+// Sphere centered on the origin, the poles on the y axis. The uvs follow the
+// OBJ convention, v going up, with a seam of duplicated vertices at u = 0.
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let (segments, rings) = (segments.max(3), rings.max(2));
+    let mut vertices = Vec::with_capacity(((segments + 1) * (rings + 1)) as usize);
+    for ring in 0..=rings {
+        let theta = std::f32::consts::PI * ring as f32 / rings as f32;
+        for segment in 0..=segments {
+            let phi = std::f32::consts::TAU * segment as f32 / segments as f32;
+            let normal = Vec3::new(
+                theta.sin() * phi.sin(),
+                theta.cos(),
+                theta.sin() * phi.cos(),
+            );
+            vertices.push(Vertex {
+                position: normal * radius,
+                color: Vec3::one(),
+                normal,
+                uv: Vec2::new(
+                    segment as f32 / segments as f32,
+                    1. - ring as f32 / rings as f32,
+                ),
+                ..Default::default()
+            });
+        }
+    }
+
+    // Counter clockwise seen from outside, the triangles touching a pole
+    // would be degenerate on one side
+    let mut indices = Vec::with_capacity((segments * rings * 6) as usize);
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let top_left = ring * (segments + 1) + segment;
+            let bottom_left = top_left + segments + 1;
+            if ring != rings - 1 {
+                indices.extend([top_left, bottom_left, bottom_left + 1]);
+            }
+            if ring != 0 {
+                indices.extend([top_left, bottom_left + 1, top_left + 1]);
+            }
+        }
+    }
+    (vertices, indices)
+}
+
+fn position_key(vertices: &[Vertex], index: u32) -> [u32; 3] {
+    let p = vertices[index as usize].position;
+    [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()]
+}
+
+// Two faces sharing an edge are consistently wound when they walk it in
+// opposite directions. Vertices are compared by position, since the OBJ
+// parser splits them on uv/normal seams.
+pub fn inconsistent_winding_ratio(vertices: &[Vertex], indices: &[u32]) -> f32 {
+    let mut directed_edges = HashMap::<([u32; 3], [u32; 3]), u32>::new();
+    for triangle in triangles(vertices, indices) {
+        for i in 0..3 {
+            let edge = (
+                position_key(vertices, triangle[i]),
+                position_key(vertices, triangle[(i + 1) % 3]),
+            );
+            *directed_edges.entry(edge).or_insert(0) += 1;
+        }
     }
 
-    pub fn get_vertex_input_binding_descriptions() -> Vec<VertexInputBindingDescription> {
-        vec![vk::VertexInputBindingDescription {
-            binding: 0,
-            stride: mem::size_of::<Vertex>() as u32,
-            input_rate: vk::VertexInputRate::VERTEX,
-        }]
+    let mut total = 0usize;
+    let mut inconsistent = 0usize;
+    for triangle in triangles(vertices, indices) {
+        total += 1;
+        if (0..3).any(|i| {
+            let edge = (
+                position_key(vertices, triangle[i]),
+                position_key(vertices, triangle[(i + 1) % 3]),
+            );
+            directed_edges[&edge] > 1
+        }) {
+            inconsistent += 1;
+        }
+    }
+
+    if total == 0 {
+        0.
+    } else {
+        inconsistent as f32 / total as f32
+    }
+}
+
+// Fraction of the triangles turning clockwise around their vertex normals,
+// about 1 for meshes exported left-handed or mirrored. Triangles without
+// normals are not counted, None when no triangle has any.
+pub fn inverted_winding_ratio(vertices: &[Vertex], indices: &[u32]) -> Option<f32> {
+    let mut total = 0usize;
+    let mut inverted = 0usize;
+    for [a, b, c] in triangles(vertices, indices) {
+        let (a, b, c) = (
+            &vertices[a as usize],
+            &vertices[b as usize],
+            &vertices[c as usize],
+        );
+        let face_normal = (b.position - a.position).cross(&(c.position - a.position));
+        let vertex_normal = a.normal + b.normal + c.normal;
+        let alignment = face_normal.dot(&vertex_normal);
+        if alignment != 0. {
+            total += 1;
+            if alignment < 0. {
+                inverted += 1;
+            }
+        }
     }
+
+    (total > 0).then(|| inverted as f32 / total as f32)
 }
 
 impl Mesh {
     pub fn builder<'a>(device: Rc<RendererDevice>) -> MeshBuilder<'a> {
         MeshBuilder {
             device,
+            staging_ring: None,
             vertices: None,
             indices: None,
+            line_indices: &[],
+            point_indices: &[],
+            bvh: false,
+            import_scale: 1.,
+            morph_targets: &[],
+            vertex_layout: VertexLayout::Interleaved,
         }
     }
 
+    pub fn triangles(&self) -> impl Iterator<Item = [u32; 3]> + '_ {
+        triangles(&self.vertices, &self.indices)
+    }
+
+    pub fn device(&self) -> &Rc<RendererDevice> {
+        &self.device
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        if self.indices.is_empty() {
+            self.vertices.len() / 3
+        } else {
+            self.indices.len() / 3
+        }
+    }
+
+    pub fn inconsistent_winding_ratio(&self) -> f32 {
+        inconsistent_winding_ratio(&self.vertices, &self.indices)
+    }
+
+    pub fn inverted_winding_ratio(&self) -> Option<f32> {
+        inverted_winding_ratio(&self.vertices, &self.indices)
+    }
+
+    pub fn stats(&self) -> MeshStats {
+        MeshStats::new(&self.vertices, &self.indices)
+    }
+
+    // The ray is cast in world space, the returned distance is in world units
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, model_matrix: &Mat4) -> Option<Hit> {
+        let inverse_model_matrix = model_matrix.inverse()?;
+        let local_origin = inverse_model_matrix.transform_point(origin);
+        let local_direction = inverse_model_matrix.transform_vector(direction);
+
+        let hit = match &self.bvh {
+            Some(bvh) => bvh.raycast(&self.vertices, &self.indices, local_origin, local_direction),
+            None => self
+                .triangles()
+                .enumerate()
+                .filter_map(|(i, t)| {
+                    let corners = t.map(|index| self.vertices[index as usize].position);
+                    ray_triangle(local_origin, local_direction, corners, i)
+                })
+                .min_by(|a, b| a.distance.total_cmp(&b.distance)),
+        }?;
+
+        let world_point =
+            model_matrix.transform_point(local_origin + local_direction * hit.distance);
+        Some(Hit {
+            distance: (world_point - origin).length(),
+            ..hit
+        })
+    }
+
+    pub fn find_morph_target(&self, name: &str) -> Option<usize> {
+        self.morph_targets
+            .iter()
+            .position(|target| target.name == name)
+    }
+
+    // The base vertices with every target added by its weight, targets
+    // without weight are skipped
+    pub fn blend_morph_targets(&self, weights: &[f32]) -> Vec<Vertex> {
+        let mut vertices = self.vertices.clone();
+        let mut normals_changed = false;
+
+        for (target, &weight) in self.morph_targets.iter().zip(weights) {
+            if weight == 0. {
+                continue;
+            }
+            for (vertex, delta) in vertices.iter_mut().zip(&target.position_deltas) {
+                vertex.position += *delta * weight;
+            }
+            for (vertex, delta) in vertices.iter_mut().zip(&target.normal_deltas) {
+                vertex.normal += *delta * weight;
+                normals_changed = true;
+            }
+        }
+
+        if normals_changed {
+            for vertex in &mut vertices {
+                if vertex.normal != Vec3::default() {
+                    vertex.normal = vertex.normal.normalized();
+                }
+            }
+        }
+        vertices
+    }
+
     pub fn bind(&self, command_buffer: CommandBuffer) {
         unsafe {
             self.device.logical_device.cmd_bind_vertex_buffers(
                 command_buffer,
                 0,
-                &[self.vertex_buffer.buffer],
-                &[0],
+                &vec![self.vertex_buffer.buffer; self.binding_offsets.len()],
+                &self.binding_offsets,
             );
+        }
+        self.bind_indices(command_buffer);
+    }
 
+    // Alone when the vertices come from elsewhere, like a `Morph`
+    pub fn bind_indices(&self, command_buffer: CommandBuffer) {
+        unsafe {
             if let Some(index_buffer) = &self.index_buffer {
                 self.device.logical_device.cmd_bind_index_buffer(
                     command_buffer,
@@ -125,7 +519,7 @@ impl Mesh {
             } else {
                 self.device.logical_device.cmd_draw(
                     command_buffer,
-                    self.vertex_buffer.instance_count as u32,
+                    self.vertices.len() as u32,
                     1,
                     0,
                     0,
@@ -145,6 +539,11 @@ impl Drop for Mesh {
 }
 
 impl<'a> MeshBuilder<'a> {
+    pub fn staging_ring(mut self, staging_ring: &'a mut ScopStagingRing) -> Self {
+        self.staging_ring = Some(staging_ring);
+        self
+    }
+
     pub fn vertices(mut self, vertices: &'a [Vertex]) -> Self {
         self.vertices = Some(vertices);
         self
@@ -155,10 +554,43 @@ impl<'a> MeshBuilder<'a> {
         self
     }
 
+    pub fn lines(mut self, line_indices: &'a [u32]) -> Self {
+        self.line_indices = line_indices;
+        self
+    }
+
+    pub fn points(mut self, point_indices: &'a [u32]) -> Self {
+        self.point_indices = point_indices;
+        self
+    }
+
+    pub fn bvh(mut self, bvh: bool) -> Self {
+        self.bvh = bvh;
+        self
+    }
+
+    pub fn import_scale(mut self, import_scale: f32) -> Self {
+        self.import_scale = import_scale;
+        self
+    }
+
+    pub fn morph_targets(mut self, morph_targets: &'a [MorphTarget]) -> Self {
+        self.morph_targets = morph_targets;
+        self
+    }
+
+    pub fn vertex_layout(mut self, vertex_layout: VertexLayout) -> Self {
+        self.vertex_layout = vertex_layout;
+        self
+    }
+
     pub fn build(self) -> Result<Mesh> {
         let vertices = self
             .vertices
             .context("Cannot build a Mesh without vertices.")?;
+        let staging_ring = self
+            .staging_ring
+            .context("Cannot upload a Mesh without staging ring.")?;
 
         let vertices_count = vertices.len();
         let indices_count = self.indices.map_or(0, |i| i.len());
@@ -173,42 +605,85 @@ impl<'a> MeshBuilder<'a> {
             "Vertices count must be a multiple of 3 when no indices"
         );
 
-        let mut vertex_buffer = ScopBuffer::new(
-            self.device.clone(),
-            vertices_count,
-            size_of::<Vertex>() as vk::DeviceSize,
-            BufferUsageFlags::VERTEX_BUFFER,
-            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
-            1,
-        )?;
-        vertex_buffer.map(WHOLE_SIZE, 0)?;
-        vertex_buffer.write_to_buffer(&vertices, 0);
-        vertex_buffer.unmap();
-
-        let index_buffer = self.indices.filter(|i| !i.is_empty()).map_or(
-            Result::<Option<ScopBuffer>>::Ok(None),
-            |indices| {
-                let mut index_buffer = ScopBuffer::new(
+        ensure!(
+            self.line_indices.len().is_multiple_of(2),
+            "Line indices count must be a multiple of 2"
+        );
+        ensure!(
+            self.line_indices
+                .iter()
+                .chain(self.point_indices)
+                .all(|&index| (index as usize) < vertices_count),
+            "Line and point indices must be in the vertices"
+        );
+
+        // A `Morph` writes whole vertices
+        ensure!(
+            self.morph_targets.is_empty() || self.vertex_layout == VertexLayout::Interleaved,
+            "Morph targets need the interleaved vertex layout"
+        );
+        for target in self.morph_targets {
+            ensure!(
+                target.position_deltas.len() == vertices_count,
+                "Morph targets need a position delta per vertex"
+            );
+            ensure!(
+                target.normal_deltas.is_empty() || target.normal_deltas.len() == vertices_count,
+                "Morph targets need a normal delta per vertex, or none"
+            );
+        }
+
+        let (vertex_buffer, binding_offsets) = match self.vertex_layout {
+            VertexLayout::Interleaved => (
+                ScopBuffer::new_device_local(
                     self.device.clone(),
-                    indices_count,
-                    size_of::<u32>() as vk::DeviceSize,
-                    BufferUsageFlags::INDEX_BUFFER,
-                    MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
-                    1,
-                )?;
-                index_buffer.map(WHOLE_SIZE, 0)?;
-                index_buffer.write_to_buffer(&indices, 0);
-                index_buffer.unmap();
+                    staging_ring,
+                    vertices,
+                    BufferUsageFlags::VERTEX_BUFFER,
+                )?,
+                vec![0],
+            ),
+            VertexLayout::Planar => {
+                let (bytes, binding_offsets) = planar_streams(vertices);
+                (
+                    ScopBuffer::new_device_local(
+                        self.device.clone(),
+                        staging_ring,
+                        &bytes,
+                        BufferUsageFlags::VERTEX_BUFFER,
+                    )?,
+                    binding_offsets,
+                )
+            }
+        };
 
-                Ok(Some(index_buffer))
-            },
-        )?;
+        let index_buffer = match self.indices.filter(|i| !i.is_empty()) {
+            Some(indices) => Some(ScopBuffer::new_device_local(
+                self.device.clone(),
+                staging_ring,
+                indices,
+                BufferUsageFlags::INDEX_BUFFER,
+            )?),
+            None => None,
+        };
+
+        let indices = self.indices.map_or(vec![], |i| i.to_vec());
+        let bvh = self.bvh.then(|| Bvh::new(vertices, &indices));
 
         Ok(Mesh {
             device: self.device,
             bounding_box: BoundingBox::from(vertices),
-            // vertices: vertices.to_vec(),
+            bounding_sphere: BoundingSphere::from(vertices),
+            vertices: vertices.to_vec(),
+            indices,
+            line_indices: self.line_indices.to_vec(),
+            point_indices: self.point_indices.to_vec(),
+            import_scale: self.import_scale,
+            morph_targets: self.morph_targets.to_vec(),
+            vertex_layout: self.vertex_layout,
+            bvh,
             vertex_buffer,
+            binding_offsets,
             index_buffer,
         })
     }
@@ -218,6 +693,71 @@ impl BoundingBox {
     pub fn get_middle_point(&self) -> Vec3 {
         self.min + (self.max - self.min) / 2.
     }
+
+    pub fn extend(&mut self, point: Vec3) {
+        for i in 0..3 {
+            self.min[i] = self.min[i].min(point[i]);
+            self.max[i] = self.max[i].max(point[i]);
+        }
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut union = *self;
+        union.extend(other.min);
+        union.extend(other.max);
+        union
+    }
+
+    // Box around the 8 transformed corners, axis aligned again
+    pub fn transform(&self, matrix: &Mat4) -> Self {
+        let mut transformed = Self {
+            min: Vec3::positive_infinity(),
+            max: Vec3::negative_infinity(),
+        };
+        for corner in 0..8 {
+            transformed.extend(matrix.transform_point(Vec3::new(
+                if corner & 1 == 0 {
+                    self.min.x
+                } else {
+                    self.max.x
+                },
+                if corner & 2 == 0 {
+                    self.min.y
+                } else {
+                    self.max.y
+                },
+                if corner & 4 == 0 {
+                    self.min.z
+                } else {
+                    self.max.z
+                },
+            )));
+        }
+        transformed
+    }
+
+    // Slab test, `max_distance` allows skipping boxes behind a closer hit
+    pub fn intersects_ray(&self, origin: Vec3, direction: Vec3, max_distance: Option<f32>) -> bool {
+        let mut t_min = 0f32;
+        let mut t_max = max_distance.unwrap_or(f32::MAX);
+
+        for i in 0..3 {
+            let inv_direction = 1. / direction[i];
+            let mut t0 = (self.min[i] - origin[i]) * inv_direction;
+            let mut t1 = (self.max[i] - origin[i]) * inv_direction;
+            if inv_direction < 0. {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return false;
+            }
+        }
+
+        true
+    }
 }
 
 impl From<&[Vertex]> for BoundingBox {
@@ -239,3 +779,192 @@ impl From<&[Vertex]> for BoundingBox {
         Self { min, max }
     }
 }
+
+impl BoundingSphere {
+    // The radius is scaled by the largest axis scale, so the sphere stays
+    // conservative under non-uniform scaling
+    pub fn transform(&self, matrix: &Mat4) -> Self {
+        let scale = (0..3)
+            .map(|i| Vec3::new(matrix[i][0], matrix[i][1], matrix[i][2]).length())
+            .fold(0f32, f32::max);
+
+        Self {
+            center: matrix.transform_point(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+// Ritter's bounding sphere, a bit larger than the minimal one but linear
+// https://en.wikipedia.org/wiki/Bounding_sphere#Ritter's_bounding_sphere
+impl From<&[Vertex]> for BoundingSphere {
+    fn from(vertices: &[Vertex]) -> Self {
+        let Some(first) = vertices.first() else {
+            return Self::default();
+        };
+
+        let farthest_from = |point: Vec3| {
+            vertices
+                .iter()
+                .map(|v| v.position)
+                .max_by(|a, b| {
+                    (*a - point)
+                        .length_squared()
+                        .total_cmp(&(*b - point).length_squared())
+                })
+                .unwrap()
+        };
+
+        let a = farthest_from(first.position);
+        let b = farthest_from(a);
+
+        let mut center = (a + b) / 2.;
+        let mut radius = (b - a).length() / 2.;
+
+        for vert in vertices {
+            let distance = (vert.position - center).length();
+            if distance > radius {
+                let new_radius = (radius + distance) / 2.;
+                center += (vert.position - center) * ((new_radius - radius) / distance);
+                radius = new_radius;
+            }
+        }
+
+        Self { center, radius }
+    }
+}
+
+impl MeshStats {
+    const UV_COVERAGE_RESOLUTION: usize = 128;
+
+    pub fn new(vertices: &[Vertex], indices: &[u32]) -> Self {
+        let mut triangle_count = 0;
+        let mut surface_area = 0.;
+        let mut degenerate_triangle_count = 0;
+
+        for [a, b, c] in triangles(vertices, indices) {
+            let (a, b, c) = (
+                vertices[a as usize].position,
+                vertices[b as usize].position,
+                vertices[c as usize].position,
+            );
+            triangle_count += 1;
+            surface_area += (b - a).cross(&(c - a)).length() / 2.;
+            if is_degenerate_triangle(a, b, c) {
+                degenerate_triangle_count += 1;
+            }
+        }
+
+        let mut unique_vertices = HashSet::<[u32; 11]>::new();
+        for vert in vertices {
+            unique_vertices.insert(
+                [
+                    vert.position.x,
+                    vert.position.y,
+                    vert.position.z,
+                    vert.color.x,
+                    vert.color.y,
+                    vert.color.z,
+                    vert.normal.x,
+                    vert.normal.y,
+                    vert.normal.z,
+                    vert.uv.x,
+                    vert.uv.y,
+                ]
+                .map(f32::to_bits),
+            );
+        }
+
+        Self {
+            vertex_count: vertices.len(),
+            triangle_count,
+            surface_area,
+            degenerate_triangle_count,
+            duplicate_vertex_count: vertices.len() - unique_vertices.len(),
+            uv_coverage: Self::uv_coverage(vertices, indices),
+            has_normals: vertices.iter().any(|v| v.normal != Vec3::default()),
+            inconsistent_winding_ratio: inconsistent_winding_ratio(vertices, indices),
+            inverted_winding_ratio: inverted_winding_ratio(vertices, indices),
+            bounding_box: BoundingBox::from(vertices),
+            bounding_sphere: BoundingSphere::from(vertices),
+        }
+    }
+
+    // Fraction of the [0, 1] UV square covered by at least one triangle,
+    // sampled on a coarse grid
+    fn uv_coverage(vertices: &[Vertex], indices: &[u32]) -> f32 {
+        let resolution = Self::UV_COVERAGE_RESOLUTION;
+        let mut covered = vec![false; resolution * resolution];
+
+        let edge =
+            |a: Vec2, b: Vec2, p: Vec2| (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x);
+
+        for [a, b, c] in triangles(vertices, indices) {
+            let (a, b, c) = (
+                vertices[a as usize].uv,
+                vertices[b as usize].uv,
+                vertices[c as usize].uv,
+            );
+            let area = edge(a, b, c);
+            if area == 0. {
+                continue;
+            }
+
+            let cell =
+                |v: f32| ((v * resolution as f32).floor().max(0.) as usize).min(resolution - 1);
+            for y in cell(a.y.min(b.y).min(c.y))..=cell(a.y.max(b.y).max(c.y)) {
+                for x in cell(a.x.min(b.x).min(c.x))..=cell(a.x.max(b.x).max(c.x)) {
+                    let p = Vec2::new(
+                        (x as f32 + 0.5) / resolution as f32,
+                        (y as f32 + 0.5) / resolution as f32,
+                    );
+                    let (w0, w1, w2) = (edge(b, c, p), edge(c, a, p), edge(a, b, p));
+                    if (w0 * area >= 0.) && (w1 * area >= 0.) && (w2 * area >= 0.) {
+                        covered[y * resolution + x] = true;
+                    }
+                }
+            }
+        }
+
+        covered.iter().filter(|&&c| c).count() as f32 / covered.len() as f32
+    }
+}
+
+impl Display for MeshStats {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "Vertices:             {}", self.vertex_count)?;
+        writeln!(f, "Triangles:            {}", self.triangle_count)?;
+        writeln!(f, "Surface area:         {}", self.surface_area)?;
+        writeln!(
+            f,
+            "Degenerate triangles: {}",
+            self.degenerate_triangle_count
+        )?;
+        writeln!(f, "Duplicate vertices:   {}", self.duplicate_vertex_count)?;
+        writeln!(f, "UV coverage:          {:.1}%", self.uv_coverage * 100.)?;
+        writeln!(
+            f,
+            "Normals:              {}",
+            if self.has_normals { "yes" } else { "no" }
+        )?;
+        writeln!(
+            f,
+            "Inconsistent winding: {:.1}%",
+            self.inconsistent_winding_ratio * 100.
+        )?;
+        match self.inverted_winding_ratio {
+            Some(ratio) => writeln!(f, "Inverted winding:     {:.1}%", ratio * 100.)?,
+            None => writeln!(f, "Inverted winding:     no normals")?,
+        }
+        writeln!(
+            f,
+            "Bounding box:         {} -> {}",
+            self.bounding_box.min, self.bounding_box.max
+        )?;
+        write!(
+            f,
+            "Bounding sphere:      {} r={}",
+            self.bounding_sphere.center, self.bounding_sphere.radius
+        )
+    }
+}
@@ -1,34 +1,167 @@
 #![allow(dead_code)]
 
+pub mod audio;
+pub mod axis_gizmo;
+pub mod bookmarks;
+pub mod bvh;
 pub mod camera;
+mod crash_report;
 mod game_object;
+pub mod input;
+pub mod jobs;
+pub mod loading;
 pub mod mesh;
+pub mod motion;
+pub mod physics;
+pub mod skeleton;
 mod transform;
 
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    ops::ControlFlow,
+    rc::Rc,
+    time::Instant,
+};
 
-use camera::Camera;
+use audio::Audio;
+use axis_gizmo::AxisGizmo;
+use bookmarks::CameraBookmarks;
+use camera::{Camera, CameraPose, CameraTransition};
 pub use game_object::*;
+use input::{FrameInput, InputEvent, InputExt, InputRecorder, InputReplay};
+use loading::LoadingSpinner;
+use math::{Mat4, Vec2, Vec3};
+use mesh::BoundingBox;
+use physics::Physics;
 pub use transform::*;
-use winit_input_helper::WinitInputHelper;
+use winit::{event::MouseButton, keyboard::KeyCode};
 
-use crate::{renderer::{Renderer, RendererWindow}, utils::Result};
+use crate::{
+    parsing::{read_frag_spv_file, read_vert_spv_file},
+    renderer::{BackgroundPass, DistinctColors, Renderer, RendererConfig, RendererWindow},
+    utils::Result,
+};
 
 pub type GameObjectId = u32;
 
+// Scene radius used when nothing is drawn, the step was fixed to 0.084 before
+// it followed the scene
+const DEFAULT_SCENE_RADIUS: f32 = 8.4;
+// Share of the distance to the point under the cursor left after one wheel line
+const ZOOM_STEP: f32 = 0.85;
+// Cycled through with F9, see `Renderer::set_render_scale`
+const RENDER_SCALES: [f32; 5] = [1., 0.5, 0.75, 1.5, 2.];
+// Stops added or removed by numpad + and -
+const EXPOSURE_STEP: f32 = 0.5;
+
+#[derive(Clone, Default, Debug)]
+pub struct EngineConfig {
+    pub renderer: RendererConfig,
+    // Writes the input of every frame to this file
+    pub record_input: Option<String>,
+    // Plays the input of a recording instead of the window one, until the
+    // recording ends
+    pub replay_input: Option<String>,
+    // Closes the window after this many frames
+    pub frame_limit: Option<u32>,
+}
+
 pub struct Engine {
     last_used_id: GameObjectId,
     pub game_objects: HashMap<GameObjectId, Rc<RefCell<GameObject>>>,
     pub renderer: Renderer,
+    pub show_stats: bool,
+    pub bookmarks: Option<CameraBookmarks>,
+    // Drawn in the renderer overlay lines when both exist
+    pub axis_gizmo: Option<AxisGizmo>,
+    // Loading state, the spinner is drawn over the scene until it is reset
+    pub loading: Option<LoadingSpinner>,
+    // Fits the camera near and far planes around the scene every frame
+    pub auto_depth_range: bool,
+    // The wheel zooms toward the point under the cursor and F turns the
+    // camera to it, which also becomes the axis gizmo target
+    pub cursor_navigation: bool,
+    // Movement of the apps per frame, relative to the radius of the scene
+    pub movement_speed: f32,
+    // Steps the rigid bodies after the app update
+    pub physics: Option<Physics>,
+    // Opened by the first call to `audio`
+    audio: Option<Audio>,
+    // Writes the input of every frame, see `InputRecorder`
+    pub input_recorder: Option<InputRecorder>,
+    // Replaces the window input until the recording ends
+    pub input_replay: Option<InputReplay>,
+    pub frame_limit: Option<u32>,
+    // Seconds taken by bookmark, gizmo and projection changes, 0 snaps
+    pub camera_transition_duration: f32,
+    camera_transition: Option<CameraTransition>,
+    // Projection to go back to while orthographic
+    perspective: Option<Mat4>,
+    // Shown in the window title, usually the loaded model
+    pub title: String,
+    // Counts the frames where an object moved, see `scene_signature`
+    scene_revision: u64,
 }
 
 impl Engine {
-    pub fn new() -> Result<Self> {
-        Ok(Engine {
+    pub fn new(config: EngineConfig) -> Result<Self> {
+        let input_recorder = match &config.record_input {
+            Some(path) => Some(
+                InputRecorder::create(path)
+                    .map_err(|e| format!("{}: cannot record input: {}", path, e))?,
+            ),
+            None => None,
+        };
+        let input_replay = match &config.replay_input {
+            Some(path) => Some(
+                InputReplay::open(path).map_err(|e| format!("{}: cannot replay input: {}", path, e))?,
+            ),
+            None => None,
+        };
+
+        let mut engine = Engine {
             last_used_id: 0,
-            renderer: Renderer::new()?,
+            renderer: Renderer::new(config.renderer)?,
             game_objects: HashMap::new(),
-        })
+            show_stats: false,
+            bookmarks: None,
+            axis_gizmo: None,
+            loading: None,
+            auto_depth_range: true,
+            cursor_navigation: true,
+            movement_speed: 0.01,
+            physics: None,
+            audio: None,
+            input_recorder,
+            input_replay,
+            frame_limit: config.frame_limit,
+            camera_transition_duration: 0.5,
+            camera_transition: None,
+            perspective: None,
+            title: String::new(),
+            scene_revision: 0,
+        };
+
+        match engine.create_background_pass() {
+            Ok(background_pass) => engine.renderer.background_pass = Some(background_pass),
+            Err(e) => eprintln!("Background pass unavailable, backgrounds are solid: {}", e),
+        }
+        Ok(engine)
+    }
+
+    fn create_background_pass(&self) -> Result<BackgroundPass> {
+        let vert_shader = read_vert_spv_file(self, "./shaders/background.vert.spv")?;
+        let frag_shader = match read_frag_spv_file(self, "./shaders/background.frag.spv") {
+            Ok(shader) => shader,
+            Err(e) => {
+                vert_shader.cleanup(&self.renderer.main_device);
+                return Err(e);
+            }
+        };
+        BackgroundPass::new(&self.renderer, &vert_shader, &frag_shader)
     }
 
     pub fn register(&mut self, game_object: GameObject) -> Rc<RefCell<GameObject>> {
@@ -40,34 +173,464 @@ impl Engine {
         go
     }
 
-    pub fn run<F: FnMut(&mut Engine, &WinitInputHelper, u32)>(
+    pub fn get(&self, id: GameObjectId) -> Option<&Rc<RefCell<GameObject>>> {
+        self.game_objects.get(&id)
+    }
+
+    // Lowest id when several objects share the name
+    pub fn find_by_name(&self, name: &str) -> Option<GameObjectId> {
+        self.game_objects
+            .iter()
+            .filter(|(_, go)| go.borrow().name.as_deref() == Some(name))
+            .map(|(id, _)| *id)
+            .min()
+    }
+
+    pub fn iter_with_name_prefix<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = (GameObjectId, &'a Rc<RefCell<GameObject>>)> + 'a {
+        self.game_objects
+            .iter()
+            .filter(move |(_, go)| {
+                go.borrow()
+                    .name
+                    .as_ref()
+                    .is_some_and(|name| name.starts_with(prefix))
+            })
+            .map(|(id, go)| (*id, go))
+    }
+
+    pub fn with_tag<'a>(
+        &'a self,
+        tag: &'a str,
+    ) -> impl Iterator<Item = (GameObjectId, &'a Rc<RefCell<GameObject>>)> + 'a {
+        self.game_objects
+            .iter()
+            .filter(move |(_, go)| go.borrow().has_tag(tag))
+            .map(|(id, go)| (*id, go))
+    }
+
+    // Objects holding a component of type T, to update them from the app
+    pub fn with_component<T: Any>(
+        &self,
+    ) -> impl Iterator<Item = (GameObjectId, &Rc<RefCell<GameObject>>)> + '_ {
+        self.game_objects
+            .iter()
+            .filter(|(_, go)| go.borrow().has_component::<T>())
+            .map(|(id, go)| (*id, go))
+    }
+
+    pub fn audio(&mut self) -> &mut Audio {
+        self.audio.get_or_insert_with(Audio::new)
+    }
+
+    pub fn traverse(&self) -> impl Iterator<Item = SceneNode> + '_ {
+        traverse(&self.game_objects)
+    }
+
+    // Union of the active objects boxes, None without any mesh
+    pub fn world_bounds(&self) -> Option<BoundingBox> {
+        self.traverse()
+            .filter(|node| node.active)
+            .filter_map(|node| {
+                let go = node.game_object.borrow();
+                Some(
+                    go.mesh
+                        .as_ref()?
+                        .bounding_box
+                        .transform(&node.world_transform),
+                )
+            })
+            .reduce(|a, b| a.union(&b))
+    }
+
+    // Distance the apps move things by this frame, so big and tiny models
+    // take as long to cross. Scaled by the Shift and Ctrl modifiers.
+    pub fn movement_step(&self, input: &FrameInput) -> f32 {
+        let radius = self
+            .world_bounds()
+            .map_or(DEFAULT_SCENE_RADIUS, |bounds| {
+                (bounds.max - bounds.min).length() / 2.
+            });
+        radius.max(f32::EPSILON) * self.movement_speed * input.speed_multiplier()
+    }
+
+    // Equal from one frame to the next while the recorded scene is the same:
+    // same camera, objects, transforms, meshes, materials and parents
+    pub fn scene_signature(&mut self, camera: &Camera) -> u64 {
+        scene_signature(&self.game_objects, camera, &mut self.scene_revision)
+    }
+
+    pub fn run<F: FnMut(&mut Engine, &mut Camera, &FrameInput, u32)>(
         &mut self,
-        camera: &Camera,
+        camera: &mut Camera,
         mut on_update: F,
     ) -> Result<()> {
+        camera.set_reverse_z(self.renderer.config.reverse_z);
+        camera.set_handedness(self.renderer.handedness());
         let event_loop = self.renderer.window.acquire_event_loop()?;
-        RendererWindow::run(event_loop, |input| {
-            let next_frame_infos = self.renderer.handle_draw_request()?;
+        let mut title_update = Instant::now();
+        let mut title_frames = 0;
+        let mut frames_drawn = 0;
+        let mut input = FrameInput::default();
+        // Events of the steps without a frame wait for the next one
+        let mut pending_events: Vec<InputEvent> = vec![];
+        RendererWindow::run(event_loop, |window_input, events| {
+            // Moving to a screen with another scale factor resizes the surface
+            if window_input.scale_factor_changed().is_some()
+                || window_input.window_resized().is_some()
+            {
+                self.renderer.handle_resize()?;
+            }
+            pending_events.extend_from_slice(events);
+
+            // The renderer errors end the loop, with a report of the frame
+            let next_frame_infos = self
+                .renderer
+                .handle_draw_request()
+                .inspect_err(|e| _ = self.write_crash_report(e.as_ref(), camera))?;
 
             if let Some((image_index, image_available, rendering_finished, may_begin_drawing)) =
                 next_frame_infos
             {
-                on_update(self, input, image_index);
+                let frame_events = self.next_input_frame(&mut pending_events)?;
+                input.step(&frame_events);
+                let input = &input;
+
+                let viewport_size = self.renderer.window.viewport_size();
+                self.update_camera_transition(camera, input, viewport_size);
+
+                let update_start = Instant::now();
+                on_update(self, camera, input, image_index);
+                motion::update(&self.game_objects, self.renderer.delta_time());
+                if let Some(physics) = &mut self.physics {
+                    physics.update(&self.game_objects, self.renderer.delta_time());
+                }
+                if let Some(audio) = &mut self.audio {
+                    audio.update(self.renderer.time());
+                }
+                self.renderer.add_update_time(update_start.elapsed());
+
+                if let (Some(axis_gizmo), Some(overlay_lines)) =
+                    (&self.axis_gizmo, &mut self.renderer.overlay_lines)
+                {
+                    axis_gizmo.draw(overlay_lines, camera, viewport_size);
+                }
+                let time = self.renderer.time();
+                if let (Some(loading), Some(overlay_lines)) =
+                    (&self.loading, &mut self.renderer.overlay_lines)
+                {
+                    loading.draw(overlay_lines, time, viewport_size);
+                }
 
+                // Projection transitions own the projection until they end
+                if self.auto_depth_range && self.camera_transition.is_none() {
+                    if let Some(bounds) = self.world_bounds() {
+                        camera.fit_depth_range(&bounds);
+                    }
+                }
+
+                if self.renderer.reuse_recordings {
+                    self.renderer.scene_signature = Some(self.scene_signature(camera));
+                }
                 self.renderer.draw(
-                    &camera,
+                    camera,
                     &self.game_objects,
                     image_index,
                     image_available,
                     rendering_finished,
                     may_begin_drawing,
-                )?;
+                )
+                .inspect_err(|e| _ = self.write_crash_report(e.as_ref(), camera))?;
+
+                title_frames += 1;
+                let elapsed = title_update.elapsed().as_secs_f32();
+                if elapsed >= 1. {
+                    self.update_title(title_frames as f32 / elapsed);
+                    title_update = Instant::now();
+                    title_frames = 0;
+                }
+
+                if input.toggled(KeyCode::F4, &mut self.renderer.naive_gamma) {
+                    println!(
+                        "{} lighting",
+                        if self.renderer.naive_gamma {
+                            "Gamma space"
+                        } else {
+                            "Linear"
+                        }
+                    );
+                }
+                if input.toggled(KeyCode::F6, &mut self.renderer.texel_density_view) {
+                    println!(
+                        "Texel density view {}",
+                        if self.renderer.texel_density_view {
+                            "enabled (blue: texture too small, green: 1 texel per pixel, red: wasted)"
+                        } else {
+                            "disabled"
+                        }
+                    );
+                }
+                if input.key_pressed(KeyCode::F7) {
+                    self.renderer.distinct_colors = match self.renderer.distinct_colors {
+                        None => Some(DistinctColors::Hcl),
+                        Some(DistinctColors::Hcl) => Some(DistinctColors::ColorBlindSafe),
+                        Some(DistinctColors::ColorBlindSafe) => None,
+                    };
+                    match self.renderer.distinct_colors {
+                        Some(colors) => println!("Distinct colors view: {:?}", colors),
+                        None => println!("Distinct colors view disabled"),
+                    }
+                }
+                if input.toggled(KeyCode::F8, &mut self.renderer.winding_view) {
+                    println!(
+                        "Winding view {}",
+                        if self.renderer.winding_view {
+                            "enabled (blue: front faces, red: back faces)"
+                        } else {
+                            "disabled"
+                        }
+                    );
+                }
+                if input.key_pressed(KeyCode::F9) {
+                    self.cycle_render_scale()?;
+                }
+                let exposure_step = if input.key_pressed(KeyCode::NumpadAdd) {
+                    EXPOSURE_STEP
+                } else if input.key_pressed(KeyCode::NumpadSubtract) {
+                    -EXPOSURE_STEP
+                } else {
+                    0.
+                };
+                if exposure_step != 0. {
+                    camera.exposure += exposure_step;
+                    println!("Exposure {:+.1} EV", camera.exposure);
+                }
+                input.toggled(KeyCode::F3, &mut self.show_stats);
+                if self.show_stats && self.renderer.frame_count.is_multiple_of(60) {
+                    self.print_stats(camera);
+                }
+
+                frames_drawn += 1;
+                if self.frame_limit.is_some_and(|limit| frames_drawn >= limit) {
+                    return Ok(ControlFlow::Break(()));
+                }
             }
-            Ok(())
+            Ok(ControlFlow::Continue(()))
         })?;
 
         Ok(())
     }
+
+    // The window events, or the replayed ones while a replay runs. Recorded
+    // either way, so a replay can be extended by recording it.
+    fn next_input_frame(&mut self, pending_events: &mut Vec<InputEvent>) -> Result<Vec<InputEvent>> {
+        let mut events = std::mem::take(pending_events);
+        if let Some(replay) = &mut self.input_replay {
+            match replay.next_frame() {
+                Some(replayed) => events = replayed,
+                None => {
+                    println!("Input replay finished");
+                    self.input_replay = None;
+                }
+            }
+        }
+
+        if let Some(recorder) = &mut self.input_recorder {
+            recorder.record(&events)?;
+        }
+        Ok(events)
+    }
+
+    // Starts transitions from bookmarks, gizmo clicks, the F key and the O
+    // key, which switches between perspective and orthographic around the
+    // gizmo target
+    fn update_camera_transition(
+        &mut self,
+        camera: &mut Camera,
+        input: &FrameInput,
+        viewport_size: Vec2,
+    ) {
+        let mut pose: Option<CameraPose> = None;
+        let mut projection: Option<Mat4> = None;
+
+        if let Some(bookmarks) = &mut self.bookmarks {
+            pose = bookmarks.update(camera, input);
+        }
+
+        if self.cursor_navigation {
+            pose = self.update_cursor_navigation(camera, input, viewport_size);
+        }
+
+        if let Some(axis_gizmo) = &self.axis_gizmo {
+            let clicked = input
+                .cursor()
+                .filter(|_| input.mouse_pressed(MouseButton::Left))
+                .and_then(|(x, y)| axis_gizmo.hit(camera, Vec2::new(x, y), viewport_size));
+            if let Some(axis) = clicked {
+                pose = Some(axis_gizmo.snap_pose(camera, axis));
+            }
+
+            if input.key_pressed(KeyCode::KeyO) {
+                projection = match self.perspective.take() {
+                    Some(perspective) => Some(perspective),
+                    None => {
+                        self.perspective = Some(*camera.get_projection());
+                        let distance = (camera.get_position() - axis_gizmo.target).length();
+                        Some(camera.orthographic_at(distance))
+                    }
+                };
+            }
+        }
+
+        if pose.is_some() || projection.is_some() {
+            // What a running transition was heading to still applies
+            let current = self.camera_transition.as_ref();
+            let pose = pose
+                .or(current.map(CameraTransition::target_pose))
+                .unwrap_or(camera.get_pose());
+            let projection = projection
+                .or(current.map(CameraTransition::target_projection))
+                .unwrap_or(*camera.get_projection());
+            self.camera_transition = Some(
+                CameraTransition::new(camera, pose, self.camera_transition_duration)
+                    .with_projection(projection),
+            );
+        }
+
+        if let Some(transition) = &mut self.camera_transition {
+            if transition.update(camera, self.renderer.delta_time()) {
+                self.camera_transition = None;
+            }
+        }
+    }
+
+    // Zooms right away, the wheel gives small steps. Returns the pose looking
+    // at the point under the cursor when F is pressed.
+    fn update_cursor_navigation(
+        &mut self,
+        camera: &mut Camera,
+        input: &FrameInput,
+        viewport_size: Vec2,
+    ) -> Option<CameraPose> {
+        let (x, y) = input.cursor()?;
+        let cursor = Vec2::new(x, y);
+        // The depth readback waits for the GPU, only done on demand
+        let focus = input.key_pressed(KeyCode::KeyF);
+        // Parallel rays do not converge toward the cursor, orthographic views
+        // keep their size
+        let zoom = input.scroll() != 0. && !camera.is_orthographic();
+        if !focus && !zoom {
+            return None;
+        }
+        let point = self.point_under_cursor(camera, cursor, viewport_size)?;
+
+        if zoom {
+            // Moving toward the point along its ray keeps it under the cursor
+            let pose = camera.get_pose();
+            let step = 1. - ZOOM_STEP.powf(input.scroll());
+            camera.set_pose(&CameraPose {
+                position: pose.position + (point - pose.position) * step,
+                ..pose
+            });
+            self.camera_transition = None;
+        }
+
+        if !focus {
+            return None;
+        }
+        if let Some(axis_gizmo) = &mut self.axis_gizmo {
+            axis_gizmo.target = point;
+        }
+        let pose = camera.get_pose();
+        Some(CameraPose {
+            direction: (point - pose.position).normalized(),
+            ..pose
+        })
+    }
+
+    // From the depth buffer, the meshes are raycast when it cannot be read
+    fn point_under_cursor(&self, camera: &Camera, cursor: Vec2, viewport_size: Vec2) -> Option<Vec3> {
+        match self.renderer.read_depth_at(camera, cursor) {
+            Ok(point) => return point,
+            Err(e) => eprintln!("Depth readback failed, raycasting the meshes: {}", e),
+        }
+
+        let (origin, direction) = camera.screen_to_ray(cursor, viewport_size)?;
+        self.game_objects
+            .values()
+            .filter_map(|go| {
+                let go = go.borrow();
+                let hit = go
+                    .mesh
+                    .as_ref()?
+                    .raycast(origin, direction, &go.world_transform())?;
+                Some(hit.distance)
+            })
+            .min_by(f32::total_cmp)
+            .map(|distance| origin + direction * distance)
+    }
+
+    fn cycle_render_scale(&mut self) -> Result<()> {
+        let current = self.renderer.render_scale();
+        let next = RENDER_SCALES
+            .iter()
+            .position(|&scale| scale == current)
+            .map_or(RENDER_SCALES[0], |index| {
+                RENDER_SCALES[(index + 1) % RENDER_SCALES.len()]
+            });
+        self.renderer.set_render_scale(next)?;
+        let extent = self.renderer.render_extent();
+        println!(
+            "Render scale {}x ({}x{})",
+            next, extent.width, extent.height
+        );
+        Ok(())
+    }
+
+    fn update_title(&self, fps: f32) {
+        let mut title = String::from("scop");
+        if !self.title.is_empty() {
+            title += &format!(" - {}", self.title);
+        }
+        if let Some(loading) = &self.loading {
+            title += &format!(
+                " - loading {} {:.0}%",
+                loading.label,
+                loading.progress * 100.
+            );
+        }
+        title += &format!(
+            " - {:.0} FPS - {} triangles",
+            fps, self.renderer.stats.triangles
+        );
+        self.renderer.window.set_title(&title);
+    }
+
+    // Stand-in for an on-screen panel until text rendering exists
+    fn print_stats(&self, camera: &Camera) {
+        let mut names: Vec<String> = self
+            .game_objects
+            .values()
+            .filter_map(|go| go.borrow().name.clone())
+            .collect();
+        names.sort();
+
+        println!("--------------------");
+        println!("Models:     {}", names.join(", "));
+        println!("{}", self.renderer.stats);
+        println!("{}", self.renderer.frame_breakdown());
+        match self.renderer.capabilities() {
+            Ok(capabilities) => println!("{}", capabilities),
+            Err(e) => eprintln!("Capabilities: {}", e),
+        }
+        println!(
+            "VRAM:       {:.1} MiB",
+            self.renderer.main_device.allocated_memory() as f64 / (1024. * 1024.)
+        );
+        println!("Camera:     {}", camera.get_position());
+    }
 }
 
 impl Drop for Engine {
@@ -75,3 +638,98 @@ impl Drop for Engine {
         self.renderer.wait_gpu();
     }
 }
+
+// Only hashes pointers and flags, transform changes are tracked by the
+// objects and counted in `scene_revision`. Material parameters, poses and
+// morph weights are uploaded per image outside the recordings, they are left
+// out.
+fn scene_signature(
+    game_objects: &HashMap<GameObjectId, Rc<RefCell<GameObject>>>,
+    camera: &Camera,
+    scene_revision: &mut u64,
+) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let mut moved = false;
+
+    game_objects.len().hash(&mut hasher);
+    for (id, go) in game_objects {
+        let go = go.borrow();
+        moved |= go.take_moved();
+
+        id.hash(&mut hasher);
+        go.enabled.hash(&mut hasher);
+        go.mesh.as_ref().map(Rc::as_ptr).hash(&mut hasher);
+        go.material.as_ref().map(Rc::as_ptr).hash(&mut hasher);
+        go.skin.is_some().hash(&mut hasher);
+        go.morph.is_some().hash(&mut hasher);
+        go.parent
+            .as_ref()
+            .map(|parent| parent.as_ptr())
+            .hash(&mut hasher);
+    }
+    if moved {
+        *scene_revision += 1;
+    }
+    scene_revision.hash(&mut hasher);
+
+    // The culling depends on the camera
+    for matrix in [camera.get_view(), camera.get_projection()] {
+        for column in 0..4 {
+            matrix[column].map(f32::to_bits).hash(&mut hasher);
+        }
+    }
+
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // At the origin looking toward -z, the object is behind it and culled
+    fn scene() -> (HashMap<GameObjectId, Rc<RefCell<GameObject>>>, Camera) {
+        let mut camera = Camera::empty();
+        camera.set_view_direction(Vec3::default(), Vec3::forward(), Vec3::up());
+        camera.set_perspective_projection(90., 1., 0.1, 10.);
+
+        let mut behind = GameObject::default();
+        behind.transform_mut().translation = Vec3::new(0., 0., 5.);
+        let game_objects = HashMap::from([(0, Rc::new(RefCell::new(behind)))]);
+        (game_objects, camera)
+    }
+
+    #[test]
+    fn culled_objects_keep_the_signature() {
+        let (game_objects, camera) = scene();
+        let mut revision = 0;
+
+        // Placing the object counts as a move
+        let first = scene_signature(&game_objects, &camera, &mut revision);
+        let second = scene_signature(&game_objects, &camera, &mut revision);
+        for _ in 0..10 {
+            assert_eq!(
+                scene_signature(&game_objects, &camera, &mut revision),
+                second
+            );
+        }
+        assert_ne!(first, second);
+        assert_eq!(revision, 1);
+    }
+
+    #[test]
+    fn moves_bump_the_revision_once() {
+        let (game_objects, camera) = scene();
+        let mut revision = 0;
+        scene_signature(&game_objects, &camera, &mut revision);
+        let still = scene_signature(&game_objects, &camera, &mut revision);
+
+        game_objects[&0].borrow_mut().transform_mut().translation.x = 1.;
+        let moved = scene_signature(&game_objects, &camera, &mut revision);
+        assert_ne!(moved, still);
+        assert_eq!(
+            scene_signature(&game_objects, &camera, &mut revision),
+            moved
+        );
+        assert_eq!(revision, 2);
+    }
+}
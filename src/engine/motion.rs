@@ -0,0 +1,72 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use math::Vec3;
+
+use super::{GameObject, GameObjectId};
+
+// Component moving the object on a circle around `center`, in its parent
+// space. It owns the translation, other changes to it are overwritten.
+#[derive(Copy, Clone, Debug)]
+pub struct Orbit {
+    pub center: Vec3,
+    pub radius: f32,
+    // Radians per second, counterclockwise seen from the tip of the axis
+    pub angular_speed: f32,
+    pub axis: Vec3,
+    // Position on the circle, 0 is toward +x, or +z when orbiting around x
+    pub angle: f32,
+}
+
+// Component turning the object on itself, added to its euler rotation
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Spin {
+    // Radians per second around each axis
+    pub speed: Vec3,
+}
+
+impl Orbit {
+    // Around the vertical axis, starting toward +x
+    pub fn new(center: Vec3, radius: f32, angular_speed: f32) -> Self {
+        Self {
+            center,
+            radius,
+            angular_speed,
+            axis: Vec3::up(),
+            angle: 0.,
+        }
+    }
+
+    pub fn position(&self) -> Vec3 {
+        let axis = self.axis.normalized();
+        let reference = if axis.x.abs() < 0.9 {
+            Vec3::right()
+        } else {
+            Vec3::backward()
+        };
+        let u = (reference - axis * axis.dot(&reference)).normalized();
+        let v = axis.cross(&u);
+        self.center + (u * self.angle.cos() + v * self.angle.sin()) * self.radius
+    }
+}
+
+impl Spin {
+    pub fn new(speed: Vec3) -> Self {
+        Self { speed }
+    }
+}
+
+// Called by the engine every frame, after the app update
+pub fn update(game_objects: &HashMap<GameObjectId, Rc<RefCell<GameObject>>>, delta_time: f32) {
+    for go in game_objects.values() {
+        let mut go = go.borrow_mut();
+        if let Some(orbit) = go.component_mut::<Orbit>() {
+            orbit.angle = (orbit.angle + orbit.angular_speed * delta_time) % std::f32::consts::TAU;
+            let position = orbit.position();
+            go.transform_mut().translation = position;
+        }
+        if let Some(spin) = go.component::<Spin>() {
+            let speed = spin.speed;
+            go.transform_mut().rotation += speed * delta_time;
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use math::Vec3;
+
+use super::{mesh::BoundingBox, GameObject, GameObjectId};
+
+// Steps have the same length whatever the frame rate
+const STEPS_PER_SECOND: f32 = 120.;
+// A longer frame slows the simulation down instead of catching up
+const MAX_STEPS_PER_UPDATE: u32 = 8;
+// Slower bounces stop, so bodies come to rest instead of jittering
+const REST_SPEED: f32 = 0.2;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Collider {
+    // The mesh bounding box in world space, still axis aligned when rotated
+    Aabb,
+    // The mesh bounding sphere in world space
+    Sphere,
+}
+
+// Makes a root object fall and collide, its rotation is left alone
+#[derive(Copy, Clone, Debug)]
+pub struct RigidBody {
+    pub collider: Collider,
+    pub velocity: Vec3,
+    // Share of the speed kept by a bounce, 0 stops dead
+    pub restitution: f32,
+    // Horizontal speed lost per second while on the ground
+    pub friction: f32,
+}
+
+// Gravity and collisions between rigid bodies and an infinite ground plane,
+// advanced in fixed steps by `update`. Every body has the same mass.
+pub struct Physics {
+    pub gravity: Vec3,
+    pub ground_height: f32,
+    // Time not simulated yet, less than a step
+    accumulator: f32,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Shape {
+    Aabb(BoundingBox),
+    Sphere(Vec3, f32),
+}
+
+impl RigidBody {
+    pub fn new(collider: Collider) -> Self {
+        Self {
+            collider,
+            velocity: Vec3::default(),
+            restitution: 0.4,
+            friction: 2.,
+        }
+    }
+}
+
+impl Physics {
+    pub fn new(ground_height: f32) -> Self {
+        Self {
+            gravity: Vec3::down() * 9.81,
+            ground_height,
+            accumulator: 0.,
+        }
+    }
+
+    // Runs the steps `delta_time` covers, the remainder waits for the next
+    // frame. Bodies of objects with a parent, or without mesh, are ignored.
+    pub fn update(
+        &mut self,
+        game_objects: &HashMap<GameObjectId, Rc<RefCell<GameObject>>>,
+        delta_time: f32,
+    ) {
+        let step = 1. / STEPS_PER_SECOND;
+        self.accumulator = (self.accumulator + delta_time).min(step * MAX_STEPS_PER_UPDATE as f32);
+        if self.accumulator < step {
+            return;
+        }
+
+        // Sorted so collisions resolve in the same order every run
+        let mut bodies: Vec<(GameObjectId, &Rc<RefCell<GameObject>>)> = game_objects
+            .iter()
+            .filter(|(_, go)| {
+                let go = go.borrow();
+                go.body.is_some() && go.mesh.is_some() && go.parent.is_none()
+            })
+            .map(|(id, go)| (*id, go))
+            .collect();
+        bodies.sort_unstable_by_key(|(id, _)| *id);
+        let bodies: Vec<&Rc<RefCell<GameObject>>> = bodies.into_iter().map(|(_, go)| go).collect();
+
+        while self.accumulator >= step {
+            self.step(&bodies, step);
+            self.accumulator -= step;
+        }
+    }
+
+    fn step(&self, bodies: &[&Rc<RefCell<GameObject>>], dt: f32) {
+        for go in bodies {
+            let mut go = go.borrow_mut();
+            let Some(body) = &mut go.body else {
+                continue;
+            };
+            body.velocity += self.gravity * dt;
+            let velocity = body.velocity;
+            go.transform_mut().translation += velocity * dt;
+        }
+
+        for go in bodies {
+            let Some(shape) = Shape::of(&go.borrow()) else {
+                continue;
+            };
+            let depth = self.ground_height - shape.bottom();
+            if depth <= 0. {
+                continue;
+            }
+
+            let mut go = go.borrow_mut();
+            go.transform_mut().translation.y += depth;
+            let Some(body) = &mut go.body else {
+                continue;
+            };
+            let bounce = -body.velocity.y * body.restitution;
+            body.velocity.y = if bounce > REST_SPEED { bounce } else { 0. };
+            let damping = (1. - body.friction * dt).max(0.);
+            body.velocity.x *= damping;
+            body.velocity.z *= damping;
+        }
+
+        for (i, a) in bodies.iter().enumerate() {
+            for b in &bodies[i + 1..] {
+                Self::collide(a, b);
+            }
+        }
+    }
+
+    // Pushes both bodies apart by half the overlap, then exchanges the speed
+    // they had toward each other
+    fn collide(a: &Rc<RefCell<GameObject>>, b: &Rc<RefCell<GameObject>>) {
+        let (Some(shape_a), Some(shape_b)) = (Shape::of(&a.borrow()), Shape::of(&b.borrow()))
+        else {
+            return;
+        };
+        let Some((normal, depth)) = shape_a.penetration(&shape_b) else {
+            return;
+        };
+
+        let (mut a, mut b) = (a.borrow_mut(), b.borrow_mut());
+        a.transform_mut().translation += normal * (depth / 2.);
+        b.transform_mut().translation -= normal * (depth / 2.);
+
+        let (Some(body_a), Some(body_b)) = (&mut a.body, &mut b.body) else {
+            return;
+        };
+        let approach = (body_a.velocity - body_b.velocity).dot(&normal);
+        if approach < 0. {
+            let restitution = body_a.restitution.min(body_b.restitution);
+            let impulse = normal * (-(1. + restitution) * approach / 2.);
+            body_a.velocity += impulse;
+            body_b.velocity -= impulse;
+        }
+    }
+}
+
+impl Shape {
+    fn of(go: &GameObject) -> Option<Self> {
+        let body = go.body.as_ref()?;
+        let mesh = go.mesh.as_ref()?;
+        let world_transform = go.world_transform();
+        Some(match body.collider {
+            Collider::Aabb => Shape::Aabb(mesh.bounding_box.transform(&world_transform)),
+            Collider::Sphere => {
+                let sphere = mesh.bounding_sphere.transform(&world_transform);
+                Shape::Sphere(sphere.center, sphere.radius)
+            }
+        })
+    }
+
+    fn bottom(&self) -> f32 {
+        match self {
+            Shape::Aabb(bounds) => bounds.min.y,
+            Shape::Sphere(center, radius) => center.y - radius,
+        }
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        match *self {
+            Shape::Aabb(bounds) => bounds,
+            Shape::Sphere(center, radius) => BoundingBox {
+                min: center - Vec3::one() * radius,
+                max: center + Vec3::one() * radius,
+            },
+        }
+    }
+
+    // Direction to move `self` along to leave `other`, and by how much.
+    // None when they do not touch.
+    fn penetration(&self, other: &Shape) -> Option<(Vec3, f32)> {
+        match (*self, *other) {
+            (Shape::Sphere(center_a, radius_a), Shape::Sphere(center_b, radius_b)) => {
+                let offset = center_a - center_b;
+                let distance = offset.length();
+                let depth = radius_a + radius_b - distance;
+                if depth <= 0. {
+                    return None;
+                }
+                let normal = if distance > f32::EPSILON {
+                    offset / distance
+                } else {
+                    Vec3::up()
+                };
+                Some((normal, depth))
+            }
+            (Shape::Sphere(center, radius), Shape::Aabb(bounds)) => {
+                let closest = Vec3::new(
+                    center.x.clamp(bounds.min.x, bounds.max.x),
+                    center.y.clamp(bounds.min.y, bounds.max.y),
+                    center.z.clamp(bounds.min.z, bounds.max.z),
+                );
+                let offset = center - closest;
+                let distance = offset.length();
+                if distance > f32::EPSILON {
+                    (distance < radius).then(|| (offset / distance, radius - distance))
+                } else {
+                    // The center is inside the box, the sphere is treated as a box
+                    Self::box_penetration(&self.bounds(), &bounds)
+                }
+            }
+            (Shape::Aabb(_), Shape::Sphere(..)) => other
+                .penetration(self)
+                .map(|(normal, depth)| (normal * -1., depth)),
+            (Shape::Aabb(a), Shape::Aabb(b)) => Self::box_penetration(&a, &b),
+        }
+    }
+
+    // Along the axis with the smallest overlap
+    fn box_penetration(a: &BoundingBox, b: &BoundingBox) -> Option<(Vec3, f32)> {
+        let mut result: Option<(Vec3, f32)> = None;
+        for axis in 0..3 {
+            let depth = (a.max[axis] - b.min[axis]).min(b.max[axis] - a.min[axis]);
+            if depth <= 0. {
+                return None;
+            }
+            if result.is_none_or(|(_, smallest)| depth < smallest) {
+                let mut normal = Vec3::default();
+                normal[axis] = if a.get_middle_point()[axis] >= b.get_middle_point()[axis] {
+                    1.
+                } else {
+                    -1.
+                };
+                result = Some((normal, depth));
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,86 @@
+use math::Mat4;
+
+use crate::{ensure, utils::Result};
+
+// Length of the joint matrix array of the skinned vertex shader
+pub const MAX_JOINTS: usize = 128;
+
+#[derive(Clone, Debug)]
+pub struct Joint {
+    pub name: String,
+    pub parent: Option<usize>,
+    // Mesh space to joint space, in the pose the mesh was modeled in
+    pub inverse_bind_matrix: Mat4,
+    // Joint space to parent space, what animations change
+    pub pose: Mat4,
+}
+
+// Joints of a rigged mesh, parents always come before their children so
+// poses are resolved in a single pass. Vertices reference joints by index.
+#[derive(Clone, Debug)]
+pub struct Skeleton {
+    joints: Vec<Joint>,
+}
+
+impl Joint {
+    // A joint at rest, `bind_matrix` being its joint to mesh space transform
+    pub fn new(name: &str, parent: Option<usize>, bind_matrix: Mat4, pose: Mat4) -> Result<Self> {
+        Ok(Self {
+            name: name.to_owned(),
+            parent,
+            inverse_bind_matrix: bind_matrix
+                .inverse()
+                .ok_or_else(|| format!("Joint \"{}\" has a singular bind matrix", name))?,
+            pose,
+        })
+    }
+}
+
+impl Skeleton {
+    pub fn new(joints: Vec<Joint>) -> Result<Self> {
+        ensure!(joints.len() <= MAX_JOINTS, "Too many joints in skeleton");
+        for (index, joint) in joints.iter().enumerate() {
+            ensure!(
+                joint.parent.is_none_or(|parent| parent < index),
+                "Joint parents must come before their children"
+            );
+        }
+
+        Ok(Self { joints })
+    }
+
+    pub fn joints(&self) -> &[Joint] {
+        &self.joints
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.joints.iter().position(|joint| joint.name == name)
+    }
+
+    pub fn set_pose(&mut self, joint: usize, pose: Mat4) {
+        self.joints[joint].pose = pose;
+    }
+
+    // Joint space to mesh space, parents applied after their children
+    pub fn mesh_poses(&self) -> Vec<Mat4> {
+        let mut poses: Vec<Mat4> = Vec::with_capacity(self.joints.len());
+        for joint in &self.joints {
+            let pose = match joint.parent {
+                Some(parent) => joint.pose * poses[parent],
+                None => joint.pose,
+            };
+            poses.push(pose);
+        }
+        poses
+    }
+
+    // Moves bind pose vertices to the current pose, the matrices the
+    // vertices blend with their weights. Identities when posed at rest.
+    pub fn joint_matrices(&self) -> Vec<Mat4> {
+        self.mesh_poses()
+            .into_iter()
+            .zip(&self.joints)
+            .map(|(pose, joint)| joint.inverse_bind_matrix * pose)
+            .collect()
+    }
+}
@@ -1,55 +1,84 @@
-use math::{Mat3, Mat4, Vec3};
+use math::{Mat4, Vec3};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Transform {
     pub pivot: Vec3,
     pub translation: Vec3,
     pub scale: Vec3,
     pub rotation: Vec3,
+    pub rotation_order: EulerOrder,
+}
+
+// Named after the matrix product, Yxz is Ry * Rx * Rz: the Z rotation is
+// applied first and Y last
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EulerOrder {
+    Xyz,
+    Xzy,
+    #[default]
+    Yxz,
+    Yzx,
+    Zxy,
+    Zyx,
 }
 
 impl Transform {
-    // Matrix corrsponds to Translate * Ry * Rx * Rz * Scale
-    // Rotations correspond to Tait-bryan angles of Y(1), X(2), Z(3)
+    // Matrix corrsponds to Translate * Scale * Rotation around the pivot, the
+    // pivot ends up at `translation`
     // https://en.wikipedia.org/wiki/Euler_angles#Rotation_matrix
     pub fn mat(&self) -> Mat4 {
-        let rotate = Mat4::translate(self.pivot * -1.)
-            * Mat4::rotate(self.rotation)
-            * Mat4::translate(self.pivot);
+        // Built in place instead of multiplying the 5 matrices: the scale
+        // multiplies the rows of the rotation, then the translation moves
+        // the transformed pivot to `translation`
+        let mut mat = self.rotation_matrix();
+        for column in 0..3 {
+            for row in 0..3 {
+                mat[column][row] *= self.scale[row];
+            }
+        }
 
-        rotate * Mat4::scale(self.scale) * Mat4::translate(self.translation - (self.pivot * self.scale))
+        let pivot = mat.transform_vector(self.pivot);
+        mat[3] = [
+            self.translation.x - pivot.x,
+            self.translation.y - pivot.y,
+            self.translation.z - pivot.z,
+            1.,
+        ];
+        mat
     }
 
-    pub fn normal_matrix(&self) -> Mat3 {
-        let c3: f32 = self.rotation.z.cos();
-        let s3: f32 = self.rotation.z.sin();
-        let c2: f32 = self.rotation.x.cos();
-        let s2: f32 = self.rotation.x.sin();
-        let c1: f32 = self.rotation.y.cos();
-        let s1: f32 = self.rotation.y.sin();
-        let inv_scale: Vec3 = Vec3::from([
-            1.0f32 / self.scale[0],
-            1.0f32 / self.scale[1],
-            1.0f32 / self.scale[2],
-        ]);
+    pub fn rotation_matrix(&self) -> Mat4 {
+        self.rotation_order.matrix(self.rotation)
+    }
+
+    // Turns +z toward `target` with +y as close as possible to `up`, both in
+    // the parent space
+    pub fn look_at(&mut self, target: Vec3, up: Vec3) {
+        let forward = (target - self.translation).normalized();
+        let right = up.cross(&forward).normalized();
+        let up = forward.cross(&right);
 
-        return Mat3::from([
-            [
-                inv_scale.x * (c1 * c3 + s1 * s2 * s3),
-                inv_scale.x * (c2 * s3),
-                inv_scale.x * (c1 * s2 * s3 - c3 * s1),
-            ],
-            [
-                inv_scale.y * (c3 * s1 * s2 - c1 * s3),
-                inv_scale.y * (c2 * c3),
-                inv_scale.y * (c1 * c3 * s2 + s1 * s3),
-            ],
-            [
-                inv_scale.z * (c2 * s1),
-                inv_scale.z * (-s2),
-                inv_scale.z * (c1 * c2),
-            ],
+        let rotation = Mat4::from([
+            [right.x, right.y, right.z, 0.],
+            [up.x, up.y, up.z, 0.],
+            [forward.x, forward.y, forward.z, 0.],
+            [0., 0., 0., 1.],
         ]);
+        self.rotation = self.rotation_order.angles(&rotation);
+    }
+
+    // Moves the pivot without moving the object
+    pub fn set_pivot(&mut self, pivot: Vec3) {
+        self.translation = self.mat().transform_point(pivot);
+        self.pivot = pivot;
+    }
+
+    // Converts the angles so the orientation is kept
+    pub fn set_rotation_order(&mut self, rotation_order: EulerOrder) {
+        self.rotation = rotation_order.angles(&self.rotation_matrix());
+        self.rotation_order = rotation_order;
     }
 }
 
@@ -60,6 +89,57 @@ impl Default for Transform {
             translation: Default::default(),
             scale: Vec3::one(),
             rotation: Default::default(),
+            rotation_order: Default::default(),
+        }
+    }
+}
+
+impl EulerOrder {
+    // Axes from the left of the matrix product, i.e. the last applied first
+    fn axes(&self) -> [usize; 3] {
+        match self {
+            Self::Xyz => [0, 1, 2],
+            Self::Xzy => [0, 2, 1],
+            Self::Yxz => [1, 0, 2],
+            Self::Yzx => [1, 2, 0],
+            Self::Zxy => [2, 0, 1],
+            Self::Zyx => [2, 1, 0],
+        }
+    }
+
+    pub fn matrix(&self, rotation: Vec3) -> Mat4 {
+        if *self == Self::Yxz {
+            return Mat4::rotate(rotation);
+        }
+
+        let axis_rotation = |axis: usize| {
+            let mut angles = Vec3::default();
+            angles[axis] = rotation[axis];
+            Mat4::rotate(angles)
+        };
+        let [i, j, k] = self.axes();
+        axis_rotation(k) * axis_rotation(j) * axis_rotation(i)
+    }
+
+    // Inverse of `matrix` for a pure rotation, the middle angle is kept in
+    // [-pi/2, pi/2]
+    // https://www.geometrictools.com/Documentation/EulerAngles.pdf
+    pub fn angles(&self, rotation: &Mat4) -> Vec3 {
+        let [i, j, k] = self.axes();
+        // Row r, column c of the rotation
+        let m = |r: usize, c: usize| rotation[c][r];
+        let sign = if (j + 3 - i) % 3 == 1 { 1. } else { -1. };
+
+        let mut angles = Vec3::default();
+        angles[j] = (sign * m(i, k)).clamp(-1., 1.).asin();
+        if m(i, k).abs() < 1. - 1e-6 {
+            angles[i] = (-sign * m(j, k)).atan2(m(k, k));
+            angles[k] = (-sign * m(i, j)).atan2(m(i, i));
+        } else {
+            // Gimbal lock, only the sum or difference of the other two
+            // angles is defined and it all goes to the first one
+            angles[i] = (sign * m(k, j)).atan2(m(j, j));
         }
+        angles
     }
 }
@@ -0,0 +1,19 @@
+#![feature(lint_reasons)]
+
+// The engine behind the scop binary. Tools can render model previews and read
+// or write the scop file formats without going through the command line.
+mod app;
+mod engine;
+pub mod parsing;
+pub mod renderer;
+mod utils;
+
+pub use app::{
+    convert::convert_obj_file, custom::AppCustom, gallery::AppGallery, objects::AppObjects,
+    preview::render_preview, samourai::AppSamourai, test_scene::AppTestScene,
+};
+pub use engine::{
+    mesh::{MeshStats, VertexLayout},
+    EngineConfig,
+};
+pub use utils::Result;
@@ -1,24 +1,164 @@
-#![feature(lint_reasons)]
+use std::env;
 
-mod app;
-mod engine;
-mod parsing;
-mod renderer;
-mod utils;
+use scop::{
+    bail, convert_obj_file, ensure,
+    parsing::{parse_obj_file, user_path, write_tga_file, ObjImportOptions, UpAxis},
+    render_preview, AppCustom, AppGallery, AppObjects, AppSamourai, AppTestScene, EngineConfig,
+    MeshStats, Result, VertexLayout,
+};
 
-use std::env;
+// A number of seconds or a fraction like 1/60
+fn parse_seconds(value: &str) -> Result<f32> {
+    let seconds = match value.split_once('/') {
+        Some((numerator, denominator)) => {
+            numerator.trim().parse::<f32>()? / denominator.trim().parse::<f32>()?
+        }
+        None => value.trim().parse::<f32>()?,
+    };
+    ensure!(
+        seconds.is_finite() && seconds > 0.,
+        "The fixed delta time must be a positive duration"
+    );
+    Ok(seconds)
+}
 
-use app::{custom::AppCustom, objects::AppObjects, samourai::AppSamourai};
-use utils::Result;
+fn parse_up_axis(value: &str) -> Result<UpAxis> {
+    match value {
+        "y" | "Y" => Ok(UpAxis::Y),
+        "z" | "Z" => Ok(UpAxis::Z),
+        "auto" => Ok(UpAxis::Auto),
+        _ => bail!(format!(
+            "Unknown up axis `{}`, expected y, z or auto",
+            value
+        )),
+    }
+}
 
 fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+    let mut config = EngineConfig::default();
+    let mut watch = false;
+    let mut up_axis = None;
+    let mut mirror_x = false;
+    let mut args: Vec<String> = vec![];
+    let mut all_args = env::args();
+    while let Some(arg) = all_args.next() {
+        match arg.as_str() {
+            "--depth-prepass" => config.renderer.depth_prepass = true,
+            "--planar-vertices" => config.renderer.vertex_layout = VertexLayout::Planar,
+            "--reverse-z" => config.renderer.reverse_z = true,
+            "--left-handed" => config.renderer.left_handed = true,
+            "--gpu-validation" => config.renderer.gpu_validation = true,
+            "--watch" => watch = true,
+            "--mirror-x" => mirror_x = true,
+            "--fixed-dt" => {
+                let seconds = all_args
+                    .next()
+                    .ok_or("--fixed-dt needs a duration in seconds, like 1/60")?;
+                config.renderer.fixed_delta_time = Some(parse_seconds(&seconds)?);
+            }
+            "--record-input" => {
+                let path = all_args.next().ok_or("--record-input needs a file")?;
+                config.record_input = Some(path);
+            }
+            "--replay-input" => {
+                let path = all_args.next().ok_or("--replay-input needs a file")?;
+                config.replay_input = Some(path);
+            }
+            "--frames" => {
+                let frames = all_args.next().ok_or("--frames needs a frame count")?;
+                config.frame_limit = Some(frames.parse()?);
+            }
+            "--render-scale" => {
+                let scale = all_args
+                    .next()
+                    .ok_or("--render-scale needs a factor between 0.5 and 2")?;
+                config.renderer.render_scale = Some(scale.parse()?);
+            }
+            "--up" => {
+                let axis = all_args.next().ok_or("--up needs an axis: y, z or auto")?;
+                up_axis = Some(parse_up_axis(&axis)?);
+            }
+            _ => args.push(arg),
+        }
+    }
+
+    // Input is recorded per frame, frames must cover the same time on replay
+    if config.record_input.is_some() || config.replay_input.is_some() {
+        config.renderer.fixed_delta_time.get_or_insert(1. / 60.);
+    }
 
-    if args.len() > 1 && args[1] == "samourai" {
-        AppSamourai::default().start()
+    if args.len() > 2 && args[1] == "--validate" {
+        let elements = parse_obj_file(&user_path(&args[2]))?;
+        println!("{}", MeshStats::new(&elements.vertices, &elements.indices));
+        for warning in &elements.warnings {
+            println!("Warning: {}", warning);
+        }
+        if !elements.lines.is_empty() || !elements.points.is_empty() {
+            println!(
+                "Lines:                {}\nPoints:               {}",
+                elements.lines.len() / 2,
+                elements.points.len()
+            );
+        }
+        Ok(())
+    } else if args.len() > 3 && args[1] == "preview" {
+        // preview <model.obj> <output.tga> [texture.tga] [size]
+        let size = match args.get(5) {
+            Some(size) => size.parse()?,
+            None => 256,
+        };
+        let texture = args.get(4).map(|texture| user_path(texture));
+        let pixels = render_preview(&user_path(&args[2]), texture.as_deref(), size)?;
+        write_tga_file(&args[3], size, size, &pixels)?;
+        println!("{}: {}x{} preview written", args[3], size, size);
+        Ok(())
+    } else if args.len() > 2 && args[1] == "convert" {
+        // convert <model.obj> [--simplify ratio] [--out output.mesh]
+        let mut simplify = None;
+        let mut output = None;
+        let mut convert_args = args[3..].iter();
+        while let Some(arg) = convert_args.next() {
+            match arg.as_str() {
+                "--simplify" => {
+                    let ratio = convert_args
+                        .next()
+                        .ok_or("--simplify needs the share of triangles to keep, like 0.25")?;
+                    simplify = Some(ratio.parse()?);
+                }
+                "--out" => output = Some(convert_args.next().ok_or("--out needs a file")?),
+                _ => bail!(format!("Unknown convert argument `{}`", arg)),
+            }
+        }
+        let options = ObjImportOptions {
+            up_axis: up_axis.unwrap_or_default(),
+            mirror_x,
+            ..Default::default()
+        };
+        convert_obj_file(
+            &user_path(&args[2]),
+            output.map(String::as_str),
+            &options,
+            simplify,
+        )
+    } else if args.len() > 1 && args[1] == "samourai" {
+        AppSamourai::default().start(config)
+    } else if args.len() > 1 && args[1] == "gallery" {
+        AppGallery::default().start(config)
+    } else if args.len() > 1 && args[1] == "testscene" {
+        // One frame is enough to catch hazards, then the test ends
+        if config.renderer.gpu_validation {
+            config.frame_limit.get_or_insert(1);
+        }
+        AppTestScene.start(config)
     } else if args.len() > 1 {
-        AppCustom::default().start(args[1].as_str())
+        let mut app = AppCustom::default();
+        app.watch = watch;
+        app.import_options.mirror_x = mirror_x;
+        if let Some(up_axis) = up_axis {
+            app.import_options.up_axis = up_axis;
+        }
+        app.start(args[1].as_str(), config)
     } else {
-        AppObjects::default().start()
+        AppObjects::default().start(config)
     }
 }
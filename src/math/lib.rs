@@ -1,12 +0,0 @@
-mod macros;
-mod vec2;
-mod vec3;
-mod vec4;
-mod mat3;
-mod mat4;
-
-pub use vec2::Vec2;
-pub use vec3::Vec3;
-pub use vec4::Vec4;
-pub use mat3::Mat3;
-pub use mat4::Mat4;
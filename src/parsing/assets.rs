@@ -0,0 +1,139 @@
+use std::{
+    env, fs,
+    path::{self, Path, PathBuf},
+    sync::OnceLock,
+};
+
+use crate::utils::Result;
+
+const ASSET_DIR_VARIABLE: &str = "SCOP_ASSET_DIR";
+// Smaller files are read, mapping them costs more than it saves
+#[cfg(feature = "mmap")]
+const MMAP_MIN_SIZE: u64 = 64 * 1024 * 1024;
+const CONFIG_FILE: &str = "scop.toml";
+
+// Relative asset paths are looked up in, by priority: $SCOP_ASSET_DIR, the
+// asset_dir of scop.toml (working directory or next to the executable), the
+// working directory, then the executable directory and its parents so
+// `target/<profile>/scop` finds the repository assets.
+pub fn resolve_asset(path: &str) -> Result<PathBuf> {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        if !path.exists() {
+            return Err(format!("Asset not found: {}", path.display()).into());
+        }
+        return Ok(path.to_path_buf());
+    }
+
+    let relative = path.strip_prefix(".").unwrap_or(path);
+    let mut tried = Vec::new();
+    for directory in search_directories() {
+        let candidate = directory.join(relative);
+        if candidate.exists() {
+            return Ok(candidate);
+        }
+        tried.push(format!("  {}", candidate.display()));
+    }
+
+    Err(format!(
+        "Asset not found: {}, tried:\n{}",
+        path.display(),
+        tried.join("\n")
+    ))?
+}
+
+// Paths typed by the user are relative to the working directory. Files found
+// there are made absolute, so `resolve_asset` does not prefer a file of the
+// same name from the asset directories. Missing ones are still looked up.
+pub fn user_path(path: &str) -> String {
+    let relative = Path::new(path);
+    if relative.is_relative() && relative.exists() {
+        if let Ok(absolute) = path::absolute(relative) {
+            return absolute.to_string_lossy().into_owned();
+        }
+    }
+    path.to_string()
+}
+
+// Maps the file in memory when it is large enough. Pages are only loaded
+// when parsed, and dropped by the system under memory pressure.
+#[cfg(feature = "mmap")]
+pub fn map_large_file(path: &Path) -> Result<Option<memmap2::Mmap>> {
+    let file = fs::File::open(path)?;
+    if file.metadata()?.len() < MMAP_MIN_SIZE {
+        return Ok(None);
+    }
+    // Undefined if another process truncates the file while it is mapped,
+    // like any mmap based reader
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    Ok(Some(map))
+}
+
+fn search_directories() -> &'static [PathBuf] {
+    static DIRECTORIES: OnceLock<Vec<PathBuf>> = OnceLock::new();
+
+    DIRECTORIES.get_or_init(|| {
+        let executable_directory = env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf));
+
+        let mut directories = vec![];
+        if let Some(directory) = env::var_os(ASSET_DIR_VARIABLE) {
+            directories.push(PathBuf::from(directory));
+        }
+
+        let config_directories = [Some(PathBuf::from(".")), executable_directory.clone()];
+        if let Some(asset_dir) = config_directories
+            .into_iter()
+            .flatten()
+            .find_map(|directory| read_config(&directory))
+        {
+            directories.push(asset_dir);
+        }
+
+        directories.push(PathBuf::from("."));
+        if let Some(executable_directory) = executable_directory {
+            directories.extend(
+                executable_directory
+                    .ancestors()
+                    .take(3)
+                    .map(Path::to_path_buf),
+            );
+        }
+
+        let mut unique = Vec::<PathBuf>::with_capacity(directories.len());
+        for directory in directories {
+            let key = directory.canonicalize().unwrap_or(directory.clone());
+            if !unique
+                .iter()
+                .any(|d| d.canonicalize().unwrap_or(d.clone()) == key)
+            {
+                unique.push(directory);
+            }
+        }
+        unique
+    })
+}
+
+// Only `asset_dir = "<path>"` is read, relative to the file
+fn read_config(directory: &Path) -> Option<PathBuf> {
+    let path = directory.join(CONFIG_FILE);
+    let content = fs::read_to_string(&path).ok()?;
+
+    let mut asset_dir = None;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+            continue;
+        }
+
+        match line.split_once('=') {
+            Some((key, value)) if key.trim() == "asset_dir" => {
+                asset_dir = Some(directory.join(value.trim().trim_matches('"')));
+            }
+            _ => eprintln!("{}: ignored line `{}`", path.display(), line),
+        }
+    }
+
+    asset_dir
+}
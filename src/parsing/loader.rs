@@ -0,0 +1,119 @@
+use std::{
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+};
+
+use crate::{
+    bail,
+    engine::{mesh::Mesh, Engine},
+    utils::Result,
+};
+
+use super::obj::{import_obj_file, upload_obj_mesh, ObjElements, ObjImportOptions, ObjWarning};
+
+// Shared by a load and its owner. Loads check it between steps and stop with
+// an error once it is set.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn check(&self) -> Result<()> {
+        if self.is_canceled() {
+            bail!("Load canceled");
+        }
+        Ok(())
+    }
+}
+
+// The errors are not Send, only their message leaves the thread
+type ParsedObj = core::result::Result<(ObjElements, f32), String>;
+
+// An OBJ file parsed on a thread of its own, the mesh is uploaded on the main
+// thread by `poll`. Dropping the load cancels it, so replacing a pending load
+// by another one aborts the first.
+pub struct ObjLoad {
+    path: String,
+    cancel: CancelToken,
+    parsed: Receiver<ParsedObj>,
+    // Bits of the parsed fraction
+    progress: Arc<AtomicU32>,
+}
+
+impl ObjLoad {
+    pub fn start(path: &str, options: ObjImportOptions) -> Self {
+        let cancel = CancelToken::default();
+        let progress = Arc::new(AtomicU32::new(0));
+        let (sender, parsed) = mpsc::channel();
+
+        let thread_path = path.to_string();
+        let thread_cancel = cancel.clone();
+        let thread_progress = progress.clone();
+        // Detached, a canceled parse ends at its next line
+        thread::spawn(move || {
+            let on_progress = |_stage, fraction: f32| {
+                thread_progress.store(fraction.to_bits(), Ordering::Relaxed)
+            };
+            let imported = import_obj_file(&thread_path, &options, &on_progress, &thread_cancel)
+                .map_err(|e| e.to_string());
+            // The load may have been dropped meanwhile
+            let _ = sender.send(imported);
+        });
+
+        Self {
+            path: path.to_string(),
+            cancel,
+            parsed,
+            progress,
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    // Fraction of the file parsed, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        f32::from_bits(self.progress.load(Ordering::Relaxed))
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    // None while the file is being parsed, the result is only returned once
+    pub fn poll(&self, engine: &Engine) -> Option<Result<(Rc<Mesh>, Vec<ObjWarning>)>> {
+        let (mut elements, import_scale) = match self.parsed.try_recv() {
+            Ok(Ok(parsed)) => parsed,
+            Ok(Err(e)) => return Some(Err(e.into())),
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => {
+                return Some(Err("The load thread stopped without a result".into()))
+            }
+        };
+
+        let uploaded = self
+            .cancel
+            .check()
+            .and_then(|_| upload_obj_mesh(engine, &elements, import_scale));
+        Some(uploaded.map(|mesh| (mesh, std::mem::take(&mut elements.warnings))))
+    }
+}
+
+impl Drop for ObjLoad {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
@@ -0,0 +1,404 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use math::{Vec2, Vec3};
+
+use crate::{bail, engine::mesh::Vertex, ensure, utils::Result};
+
+use super::obj::{ObjElements, ObjImportOptions, ObjWarning};
+
+const CACHE_DIRECTORY: &str = "./.scop/meshes";
+// Smaller files parse about as fast as their cache reads
+pub const CACHE_MIN_SIZE: u64 = 1024 * 1024;
+const MAGIC: &[u8; 8] = b"SCOPMESH";
+// Changed with the layout, caches of another version are parsed again
+const VERSION: u32 = 1;
+
+// The imported elements of an OBJ file, in `./.scop/meshes` or wherever
+// `scop convert` was told. Only position, color, normal and uv are stored,
+// OBJ files have no skinning.
+//
+//   magic, version u32, key u64, import scale f32
+//   vertex count u64, then 11 f32 per vertex
+//   index, line and point counts u64, each followed by its u32 indices
+//   warning count u64, then a kind u8 and a line u64 per warning
+//
+// Little endian. The key hashes the source path, size and modification time
+// with the import options. The content is not hashed, reading the whole
+// source would cost most of what the cache saves.
+
+fn source_key(source: &Path, options: &ObjImportOptions) -> Result<u64> {
+    let metadata = fs::metadata(source)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos());
+
+    // The hasher may change with the toolchain, the caches are then rebuilt
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    modified.hash(&mut hasher);
+    options.up_axis.hash(&mut hasher);
+    options.mirror_x.hash(&mut hasher);
+    options.normalize_size.map(f32::to_bits).hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+fn cache_path(source: &Path) -> PathBuf {
+    let file_name: String = source
+        .to_string_lossy()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    PathBuf::from(CACHE_DIRECTORY).join(file_name + ".mesh")
+}
+
+// None when there is no cache or it was written for another version of the
+// source, an error when it cannot be read
+pub fn read_mesh_cache(
+    source: &Path,
+    options: &ObjImportOptions,
+) -> Result<Option<(ObjElements, f32)>> {
+    let path = cache_path(source);
+    if !path.exists() {
+        return Ok(None);
+    }
+    decode_mesh(&fs::read(&path)?, Some(source_key(source, options)?))
+}
+
+// A mesh written by `scop convert`, used as is whatever its source became.
+// The import options were applied before it was written.
+pub fn read_mesh_file(path: &Path) -> Result<(ObjElements, f32)> {
+    let decoded = decode_mesh(&fs::read(path)?, None)?;
+    Ok(decoded.ok_or_else(|| {
+        format!(
+            "{}: not a mesh file of version {}, convert the model again",
+            path.display(),
+            VERSION
+        )
+    })?)
+}
+
+// None when the header does not match, `key` is not checked without a value
+fn decode_mesh(bytes: &[u8], key: Option<u64>) -> Result<Option<(ObjElements, f32)>> {
+    let mut reader = CacheReader { bytes };
+
+    if reader.take(MAGIC.len())? != MAGIC || reader.u32()? != VERSION {
+        return Ok(None);
+    }
+    let source_key = reader.u64()?;
+    if key.is_some_and(|key| key != source_key) {
+        return Ok(None);
+    }
+    let import_scale = reader.f32()?;
+
+    let vertex_count = reader.count(11 * 4)?;
+    let mut vertices = Vec::with_capacity(vertex_count);
+    for _ in 0..vertex_count {
+        vertices.push(Vertex {
+            position: reader.vec3()?,
+            color: reader.vec3()?,
+            normal: reader.vec3()?,
+            uv: Vec2::new(reader.f32()?, reader.f32()?),
+            ..Default::default()
+        });
+    }
+    let indices = reader.indices()?;
+    let lines = reader.indices()?;
+    let points = reader.indices()?;
+
+    let warning_count = reader.count(1 + 8)?;
+    let mut warnings = Vec::with_capacity(warning_count);
+    for _ in 0..warning_count {
+        let kind = reader.take(1)?[0];
+        let line = reader.u64()? as usize;
+        warnings.push(match kind {
+            0 => ObjWarning::IndexOutOfRange { line },
+            1 => ObjWarning::DegenerateTriangle { line },
+            2 => ObjWarning::DuplicateFace { line },
+            _ => bail!(format!("Unknown warning kind {} in the mesh cache", kind)),
+        });
+    }
+    ensure!(reader.bytes.is_empty(), "Trailing data in the mesh cache");
+
+    // Indices are trusted by the upload, a corrupted cache must not get there
+    let vertex_count = vertices.len() as u32;
+    ensure!(
+        [&indices, &lines, &points]
+            .iter()
+            .all(|indices| indices.iter().all(|&index| index < vertex_count)),
+        "Index out of range in the mesh cache"
+    );
+
+    let elements = ObjElements {
+        vertices,
+        indices,
+        lines,
+        points,
+        warnings,
+    };
+    Ok(Some((elements, import_scale)))
+}
+
+pub fn write_mesh_cache(
+    source: &Path,
+    options: &ObjImportOptions,
+    elements: &ObjElements,
+    import_scale: f32,
+) -> Result<()> {
+    fs::create_dir_all(CACHE_DIRECTORY)?;
+    write_mesh_file(&cache_path(source), source, options, elements, import_scale)
+}
+
+// The cache layout at any path, keyed like the cache. Written to a temporary
+// file first, a reader never sees half a mesh.
+pub fn write_mesh_file(
+    path: &Path,
+    source: &Path,
+    options: &ObjImportOptions,
+    elements: &ObjElements,
+    import_scale: f32,
+) -> Result<()> {
+    let mut bytes = Vec::with_capacity(
+        64 + elements.vertices.len() * 11 * 4
+            + (elements.indices.len() + elements.lines.len() + elements.points.len()) * 4,
+    );
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&source_key(source, options)?.to_le_bytes());
+    bytes.extend_from_slice(&import_scale.to_le_bytes());
+
+    bytes.extend_from_slice(&(elements.vertices.len() as u64).to_le_bytes());
+    for vertex in &elements.vertices {
+        let uv = [vertex.uv.x, vertex.uv.y];
+        for value in [vertex.position, vertex.color, vertex.normal]
+            .iter()
+            .flat_map(|v| [v.x, v.y, v.z])
+            .chain(uv)
+        {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    for indices in [&elements.indices, &elements.lines, &elements.points] {
+        bytes.extend_from_slice(&(indices.len() as u64).to_le_bytes());
+        for index in indices.iter() {
+            bytes.extend_from_slice(&index.to_le_bytes());
+        }
+    }
+
+    bytes.extend_from_slice(&(elements.warnings.len() as u64).to_le_bytes());
+    for warning in &elements.warnings {
+        let (kind, line) = match *warning {
+            ObjWarning::IndexOutOfRange { line } => (0u8, line),
+            ObjWarning::DegenerateTriangle { line } => (1, line),
+            ObjWarning::DuplicateFace { line } => (2, line),
+        };
+        bytes.push(kind);
+        bytes.extend_from_slice(&(line as u64).to_le_bytes());
+    }
+
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    fs::write(&temporary, bytes)?;
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+struct CacheReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> CacheReader<'a> {
+    fn take(&mut self, size: usize) -> Result<&'a [u8]> {
+        ensure!(self.bytes.len() >= size, "Truncated mesh cache");
+        let (taken, rest) = self.bytes.split_at(size);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn vec3(&mut self) -> Result<Vec3> {
+        Ok(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    // A count of items of `item_size` bytes, checked against what is left so
+    // a corrupted count does not allocate
+    fn count(&mut self, item_size: usize) -> Result<usize> {
+        let count = self.u64()?;
+        ensure!(
+            count <= (self.bytes.len() / item_size) as u64,
+            "Truncated mesh cache"
+        );
+        Ok(count as usize)
+    }
+
+    fn indices(&mut self) -> Result<Vec<u32>> {
+        let count = self.count(4)?;
+        (0..count).map(|_| self.u32()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A source file and the mesh file written for it, removed on drop
+    struct Fixture {
+        source: PathBuf,
+        mesh: PathBuf,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let directory = std::env::temp_dir();
+            let name = format!("scop-mesh-cache-{}-{}", std::process::id(), name);
+            let fixture = Self {
+                source: directory.join(format!("{}.obj", name)),
+                mesh: directory.join(format!("{}.mesh", name)),
+            };
+            fs::write(&fixture.source, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+            write_mesh_file(
+                &fixture.mesh,
+                &fixture.source,
+                &ObjImportOptions::default(),
+                &elements(),
+                0.5,
+            )
+            .unwrap();
+            fixture
+        }
+
+        fn bytes(&self) -> Vec<u8> {
+            fs::read(&self.mesh).unwrap()
+        }
+
+        fn key(&self) -> u64 {
+            source_key(&self.source, &ObjImportOptions::default()).unwrap()
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.source);
+            let _ = fs::remove_file(&self.mesh);
+        }
+    }
+
+    fn elements() -> ObjElements {
+        let vertex = |x: f32, y: f32| Vertex {
+            position: Vec3::new(x, y, 0.),
+            color: Vec3::new(1., 0.5, 0.25),
+            normal: Vec3::new(0., 0., 1.),
+            uv: Vec2::new(x, y),
+            ..Default::default()
+        };
+        ObjElements {
+            vertices: vec![vertex(0., 0.), vertex(1., 0.), vertex(0., 1.)],
+            indices: vec![0, 1, 2],
+            lines: vec![0, 2],
+            points: vec![1],
+            warnings: vec![
+                ObjWarning::IndexOutOfRange { line: 7 },
+                ObjWarning::DegenerateTriangle { line: 8 },
+                ObjWarning::DuplicateFace { line: 9 },
+            ],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let fixture = Fixture::new("round-trip");
+        let expected = elements();
+
+        for key in [None, Some(fixture.key())] {
+            let (read, import_scale) = decode_mesh(&fixture.bytes(), key).unwrap().unwrap();
+            assert_eq!(import_scale, 0.5);
+            assert_eq!(read.vertices, expected.vertices);
+            assert_eq!(read.indices, expected.indices);
+            assert_eq!(read.lines, expected.lines);
+            assert_eq!(read.points, expected.points);
+            assert_eq!(read.warnings, expected.warnings);
+        }
+        assert!(read_mesh_file(&fixture.mesh).is_ok());
+    }
+
+    #[test]
+    fn stale_caches_are_ignored() {
+        let fixture = Fixture::new("stale");
+        fs::write(&fixture.source, "v 0 0 0\n# edited\n").unwrap();
+
+        assert!(decode_mesh(&fixture.bytes(), Some(fixture.key()))
+            .unwrap()
+            .is_none());
+        let options = ObjImportOptions {
+            mirror_x: true,
+            ..Default::default()
+        };
+        let key = source_key(&fixture.source, &options).unwrap();
+        assert!(decode_mesh(&fixture.bytes(), Some(key)).unwrap().is_none());
+        // Converted meshes are kept whatever the source became
+        assert!(read_mesh_file(&fixture.mesh).is_ok());
+    }
+
+    #[test]
+    fn truncated_caches_are_rejected() {
+        let fixture = Fixture::new("truncated");
+        let bytes = fixture.bytes();
+
+        for length in [0, 4, MAGIC.len() + 4, bytes.len() / 2, bytes.len() - 1] {
+            assert!(decode_mesh(&bytes[..length], None).is_err(), "{}", length);
+        }
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(decode_mesh(&trailing, None).is_err());
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let fixture = Fixture::new("version");
+        let mut bytes = fixture.bytes();
+        bytes[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        fs::write(&fixture.mesh, &bytes).unwrap();
+
+        assert!(decode_mesh(&bytes, Some(fixture.key())).unwrap().is_none());
+        assert!(read_mesh_file(&fixture.mesh).is_err());
+
+        bytes[0] = b'X';
+        assert!(decode_mesh(&bytes, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn out_of_range_indices_are_rejected() {
+        let fixture = Fixture::new("indices");
+        let mut bytes = fixture.bytes();
+        // The first triangle index follows the header, the vertices and the
+        // index count
+        let first_index = MAGIC.len() + 4 + 8 + 4 + 8 + 3 * 11 * 4 + 8;
+        bytes[first_index..first_index + 4].copy_from_slice(&3u32.to_le_bytes());
+
+        assert!(decode_mesh(&bytes, None).is_err());
+    }
+}
@@ -1,8 +1,24 @@
+mod assets;
+mod loader;
+mod mesh_cache;
+mod mtl;
 mod obj;
+mod progress;
+mod simplify;
 mod spv;
 mod tga;
+mod watcher;
 
-// pub use mtl::read_mtl_file;
-pub use obj::read_obj_file;
+pub use assets::{resolve_asset, user_path};
+pub use loader::{CancelToken, ObjLoad};
+pub use mesh_cache::write_mesh_file;
+pub use mtl::read_obj_materials;
+pub use obj::{
+    import_obj_file, parse_obj_file, print_obj_warnings, read_obj_file, read_obj_file_reported,
+    read_obj_files_with, ObjImportOptions, ObjWarning, UpAxis,
+};
+pub use progress::ProgressBar;
+pub use simplify::simplify_mesh;
 pub use spv::{read_frag_spv_file, read_vert_spv_file};
-pub use tga::read_tga_r8g8b8a8_srgb_file;
+pub use tga::{read_tga_r8g8b8a8_srgb_file, read_tga_r8g8b8a8_srgb_file_with, write_tga_file};
+pub use watcher::FileWatcher;
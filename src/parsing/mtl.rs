@@ -0,0 +1,128 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use crate::utils::Result;
+
+use super::resolve_asset;
+
+// Only what the renderer uses, the other statements are ignored
+#[derive(Clone, Debug, Default)]
+pub struct MtlMaterial {
+    pub name: String,
+    // Resolved relative to the MTL file, the file may not exist
+    pub diffuse_map: Option<PathBuf>,
+}
+
+// Options of texture statements, with their maximum value count
+const MAP_OPTIONS: [(&str, usize); 12] = [
+    ("-blendu", 1),
+    ("-blendv", 1),
+    ("-bm", 1),
+    ("-boost", 1),
+    ("-cc", 1),
+    ("-clamp", 1),
+    ("-imfchan", 1),
+    ("-mm", 2),
+    ("-o", 3),
+    ("-s", 3),
+    ("-t", 3),
+    ("-texres", 1),
+];
+
+pub fn read_mtl_file(path: &Path) -> Result<Vec<MtlMaterial>> {
+    let directory = path.parent().unwrap_or(Path::new("."));
+    let file = File::open(path)?;
+
+    let mut materials = Vec::<MtlMaterial>::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let line = line.trim();
+
+        if let Some(name) = line.strip_prefix("newmtl ") {
+            materials.push(MtlMaterial {
+                name: name.trim().to_string(),
+                ..Default::default()
+            });
+        } else if let Some(map) = line.strip_prefix("map_Kd ") {
+            let Some(material) = materials.last_mut() else {
+                eprintln!("{}: map_Kd before any newmtl, ignored", path.display());
+                continue;
+            };
+            let file_name = map_file_name(map);
+            if file_name.is_empty() {
+                eprintln!("{}: map_Kd without file name, ignored", path.display());
+                continue;
+            }
+            // Exporters on Windows write backslashes
+            material.diffuse_map = Some(directory.join(file_name.replace('\\', "/")));
+        }
+    }
+
+    Ok(materials)
+}
+
+// The materials of every `mtllib` of an OBJ file, a library that cannot be
+// read is reported and skipped
+pub fn read_obj_materials(obj_path: &str) -> Result<Vec<MtlMaterial>> {
+    let obj_path = resolve_asset(obj_path)?;
+    let directory = obj_path.parent().unwrap_or(Path::new("."));
+    let file = File::open(&obj_path)?;
+
+    let mut materials = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let Some(libraries) = line.strip_prefix("mtllib ") else {
+            continue;
+        };
+
+        for library in library_paths(directory, libraries.trim()) {
+            match read_mtl_file(&library) {
+                Ok(library_materials) => materials.extend(library_materials),
+                Err(e) => eprintln!("{}: skipped, {}", library.display(), e),
+            }
+        }
+    }
+
+    Ok(materials)
+}
+
+// Several libraries are separated by spaces, but a single file name may
+// contain some: the whole value is tried first
+fn library_paths(directory: &Path, libraries: &str) -> Vec<PathBuf> {
+    let whole = directory.join(libraries);
+    if whole.is_file() {
+        return vec![whole];
+    }
+    libraries
+        .split_whitespace()
+        .map(|library| directory.join(library))
+        .collect()
+}
+
+// The rest of the statement once its options are skipped, spaces included
+fn map_file_name(map: &str) -> &str {
+    let mut rest = map.trim();
+    while let Some((option, values)) = MAP_OPTIONS
+        .iter()
+        .find(|(option, _)| rest.split_whitespace().next() == Some(*option))
+    {
+        rest = rest[option.len()..].trim_start();
+        for _ in 0..*values {
+            match rest.split_once(char::is_whitespace) {
+                Some((value, after)) if !value.is_empty() && is_option_value(value) => {
+                    rest = after.trim_start()
+                }
+                _ => break,
+            }
+        }
+    }
+    rest
+}
+
+fn is_option_value(value: &str) -> bool {
+    value.parse::<f32>().is_ok()
+        || matches!(value, "on" | "off" | "r" | "g" | "b" | "m" | "l" | "z")
+}
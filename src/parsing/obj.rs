@@ -1,17 +1,27 @@
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
-use std::fs::File;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
 use std::io::{BufRead, BufReader};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use math::{Vec2, Vec3};
 
-use crate::engine::mesh::{Mesh, Vertex};
+use crate::engine::mesh::{is_degenerate_triangle, mirror_x, BoundingBox, Mesh, Vertex};
 use crate::engine::Engine;
+#[cfg(feature = "mmap")]
+use crate::parsing::assets::map_large_file;
+use crate::parsing::loader::CancelToken;
+use crate::parsing::mesh_cache::{
+    read_mesh_cache, read_mesh_file, write_mesh_cache, CACHE_MIN_SIZE,
+};
+use crate::parsing::progress::{no_progress, ByteProgress, LoadStage, OnProgress};
+use crate::parsing::resolve_asset;
 use crate::{bail, ensure};
 use crate::utils::{Context, Result};
 
-fn get_content_of<'a>(line: &'a String, prefix: &'static str) -> Result<Option<&'a str>> {
+fn get_content_of<'a>(line: &'a str, prefix: &'static str) -> Result<Option<&'a str>> {
     if line.starts_with(prefix) {
         ensure!(line.len() >= prefix.len() + 1, "Prefix has no value"); // Prefix size + not empty
         return Ok(Some(&line[prefix.len()..]));
@@ -20,32 +30,564 @@ fn get_content_of<'a>(line: &'a String, prefix: &'static str) -> Result<Option<&
     Ok(None)
 }
 
+// Axis pointing up in the file, models are converted to our Y up
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum UpAxis {
+    #[default]
+    Y,
+    Z,
+    // Z when the model stands on the z = 0 plane and not on the y = 0 one,
+    // which is where most modelers put the ground
+    Auto,
+}
+
+// Changes applied to the model while loading it
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ObjImportOptions {
+    pub up_axis: UpAxis,
+    // For exporters writing mirrored geometry, applied after the up axis
+    pub mirror_x: bool,
+    // Uniform scale so the largest side of the bounding box gets this size,
+    // for models exported in millimeters or kilometers
+    pub normalize_size: Option<f32>,
+}
+
 pub fn read_obj_file<'a>(engine: &Engine, path: &'a str) -> Result<Rc<Mesh>> {
+    read_obj_file_with(engine, path, &ObjImportOptions::default())
+}
+
+pub fn read_obj_file_with<'a>(
+    engine: &Engine,
+    path: &'a str,
+    options: &ObjImportOptions,
+) -> Result<Rc<Mesh>> {
+    let (mesh, warnings) = read_obj_file_reported(engine, path, options, &no_progress)?;
+    print_obj_warnings(path, &warnings);
+    Ok(mesh)
+}
+
+// With the problems the parser worked around, for viewers to show them
+pub fn read_obj_file_reported(
+    engine: &Engine,
+    path: &str,
+    options: &ObjImportOptions,
+    on_progress: OnProgress,
+) -> Result<(Rc<Mesh>, Vec<ObjWarning>)> {
+    let (mut elements, import_scale) =
+        import_obj_file(path, options, on_progress, &CancelToken::default())?;
+    on_progress(LoadStage::Uploading, 0.);
+    let mesh = upload_obj_mesh(engine, &elements, import_scale)?;
+    on_progress(LoadStage::Uploading, 1.);
+    Ok((mesh, std::mem::take(&mut elements.warnings)))
+}
+
+// A summary and the first warnings, `--validate` lists them all
+pub fn print_obj_warnings(path: &str, warnings: &[ObjWarning]) {
+    const SHOWN: usize = 5;
+
+    if warnings.is_empty() {
+        return;
+    }
+    eprintln!("{}: loaded with {} warnings", path, warnings.len());
+    for warning in warnings.iter().take(SHOWN) {
+        eprintln!("  {}", warning);
+    }
+    if warnings.len() > SHOWN {
+        eprintln!("  ...");
+    }
+}
+
+// Files are parsed on the job threads, the meshes are then uploaded in order.
+// The progress is the fraction of the files done.
+pub fn read_obj_files_with(
+    engine: &Engine,
+    paths: &[String],
+    options: &ObjImportOptions,
+    on_progress: OnProgress,
+) -> Vec<Result<Rc<Mesh>>> {
+    let parsed = AtomicUsize::new(0);
+    on_progress(LoadStage::Parsing, 0.);
+    // The errors are not Send, only their message leaves the workers
+    let imported = engine.renderer.jobs.map(paths, |path| {
+        let imported = import_obj_file(path, options, &no_progress, &CancelToken::default())
+            .map_err(|e| e.to_string());
+        let parsed = parsed.fetch_add(1, Ordering::Relaxed) + 1;
+        on_progress(LoadStage::Parsing, parsed as f32 / paths.len() as f32);
+        imported
+    });
+
+    let mut warnings = vec![];
+    let meshes = imported
+        .into_iter()
+        .zip(paths)
+        .enumerate()
+        .map(|(i, (imported, path))| {
+            let (mut elements, import_scale) = imported?;
+            warnings.push((path, std::mem::take(&mut elements.warnings)));
+            let mesh = upload_obj_mesh(engine, &elements, import_scale);
+            on_progress(LoadStage::Uploading, (i + 1) as f32 / paths.len() as f32);
+            mesh
+        })
+        .collect();
+    // Printed once the progress lines are done
+    for (path, warnings) in warnings {
+        print_obj_warnings(path, &warnings);
+    }
+    meshes
+}
+
+// Parses and applies the import options, returns the scale it applied. Large
+// files are imported once, later imports read the mesh cache. Files converted
+// by `scop convert` are read as they are.
+pub fn import_obj_file(
+    path: &str,
+    options: &ObjImportOptions,
+    on_progress: OnProgress,
+    cancel: &CancelToken,
+) -> Result<(ObjElements, f32)> {
+    let source = resolve_asset(path)?;
+    if source
+        .extension()
+        .is_some_and(|extension| extension == "mesh")
+    {
+        let imported = read_mesh_file(&source)?;
+        on_progress(LoadStage::Parsing, 1.);
+        return Ok(imported);
+    }
+    if fs::metadata(&source)?.len() < CACHE_MIN_SIZE {
+        return import_obj_source(path, options, on_progress, cancel);
+    }
+
+    match read_mesh_cache(&source, options) {
+        Ok(Some(imported)) => {
+            on_progress(LoadStage::Parsing, 1.);
+            return Ok(imported);
+        }
+        Ok(None) => {}
+        Err(e) => eprintln!("{}: mesh cache ignored, {}", path, e),
+    }
+    let (elements, import_scale) = import_obj_source(path, options, on_progress, cancel)?;
+    if let Err(e) = write_mesh_cache(&source, options, &elements, import_scale) {
+        eprintln!("{}: mesh cache not written, {}", path, e);
+    }
+    Ok((elements, import_scale))
+}
+
+fn import_obj_source(
+    path: &str,
+    options: &ObjImportOptions,
+    on_progress: OnProgress,
+    cancel: &CancelToken,
+) -> Result<(ObjElements, f32)> {
+    let mut elements = parse_obj_source(path, on_progress, cancel)?;
+    // Lines and points alone cannot be drawn by the materials
+    ensure!(
+        !elements.indices.is_empty() || elements.lines.is_empty() && elements.points.is_empty(),
+        "The model only has lines and points, it needs faces"
+    );
+
+    if options.up_axis.resolve(&elements.vertices) == UpAxis::Z {
+        convert_z_up(&mut elements.vertices);
+    }
+    if options.mirror_x {
+        mirror_x(&mut elements.vertices, &mut elements.indices);
+    }
+    let import_scale = match options.normalize_size {
+        Some(size) => normalize_size(&mut elements.vertices, size),
+        None => 1.,
+    };
+
+    Ok((elements, import_scale))
+}
+
+pub fn upload_obj_mesh(
+    engine: &Engine,
+    elements: &ObjElements,
+    import_scale: f32,
+) -> Result<Rc<Mesh>> {
+    Mesh::builder(engine.renderer.main_device.clone())
+        .staging_ring(&mut engine.renderer.staging_ring.borrow_mut())
+        .vertices(&elements.vertices)
+        .indices(&elements.indices)
+        .lines(&elements.lines)
+        .points(&elements.points)
+        .bvh(true)
+        .import_scale(import_scale)
+        .vertex_layout(engine.renderer.config.vertex_layout)
+        .build()
+        .map(Rc::new)
+}
+
+impl UpAxis {
+    fn resolve(self, vertices: &[Vertex]) -> Self {
+        if self != Self::Auto {
+            return self;
+        }
+
+        let bounding_box = BoundingBox::from(vertices);
+        let extent = bounding_box.max - bounding_box.min;
+        let tolerance = extent.x.max(extent.y).max(extent.z) * 0.01;
+        if bounding_box.min.z.abs() <= tolerance && bounding_box.min.y.abs() > tolerance {
+            Self::Z
+        } else {
+            Self::Y
+        }
+    }
+}
+
+// Rotation of -90 degrees around x, a rotation keeps the winding of the faces
+fn convert_z_up(vertices: &mut [Vertex]) {
+    for vertex in vertices.iter_mut() {
+        let Vec3 { x, y, z } = vertex.position;
+        vertex.position = Vec3::new(x, z, -y);
+        let Vec3 { x, y, z } = vertex.normal;
+        vertex.normal = Vec3::new(x, z, -y);
+    }
+}
+
+// Scales around the origin so the model keeps its place relative to it.
+// Returns the applied factor, 1 for a flat or empty model.
+fn normalize_size(vertices: &mut [Vertex], size: f32) -> f32 {
+    let bounding_box = BoundingBox::from(&*vertices);
+    let extent = bounding_box.max - bounding_box.min;
+    let largest = extent.x.max(extent.y).max(extent.z);
+    if !largest.is_finite() || largest <= f32::EPSILON {
+        return 1.;
+    }
+
+    let scale = size / largest;
+    for vertex in vertices.iter_mut() {
+        vertex.position *= scale;
+    }
+    scale
+}
+
+// Corners of the triangles covering the polygon, in its winding order.
+// Convex polygons are fanned, others are ear clipped on the plane they are
+// the most facing, so concave faces do not overlap. Polygons that cannot be
+// clipped, like self-intersecting ones, fall back to the fan.
+fn triangulate_polygon(positions: &[Vec3]) -> Vec<[usize; 3]> {
+    let fan = || (1..positions.len() - 1).map(|i| [0, i, i + 1]).collect();
+    if positions.len() == 3 {
+        return fan();
+    }
+
+    // Newell's normal, the same direction as the winding
+    let mut normal = Vec3::default();
+    for (i, a) in positions.iter().enumerate() {
+        let b = positions[(i + 1) % positions.len()];
+        normal.x += (a.y - b.y) * (a.z + b.z);
+        normal.y += (a.z - b.z) * (a.x + b.x);
+        normal.z += (a.x - b.x) * (a.y + b.y);
+    }
+    // Dropping the largest axis keeps the most area, flipped for a negative
+    // one so the projection keeps the winding counter-clockwise
+    let (x, y) = if normal.x.abs() >= normal.y.abs() && normal.x.abs() >= normal.z.abs() {
+        if normal.x >= 0. {
+            (1, 2)
+        } else {
+            (2, 1)
+        }
+    } else if normal.y.abs() >= normal.z.abs() {
+        if normal.y >= 0. {
+            (2, 0)
+        } else {
+            (0, 2)
+        }
+    } else if normal.z >= 0. {
+        (0, 1)
+    } else {
+        (1, 0)
+    };
+    let points: Vec<Vec2> = positions.iter().map(|p| Vec2::new(p[x], p[y])).collect();
+
+    let cross = |o: Vec2, a: Vec2, b: Vec2| (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x);
+    let is_convex = (0..points.len()).all(|i| {
+        let previous = points[(i + points.len() - 1) % points.len()];
+        let next = points[(i + 1) % points.len()];
+        cross(previous, points[i], next) >= 0.
+    });
+    if is_convex {
+        return fan();
+    }
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let [a, b, c] = [(i + count - 1) % count, i, (i + 1) % count].map(|k| remaining[k]);
+            let [pa, pb, pc] = [a, b, c].map(|k| points[k]);
+            // Reflex corners are not ears, nor corners with another one inside
+            cross(pa, pb, pc) > 0.
+                && remaining.iter().all(|&k| {
+                    k == a
+                        || k == b
+                        || k == c
+                        || cross(pa, pb, points[k]) < 0.
+                        || cross(pb, pc, points[k]) < 0.
+                        || cross(pc, pa, points[k]) < 0.
+                })
+        });
+        let Some(i) = ear else {
+            return fan();
+        };
+
+        triangles.push([
+            remaining[(i + count - 1) % count],
+            remaining[i],
+            remaining[(i + 1) % count],
+        ]);
+        remaining.remove(i);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+// Vertices sharing a key share their generated normal
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum SmoothingKey {
+    Group(u32),
+    // Smoothing off, the normal of the polygon
+    Face(u32),
+}
+
+// Each corner without a normal from the file gets the area weighted average
+// of the faces sharing its vertex and its smoothing key. Vertices are split
+// where keys differ, which gives hard edges at group boundaries.
+fn generate_smoothed_normals(
+    vertices: &mut Vec<Vertex>,
+    indices: &mut [u32],
+    corner_smoothing: &[Option<SmoothingKey>],
+) {
+    // Vertices only used by generated corners are reused for the first key
+    let mut reusable = vec![true; vertices.len()];
+    for (&index, smoothing) in indices.iter().zip(corner_smoothing) {
+        if smoothing.is_none() {
+            reusable[index as usize] = false;
+        }
+    }
+
+    let mut split = HashMap::<(u32, SmoothingKey), u32>::new();
+    let mut generated = vec![];
+    for (triangle, smoothing) in indices
+        .chunks_exact_mut(3)
+        .zip(corner_smoothing.chunks_exact(3))
+    {
+        let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize].position);
+        // Its length is twice the area of the triangle
+        let face_normal = (b - a).cross(&(c - a));
+
+        for (index, smoothing) in triangle.iter_mut().zip(smoothing) {
+            let Some(key) = *smoothing else {
+                continue;
+            };
+            let new_index = *split.entry((*index, key)).or_insert_with(|| {
+                let new_index = if reusable[*index as usize] {
+                    reusable[*index as usize] = false;
+                    *index
+                } else {
+                    vertices.push(vertices[*index as usize]);
+                    vertices.len() as u32 - 1
+                };
+                vertices[new_index as usize].normal = Vec3::default();
+                generated.push(new_index);
+                new_index
+            });
+            vertices[new_index as usize].normal += face_normal;
+            *index = new_index;
+        }
+    }
+
+    for index in generated {
+        let vertex = &mut vertices[index as usize];
+        if vertex.normal != Vec3::default() {
+            vertex.normal = vertex.normal.normalized();
+        }
+    }
+}
+
+// File ids of a `v/vt/vn` entry, 0 for the missing parts. None when an id
+// is past the elements read so far.
+fn parse_entry(
+    entry: &str,
+    (vertices, uvs, normals): (&[Vertex], &[Vec2], &[Vec3]),
+) -> Result<Option<(u32, u32, u32)>> {
+    let mut parts = entry.split('/');
+    let vertex_id = parts.next().context("Invalid index")?.parse::<u32>()?;
+    let uv_id = parts
+        .next()
+        .filter(|e| !e.is_empty())
+        .map_or(Ok(0), |f| f.parse::<u32>())?;
+    let normal_id = parts
+        .next()
+        .filter(|e| !e.is_empty())
+        .map_or(Ok(0), |f| f.parse::<u32>())?;
+
+    if vertex_id < 1
+        || vertex_id as usize > vertices.len()
+        || uv_id as usize > uvs.len()
+        || normal_id as usize > normals.len()
+    {
+        return Ok(None);
+    }
+    Ok(Some((vertex_id, uv_id, normal_id)))
+}
+
+// All the entries of an element, None when one is out of range
+fn parse_entries(
+    content: &str,
+    sources: (&[Vertex], &[Vec2], &[Vec3]),
+) -> Result<Option<Vec<(u32, u32, u32)>>> {
+    content
+        .split_whitespace()
+        .map(|entry| parse_entry(entry, sources))
+        .collect::<Result<Vec<_>>>()
+        .map(|entries| entries.into_iter().collect())
+}
+
+// Index in `unique_vertices` of the entry, added the first time the
+// combination is used
+fn unique_vertex(
+    key: (u32, u32, u32),
+    (vertices, uvs, normals): (&[Vertex], &[Vec2], &[Vec3]),
+    faces: &mut HashMap<(u32, u32, u32), u32>,
+    unique_vertices: &mut Vec<Vertex>,
+) -> u32 {
+    let (vertex_id, uv_id, normal_id) = key;
+    match faces.entry(key) {
+        Entry::Occupied(o) => *o.get(),
+        Entry::Vacant(v) => {
+            let mut vertex = vertices[(vertex_id - 1) as usize];
+            if uv_id > 0 {
+                vertex.uv = uvs[(uv_id - 1) as usize];
+            }
+            if normal_id > 0 {
+                vertex.normal = normals[(normal_id - 1) as usize];
+            }
+            unique_vertices.push(vertex);
+            *v.insert(unique_vertices.len() as u32 - 1)
+        }
+    }
+}
+
+// Problems the parser works around, the element is skipped. Lines are
+// counted from 1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ObjWarning {
+    IndexOutOfRange { line: usize },
+    DegenerateTriangle { line: usize },
+    DuplicateFace { line: usize },
+}
+
+impl fmt::Display for ObjWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjWarning::IndexOutOfRange { line } => {
+                write!(f, "line {}: index out of range, element skipped", line)
+            }
+            ObjWarning::DegenerateTriangle { line } => {
+                write!(f, "line {}: degenerate triangle skipped", line)
+            }
+            ObjWarning::DuplicateFace { line } => {
+                write!(f, "line {}: duplicate triangle skipped", line)
+            }
+        }
+    }
+}
+
+// Lines and points index the same vertices as the triangles
+#[derive(Clone, Debug, Default)]
+pub struct ObjElements {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    // Segments, pairs of indices
+    pub lines: Vec<u32>,
+    pub points: Vec<u32>,
+    pub warnings: Vec<ObjWarning>,
+}
+
+pub fn parse_obj_file(path: &str) -> Result<ObjElements> {
+    parse_obj_file_with_progress(path, &no_progress)
+}
+
+// The parsing progress follows the bytes read
+pub fn parse_obj_file_with_progress(path: &str, on_progress: OnProgress) -> Result<ObjElements> {
+    parse_obj_source(path, on_progress, &CancelToken::default())
+}
+
+// A canceled parse stops at the next line
+fn parse_obj_source(
+    path: &str,
+    on_progress: OnProgress,
+    cancel: &CancelToken,
+) -> Result<ObjElements> {
+    let path = resolve_asset(path)?;
+    let file = File::open(&path)?;
+    let mut progress = ByteProgress::new(on_progress, LoadStage::Parsing, file.metadata()?.len());
+
+    // Lines are parsed in place, the file is never copied
+    #[cfg(feature = "mmap")]
+    if let Some(map) = map_large_file(&path)? {
+        let lines = map.split(|&byte| byte == b'\n').map(|line| {
+            cancel.check()?;
+            progress.advance(line.len() as u64 + 1);
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            Ok(std::str::from_utf8(line)?)
+        });
+        let elements = parse_obj_lines(lines);
+        on_progress(LoadStage::Parsing, 1.);
+        return elements;
+    }
+
+    // The line ending is not counted, the fraction reaches 1 at the end
+    let lines = BufReader::new(file).lines().map(|line| {
+        let line = line?;
+        cancel.check()?;
+        progress.advance(line.len() as u64 + 1);
+        Ok(line)
+    });
+    let elements = parse_obj_lines(lines);
+    on_progress(LoadStage::Parsing, 1.);
+    elements
+}
+
+fn parse_obj_lines<L: AsRef<str>>(
+    obj_lines: impl Iterator<Item = Result<L>>,
+) -> Result<ObjElements> {
     let mut object_name = String::new();
     let mut vertices = Vec::<Vertex>::new();
     let mut uvs = Vec::<Vec2>::new();
     let mut normals = Vec::<Vec3>::new();
     let mut indices = Vec::<u32>::new();
-    let mut indices_group: [u32; 3] = Default::default();
+    let mut lines = Vec::<u32>::new();
+    let mut points = Vec::<u32>::new();
+    let mut warnings = Vec::<ObjWarning>::new();
+    // Positions of the triangles, whatever their uvs and normals
+    let mut triangle_keys = HashSet::<[[u32; 3]; 3]>::new();
+    // Normals are generated from the smoothing groups once they are used
+    let mut smoothing_group = 0;
+    let mut uses_smoothing = false;
+    let mut polygon_count = 0;
+    let mut corner_smoothing = Vec::<Option<SmoothingKey>>::new();
     let mut faces = HashMap::<(u32, u32, u32), u32>::new();
     let mut unique_vertices = Vec::<Vertex>::new();
 
-    let file = File::open(path)?;
-    let buf_reader = BufReader::new(file);
-    for line in buf_reader.lines() {
+    for (line_number, line) in (1..).zip(obj_lines) {
         let line = line?;
+        let line = line.as_ref();
         if line.is_empty() || line.starts_with('#') {
             continue;
         }
 
-        if let Some(content) = get_content_of(&line, "o ")? {
+        if let Some(content) = get_content_of(line, "o ")? {
             ensure!(object_name.is_empty(), "Only one object allowed");
             object_name = String::from(content);
 
             continue;
         }
 
-        if let Some(content) = get_content_of(&line, "v ")? {
+        if let Some(content) = get_content_of(line, "v ")? {
             let mut values = content.splitn(3, ' ').map(str::parse::<f32>);
 
             let mut vert = Vertex::default();
@@ -60,7 +602,7 @@ pub fn read_obj_file<'a>(engine: &Engine, path: &'a str) -> Result<Rc<Mesh>> {
             continue;
         }
 
-        if let Some(content) = get_content_of(&line, "vt ")? {
+        if let Some(content) = get_content_of(line, "vt ")? {
             let mut values = content.splitn(3, ' ').map(str::parse::<f32>);
 
             let mut uv = Vec2::default();
@@ -72,7 +614,7 @@ pub fn read_obj_file<'a>(engine: &Engine, path: &'a str) -> Result<Rc<Mesh>> {
             continue;
         }
 
-        if let Some(content) = get_content_of(&line, "vn ")? {
+        if let Some(content) = get_content_of(line, "vn ")? {
             let mut values = content.splitn(3, ' ').map(str::parse::<f32>);
 
             let mut normal = Vec3::default();
@@ -85,92 +627,139 @@ pub fn read_obj_file<'a>(engine: &Engine, path: &'a str) -> Result<Rc<Mesh>> {
             continue;
         }
 
-        if let Some(content) = get_content_of(&line, "f ")? {
-            let mut indices_group_id = 0;
-            for entry in content.split(' ') {
-                let mut parts = entry.split('/');
-                if let Some(vertex_str) = parts.next() {
-                    let vertex_id = vertex_str.parse::<u32>()?;
-                    let uv_id = parts
-                        .next()
-                        .filter(|e| !e.is_empty())
-                        .map_or(Ok(0), |f| f.parse::<u32>())?;
-                    let normal_id = parts
-                        .next()
-                        .filter(|e| !e.is_empty())
-                        .map_or(Ok(0), |f| f.parse::<u32>())?;
-
-                    if vertex_id < 1
-                        || vertex_id as usize > vertices.len()
-                        || uv_id as usize > uvs.len()
-                        || normal_id as usize > normals.len()
-                    {
-                        bail!("Invalid index");
-                    }
-
-                    let key = (vertex_id, uv_id, normal_id);
-                    let (unique_vertex_index, vertex) = match faces.entry(key) {
-                        Entry::Occupied(o) => (*o.get(), &mut unique_vertices[*o.get() as usize]),
-                        Entry::Vacant(v) => {
-                            let index = unique_vertices.len();
-                            unique_vertices.push(vertices[(vertex_id - 1) as usize]);
-                            v.insert(index as u32);
-                            (index as u32, unique_vertices.last_mut().unwrap())
-                        }
-                    };
-
-                    if uv_id > 0 {
-                        vertex.uv = uvs[(uv_id - 1) as usize];
-                    }
-                    if normal_id > 0 {
-                        vertex.normal = normals[(normal_id - 1) as usize];
-                    }
-
-                    indices_group[indices_group_id] = unique_vertex_index;
-                    if indices_group_id < 2 {
-                        indices_group_id += 1;
-                    } else {
-                        indices.extend_from_slice(&indices_group);
-                        indices_group[1] = indices_group[2];
-                    }
+        if let Some(content) = get_content_of(line, "f ")? {
+            let sources = (&vertices[..], &uvs[..], &normals[..]);
+            let Some(entries) = parse_entries(content, sources)? else {
+                warnings.push(ObjWarning::IndexOutOfRange { line: line_number });
+                continue;
+            };
+            if entries.len() < 3 {
+                bail!("Not enough values for index");
+            }
+
+            let mut polygon = Vec::<u32>::new();
+            let mut polygon_smoothing = Vec::<Option<SmoothingKey>>::new();
+            for key in entries {
+                polygon.push(unique_vertex(
+                    key,
+                    sources,
+                    &mut faces,
+                    &mut unique_vertices,
+                ));
+                // Corners with their own normal keep it
+                polygon_smoothing.push(if key.2 > 0 {
+                    None
+                } else if smoothing_group > 0 {
+                    Some(SmoothingKey::Group(smoothing_group))
                 } else {
-                    bail!("Invalid index");
+                    Some(SmoothingKey::Face(polygon_count))
+                });
+            }
+            polygon_count += 1;
+
+            let positions: Vec<Vec3> = polygon
+                .iter()
+                .map(|&index| unique_vertices[index as usize].position)
+                .collect();
+            for triangle in triangulate_polygon(&positions) {
+                let [a, b, c] = triangle.map(|corner| positions[corner]);
+                if is_degenerate_triangle(a, b, c) {
+                    warnings.push(ObjWarning::DegenerateTriangle { line: line_number });
+                    continue;
+                }
+                // Rotated to start at its smallest corner, the back face of
+                // a double-sided model is not a duplicate
+                let mut key = [a, b, c].map(|p| [p.x, p.y, p.z].map(f32::to_bits));
+                let first = (0..3).min_by_key(|&i| key[i]).unwrap_or(0);
+                key.rotate_left(first);
+                if !triangle_keys.insert(key) {
+                    warnings.push(ObjWarning::DuplicateFace { line: line_number });
+                    continue;
+                }
+
+                for corner in triangle {
+                    indices.push(polygon[corner]);
+                    corner_smoothing.push(polygon_smoothing[corner]);
                 }
             }
 
-            if indices_group_id < 2 {
-                bail!("Not enough values for index");
+            continue;
+        }
+
+        // Polylines are split in segments
+        if let Some(content) = get_content_of(line, "l ")? {
+            let sources = (&vertices[..], &uvs[..], &normals[..]);
+            let Some(entries) = parse_entries(content, sources)? else {
+                warnings.push(ObjWarning::IndexOutOfRange { line: line_number });
+                continue;
+            };
+            ensure!(entries.len() >= 2, "Not enough values for line");
+
+            let polyline: Vec<u32> = entries
+                .into_iter()
+                .map(|key| unique_vertex(key, sources, &mut faces, &mut unique_vertices))
+                .collect();
+            for segment in polyline.windows(2) {
+                lines.extend_from_slice(segment);
             }
 
             continue;
         }
 
-        if let Some(_content) = get_content_of(&line, "mtllib ")? {
+        if let Some(content) = get_content_of(line, "p ")? {
+            let sources = (&vertices[..], &uvs[..], &normals[..]);
+            let Some(entries) = parse_entries(content, sources)? else {
+                warnings.push(ObjWarning::IndexOutOfRange { line: line_number });
+                continue;
+            };
+            for key in entries {
+                points.push(unique_vertex(
+                    key,
+                    sources,
+                    &mut faces,
+                    &mut unique_vertices,
+                ));
+            }
+
             continue;
         }
 
-        if let Some(_content) = get_content_of(&line, "usemtl ")? {
+        if let Some(_content) = get_content_of(line, "mtllib ")? {
             continue;
         }
 
-        if let Some(_content) = get_content_of(&line, "g ")? {
+        if let Some(_content) = get_content_of(line, "usemtl ")? {
             continue;
         }
 
-        if let Some(_content) = get_content_of(&line, "s ")? {
+        if let Some(_content) = get_content_of(line, "g ")? {
+            continue;
+        }
+
+        if let Some(content) = get_content_of(line, "s ")? {
+            smoothing_group = match content.trim() {
+                "off" => 0,
+                group => group.parse::<u32>()?,
+            };
+            uses_smoothing = true;
+
             continue;
         }
 
         bail!(format!("Unknown key in line `{}`", line))
     }
 
-    Mesh::builder(engine.renderer.main_device.clone())
-        .vertices(if unique_vertices.len() > 0 {
-            &unique_vertices
-        } else {
-            &vertices
-        })
-        .indices(&indices)
-        .build()
-        .map(Rc::new)
+    if unique_vertices.len() > 0 {
+        if uses_smoothing {
+            generate_smoothed_normals(&mut unique_vertices, &mut indices, &corner_smoothing);
+        }
+        vertices = unique_vertices;
+    }
+    Ok(ObjElements {
+        vertices,
+        indices,
+        lines,
+        points,
+        warnings,
+    })
 }
@@ -0,0 +1,103 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LoadStage {
+    // Reading and parsing the file
+    Parsing,
+    // Converting the pixels of an image
+    Decoding,
+    // Copying to the GPU
+    Uploading,
+}
+
+// Called with the stage being worked on and how much of it is done, from 0
+// to 1. Loads of several files call it from the job threads.
+pub type OnProgress<'a> = &'a (dyn Fn(LoadStage, f32) + Sync);
+
+pub fn no_progress(_stage: LoadStage, _fraction: f32) {}
+
+impl fmt::Display for LoadStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadStage::Parsing => write!(f, "parsing"),
+            LoadStage::Decoding => write!(f, "decoding"),
+            LoadStage::Uploading => write!(f, "uploading"),
+        }
+    }
+}
+
+// Counts the bytes read from a file, reports every percent
+pub struct ByteProgress<'a> {
+    on_progress: OnProgress<'a>,
+    stage: LoadStage,
+    total: u64,
+    read: u64,
+    percent: u64,
+}
+
+impl<'a> ByteProgress<'a> {
+    pub fn new(on_progress: OnProgress<'a>, stage: LoadStage, total: u64) -> Self {
+        on_progress(stage, 0.);
+        Self {
+            on_progress,
+            stage,
+            total: total.max(1),
+            read: 0,
+            percent: 0,
+        }
+    }
+
+    pub fn advance(&mut self, bytes: u64) {
+        self.read += bytes;
+        let percent = (self.read * 100 / self.total).min(100);
+        if percent > self.percent {
+            self.percent = percent;
+            (self.on_progress)(self.stage, percent as f32 / 100.);
+        }
+    }
+}
+
+// A bar redrawn on one terminal line, a stage ends its line when done
+pub struct ProgressBar {
+    name: String,
+    // Stage and percent last drawn, redrawn only when they change
+    last: AtomicU32,
+}
+
+impl ProgressBar {
+    const WIDTH: usize = 30;
+
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            last: AtomicU32::new(u32::MAX),
+        }
+    }
+
+    pub fn update(&self, stage: LoadStage, fraction: f32) {
+        let percent = (fraction.clamp(0., 1.) * 100.) as u32;
+        let key = (stage as u32) << 8 | percent;
+        if self.last.swap(key, Ordering::Relaxed) == key {
+            return;
+        }
+
+        let filled = Self::WIDTH * percent as usize / 100;
+        let mut stderr = io::stderr().lock();
+        let _ = write!(
+            stderr,
+            "\r{}: {:<9} [{}{}] {:>3}%",
+            self.name,
+            stage,
+            "#".repeat(filled),
+            " ".repeat(Self::WIDTH - filled),
+            percent
+        );
+        if percent == 100 {
+            let _ = writeln!(stderr);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::{HashMap, HashSet};
+
+use math::Vec3;
+
+use crate::{
+    engine::mesh::{BoundingBox, Vertex},
+    ensure,
+    utils::Result,
+};
+
+use super::obj::ObjElements;
+
+// Finest grid tried, in cells along the longest side of the model
+const MAX_GRID_SIZE: u32 = 4096;
+
+// Vertex clustering: the bounding box is cut in cubic cells and the vertices
+// of a cell merged into one at their average position, triangles left with
+// less than three corners are dropped. The finest grid keeping at most
+// `ratio` of the triangles is found by bisection. Fast and never fails, but
+// the attributes are not weighed: a merged vertex keeps the color and uv of
+// its first vertex.
+pub fn simplify_mesh(elements: &mut ObjElements, ratio: f32) -> Result<()> {
+    ensure!(
+        ratio > 0. && ratio <= 1.,
+        "The simplification ratio must be above 0 and at most 1"
+    );
+    let triangle_count = elements.indices.len() / 3;
+    let target = ((triangle_count as f32 * ratio) as usize).max(1);
+    if target >= triangle_count {
+        return Ok(());
+    }
+
+    let bounds = BoundingBox::from(elements.vertices.as_slice());
+    let size = bounds.max - bounds.min;
+    let longest = size.x.max(size.y).max(size.z).max(f32::EPSILON);
+
+    // Finer grids keep more triangles
+    let (mut low, mut high) = (1, MAX_GRID_SIZE);
+    while low < high {
+        let grid_size = (low + high).div_ceil(2);
+        let (clusters, _) =
+            cluster_vertices(&elements.vertices, bounds.min, longest / grid_size as f32);
+        if count_triangles(&elements.indices, &clusters) <= target {
+            low = grid_size;
+        } else {
+            high = grid_size - 1;
+        }
+    }
+
+    let (clusters, cluster_count) =
+        cluster_vertices(&elements.vertices, bounds.min, longest / low as f32);
+    merge_clusters(elements, &clusters, cluster_count);
+    Ok(())
+}
+
+// Cluster of each vertex, and the number of clusters
+fn cluster_vertices(vertices: &[Vertex], origin: Vec3, cell_size: f32) -> (Vec<u32>, usize) {
+    let mut cells = HashMap::<(u32, u32, u32), u32>::new();
+    let clusters = vertices
+        .iter()
+        .map(|vertex| {
+            let cell = (vertex.position - origin) / cell_size;
+            let key = (cell.x as u32, cell.y as u32, cell.z as u32);
+            let next = cells.len() as u32;
+            *cells.entry(key).or_insert(next)
+        })
+        .collect();
+    (clusters, cells.len())
+}
+
+// Corners in their clusters, None once two of them merged
+fn cluster_triangle(triangle: &[u32], clusters: &[u32]) -> Option<[u32; 3]> {
+    let [a, b, c] = [0, 1, 2].map(|i| clusters[triangle[i] as usize]);
+    (a != b && b != c && a != c).then_some([a, b, c])
+}
+
+// Distinct triangles left, whatever their winding
+fn count_triangles(indices: &[u32], clusters: &[u32]) -> usize {
+    let mut seen = HashSet::new();
+    indices
+        .chunks_exact(3)
+        .filter_map(|triangle| cluster_triangle(triangle, clusters))
+        .filter(|corners| {
+            let mut sorted = *corners;
+            sorted.sort_unstable();
+            seen.insert(sorted)
+        })
+        .count()
+}
+
+fn merge_clusters(elements: &mut ObjElements, clusters: &[u32], cluster_count: usize) {
+    let mut merged: Vec<Option<Vertex>> = vec![None; cluster_count];
+    let mut sums = vec![(Vec3::default(), Vec3::default(), 0.); cluster_count];
+    for (vertex, &cluster) in elements.vertices.iter().zip(clusters) {
+        merged[cluster as usize].get_or_insert(*vertex);
+        let (position, normal, count) = &mut sums[cluster as usize];
+        *position += vertex.position;
+        *normal += vertex.normal;
+        *count += 1.;
+    }
+    elements.vertices = merged
+        .into_iter()
+        .zip(sums)
+        .map(|(vertex, (position, normal, count))| {
+            let mut vertex = vertex.unwrap_or_default();
+            vertex.position = position / count;
+            // Opposite normals cancel out on thin parts, the first one stays
+            if normal.length() > f32::EPSILON {
+                vertex.normal = normal.normalized();
+            }
+            vertex
+        })
+        .collect();
+
+    let mut seen = HashSet::new();
+    elements.indices = elements
+        .indices
+        .chunks_exact(3)
+        .filter_map(|triangle| cluster_triangle(triangle, clusters))
+        .filter(|corners| {
+            let mut sorted = *corners;
+            sorted.sort_unstable();
+            seen.insert(sorted)
+        })
+        .flatten()
+        .collect();
+
+    elements.lines = elements
+        .lines
+        .chunks_exact(2)
+        .map(|segment| [clusters[segment[0] as usize], clusters[segment[1] as usize]])
+        .filter(|[a, b]| a != b)
+        .flatten()
+        .collect();
+
+    let mut seen = HashSet::new();
+    elements.points = elements
+        .points
+        .iter()
+        .map(|&point| clusters[point as usize])
+        .filter(|&point| seen.insert(point))
+        .collect();
+}
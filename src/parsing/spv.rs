@@ -1,31 +1,78 @@
-use std::{
-    fs::{self, File},
-    io::Read,
-    mem::size_of,
-};
+use std::{fs::File, io::Read, mem::size_of};
 
 use ash::vk;
 
 use crate::{engine::Engine, ensure, renderer::Shader, utils::Result};
 
+use super::resolve_asset;
+
+// Compiled by build.rs, empty when glslc was not available
+const EMBEDDED_SHADERS: [(&str, &[u8]); 8] = [
+    (
+        "shaders/default.vert.spv",
+        include_bytes!(concat!(env!("OUT_DIR"), "/default.vert.spv")),
+    ),
+    (
+        "shaders/default.frag.spv",
+        include_bytes!(concat!(env!("OUT_DIR"), "/default.frag.spv")),
+    ),
+    (
+        "shaders/debug.vert.spv",
+        include_bytes!(concat!(env!("OUT_DIR"), "/debug.vert.spv")),
+    ),
+    (
+        "shaders/debug.frag.spv",
+        include_bytes!(concat!(env!("OUT_DIR"), "/debug.frag.spv")),
+    ),
+    (
+        "shaders/overlay.vert.spv",
+        include_bytes!(concat!(env!("OUT_DIR"), "/overlay.vert.spv")),
+    ),
+    (
+        "shaders/skinned.vert.spv",
+        include_bytes!(concat!(env!("OUT_DIR"), "/skinned.vert.spv")),
+    ),
+    (
+        "shaders/background.vert.spv",
+        include_bytes!(concat!(env!("OUT_DIR"), "/background.vert.spv")),
+    ),
+    (
+        "shaders/background.frag.spv",
+        include_bytes!(concat!(env!("OUT_DIR"), "/background.frag.spv")),
+    ),
+];
+
 fn read_spv_file(path: &str) -> Result<Vec<u32>> {
-    let mut f = File::open(&path)?;
-    let metadata = fs::metadata(&path)?;
+    let resolved = match resolve_asset(path) {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            let name = path.trim_start_matches("./");
+            let Some((_, code)) = EMBEDDED_SHADERS
+                .iter()
+                .find(|(embedded, code)| *embedded == name && !code.is_empty())
+            else {
+                return Err(e);
+            };
+            eprintln!("{}: not found, using the embedded shader", path);
+            return spv_words(code);
+        }
+    };
+
+    let mut bytes = vec![];
+    File::open(resolved)?.read_to_end(&mut bytes)?;
+    spv_words(&bytes)
+}
 
+fn spv_words(bytes: &[u8]) -> Result<Vec<u32>> {
     ensure!(
-        metadata.len() % 4 == 0,
+        bytes.len().is_multiple_of(4),
         "Spir-V shader code len should be a multpile of 4"
     );
 
-    let len = metadata.len() as usize;
-
-    unsafe {
-        let mut buffer = vec![0u32; len / size_of::<u32>()];
-        let bytes: &mut [u8] = std::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, len);
-        f.read_exact(bytes)?;
-
-        Ok(buffer)
-    }
+    Ok(bytes
+        .chunks_exact(size_of::<u32>())
+        .map(|word| u32::from_ne_bytes([word[0], word[1], word[2], word[3]]))
+        .collect())
 }
 
 pub fn read_vert_spv_file(engine: &Engine, path: &str) -> Result<Shader> {
@@ -1,6 +1,6 @@
 use std::{
     fs::File,
-    io::{Read, Seek},
+    io::{BufWriter, Read, Seek, Write},
     mem::size_of,
 };
 
@@ -8,6 +8,13 @@ use ash::vk;
 
 use crate::{engine::Engine, ensure, renderer::ScopTexture2D, utils::Result};
 
+#[cfg(feature = "mmap")]
+use super::assets::map_large_file;
+use super::{
+    progress::{no_progress, ByteProgress, LoadStage, OnProgress},
+    resolve_asset,
+};
+
 #[derive(Default, Debug, Copy, Clone)]
 #[repr(packed)]
 struct TgaColorMapSpecifications {
@@ -37,24 +44,72 @@ struct TgaHeader {
     image: TgaImageSpecifications,
 }
 
-pub fn read_tga_r8g8b8a8_srgb_file(engine: &Engine, path: &'static str) -> Result<ScopTexture2D> {
+pub struct TgaImage {
+    pub width: u32,
+    pub height: u32,
+    // B8G8R8A8 pixels, rows from bottom to top like OBJ texture coordinates
+    pub bytes: Vec<u8>,
+}
+
+// Big enough to report the progress of large files
+const READ_CHUNK_SIZE: usize = 1024 * 1024;
+
+pub fn parse_tga_file(path: &str, on_progress: OnProgress) -> Result<TgaImage> {
+    let path = resolve_asset(path)?;
+    let tga_header_size = size_of::<TgaHeader>();
+
+    // The pixels are converted from the mapped file, without reading it first
+    #[cfg(feature = "mmap")]
+    if let Some(map) = map_large_file(&path)? {
+        return parse_tga_bytes(&map, on_progress);
+    }
+
     let mut file = File::open(path)?;
     let mut tga_header = TgaHeader::default();
-    let tga_header_size = size_of::<TgaHeader>();
 
     unsafe {
         let p: *mut TgaHeader = &mut tga_header;
         let p: *mut u8 = p as *mut u8;
         file.read_exact(std::slice::from_raw_parts_mut(p, tga_header_size))?;
     }
+    check_tga_header(&tga_header)?;
+
+    file.seek_relative(tga_header.id_length as i64)?; // Skip id field
+
+    let mut content = vec![0u8; tga_content_size(&tga_header)];
+    let mut progress = ByteProgress::new(on_progress, LoadStage::Parsing, content.len() as u64);
+    for chunk in content.chunks_mut(READ_CHUNK_SIZE) {
+        file.read_exact(chunk)?;
+        progress.advance(chunk.len() as u64);
+    }
+
+    Ok(decode_tga_pixels(&tga_header, &content, on_progress))
+}
 
+// Whole file already in memory
+#[cfg(any(feature = "mmap", test))]
+fn parse_tga_bytes(bytes: &[u8], on_progress: OnProgress) -> Result<TgaImage> {
+    let tga_header_size = size_of::<TgaHeader>();
+    ensure!(bytes.len() >= tga_header_size, "Invalid TGA file");
+    let tga_header = unsafe { (bytes.as_ptr() as *const TgaHeader).read_unaligned() };
+    check_tga_header(&tga_header)?;
+
+    let start = tga_header_size + tga_header.id_length as usize;
+    let content = bytes
+        .get(start..start + tga_content_size(&tga_header))
+        .ok_or("The TGA file is truncated")?;
+    on_progress(LoadStage::Parsing, 1.);
+    Ok(decode_tga_pixels(&tga_header, content, on_progress))
+}
+
+fn check_tga_header(tga_header: &TgaHeader) -> Result<()> {
     ensure!(
         tga_header.color_map_type == 0,
         "The TGA file must not contain a color map"
     );
     ensure!(
-        tga_header.image_type == 2,
-        "The TGA file must not contain an uncompressed true-color image"
+        tga_header.image_type == 2 || tga_header.image_type == 3,
+        "The TGA file must contain an uncompressed true-color or grayscale image"
     );
     ensure!(
         tga_header.color_map.first_entry_index
@@ -65,51 +120,232 @@ pub fn read_tga_r8g8b8a8_srgb_file(engine: &Engine, path: &'static str) -> Resul
     );
     ensure!(
         tga_header.image.x_origin | tga_header.image.y_origin == 0,
-        "The TGA file image origin should be at [0,0] from the bottom left"
+        "The TGA file image origin should be at [0,0]"
     );
     ensure!(
         tga_header.image.width > 0 && tga_header.image.height > 0,
         "Invalid TGA file"
     );
+
+    let bits_per_pixel = tga_header.image.bits_per_pixel;
+    let alpha_bits = tga_header.image.image_descriptor & 0b00001111;
+    if tga_header.image_type == 2 {
+        ensure!(
+            (bits_per_pixel == 32 && alpha_bits == 8) || (bits_per_pixel == 24 && alpha_bits == 0),
+            "True-color TGA files must contain 24 bits per pixel, or 32 with 8 bits for alpha"
+        );
+    } else {
+        ensure!(
+            bits_per_pixel == 8 && alpha_bits == 0,
+            "Grayscale TGA files must contain 8 bits per pixel"
+        );
+    }
     ensure!(
-        tga_header.image.bits_per_pixel == 32,
-        "The TGA file must contain 32 bits per pixel"
-    );
-    ensure!(
-        tga_header.image.image_descriptor == 0b00001000,
-        "The TGA file must contain 8 bits for alpha, and be in bottom-to-top, left-to-right order"
+        tga_header.image.image_descriptor & 0b00010000 == 0,
+        "The TGA file must be in left-to-right order"
     );
+    Ok(())
+}
 
-    file.seek_relative(tga_header.id_length as i64)?; // Skip id field
+fn tga_content_size(tga_header: &TgaHeader) -> usize {
+    let width = tga_header.image.width as usize;
+    let height = tga_header.image.height as usize;
+    width * height * (tga_header.image.bits_per_pixel / 8) as usize
+}
 
+// B8G8R8A8 rows from bottom to top, whatever the order of the file
+fn decode_tga_pixels(tga_header: &TgaHeader, content: &[u8], on_progress: OnProgress) -> TgaImage {
+    let top_to_bottom = tga_header.image.image_descriptor & 0b00100000 != 0;
+    let width = tga_header.image.width as usize;
+    let height = tga_header.image.height as usize;
     let bytes_per_pixel = (tga_header.image.bits_per_pixel / 8) as usize;
 
-    let content_len =
-        tga_header.image.width as usize * tga_header.image.height as usize * bytes_per_pixel;
+    let mut progress = ByteProgress::new(on_progress, LoadStage::Decoding, height as u64);
+    let mut bytes = Vec::with_capacity(width * height * 4);
+    for row in 0..height {
+        progress.advance(1);
+        let row = if top_to_bottom { height - 1 - row } else { row };
+        let row = &content[row * width * bytes_per_pixel..(row + 1) * width * bytes_per_pixel];
+
+        for pixel in row.chunks_exact(bytes_per_pixel) {
+            match *pixel {
+                [gray] => bytes.extend_from_slice(&[gray, gray, gray, 255]),
+                [b, g, r] => bytes.extend_from_slice(&[b, g, r, 255]),
+                _ => bytes.extend_from_slice(pixel),
+            }
+        }
+    }
 
-    let mut bytes = vec![0u8; content_len];
-    file.read_exact(&mut bytes)?;
+    TgaImage {
+        width: width as u32,
+        height: height as u32,
+        bytes,
+    }
+}
 
-    // if tga_header.image.height > 1 {
-    //     let half_len = content_len / 2;
-    //     let (left, right) = bytes.split_at_mut(half_len);
-    //     let width = tga_header.image.width as usize * bytes_per_pixel;
+pub fn read_tga_r8g8b8a8_srgb_file(engine: &Engine, path: &str) -> Result<ScopTexture2D> {
+    read_tga_r8g8b8a8_srgb_file_with(engine, path, &no_progress)
+}
 
-    //     for i in (0..half_len).step_by(width as usize) {
-    //         if i <= half_len - width {
-    //             // Greater when height is odd
-    //             left[i..i + width].swap_with_slice(&mut right[half_len - i - width..half_len - i])
-    //         }
-    //     }
-    // }
+pub fn read_tga_r8g8b8a8_srgb_file_with(
+    engine: &Engine,
+    path: &str,
+    on_progress: OnProgress,
+) -> Result<ScopTexture2D> {
+    // Any model can still be viewed without the textures folder
+    if let Err(e) = resolve_asset(path) {
+        eprintln!("{}\nUsing a generated UV grid instead", e);
+        return ScopTexture2D::uv_grid(
+            engine.renderer.main_device.clone(),
+            &mut engine.renderer.staging_ring.borrow_mut(),
+            256,
+        );
+    }
 
-    ScopTexture2D::new(
+    let image = parse_tga_file(path, on_progress)?;
+
+    on_progress(LoadStage::Uploading, 0.);
+    let texture = ScopTexture2D::new(
         engine.renderer.main_device.clone(),
-        &engine.renderer.graphic_command_pools[0],
-        &bytes,
-        tga_header.image.width as u32,
-        tga_header.image.height as u32,
+        &mut engine.renderer.staging_ring.borrow_mut(),
+        &image.bytes,
+        image.width,
+        image.height,
         vk::Format::B8G8R8A8_SRGB,
-        tga_header.image.bits_per_pixel as u16,
-    )
+        32,
+    )?;
+    on_progress(LoadStage::Uploading, 1.);
+    Ok(texture)
+}
+
+// Uncompressed 32 bits image from RGBA8 pixels, rows from top to bottom
+pub fn write_tga_file(path: &str, width: u32, height: u32, rgba: &[u8]) -> Result<()> {
+    ensure!(
+        width > 0 && height > 0 && width <= u16::MAX as u32 && height <= u16::MAX as u32,
+        "TGA images are between 1 and 65535 pixels wide and high"
+    );
+    ensure!(
+        rgba.len() == width as usize * height as usize * 4,
+        "The pixel count does not match the image size"
+    );
+
+    let tga_header = TgaHeader {
+        image_type: 2,
+        image: TgaImageSpecifications {
+            width: width as u16,
+            height: height as u16,
+            bits_per_pixel: 32,
+            // 8 bits of alpha, top to bottom
+            image_descriptor: 0b00101000,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut file = BufWriter::new(File::create(path)?);
+    unsafe {
+        let p: *const TgaHeader = &tga_header;
+        file.write_all(std::slice::from_raw_parts(
+            p as *const u8,
+            size_of::<TgaHeader>(),
+        ))?;
+    }
+    for pixel in rgba.chunks_exact(4) {
+        file.write_all(&[pixel[2], pixel[1], pixel[0], pixel[3]])?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Uncompressed image without id field nor color map, TGA is little endian
+    fn fixture(image_type: u8, size: [u16; 2], bits: u8, descriptor: u8, pixels: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0, 0, image_type, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        bytes.extend_from_slice(&size[0].to_le_bytes());
+        bytes.extend_from_slice(&size[1].to_le_bytes());
+        bytes.extend_from_slice(&[bits, descriptor]);
+        bytes.extend_from_slice(pixels);
+        bytes
+    }
+
+    fn parse(bytes: &[u8]) -> Result<TgaImage> {
+        parse_tga_bytes(bytes, &no_progress)
+    }
+
+    #[test]
+    fn bottom_to_top_rows_are_kept() {
+        let bytes = fixture(2, [1, 2], 32, 0b00001000, &[1, 2, 3, 4, 5, 6, 7, 8]);
+        let image = parse(&bytes).unwrap();
+        assert_eq!((image.width, image.height), (1, 2));
+        assert_eq!(image.bytes, [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn top_to_bottom_rows_are_flipped() {
+        let bytes = fixture(
+            2,
+            [2, 2],
+            32,
+            0b00101000,
+            &[
+                1, 1, 1, 1, 2, 2, 2, 2, // Top row
+                3, 3, 3, 3, 4, 4, 4, 4, // Bottom row
+            ],
+        );
+        let image = parse(&bytes).unwrap();
+        assert_eq!(
+            image.bytes,
+            [3, 3, 3, 3, 4, 4, 4, 4, 1, 1, 1, 1, 2, 2, 2, 2]
+        );
+    }
+
+    #[test]
+    fn grayscale_is_expanded_to_opaque_bgra() {
+        let bytes = fixture(3, [2, 1], 8, 0, &[10, 200]);
+        let image = parse(&bytes).unwrap();
+        assert_eq!(image.bytes, [10, 10, 10, 255, 200, 200, 200, 255]);
+    }
+
+    #[test]
+    fn true_color_without_alpha_is_opaque() {
+        let bytes = fixture(2, [2, 1], 24, 0, &[1, 2, 3, 4, 5, 6]);
+        let image = parse(&bytes).unwrap();
+        assert_eq!(image.bytes, [1, 2, 3, 255, 4, 5, 6, 255]);
+    }
+
+    #[test]
+    fn id_field_is_skipped() {
+        let mut bytes = fixture(3, [1, 1], 8, 0, &[0xAB, 42]);
+        bytes[0] = 1;
+        let image = parse(&bytes).unwrap();
+        assert_eq!(image.bytes, [42, 42, 42, 255]);
+    }
+
+    #[test]
+    fn truncated_files_are_rejected() {
+        let bytes = fixture(2, [2, 2], 32, 0b00001000, &[0; 15]);
+        assert!(parse(&bytes).is_err());
+        assert!(parse(&bytes[..10]).is_err());
+        assert!(parse(&[]).is_err());
+    }
+
+    #[test]
+    fn unsupported_headers_are_rejected() {
+        let pixels = [0; 16];
+        // Color mapped, run-length encoded
+        assert!(parse(&fixture(1, [2, 2], 8, 0, &pixels)).is_err());
+        assert!(parse(&fixture(10, [2, 2], 32, 0b00001000, &pixels)).is_err());
+        // 16 bits, and 32 bits without alpha
+        assert!(parse(&fixture(2, [2, 2], 16, 0b00000001, &pixels)).is_err());
+        assert!(parse(&fixture(2, [2, 2], 32, 0, &pixels)).is_err());
+        // 8 bits true-color, 24 bits grayscale
+        assert!(parse(&fixture(2, [2, 2], 8, 0, &pixels)).is_err());
+        assert!(parse(&fixture(3, [2, 2], 24, 0, &pixels)).is_err());
+        // Right to left, empty
+        assert!(parse(&fixture(2, [2, 2], 32, 0b00011000, &pixels)).is_err());
+        assert!(parse(&fixture(2, [0, 2], 32, 0b00001000, &pixels)).is_err());
+    }
 }
@@ -0,0 +1,130 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+use crate::utils::Result;
+
+use super::resolve_asset;
+
+struct WatchedFile {
+    // As given to `watch`, and reported back
+    path: String,
+    resolved: PathBuf,
+    modified: Option<SystemTime>,
+    // Seen on the last poll, reported once it stays the same for a whole
+    // interval so files still being written are not read
+    pending: Option<SystemTime>,
+}
+
+// Polls the modification time of asset files from a thread. Changed files
+// are collected on the main thread with `changed`.
+pub struct FileWatcher {
+    files: Arc<Mutex<Vec<WatchedFile>>>,
+    changes: Receiver<String>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WatchedFile {
+    fn poll(&mut self) -> bool {
+        let modified = fs::metadata(&self.resolved)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if modified.is_none() || modified == self.modified {
+            self.pending = None;
+            return false;
+        }
+
+        if self.pending != modified {
+            self.pending = modified;
+            return false;
+        }
+        self.modified = modified;
+        self.pending = None;
+        true
+    }
+}
+
+impl FileWatcher {
+    pub fn new(interval: Duration) -> Self {
+        let files: Arc<Mutex<Vec<WatchedFile>>> = Arc::default();
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, changes) = mpsc::channel();
+
+        let thread = {
+            let files = files.clone();
+            let stop = stop.clone();
+            thread::spawn(move || Self::run(&files, &stop, &sender, interval))
+        };
+
+        Self {
+            files,
+            changes,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    fn run(
+        files: &Mutex<Vec<WatchedFile>>,
+        stop: &AtomicBool,
+        sender: &Sender<String>,
+        interval: Duration,
+    ) {
+        while !stop.load(Ordering::Relaxed) {
+            thread::sleep(interval);
+
+            let Ok(mut files) = files.lock() else {
+                return;
+            };
+            for file in files.iter_mut() {
+                if file.poll() && sender.send(file.path.clone()).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    pub fn watch(&self, path: &str) -> Result<()> {
+        let resolved = resolve_asset(path)?;
+        let modified = fs::metadata(&resolved)?.modified().ok();
+
+        let mut files = self
+            .files
+            .lock()
+            .map_err(|_| "File watcher thread panicked")?;
+        if files.iter().all(|file| file.path != path) {
+            files.push(WatchedFile {
+                path: path.to_owned(),
+                resolved,
+                modified,
+                pending: None,
+            });
+        }
+        Ok(())
+    }
+
+    // Paths given to `watch` whose file changed since the last call
+    pub fn changed(&self) -> Vec<String> {
+        let mut changed: Vec<String> = self.changes.try_iter().collect();
+        changed.dedup();
+        changed
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
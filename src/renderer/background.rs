@@ -0,0 +1,147 @@
+use ash::vk;
+use math::{Vec3, Vec4};
+
+use crate::utils::Result;
+
+use super::{Renderer, RendererPipeline, Shader};
+
+// What is drawn where the scene leaves the screen empty. Colors are sRGB.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Background {
+    Solid(Vec3),
+    // Top and bottom of the screen
+    Gradient(Vec3, Vec3),
+    // Follows the camera, sky above the horizon and ground below
+    Environment {
+        sky: Vec3,
+        horizon: Vec3,
+        ground: Vec3,
+    },
+    // Screen space squares
+    Checker(Vec3, Vec3),
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct BackgroundPushConstantData {
+    color_a: Vec4,
+    color_b: Vec4,
+    color_c: Vec4,
+    mode: u32,
+    depth: f32,
+}
+
+// Fullscreen triangle drawn after the scene, at the far plane
+pub struct BackgroundPass {
+    pipeline: RendererPipeline,
+}
+
+impl Background {
+    // Clear color of the render pass, the main color for the drawn modes.
+    // An sRGB swapchain encodes on write, it is given linear colors.
+    pub fn clear_value(&self, srgb_swapchain: bool) -> [f32; 4] {
+        let color = match *self {
+            Background::Solid(color) => color,
+            Background::Gradient(top, bottom) => (top + bottom) / 2.,
+            Background::Environment { horizon, .. } => horizon,
+            Background::Checker(a, b) => (a + b) / 2.,
+        };
+        let to_linear = |c: f32| {
+            if !srgb_swapchain {
+                c
+            } else if c > 0.04045 {
+                ((c + 0.055) / 1.055).powf(2.4)
+            } else {
+                c / 12.92
+            }
+        };
+        [
+            to_linear(color.x),
+            to_linear(color.y),
+            to_linear(color.z),
+            1.,
+        ]
+    }
+
+    fn push_constants(&self, depth: f32) -> BackgroundPushConstantData {
+        let (mode, [a, b, c]) = match *self {
+            Background::Solid(color) => (0, [color, color, color]),
+            Background::Gradient(top, bottom) => (1, [top, bottom, bottom]),
+            Background::Environment {
+                sky,
+                horizon,
+                ground,
+            } => (2, [sky, horizon, ground]),
+            Background::Checker(a, b) => (3, [a, b, b]),
+        };
+        BackgroundPushConstantData {
+            color_a: Vec4::from(a),
+            color_b: Vec4::from(b),
+            color_c: Vec4::from(c),
+            mode,
+            depth,
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Solid(Vec3::one())
+    }
+}
+
+impl BackgroundPass {
+    pub fn new(renderer: &Renderer, vert_shader: &Shader, frag_shader: &Shader) -> Result<Self> {
+        // Passes where the depth is still the cleared one
+        let depth_compare = if renderer.config.reverse_z {
+            vk::CompareOp::GREATER_OR_EQUAL
+        } else {
+            vk::CompareOp::LESS_OR_EQUAL
+        };
+        let pipeline = RendererPipeline::builder(renderer.main_device.clone())
+            .render_pass(&renderer.defaut_render_pass)
+            .subpass(renderer.defaut_render_pass.main_subpass())
+            .vert_shader(vert_shader)
+            .frag_shader(frag_shader)
+            .set_layouts(&[renderer.global_descriptor_set_layout.set_layout])
+            .cull_mode(vk::CullModeFlags::NONE)
+            .depth_write(false)
+            .depth_compare(depth_compare)
+            .build();
+
+        vert_shader.cleanup(&renderer.main_device);
+        frag_shader.cleanup(&renderer.main_device);
+
+        Ok(Self {
+            pipeline: pipeline?,
+        })
+    }
+
+    pub fn draw(
+        &self,
+        background: &Background,
+        clear_depth: f32,
+        global_descriptor_set: vk::DescriptorSet,
+        command_buffer: vk::CommandBuffer,
+    ) {
+        let push = background.push_constants(clear_depth);
+        self.pipeline
+            .bind(command_buffer, vk::PipelineBindPoint::GRAPHICS);
+        self.pipeline.bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            &[global_descriptor_set],
+        );
+        unsafe {
+            let device = &self.pipeline.device.logical_device;
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                0,
+                crate::utils::any_as_u8_slice(&push),
+            );
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+    }
+}
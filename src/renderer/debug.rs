@@ -1,7 +1,10 @@
 use ash::extensions::ext;
 use ash::vk;
 
-use std::ffi;
+use std::{
+    ffi,
+    sync::atomic::{AtomicU32, Ordering},
+};
 
 use crate::utils::Result;
 
@@ -15,11 +18,30 @@ unsafe extern "system" fn vulkan_debug_utils_callback(
     let severity = format!("{:?}", message_severity).to_lowercase();
     let ty = format!("{:?}", message_type).to_lowercase();
 
-    println!("[Debug][{}][{}] {:?}", severity, ty, message);
+    // Errors go to the unbuffered stderr, they are still printed if the
+    // driver crashes right after
+    if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+        VALIDATION_ERRORS.fetch_add(1, Ordering::Relaxed);
+        eprintln!("[Debug][{}][{}] {:?}", severity, ty, message);
+    } else {
+        println!("[Debug][{}][{}] {:?}", severity, ty, message);
+    }
 
     vk::FALSE
 }
 
+// Error messages received since the start, from any instance
+static VALIDATION_ERRORS: AtomicU32 = AtomicU32::new(0);
+
+// Checks of the validation layer off by default, far slower: GPU-assisted
+// validation instruments the shaders to catch out of bounds accesses, and
+// synchronization validation reports hazards between commands
+const GPU_VALIDATION_FEATURES: [vk::ValidationFeatureEnableEXT; 3] = [
+    vk::ValidationFeatureEnableEXT::GPU_ASSISTED,
+    vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT,
+    vk::ValidationFeatureEnableEXT::SYNCHRONIZATION_VALIDATION,
+];
+
 pub struct RendererDebug {
     debug_utils: ext::DebugUtils,
     debug_messenger: vk::DebugUtilsMessengerEXT,
@@ -29,7 +51,20 @@ impl RendererDebug {
     pub fn new(entry: &ash::Entry, instance: &ash::Instance) -> Result<Self> {
         let debug_utils = ext::DebugUtils::new(entry, instance);
 
-        let messenger_info = vk::DebugUtilsMessengerCreateInfoEXT {
+        let messenger_info = Self::messenger_info();
+        let debug_messenger =
+            unsafe { debug_utils.create_debug_utils_messenger(&messenger_info, None)? };
+
+        Ok(Self {
+            debug_utils,
+            debug_messenger,
+        })
+    }
+
+    // Also given to the instance creation, to report what happens before the
+    // messenger exists and after it is destroyed
+    pub fn messenger_info() -> vk::DebugUtilsMessengerCreateInfoEXT {
+        vk::DebugUtilsMessengerCreateInfoEXT {
             message_severity: vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
                 | vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
                 | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
@@ -39,15 +74,16 @@ impl RendererDebug {
                 | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
             pfn_user_callback: Some(vulkan_debug_utils_callback),
             ..Default::default()
-        };
+        }
+    }
 
-        let debug_messenger =
-            unsafe { debug_utils.create_debug_utils_messenger(&messenger_info, None)? };
+    // Chained to the instance info, needs VK_EXT_validation_features
+    pub fn gpu_validation_features() -> &'static [vk::ValidationFeatureEnableEXT] {
+        &GPU_VALIDATION_FEATURES
+    }
 
-        Ok(Self {
-            debug_utils,
-            debug_messenger,
-        })
+    pub fn error_count() -> u32 {
+        VALIDATION_ERRORS.load(Ordering::Relaxed)
     }
 
     pub fn cleanup(&mut self) {
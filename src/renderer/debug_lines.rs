@@ -0,0 +1,107 @@
+use std::mem::size_of;
+
+use ash::vk;
+use math::Vec3;
+
+use crate::{engine::mesh::Vertex, utils::Result};
+
+use super::{Renderer, RendererPipeline, ScopBuffer, Shader};
+
+pub struct DebugLines {
+    pipeline: RendererPipeline,
+    vertex_buffers: Vec<Option<ScopBuffer>>,
+    vertices: Vec<Vertex>,
+}
+
+impl DebugLines {
+    pub fn new(renderer: &Renderer, vert_shader: &Shader, frag_shader: &Shader) -> Result<Self> {
+        let pipeline = RendererPipeline::builder(renderer.main_device.clone())
+            .render_pass(&renderer.defaut_render_pass)
+            .subpass(renderer.defaut_render_pass.main_subpass())
+            .vert_shader(vert_shader)
+            .frag_shader(frag_shader)
+            .set_layouts(&[renderer.global_descriptor_set_layout.set_layout])
+            .cull_mode(vk::CullModeFlags::NONE)
+            .topology(vk::PrimitiveTopology::LINE_LIST)
+            .depth_test(false)
+            .build();
+
+        vert_shader.cleanup(&renderer.main_device);
+        frag_shader.cleanup(&renderer.main_device);
+
+        Ok(Self {
+            pipeline: pipeline?,
+            vertex_buffers: (0..renderer.swapchain.image_count).map(|_| None).collect(),
+            vertices: vec![],
+        })
+    }
+
+    // Lines only live for the frame they were added in
+    pub fn line(&mut self, from: Vec3, to: Vec3, color: Vec3) {
+        for position in [from, to] {
+            self.vertices.push(Vertex {
+                position,
+                color,
+                ..Default::default()
+            });
+        }
+    }
+
+    pub fn draw(
+        &mut self,
+        global_descriptor_set: vk::DescriptorSet,
+        command_buffer: vk::CommandBuffer,
+        image_index: u32,
+    ) -> Result<()> {
+        if self.vertices.is_empty() {
+            return Ok(());
+        }
+
+        let slot = &mut self.vertex_buffers[image_index as usize];
+        if slot
+            .as_ref()
+            .is_none_or(|b| b.instance_count < self.vertices.len())
+        {
+            if let Some(buffer) = slot {
+                buffer.cleanup();
+            }
+            // Stays mapped for its whole life
+            let mut buffer = ScopBuffer::new(
+                self.pipeline.device.clone(),
+                self.vertices.len().next_power_of_two(),
+                size_of::<Vertex>() as vk::DeviceSize,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                vk::MemoryPropertyFlags::HOST_VISIBLE,
+                1,
+            )?;
+            buffer.map(vk::WHOLE_SIZE, 0)?;
+            *slot = Some(buffer);
+        }
+        let vertex_buffer = slot.as_mut().unwrap();
+
+        vertex_buffer.write_to_buffer(&self.vertices, 0);
+        vertex_buffer.flush(vk::WHOLE_SIZE, 0)?;
+
+        self.pipeline
+            .bind(command_buffer, vk::PipelineBindPoint::GRAPHICS);
+        self.pipeline.bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            &[global_descriptor_set],
+        );
+        unsafe {
+            let device = &self.pipeline.device.logical_device;
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[vertex_buffer.buffer], &[0]);
+            device.cmd_draw(command_buffer, self.vertices.len() as u32, 1, 0, 0);
+        }
+
+        self.vertices.clear();
+        Ok(())
+    }
+
+    pub fn cleanup(&mut self) {
+        for buffer in self.vertex_buffers.iter_mut().flatten() {
+            buffer.cleanup();
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use ash::vk;
+use math::{Vec2, Vec3};
+
+use crate::{engine::camera::Camera, utils::Result};
+
+use super::{Renderer, ScopBuffer};
+
+impl Renderer {
+    // World position of the surface drawn under the cursor in the last frame,
+    // None over the background. The depth texel is copied to the host and
+    // unprojected with the camera, no triangle is tested. Waits for the GPU,
+    // meant for clicks and not for every frame.
+    pub fn read_depth_at(&self, camera: &Camera, cursor: Vec2) -> Result<Option<Vec3>> {
+        let viewport_size = self.window.viewport_size();
+        let Some(depth) = self.read_depth_texel(cursor, viewport_size)? else {
+            return Ok(None);
+        };
+        // Cleared pixels were not drawn
+        if depth == self.defaut_render_pass.clear_depth {
+            return Ok(None);
+        }
+        Ok(camera.screen_to_world(cursor, depth, viewport_size))
+    }
+
+    // Normalized depth of the pixel, None outside of the swapchain
+    fn read_depth_texel(&self, cursor: Vec2, viewport_size: Vec2) -> Result<Option<f32>> {
+        // Scaled renders have a depth image per swapchain image
+        let (extent, depth_image) = match &self.scaled {
+            Some(scaled) => (scaled.extent, scaled.depth_image(self.last_image_index)),
+            None => (self.swapchain.extent, &self.swapchain.depth_image),
+        };
+        // The swapchain may lag behind a resize of the window
+        let x = (cursor.x * extent.width as f32 / viewport_size.x).floor();
+        let y = (cursor.y * extent.height as f32 / viewport_size.y).floor();
+        if x < 0. || y < 0. || x >= extent.width as f32 || y >= extent.height as f32 {
+            return Ok(None);
+        }
+
+        // Also written by the frames in flight
+        self.wait_gpu();
+
+        let mut buffer = ScopBuffer::new(
+            self.main_device.clone(),
+            1,
+            4,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            1,
+        )?;
+
+        let device = &self.main_device.logical_device;
+        let subresource_range = vk::ImageSubresourceRange::builder()
+            .aspect_mask(vk::ImageAspectFlags::DEPTH)
+            .level_count(1)
+            .layer_count(1)
+            .build();
+        // The render passes leave the depth attachment in this layout
+        let barrier = |old_layout, new_layout, src_access_mask, dst_access_mask| {
+            vk::ImageMemoryBarrier::builder()
+                .old_layout(old_layout)
+                .new_layout(new_layout)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .src_access_mask(src_access_mask)
+                .dst_access_mask(dst_access_mask)
+                .image(depth_image.image)
+                .subresource_range(subresource_range)
+                .build()
+        };
+        let region = vk::BufferImageCopy::builder()
+            .image_subresource(
+                *vk::ImageSubresourceLayers::builder()
+                    .aspect_mask(vk::ImageAspectFlags::DEPTH)
+                    .layer_count(1),
+            )
+            .image_offset(vk::Offset3D {
+                x: x as i32,
+                y: y as i32,
+                z: 0,
+            })
+            .image_extent(vk::Extent3D {
+                width: 1,
+                height: 1,
+                depth: 1,
+            })
+            .build();
+
+        let command_pool = &self.graphic_command_pools[0];
+        let copied = command_pool
+            .begin_single_time_commands()
+            .and_then(|command_buffer| {
+                unsafe {
+                    device.cmd_pipeline_barrier(
+                        command_buffer,
+                        vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                        vk::PipelineStageFlags::TRANSFER,
+                        vk::DependencyFlags::empty(),
+                        &[],
+                        &[],
+                        &[barrier(
+                            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                            vk::AccessFlags::TRANSFER_READ,
+                        )],
+                    );
+                    device.cmd_copy_image_to_buffer(
+                        command_buffer,
+                        depth_image.image,
+                        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                        buffer.buffer,
+                        &[region],
+                    );
+                    device.cmd_pipeline_barrier(
+                        command_buffer,
+                        vk::PipelineStageFlags::TRANSFER,
+                        vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                        vk::DependencyFlags::empty(),
+                        &[],
+                        &[],
+                        &[barrier(
+                            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                            vk::AccessFlags::TRANSFER_READ,
+                            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                        )],
+                    );
+                }
+                command_pool.end_single_time_commands(command_buffer)
+            })
+            .and_then(|()| buffer.map(vk::WHOLE_SIZE, 0));
+        if let Err(e) = copied {
+            buffer.cleanup();
+            return Err(e);
+        }
+
+        let bytes: [u8; 4] = buffer.read_bytes(4, 0).try_into().unwrap();
+        buffer.cleanup();
+        let depth = match depth_image.format {
+            // 24 bits normalized, the high byte is undefined
+            vk::Format::D24_UNORM_S8_UINT => {
+                (u32::from_le_bytes(bytes) & 0xff_ffff) as f32 / 0xff_ffff as f32
+            }
+            _ => f32::from_le_bytes(bytes),
+        };
+        Ok(Some(depth))
+    }
+}
@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use ash::{
     prelude::VkResult,
@@ -11,6 +11,8 @@ use ash::{
 
 use crate::{bail, utils::{Context, Result}};
 
+use super::{LeakTracker, TrackedKind};
+
 pub type QueueFamilyId = usize;
 
 pub struct QueueFamily {
@@ -25,7 +27,12 @@ pub struct RendererDevice {
     pub physical_device: PhysicalDevice,
     pub logical_device: ash::Device,
     pub memory_properties: vk::PhysicalDeviceMemoryProperties,
+    pub properties: vk::PhysicalDeviceProperties,
+    // Only the optional features that were enabled
+    pub features: vk::PhysicalDeviceFeatures,
     queue_families: Vec<QueueFamily>,
+    allocations: RefCell<HashMap<vk::DeviceMemory, vk::DeviceSize>>,
+    pub leaks: LeakTracker,
 }
 
 impl RendererDevice {
@@ -54,10 +61,22 @@ impl RendererDevice {
         let props =
             unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
 
+        // Every graphics family, and the first compute only one for work that
+        // can overlap with the graphics queue
+        let async_compute = props.iter().position(|qf| {
+            qf.queue_count > 0
+                && qf.queue_flags.contains(QueueFlags::COMPUTE)
+                && !qf.queue_flags.contains(QueueFlags::GRAPHICS)
+        });
+
         props
             .into_iter()
             .enumerate()
-            .filter(|(_, qf)| qf.queue_count > 0 && qf.queue_flags.contains(QueueFlags::GRAPHICS))
+            .filter(|(index, qf)| {
+                qf.queue_count > 0
+                    && (qf.queue_flags.contains(QueueFlags::GRAPHICS)
+                        || async_compute == Some(*index))
+            })
             .enumerate()
             .map(|(i, (index, qf))| QueueFamily {
                 id: i,
@@ -72,6 +91,7 @@ impl RendererDevice {
         instance: &Rc<Instance>,
         physical_device: PhysicalDevice,
         queue_families: &Vec<QueueFamily>,
+        features: &vk::PhysicalDeviceFeatures,
     ) -> VkResult<ash::Device> {
         let queue_priorities = [1.0f32];
 
@@ -89,7 +109,8 @@ impl RendererDevice {
 
         let create_info = DeviceCreateInfo::builder()
             .queue_create_infos(&queue_create_infos)
-            .enabled_extension_names(&extensions);
+            .enabled_extension_names(&extensions)
+            .enabled_features(features);
 
         unsafe { instance.create_device(physical_device, &create_info, None) }
     }
@@ -103,8 +124,14 @@ impl RendererDevice {
             bail!("No suitable queue family found");
         }
 
+        let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
+        let features = vk::PhysicalDeviceFeatures {
+            pipeline_statistics_query: supported_features.pipeline_statistics_query,
+            ..Default::default()
+        };
+
         let logical_device =
-            Self::create_logical_device(instance, physical_device, &queue_families)?;
+            Self::create_logical_device(instance, physical_device, &queue_families, &features)?;
 
         queue_families.iter_mut().for_each(|family| {
             family
@@ -114,13 +141,18 @@ impl RendererDevice {
 
         let device_memory_properties =
             unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
 
         Ok(Self {
             instance: instance.clone(),
             physical_device,
             logical_device,
             memory_properties: device_memory_properties,
+            properties,
+            features,
             queue_families,
+            allocations: RefCell::new(HashMap::new()),
+            leaks: LeakTracker::default(),
         })
     }
 
@@ -164,6 +196,19 @@ impl RendererDevice {
         bail!("Cannot find satisfying format")
     }
 
+    // Floating point formats first, reversed depth relies on their precision
+    pub fn find_depth_format(&self) -> Result<vk::Format> {
+        self.find_supported_format(
+            vec![
+                vk::Format::D32_SFLOAT,
+                vk::Format::D32_SFLOAT_S8_UINT,
+                vk::Format::D24_UNORM_S8_UINT,
+            ],
+            vk::ImageTiling::OPTIMAL,
+            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+        )
+    }
+
     pub fn get_queue_family(&self, id: QueueFamilyId) -> &QueueFamily {
         &self.queue_families[id]
     }
@@ -172,6 +217,15 @@ impl RendererDevice {
         self.queue_families.iter().find(|f| f.flags.contains(flags))
     }
 
+    // A compute family without graphics, its queues run next to the graphics
+    // one on GPUs with async compute. None when compute shares the graphics
+    // queues.
+    pub fn async_compute_queue_family(&self) -> Option<&QueueFamily> {
+        self.queue_families.iter().find(|f| {
+            f.flags.contains(QueueFlags::COMPUTE) && !f.flags.contains(QueueFlags::GRAPHICS)
+        })
+    }
+
     pub fn begin_command_buffer(&self, command_buffer: vk::CommandBuffer) -> Result<()> {
         let begin_info = vk::CommandBufferBeginInfo::builder();
 
@@ -189,6 +243,45 @@ impl RendererDevice {
         Ok(())
     }
 
+    pub fn allocate_memory(
+        &self,
+        allocate_info: &vk::MemoryAllocateInfo,
+    ) -> VkResult<vk::DeviceMemory> {
+        let memory = unsafe { self.logical_device.allocate_memory(allocate_info, None) }?;
+        self.allocations
+            .borrow_mut()
+            .insert(memory, allocate_info.allocation_size);
+        Ok(memory)
+    }
+
+    pub fn free_memory(&self, memory: vk::DeviceMemory) {
+        self.allocations.borrow_mut().remove(&memory);
+        unsafe { self.logical_device.free_memory(memory, None) };
+    }
+
+    // Tracked until their owner calls `release_descriptor_sets`, the pool
+    // frees them
+    pub fn allocate_descriptor_sets(
+        &self,
+        allocate_info: &vk::DescriptorSetAllocateInfo,
+    ) -> VkResult<Vec<vk::DescriptorSet>> {
+        let sets = unsafe { self.logical_device.allocate_descriptor_sets(allocate_info) }?;
+        for set in &sets {
+            self.leaks.track(TrackedKind::DescriptorSet, *set);
+        }
+        Ok(sets)
+    }
+
+    pub fn release_descriptor_sets(&self, sets: &[vk::DescriptorSet]) {
+        for set in sets {
+            self.leaks.untrack(TrackedKind::DescriptorSet, *set);
+        }
+    }
+
+    pub fn allocated_memory(&self) -> vk::DeviceSize {
+        self.allocations.borrow().values().sum()
+    }
+
     pub fn cleanup(&self) {
         unsafe { self.logical_device.destroy_device(None) };
     }
@@ -0,0 +1,73 @@
+use math::Vec3;
+
+// Debug view coloring each object flat with a color of its own, derived from
+// its id so it stays the same from one run to the next
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DistinctColors {
+    // Hues a golden angle apart at a few lightness levels, in OkLCh so equal
+    // steps look equally different
+    Hcl,
+    // The 8 colors of Okabe and Ito, told apart with the common color vision
+    // deficiencies. Repeats after 8 objects.
+    ColorBlindSafe,
+}
+
+const OKABE_ITO: [[u8; 3]; 8] = [
+    [0xe6, 0x9f, 0x00],
+    [0x56, 0xb4, 0xe9],
+    [0x00, 0x9e, 0x73],
+    [0xf0, 0xe4, 0x42],
+    [0x00, 0x72, 0xb2],
+    [0xd5, 0x5e, 0x00],
+    [0xcc, 0x79, 0xa7],
+    // Black in the original palette, lighting could not shade it
+    [0x99, 0x99, 0x99],
+];
+
+impl DistinctColors {
+    // sRGB color of the object `id`
+    pub fn color(self, id: u32) -> Vec3 {
+        match self {
+            DistinctColors::Hcl => {
+                let hue = (id as f32 * 0.618_034).fract() * std::f32::consts::TAU;
+                let lightness = [0.75, 0.6, 0.85][(id % 3) as usize];
+                oklch_to_srgb(lightness, 0.12, hue)
+            }
+            DistinctColors::ColorBlindSafe => {
+                let [r, g, b] = OKABE_ITO[id as usize % OKABE_ITO.len()];
+                Vec3::new(r as f32, g as f32, b as f32) / 255.
+            }
+        }
+    }
+
+    // As read by `unpackUnorm4x8` in the shaders, red in the low byte
+    pub fn packed(self, id: u32) -> u32 {
+        let color = self.color(id);
+        let byte = |c: f32| (c.clamp(0., 1.) * 255.).round() as u32;
+        byte(color.x) | byte(color.y) << 8 | byte(color.z) << 16 | 255 << 24
+    }
+}
+
+// Out of gamut colors are clamped
+fn oklch_to_srgb(lightness: f32, chroma: f32, hue: f32) -> Vec3 {
+    let a = chroma * hue.cos();
+    let b = chroma * hue.sin();
+
+    let l = (lightness + 0.396_337_78 * a + 0.215_803_76 * b).powi(3);
+    let m = (lightness - 0.105_561_346 * a - 0.063_854_17 * b).powi(3);
+    let s = (lightness - 0.089_484_18 * a - 1.291_485_5 * b).powi(3);
+
+    let encode = |c: f32| {
+        let c = c.clamp(0., 1.);
+        if c <= 0.003_130_8 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1. / 2.4) - 0.055
+        }
+    };
+    Vec3::new(
+        encode(4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s),
+        encode(-1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s),
+        encode(-0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s),
+    )
+}
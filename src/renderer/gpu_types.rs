@@ -0,0 +1,29 @@
+use math::{Mat3, Mat4};
+
+// Matrices as laid out by GLSL in uniform (std140) and push constant blocks.
+// Structs shared with shaders use these instead of the math types, so the
+// layout is stated by the type and not by padding fields.
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct GpuMat4([[f32; 4]; 4]);
+
+// Each column is padded to a vec4
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct GpuMat3([[f32; 4]; 3]);
+
+impl From<Mat4> for GpuMat4 {
+    fn from(mat: Mat4) -> Self {
+        Self([mat[0], mat[1], mat[2], mat[3]])
+    }
+}
+
+impl From<Mat3> for GpuMat3 {
+    fn from(mat: Mat3) -> Self {
+        Self([0, 1, 2].map(|column| {
+            let [x, y, z] = mat[column];
+            [x, y, z, 0.]
+        }))
+    }
+}
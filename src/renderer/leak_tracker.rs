@@ -0,0 +1,90 @@
+use std::{cell::RefCell, collections::HashMap, fmt};
+
+#[cfg(feature = "leak-backtraces")]
+use std::backtrace::Backtrace;
+
+use ash::vk::Handle;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TrackedKind {
+    Buffer,
+    Image,
+    Pipeline,
+    DescriptorSet,
+}
+
+impl fmt::Display for TrackedKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            TrackedKind::Buffer => "buffer",
+            TrackedKind::Image => "image",
+            TrackedKind::Pipeline => "pipeline",
+            TrackedKind::DescriptorSet => "descriptor set",
+        })
+    }
+}
+
+// Live Vulkan objects of a device, in debug builds. Whatever is still live
+// when the renderer is dropped was never cleaned up, like a texture without
+// its `cleanup`. With the `leak-backtraces` feature, the report also tells
+// where each of them was created.
+#[derive(Default)]
+pub struct LeakTracker {
+    live: RefCell<HashMap<(TrackedKind, u64), Creation>>,
+}
+
+#[cfg(feature = "leak-backtraces")]
+type Creation = Backtrace;
+#[cfg(not(feature = "leak-backtraces"))]
+type Creation = ();
+
+impl LeakTracker {
+    pub fn track<H: Handle>(&self, kind: TrackedKind, handle: H) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        #[cfg(feature = "leak-backtraces")]
+        let creation = Backtrace::force_capture();
+        #[cfg(not(feature = "leak-backtraces"))]
+        let creation = ();
+        self.live
+            .borrow_mut()
+            .insert((kind, handle.as_raw()), creation);
+    }
+
+    // Releasing twice or an untracked handle is ignored, some owners clean up
+    // on errors and again when dropped
+    pub fn untrack<H: Handle>(&self, kind: TrackedKind, handle: H) {
+        self.live.borrow_mut().remove(&(kind, handle.as_raw()));
+    }
+
+    // Prints what is still live, returns how many there are
+    pub fn report(&self) -> usize {
+        let live = self.live.borrow();
+        if live.is_empty() {
+            return 0;
+        }
+
+        let mut counts = HashMap::<TrackedKind, usize>::new();
+        for (kind, _) in live.keys() {
+            *counts.entry(*kind).or_default() += 1;
+        }
+        let mut counts = counts.into_iter().collect::<Vec<_>>();
+        counts.sort_by_key(|(kind, _)| *kind as u8);
+        let summary = counts
+            .iter()
+            .map(|(kind, count)| format!("{} {}", count, kind))
+            .collect::<Vec<_>>()
+            .join(", ");
+        eprintln!("Leaked on shutdown: {}", summary);
+
+        #[cfg(feature = "leak-backtraces")]
+        for ((kind, handle), creation) in live.iter() {
+            eprintln!("{} {:#x} created at:\n{}", kind, handle, creation);
+        }
+        #[cfg(not(feature = "leak-backtraces"))]
+        eprintln!("Build with the leak-backtraces feature to see where they were created");
+
+        live.len()
+    }
+}
@@ -1,15 +1,44 @@
-use std::rc::Rc;
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
 
 use ash::vk::{self};
 
-use crate::{renderer::{Renderer, RendererPipeline, ScopDescriptorSetLayout, Shader}, utils::Result};
+use crate::{
+    renderer::{
+        Renderer, RendererDevice, RendererPipeline, ScopDescriptorSetLayout, ScopRenderPass, Shader,
+    },
+    utils::Result,
+};
 
-use super::ScopDescriptorWriter;
+use super::{
+    pipeline::RenderState,
+    pipeline_cache::{PipelineKey, ScopPipelineCache},
+    MaterialParam, MaterialParams, ScopBuffer, ScopDescriptorWriter, ScopTexture2D,
+};
 
+// Shaders and set layouts shared by every pipeline variant of the material,
+// variants are created on demand by `pipeline`
 pub struct Material {
-    pub pipeline: RendererPipeline,
+    pub device: Rc<RendererDevice>,
     pub material_sets_layouts: Vec<ScopDescriptorSetLayout>,
+    pub double_sided: bool,
+    pub params: MaterialParams,
+    // State of the color pass, other passes derive from it
+    pub render_state: RenderState,
+    // The parameter block gets its own set, after the given layouts
+    params_set_index: Option<usize>,
+    vert_shader: Cell<Shader>,
+    frag_shader: Cell<Shader>,
+    // Replaces `vert_shader` for objects with a skin
+    skinned_vert_shader: Cell<Option<Shader>>,
+    // The global set layout followed by the material ones
+    vk_set_layouts: Vec<vk::DescriptorSetLayout>,
+    // The same with the skin set layout at the end
+    vk_skinned_set_layouts: Vec<vk::DescriptorSetLayout>,
     vk_material_sets_layouts: Vec<vk::DescriptorSetLayout>,
+    pipelines: ScopPipelineCache,
 }
 
 pub type MaterialRef = Rc<Material>;
@@ -17,17 +46,50 @@ pub type MaterialRef = Rc<Material>;
 pub struct MaterialInstance {
     pub material: MaterialRef,
     pub material_sets: Vec<vk::DescriptorSet>,
+    params: Option<RefCell<InstanceParams>>,
+}
+
+// One instance of `buffer` per swapchain image. Changes are kept in `values`
+// and copied to an instance when its image is drawn, once the GPU is done
+// with the previous use of that instance.
+struct InstanceParams {
+    buffer: ScopBuffer,
+    values: Vec<u8>,
+    stale: Vec<bool>,
 }
 
 pub type MaterialInstanceRef = Rc<MaterialInstance>;
 
 impl Material {
+    // Takes ownership of the shaders, they are destroyed with the material
     pub fn new(
         renderer: &Renderer,
-        material_sets_layouts: Vec<ScopDescriptorSetLayout>,
+        mut material_sets_layouts: Vec<ScopDescriptorSetLayout>,
+        params: MaterialParams,
         vert_shader: &Shader,
         frag_shader: &Shader,
+        double_sided: bool,
     ) -> Result<MaterialRef> {
+        let params_set_index = if params.is_empty() {
+            None
+        } else {
+            let params_set_layout = ScopDescriptorSetLayout::builder(&renderer.main_device)
+                .add_buffer_binding(
+                    0,
+                    vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                )
+                .build();
+            match params_set_layout {
+                Ok(set_layout) => material_sets_layouts.push(set_layout),
+                Err(e) => {
+                    vert_shader.cleanup(&renderer.main_device);
+                    frag_shader.cleanup(&renderer.main_device);
+                    return Err(e);
+                }
+            }
+            Some(material_sets_layouts.len() - 1)
+        };
+
         let vk_material_sets_layouts = material_sets_layouts
             .iter()
             .map(|e| e.set_layout)
@@ -35,32 +97,122 @@ impl Material {
 
         let mut vk_set_layouts = vec![renderer.global_descriptor_set_layout.set_layout];
         vk_set_layouts.extend_from_slice(&vk_material_sets_layouts);
+        let mut vk_skinned_set_layouts = vk_set_layouts.clone();
+        vk_skinned_set_layouts.push(renderer.skin_set_layout.set_layout);
 
-        let pipeline = RendererPipeline::builder(renderer.main_device.clone())
-            .render_pass(&renderer.defaut_render_pass)
-            .vert_shader(vert_shader)
-            .frag_shader(frag_shader)
-            .set_layouts(&vk_set_layouts)
-            .extent(renderer.swapchain.extent)
-            .build();
-
-        vert_shader.cleanup(&renderer.main_device);
-        frag_shader.cleanup(&renderer.main_device);
+        let cull_mode = if double_sided {
+            vk::CullModeFlags::NONE
+        } else {
+            vk::CullModeFlags::BACK
+        };
 
-        let pipeline = pipeline?;
-
-        Ok(MaterialRef::new(Self {
-            pipeline,
+        let material = Self {
+            device: renderer.main_device.clone(),
             material_sets_layouts,
+            double_sided,
+            params,
+            render_state: RenderState {
+                cull_mode,
+                front_face: renderer.front_face(),
+                depth_compare: renderer.depth_compare_op(),
+                ..Default::default()
+            },
+            params_set_index,
+            vert_shader: Cell::new(*vert_shader),
+            frag_shader: Cell::new(*frag_shader),
+            skinned_vert_shader: Cell::new(None),
+            vk_set_layouts,
+            vk_skinned_set_layouts,
             vk_material_sets_layouts,
-        }))
+            pipelines: ScopPipelineCache::default(),
+        };
+
+        // Creation errors show up here rather than in the middle of a frame
+        renderer.warm_pipelines(&material)?;
+
+        Ok(MaterialRef::new(material))
+    }
+
+    // Depth only states are built without the fragment shader
+    pub fn pipeline(
+        &self,
+        render_pass: &ScopRenderPass,
+        subpass: u32,
+        render_state: RenderState,
+    ) -> Result<Rc<RendererPipeline>> {
+        let key = PipelineKey {
+            render_pass: render_pass.render_pass,
+            subpass,
+            render_state,
+        };
+        let (vert_shader, set_layouts) = if render_state.skinned {
+            let vert_shader = self
+                .skinned_vert_shader
+                .get()
+                .ok_or("Material has no skinned vertex shader")?;
+            (vert_shader, &self.vk_skinned_set_layouts)
+        } else {
+            (self.vert_shader.get(), &self.vk_set_layouts)
+        };
+        let frag_shader = self.frag_shader.get();
+
+        self.pipelines.get_or_create(key, || {
+            let builder = RendererPipeline::builder(self.device.clone())
+                .render_pass(render_pass)
+                .subpass(subpass)
+                .vert_shader(&vert_shader)
+                .set_layouts(set_layouts)
+                .render_state(render_state);
+            if render_state.color_write {
+                builder.frag_shader(&frag_shader).build()
+            } else {
+                builder.build()
+            }
+        })
+    }
+
+    pub fn pipeline_count(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    // Destroys the old shaders and every variant, the next draws rebuild the
+    // pipelines they use. The GPU must be idle, and the recorded command
+    // buffers invalidated with `Renderer::invalidate_recordings`.
+    pub fn replace_shaders(&self, vert_shader: &Shader, frag_shader: &Shader) {
+        self.pipelines.evict();
+        self.vert_shader.replace(*vert_shader).cleanup(&self.device);
+        self.frag_shader.replace(*frag_shader).cleanup(&self.device);
+    }
+
+    // Takes ownership of the shader. Like `replace_shaders`, the GPU must be
+    // idle when a previous one is replaced.
+    pub fn set_skinned_vert_shader(&self, vert_shader: &Shader) {
+        if let Some(previous) = self.skinned_vert_shader.replace(Some(*vert_shader)) {
+            self.pipelines.evict();
+            previous.cleanup(&self.device);
+        }
+    }
+
+    pub fn is_skinnable(&self) -> bool {
+        self.skinned_vert_shader.get().is_some()
+    }
+
+    // Where skinned draws bind the joint matrices
+    pub fn skin_set_index(&self) -> u32 {
+        self.vk_set_layouts.len() as u32
     }
 }
 
 impl Drop for Material {
     fn drop(&mut self) {
+        self.pipelines.evict();
+        self.vert_shader.get().cleanup(&self.device);
+        self.frag_shader.get().cleanup(&self.device);
+        if let Some(skinned_vert_shader) = self.skinned_vert_shader.get() {
+            skinned_vert_shader.cleanup(&self.device);
+        }
         for set_layout in &mut self.material_sets_layouts {
-            set_layout.cleanup(&self.pipeline.device);
+            set_layout.cleanup(&self.device);
         }
     }
 }
@@ -74,35 +226,166 @@ impl MaterialInstance {
             .set_layouts(&material.vk_material_sets_layouts);
 
         for _ in 0..renderer.swapchain.image_count {
-            material_sets.extend(unsafe {
+            material_sets.extend(
                 renderer
                     .main_device
-                    .logical_device
-                    .allocate_descriptor_sets(&allocate_info)?
-            });
+                    .allocate_descriptor_sets(&allocate_info)?,
+            );
         }
 
-        Ok(MaterialInstanceRef::new(Self {
+        let mut instance = Self {
             material,
             material_sets,
-        }))
+            params: None,
+        };
+        if let Some(params_set_index) = instance.material.params_set_index {
+            let params = InstanceParams::new(renderer, &instance.material.params)?;
+            for image_index in 0..renderer.swapchain.image_count {
+                instance
+                    .writer_index(params_set_index, image_index)
+                    .set_buffer_info(0, params.descriptor_info(image_index))
+                    .write()?;
+            }
+            instance.params = Some(RefCell::new(params));
+        }
+
+        Ok(MaterialInstanceRef::new(instance))
     }
 
-    pub fn writer_index(&self, set_layout_index: usize, index: usize) -> ScopDescriptorWriter {
+    // Textures of the default material, the second one shows through with
+    // the "texture_blend" parameter. Like any descriptor write, no frame in
+    // flight may use the instance.
+    pub fn set_textures(&self, primary: &ScopTexture2D, secondary: &ScopTexture2D) -> Result<()> {
+        self.writer(0)
+            .set_texture_all_frames(0, primary)
+            .set_texture_all_frames(1, secondary)
+            .write()
+    }
+
+    // Replaces a single texture and keeps the other bindings. Written in the
+    // set of the first image, then copied to the sets of the others.
+    pub fn set_texture(&self, binding: u32, texture: &ScopTexture2D) -> Result<()> {
+        let sets = self.layout_sets(0);
+        self.writer_index(0, 0)
+            .set_texture_all_frames(binding, texture)
+            .write()?;
+        self.writer(0).copy_from(sets[0], &[binding])
+    }
+
+    // Takes effect from the next frame, nothing to synchronize
+    pub fn set_param(&self, name: &str, value: impl Into<MaterialParam>) -> Result<()> {
+        let mut params = self
+            .params
+            .as_ref()
+            .ok_or_else(|| format!("Material has no parameter named \"{}\"", name))?
+            .borrow_mut();
+
+        self.material
+            .params
+            .write(&mut params.values, name, value.into())?;
+        params.stale.fill(true);
+        Ok(())
+    }
+
+    // Called by the renderer before drawing with this instance
+    pub fn upload_params(&self, image_index: usize) -> Result<()> {
+        match &self.params {
+            Some(params) => params.borrow_mut().upload(image_index),
+            None => Ok(()),
+        }
+    }
+
+    // Sets of every material layout used to draw the given swapchain image
+    pub fn frame_sets(&self, image_index: usize) -> &[vk::DescriptorSet] {
+        let layout_count = self.material.material_sets_layouts.len();
+        &self.material_sets[image_index * layout_count..][..layout_count]
+    }
+
+    // The set of `set_layout_index` for each swapchain image, in image order
+    fn layout_sets(&self, set_layout_index: usize) -> Vec<vk::DescriptorSet> {
+        let layout_count = self.material.material_sets_layouts.len();
+        self.material_sets
+            .iter()
+            .skip(set_layout_index)
+            .step_by(layout_count)
+            .copied()
+            .collect()
+    }
+
+    pub fn writer_index(
+        &self,
+        set_layout_index: usize,
+        image_index: usize,
+    ) -> ScopDescriptorWriter {
         let mut writer = ScopDescriptorWriter::new(
-            &self.material.pipeline.device,
+            &self.material.device,
             &self.material.material_sets_layouts[set_layout_index],
         );
-        writer.descriptors(std::slice::from_ref(self.material_sets.get(index).unwrap()));
+        writer.descriptors(&self.frame_sets(image_index)[set_layout_index..][..1]);
         writer
     }
 
+    // Writes the sets of every swapchain image, see `set_buffer_per_frame` for
+    // bindings that differ between images
     pub fn writer(&self, set_layout_index: usize) -> ScopDescriptorWriter {
         let mut writer = ScopDescriptorWriter::new(
-            &self.material.pipeline.device,
+            &self.material.device,
             &self.material.material_sets_layouts[set_layout_index],
         );
-        writer.descriptors(&self.material_sets);
+        writer.descriptors(&self.layout_sets(set_layout_index));
         writer
     }
 }
+
+impl Drop for MaterialInstance {
+    fn drop(&mut self) {
+        if let Some(params) = &self.params {
+            params.borrow_mut().buffer.cleanup();
+        }
+        self.material
+            .device
+            .release_descriptor_sets(&self.material_sets);
+    }
+}
+
+impl InstanceParams {
+    fn new(renderer: &Renderer, params: &MaterialParams) -> Result<Self> {
+        // Flushed after each upload, so coherent memory is not needed
+        let buffer = ScopBuffer::new_mapped(
+            renderer.main_device.clone(),
+            renderer.swapchain.image_count,
+            params.size() as vk::DeviceSize,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+            renderer
+                .main_device
+                .properties
+                .limits
+                .min_uniform_buffer_offset_alignment
+                .max(1),
+        )?;
+
+        Ok(Self {
+            buffer,
+            values: params.defaults(),
+            stale: vec![true; renderer.swapchain.image_count],
+        })
+    }
+
+    fn descriptor_info(&self, image_index: usize) -> vk::DescriptorBufferInfo {
+        self.buffer.descriptor_info(
+            self.values.len() as vk::DeviceSize,
+            self.buffer.offset_of(image_index),
+        )
+    }
+
+    fn upload(&mut self, image_index: usize) -> Result<()> {
+        if self.stale[image_index] {
+            let offset = self.buffer.offset_of(image_index);
+            self.buffer.write_bytes(&self.values, offset);
+            self.buffer.flush_dirty()?;
+            self.stale[image_index] = false;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+use math::Vec4;
+
+use crate::{bail, utils::Result};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MaterialParam {
+    Float(f32),
+    Vec4(Vec4),
+}
+
+// Uniform parameters declared by a material, laid out like a GLSL std140
+// block in declaration order. Each instance gets its own copy of the block,
+// starting from the declared defaults.
+#[derive(Clone, Debug, Default)]
+pub struct MaterialParams {
+    params: Vec<MaterialParamSlot>,
+    size: usize,
+}
+
+#[derive(Clone, Debug)]
+struct MaterialParamSlot {
+    name: String,
+    offset: usize,
+    default: MaterialParam,
+}
+
+pub struct MaterialParamsBuilder {
+    params: Vec<(String, MaterialParam)>,
+}
+
+impl MaterialParam {
+    fn type_name(&self) -> &'static str {
+        match self {
+            MaterialParam::Float(_) => "float",
+            MaterialParam::Vec4(_) => "vec4",
+        }
+    }
+
+    // std140 size and alignment are equal for both types
+    fn size(&self) -> usize {
+        match self {
+            MaterialParam::Float(_) => 4,
+            MaterialParam::Vec4(_) => 16,
+        }
+    }
+
+    fn write(&self, bytes: &mut [u8]) {
+        let values = match self {
+            MaterialParam::Float(v) => vec![*v],
+            MaterialParam::Vec4(v) => vec![v.x, v.y, v.z, v.w],
+        };
+        for (chunk, value) in bytes.chunks_exact_mut(4).zip(values) {
+            chunk.copy_from_slice(&value.to_ne_bytes());
+        }
+    }
+}
+
+impl From<f32> for MaterialParam {
+    fn from(value: f32) -> Self {
+        MaterialParam::Float(value)
+    }
+}
+
+impl From<Vec4> for MaterialParam {
+    fn from(value: Vec4) -> Self {
+        MaterialParam::Vec4(value)
+    }
+}
+
+impl MaterialParams {
+    pub fn builder() -> MaterialParamsBuilder {
+        MaterialParamsBuilder { params: vec![] }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    // Size of the block in bytes, a multiple of 16 like GLSL rounds it
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // The block filled with the default values
+    pub fn defaults(&self) -> Vec<u8> {
+        let mut bytes = vec![0; self.size];
+        for slot in &self.params {
+            slot.default
+                .write(&mut bytes[slot.offset..slot.offset + slot.default.size()]);
+        }
+        bytes
+    }
+
+    // Writes `value` in `bytes`, a block from `defaults`
+    pub fn write(&self, bytes: &mut [u8], name: &str, value: MaterialParam) -> Result<()> {
+        let slot = self
+            .params
+            .iter()
+            .find(|slot| slot.name == name)
+            .ok_or_else(|| format!("Material has no parameter named \"{}\"", name))?;
+
+        if std::mem::discriminant(&slot.default) != std::mem::discriminant(&value) {
+            bail!(format!(
+                "Material parameter \"{}\" is a {}, not a {}",
+                name,
+                slot.default.type_name(),
+                value.type_name()
+            ));
+        }
+
+        value.write(&mut bytes[slot.offset..slot.offset + value.size()]);
+        Ok(())
+    }
+}
+
+impl MaterialParamsBuilder {
+    pub fn float(mut self, name: &str, default: f32) -> Self {
+        self.params.push((name.to_owned(), default.into()));
+        self
+    }
+
+    pub fn vec4(mut self, name: &str, default: Vec4) -> Self {
+        self.params.push((name.to_owned(), default.into()));
+        self
+    }
+
+    pub fn build(self) -> Result<MaterialParams> {
+        let mut params: Vec<MaterialParamSlot> = Vec::with_capacity(self.params.len());
+        let mut offset: usize = 0;
+
+        for (name, default) in self.params {
+            if params.iter().any(|slot| slot.name == name) {
+                bail!(format!("Material parameter \"{}\" declared twice", name));
+            }
+
+            offset = offset.next_multiple_of(default.size());
+            params.push(MaterialParamSlot {
+                name,
+                offset,
+                default,
+            });
+            offset += default.size();
+        }
+
+        Ok(MaterialParams {
+            params,
+            size: offset.next_multiple_of(16),
+        })
+    }
+}
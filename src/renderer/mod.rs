@@ -1,9 +1,21 @@
 #![allow(dead_code)]
 
+mod background;
 mod debug;
+mod debug_lines;
+mod depth_readback;
 mod device;
+mod distinct_colors;
+mod gpu_types;
+mod leak_tracker;
 mod material;
+mod material_params;
+mod morph;
+mod picture_in_picture;
 mod pipeline;
+mod pipeline_cache;
+mod render_graph;
+mod render_scale;
 mod renderer;
 mod scop_buffer;
 mod scop_command_pool;
@@ -13,16 +25,31 @@ mod scop_descriptor_writer;
 mod scop_framebuffer;
 mod scop_image;
 mod scop_render_pass;
+mod scop_render_target;
+mod scop_staging_ring;
 mod scop_swapchain;
 mod scop_texture2d;
+mod scop_uniform_ring;
 mod shader;
+mod skin;
 mod window;
 
+pub use background::{Background, BackgroundPass};
 pub use debug::RendererDebug;
+pub use debug_lines::DebugLines;
 pub use device::{QueueFamily, QueueFamilyId, RendererDevice};
-pub use material::{Material, MaterialInstance, MaterialInstanceRef};
-pub use pipeline::{RendererPipeline, ScopGpuCameraData, SimplePushConstantData};
-pub use renderer::Renderer;
+pub use distinct_colors::DistinctColors;
+pub use gpu_types::{GpuMat3, GpuMat4};
+pub use leak_tracker::{LeakTracker, TrackedKind};
+pub use material::{Material, MaterialInstance, MaterialInstanceRef, MaterialRef};
+pub use material_params::{MaterialParam, MaterialParams};
+pub use morph::Morph;
+pub use picture_in_picture::{PictureInPicture, PipClear};
+pub use pipeline::{
+    RendererPipeline, ScopGpuCameraData, SimplePushConstantData,
+};
+pub use render_graph::{FrameContext, GraphResource, RenderGraph, RenderGraphPass, ResourceUse};
+pub use renderer::{Renderer, RendererConfig};
 pub use scop_buffer::ScopBuffer;
 pub use scop_command_pool::ScopCommandPool;
 pub use scop_descriptor_layout::ScopDescriptorSetLayout;
@@ -30,8 +57,12 @@ pub use scop_descriptor_pool::ScopDescriptorPool;
 pub use scop_descriptor_writer::ScopDescriptorWriter;
 pub use scop_framebuffer::ScopFramebuffer;
 pub use scop_image::ScopImage;
-pub use scop_render_pass::ScopRenderPass;
+pub use scop_render_pass::{ScopRenderPass, ScopSubpass};
+pub use scop_render_target::ScopRenderTarget;
+pub use scop_staging_ring::ScopStagingRing;
 pub use scop_swapchain::ScopSwapchain;
 pub use scop_texture2d::ScopTexture2D;
+pub use scop_uniform_ring::ScopUniformRing;
 pub use shader::Shader;
+pub use skin::Skin;
 pub use window::RendererWindow;
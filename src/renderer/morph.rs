@@ -0,0 +1,119 @@
+use std::{cell::RefCell, mem::size_of};
+
+use ash::vk;
+
+use crate::{
+    engine::mesh::{Mesh, Vertex},
+    utils::Result,
+};
+
+use super::{Renderer, ScopBuffer};
+
+// Morph target weights of one object. The mesh is blended on the CPU into a
+// host visible vertex buffer, with one region per swapchain image refreshed
+// when its image is drawn after the weights changed.
+pub struct Morph {
+    weights: Vec<f32>,
+    vertices: RefCell<MorphVertices>,
+}
+
+struct MorphVertices {
+    buffer: ScopBuffer,
+    vertex_count: usize,
+    region_size: vk::DeviceSize,
+    stale: Vec<bool>,
+}
+
+impl Morph {
+    // Every weight starts at 0, drawing the base mesh
+    pub fn new(renderer: &Renderer, mesh: &Mesh) -> Result<Self> {
+        let vertex_count = mesh.vertices.len();
+        let region_size = (size_of::<Vertex>() * vertex_count) as vk::DeviceSize;
+        let image_count = renderer.swapchain.image_count;
+
+        let mut buffer = ScopBuffer::new(
+            renderer.main_device.clone(),
+            1,
+            region_size * image_count as vk::DeviceSize,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            1,
+        )?;
+        if let Err(e) = buffer.map(vk::WHOLE_SIZE, 0) {
+            buffer.cleanup();
+            return Err(e);
+        }
+
+        Ok(Self {
+            weights: vec![0.; mesh.morph_targets.len()],
+            vertices: RefCell::new(MorphVertices {
+                buffer,
+                vertex_count,
+                region_size,
+                stale: vec![true; image_count],
+            }),
+        })
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    // Every image gets the new blend the next time it is drawn
+    pub fn set_weight(&mut self, target: usize, weight: f32) {
+        if self.weights[target] != weight {
+            self.weights[target] = weight;
+            self.vertices.get_mut().stale.fill(true);
+        }
+    }
+
+    // Made for `mesh`, a reloaded mesh needs a new morph
+    pub fn fits(&self, mesh: &Mesh) -> bool {
+        self.vertices.borrow().vertex_count == mesh.vertices.len()
+            && self.weights.len() == mesh.morph_targets.len()
+    }
+
+    // Blends `mesh` again for the image if the weights changed since it
+    // was last drawn. Called by the renderer for every enabled object.
+    pub fn upload(&self, mesh: &Mesh, image_index: usize) {
+        let mut vertices = self.vertices.borrow_mut();
+        if !vertices.stale[image_index] {
+            return;
+        }
+
+        let offset = vertices.region_size * image_index as vk::DeviceSize;
+        let blended = mesh.blend_morph_targets(&self.weights);
+        vertices.buffer.write_bytes(
+            unsafe {
+                std::slice::from_raw_parts(
+                    blended.as_ptr() as *const u8,
+                    size_of::<Vertex>() * blended.len(),
+                )
+            },
+            offset,
+        );
+        vertices.stale[image_index] = false;
+    }
+
+    // Binds the blend of the image and the mesh indices. Called by the
+    // renderer instead of `Mesh::bind`.
+    pub fn bind(&self, mesh: &Mesh, command_buffer: vk::CommandBuffer, image_index: usize) {
+        let vertices = self.vertices.borrow();
+        let offset = vertices.region_size * image_index as vk::DeviceSize;
+        unsafe {
+            mesh.device().logical_device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[vertices.buffer.buffer],
+                &[offset],
+            );
+        }
+        mesh.bind_indices(command_buffer);
+    }
+}
+
+impl Drop for Morph {
+    fn drop(&mut self) {
+        self.vertices.get_mut().buffer.cleanup();
+    }
+}
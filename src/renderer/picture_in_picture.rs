@@ -0,0 +1,154 @@
+use std::{mem::size_of, rc::Rc};
+
+use ash::vk;
+use math::{Vec2, Vec3};
+
+use crate::{
+    engine::{camera::Camera, GameObjectId},
+    utils::Result,
+};
+
+use super::{Renderer, RendererDevice, ScopBuffer, ScopDescriptorWriter, ScopGpuCameraData};
+
+// What is left in the rectangle before the view draws
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PipClear {
+    // Color and depth, the scene under the view is hidden. An sRGB color
+    // like the backgrounds.
+    Color(Vec3),
+    // The view is drawn over the scene, which still shows around the objects
+    Depth,
+    // Depth tested against the scene, the objects blend into it
+    Keep,
+}
+
+// Another camera drawing some game objects, with their children, into a
+// rectangle of the screen. Drawn after the scene in the same render pass, see
+// `Renderer::pictures_in_picture`. Like skins, the camera data has one
+// instance per swapchain image, written when the image is recorded.
+pub struct PictureInPicture {
+    pub camera: Camera,
+    pub objects: Vec<GameObjectId>,
+    // Fractions of the screen, from its top left corner
+    pub position: Vec2,
+    pub size: Vec2,
+    pub clear: PipClear,
+    pub enabled: bool,
+    device: Rc<RendererDevice>,
+    camera_buffer: ScopBuffer,
+    sets: Vec<vk::DescriptorSet>,
+}
+
+impl PictureInPicture {
+    pub fn new(renderer: &Renderer, camera: Camera) -> Result<Self> {
+        let block_size = size_of::<ScopGpuCameraData>() as vk::DeviceSize;
+        let image_count = renderer.swapchain.image_count;
+
+        let mut camera_buffer = ScopBuffer::new_mapped(
+            renderer.main_device.clone(),
+            image_count,
+            block_size,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+            renderer
+                .main_device
+                .properties
+                .limits
+                .min_uniform_buffer_offset_alignment
+                .max(1),
+        )?;
+
+        let set_layouts = vec![renderer.global_descriptor_set_layout.set_layout; image_count];
+        let allocate_info = *vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(renderer.global_descriptor_pool.descriptor_pool)
+            .set_layouts(&set_layouts);
+        let sets = match renderer
+            .main_device
+            .allocate_descriptor_sets(&allocate_info)
+        {
+            Ok(sets) => sets,
+            Err(e) => {
+                camera_buffer.cleanup();
+                return Err(e.into());
+            }
+        };
+
+        for (image_index, set) in sets.iter().enumerate() {
+            let written = ScopDescriptorWriter::new(
+                &renderer.main_device,
+                &renderer.global_descriptor_set_layout,
+            )
+            .descriptors(&[*set])
+            .set_buffer_info(
+                0,
+                camera_buffer.descriptor_info(block_size, camera_buffer.offset_of(image_index)),
+            )
+            .write();
+            if let Err(e) = written {
+                camera_buffer.cleanup();
+                renderer.main_device.release_descriptor_sets(&sets);
+                return Err(e);
+            }
+        }
+
+        Ok(Self {
+            camera,
+            objects: vec![],
+            position: Vec2::new(0.65, 0.65),
+            size: Vec2::new(0.3, 0.3),
+            clear: PipClear::Depth,
+            enabled: true,
+            device: renderer.main_device.clone(),
+            camera_buffer,
+            sets,
+        })
+    }
+
+    // The rectangle in pixels of a target of this size, at least one pixel
+    // and inside of the target
+    pub fn pixel_rect(&self, extent: vk::Extent2D) -> vk::Rect2D {
+        let (width, height) = (extent.width as f32, extent.height as f32);
+        let x = (self.position.x.clamp(0., 1.) * width) as u32;
+        let y = (self.position.y.clamp(0., 1.) * height) as u32;
+        vk::Rect2D {
+            offset: vk::Offset2D {
+                x: x.min(extent.width.saturating_sub(1)) as i32,
+                y: y.min(extent.height.saturating_sub(1)) as i32,
+            },
+            extent: vk::Extent2D {
+                width: ((self.size.x * width) as u32)
+                    .clamp(1, extent.width.saturating_sub(x).max(1)),
+                height: ((self.size.y * height) as u32)
+                    .clamp(1, extent.height.saturating_sub(y).max(1)),
+            },
+        }
+    }
+
+    // For the projection of `camera`, the rectangle is rarely shaped like
+    // the window
+    pub fn aspect_ratio(&self, extent: vk::Extent2D) -> f32 {
+        let rect = self.pixel_rect(extent);
+        rect.extent.width as f32 / rect.extent.height as f32
+    }
+
+    // Called by the renderer when the image is recorded
+    pub fn write_camera_data(
+        &mut self,
+        image_index: usize,
+        camera_data: &ScopGpuCameraData,
+    ) -> Result<()> {
+        self.camera_buffer.write_at(image_index, camera_data);
+        self.camera_buffer.flush_dirty()
+    }
+
+    pub fn frame_set(&self, image_index: usize) -> vk::DescriptorSet {
+        self.sets[image_index]
+    }
+}
+
+impl Drop for PictureInPicture {
+    fn drop(&mut self) {
+        self.camera_buffer.cleanup();
+        self.device.release_descriptor_sets(&self.sets);
+    }
+}
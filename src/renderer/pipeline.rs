@@ -2,25 +2,72 @@ use core::slice;
 use std::{ffi, mem, rc::Rc};
 
 use ash::vk::{self, PushConstantRange, ShaderStageFlags};
-use math::{Mat3, Mat4};
+use math::{Vec2, Vec4};
 
-use crate::{engine::mesh::Vertex, ensure, utils::Result};
+use crate::{engine::mesh::VertexLayout, ensure, utils::Result};
 
-use super::{RendererDevice, ScopRenderPass, Shader};
+use super::{GpuMat3, GpuMat4, RendererDevice, ScopRenderPass, Shader, TrackedKind};
 
+#[repr(C)]
+#[derive(Copy, Clone)]
 pub struct SimplePushConstantData {
-    pub model_matrix: Mat4,
-    pub normal_matrix: Mat3,
-    pub dummy0: f32,
-    pub dummy1: f32,
-    pub dummy2: f32,
+    pub model_matrix: GpuMat4,
+    pub normal_matrix: GpuMat3,
     pub flat_texture_interpolation: f32,
+    // RGBA8 flat color of the distinct colors view
+    pub debug_color: u32,
 }
 
+#[repr(C)]
 #[derive(Copy, Clone)]
 pub struct ScopGpuCameraData {
-    pub projection: Mat4,
-    pub view: Mat4,
+    pub projection: GpuMat4,
+    pub view: GpuMat4,
+    // Its translation is the camera position
+    pub inverse_view: GpuMat4,
+    // Fragments with dot(xyz, position) + w < 0 are discarded, zero disables clipping
+    pub clip_plane: Vec4,
+    // In pixels
+    pub viewport_size: Vec2,
+    // Seconds since the renderer was created, and since the previous frame
+    pub time: f32,
+    pub delta_time: f32,
+    pub color_flags: u32,
+    // Scale of the lit color before the display encoding, 2^exposure
+    pub exposure: f32,
+    pub sun_intensity: f32,
+    pub ambient_intensity: f32,
+    // World space direction the sun light travels in, w is unused
+    pub sun_direction: Vec4,
+}
+
+impl ScopGpuCameraData {
+    // The swapchain is not sRGB, shaders encode their output
+    pub const COLOR_ENCODE_SRGB: u32 = 1;
+    // Debug comparison, lighting is applied on gamma encoded colors
+    pub const COLOR_NAIVE_GAMMA: u32 = 2;
+    // Debug view, textured surfaces are colored by their texel density
+    pub const COLOR_TEXEL_DENSITY: u32 = 4;
+    // Debug view, objects are colored by the `debug_color` push constant
+    pub const COLOR_DISTINCT: u32 = 8;
+    // Debug view, front faces are blue and back faces red
+    pub const COLOR_WINDING: u32 = 16;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RenderState {
+    pub cull_mode: vk::CullModeFlags,
+    pub front_face: vk::FrontFace,
+    pub topology: vk::PrimitiveTopology,
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub depth_compare: vk::CompareOp,
+    // Depth only pipelines need no fragment shader
+    pub color_write: bool,
+    // Uses the skinned vertex shader of the material, see `Skin`
+    pub skinned: bool,
+    // Of the drawn mesh
+    pub vertex_layout: VertexLayout,
 }
 
 pub struct RendererPipeline {
@@ -35,7 +82,8 @@ pub struct ScopPipelineBuilder<'a> {
     vert_shader: Option<&'a Shader>,
     frag_shader: Option<&'a Shader>,
     set_layouts: &'a [vk::DescriptorSetLayout],
-    extent: Option<vk::Extent2D>,
+    render_state: RenderState,
+    subpass: u32,
 }
 
 impl RendererPipeline {
@@ -45,56 +93,50 @@ impl RendererPipeline {
             render_pass: None,
             vert_shader: None,
             frag_shader: None,
-            extent: None,
             set_layouts: &[],
+            render_state: RenderState::default(),
+            subpass: 0,
         }
     }
 
     pub fn new(
         device: Rc<RendererDevice>,
-        extent: vk::Extent2D,
         render_pass: vk::RenderPass,
         set_layouts: &[vk::DescriptorSetLayout],
+        subpass: u32,
         shader_stages: &[vk::PipelineShaderStageCreateInfo],
+        render_state: RenderState,
     ) -> Result<RendererPipeline> {
-        let vertex_input_attribute_descriptions = Vertex::get_vertex_input_attribute_descriptions();
-        let vertex_input_binding_descriptions = Vertex::get_vertex_input_binding_descriptions();
+        let vertex_input_attribute_descriptions =
+            render_state.vertex_layout.attribute_descriptions();
+        let vertex_input_binding_descriptions = render_state.vertex_layout.binding_descriptions();
         let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::builder()
             .vertex_attribute_descriptions(vertex_input_attribute_descriptions.as_slice())
             .vertex_binding_descriptions(vertex_input_binding_descriptions.as_slice());
 
         // input:
 
-        let input_assembly_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
-            .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+        let input_assembly_info =
+            vk::PipelineInputAssemblyStateCreateInfo::builder().topology(render_state.topology);
 
         // viewport:
 
-        let viewports = [vk::Viewport {
-            x: 0.0,
-            y: 0.0,
-            width: extent.width as f32,
-            height: extent.height as f32,
-            min_depth: 0.0,
-            max_depth: 1.0,
-        }];
-
-        let scissors = [vk::Rect2D {
-            offset: vk::Offset2D { x: 0, y: 0 },
-            extent,
-        }];
-
+        // Set when a render pass begins, the same pipeline draws to any target size
         let viewport_info = vk::PipelineViewportStateCreateInfo::builder()
-            .viewports(&viewports)
-            .scissors(&scissors);
+            .viewport_count(1)
+            .scissor_count(1);
+
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let dynamic_state_info =
+            vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
 
         // rasterizer:
 
         let rasterizer_info = vk::PipelineRasterizationStateCreateInfo::builder()
             .polygon_mode(vk::PolygonMode::FILL)
             .line_width(1f32)
-            .cull_mode(vk::CullModeFlags::BACK)
-            .front_face(vk::FrontFace::COUNTER_CLOCKWISE);
+            .cull_mode(render_state.cull_mode)
+            .front_face(render_state.front_face);
 
         // multisampler:
 
@@ -111,12 +153,14 @@ impl RendererPipeline {
             .src_alpha_blend_factor(vk::BlendFactor::SRC_ALPHA)
             .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
             .alpha_blend_op(vk::BlendOp::ADD)
-            .color_write_mask(
+            .color_write_mask(if render_state.color_write {
                 vk::ColorComponentFlags::R
                     | vk::ColorComponentFlags::G
                     | vk::ColorComponentFlags::B
-                    | vk::ColorComponentFlags::A,
-            )
+                    | vk::ColorComponentFlags::A
+            } else {
+                vk::ColorComponentFlags::empty()
+            })
             .build()];
 
         let color_blend_info =
@@ -139,9 +183,9 @@ impl RendererPipeline {
         };
 
         let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
-            .depth_test_enable(true)
-            .depth_write_enable(true)
-            .depth_compare_op(vk::CompareOp::LESS)
+            .depth_test_enable(render_state.depth_test)
+            .depth_write_enable(render_state.depth_test && render_state.depth_write)
+            .depth_compare_op(render_state.depth_compare)
             .depth_bounds_test_enable(false)
             .min_depth_bounds(0f32)
             .max_depth_bounds(1f32)
@@ -152,13 +196,14 @@ impl RendererPipeline {
             .vertex_input_state(&vertex_input_info)
             .input_assembly_state(&input_assembly_info)
             .viewport_state(&viewport_info)
+            .dynamic_state(&dynamic_state_info)
             .rasterization_state(&rasterizer_info)
             .multisample_state(&multisampler_info)
             .color_blend_state(&color_blend_info)
             .layout(pipeline_layout)
             .render_pass(render_pass)
             .depth_stencil_state(&depth_stencil_state)
-            .subpass(0)];
+            .subpass(subpass)];
 
         let pipeline = unsafe {
             device
@@ -166,6 +211,7 @@ impl RendererPipeline {
                 .create_graphics_pipelines(vk::PipelineCache::null(), &pipeline_infos, None)
                 .unwrap()
         }[0];
+        device.leaks.track(TrackedKind::Pipeline, pipeline);
 
         Ok(RendererPipeline {
             device,
@@ -193,13 +239,24 @@ impl RendererPipeline {
         command_buffer: vk::CommandBuffer,
         pipeline_bind_point: vk::PipelineBindPoint,
         descriptor_sets: &[vk::DescriptorSet],
+    ) {
+        self.bind_descriptor_sets_from(command_buffer, pipeline_bind_point, 0, descriptor_sets);
+    }
+
+    // Sets bound before `first_set` stay bound
+    pub fn bind_descriptor_sets_from(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        pipeline_bind_point: vk::PipelineBindPoint,
+        first_set: u32,
+        descriptor_sets: &[vk::DescriptorSet],
     ) {
         unsafe {
             self.device.logical_device.cmd_bind_descriptor_sets(
                 command_buffer,
                 pipeline_bind_point,
                 self.pipeline_layout,
-                0,
+                first_set,
                 descriptor_sets,
                 &[],
             )
@@ -217,6 +274,25 @@ impl Drop for RendererPipeline {
                 .logical_device
                 .destroy_pipeline_layout(self.pipeline_layout, None);
         }
+        self.device
+            .leaks
+            .untrack(TrackedKind::Pipeline, self.pipeline);
+    }
+}
+
+impl Default for RenderState {
+    fn default() -> Self {
+        Self {
+            cull_mode: vk::CullModeFlags::BACK,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            depth_test: true,
+            depth_write: true,
+            depth_compare: vk::CompareOp::LESS,
+            color_write: true,
+            skinned: false,
+            vertex_layout: VertexLayout::Interleaved,
+        }
     }
 }
 
@@ -241,8 +317,44 @@ impl<'a> ScopPipelineBuilder<'a> {
         self
     }
 
-    pub fn extent(mut self, extent: vk::Extent2D) -> Self {
-        self.extent = Some(extent);
+    // Index in the render pass, 0 by default
+    pub fn subpass(mut self, subpass: u32) -> Self {
+        self.subpass = subpass;
+        self
+    }
+
+    pub fn render_state(mut self, render_state: RenderState) -> Self {
+        self.render_state = render_state;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: vk::CullModeFlags) -> Self {
+        self.render_state.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn topology(mut self, topology: vk::PrimitiveTopology) -> Self {
+        self.render_state.topology = topology;
+        self
+    }
+
+    pub fn depth_test(mut self, depth_test: bool) -> Self {
+        self.render_state.depth_test = depth_test;
+        self
+    }
+
+    pub fn depth_write(mut self, depth_write: bool) -> Self {
+        self.render_state.depth_write = depth_write;
+        self
+    }
+
+    pub fn depth_compare(mut self, depth_compare: vk::CompareOp) -> Self {
+        self.render_state.depth_compare = depth_compare;
+        self
+    }
+
+    pub fn color_write(mut self, color_write: bool) -> Self {
+        self.render_state.color_write = color_write;
         self
     }
 
@@ -258,23 +370,39 @@ impl<'a> ScopPipelineBuilder<'a> {
         );
         ensure!(
             self.frag_shader
-                .is_some_and(|s| s.stage.contains(vk::ShaderStageFlags::FRAGMENT)),
+                .map_or(!self.render_state.color_write, |s| s
+                    .stage
+                    .contains(vk::ShaderStageFlags::FRAGMENT)),
             "ScopPipelineBuilder: No fragment shader, or does not contains fragment stage"
         );
-        ensure!(self.extent.is_some(), "ScopPipelineBuilder: No extent");
+
+        let subpass = self
+            .render_pass
+            .unwrap()
+            .subpasses
+            .get(self.subpass as usize);
+        ensure!(
+            subpass.is_some(),
+            "ScopPipelineBuilder: Subpass not in the render pass"
+        );
+        ensure!(
+            subpass.is_some_and(|subpass| subpass.color || !self.render_state.color_write),
+            "ScopPipelineBuilder: Color write in a subpass without color attachment"
+        );
 
         let entry_point = ffi::CString::new("main")?;
-        let shader_stages = [
-            self.vert_shader.unwrap().shader_stage(&entry_point),
-            self.frag_shader.unwrap().shader_stage(&entry_point),
-        ];
+        let mut shader_stages = vec![self.vert_shader.unwrap().shader_stage(&entry_point)];
+        if let Some(frag_shader) = self.frag_shader {
+            shader_stages.push(frag_shader.shader_stage(&entry_point));
+        }
 
         RendererPipeline::new(
             self.device,
-            self.extent.unwrap(),
             self.render_pass.unwrap().render_pass,
             self.set_layouts,
+            self.subpass,
             &shader_stages,
+            self.render_state,
         )
     }
 }
@@ -0,0 +1,51 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use ash::vk;
+
+use crate::utils::Result;
+
+use super::{pipeline::RenderState, RendererPipeline};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    pub render_pass: vk::RenderPass,
+    pub subpass: u32,
+    pub render_state: RenderState,
+}
+
+// Pipeline variants of one material, created the first time a render pass
+// and render state combination is drawn and reused afterward
+#[derive(Default)]
+pub struct ScopPipelineCache {
+    pipelines: RefCell<HashMap<PipelineKey, Rc<RendererPipeline>>>,
+}
+
+impl ScopPipelineCache {
+    pub fn get_or_create(
+        &self,
+        key: PipelineKey,
+        create: impl FnOnce() -> Result<RendererPipeline>,
+    ) -> Result<Rc<RendererPipeline>> {
+        if let Some(pipeline) = self.pipelines.borrow().get(&key) {
+            return Ok(pipeline.clone());
+        }
+
+        let pipeline = Rc::new(create()?);
+        self.pipelines.borrow_mut().insert(key, pipeline.clone());
+        Ok(pipeline)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pipelines.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.borrow().is_empty()
+    }
+
+    // Pipelines still referenced by a pending command buffer must not be
+    // destroyed, wait for the GPU first
+    pub fn evict(&self) {
+        self.pipelines.borrow_mut().clear();
+    }
+}
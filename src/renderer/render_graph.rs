@@ -0,0 +1,243 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use ash::vk;
+
+use crate::{
+    bail,
+    engine::{camera::Camera, GameObject},
+    utils::Result,
+};
+
+use super::Renderer;
+
+// Something a pass reads or writes. Image layouts stay the business of the
+// render passes, the graph only orders the passes and makes the writes of one
+// visible to the next.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GraphResource {
+    SwapchainColor,
+    SwapchainDepth,
+    Named(&'static str),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ResourceUse {
+    pub resource: GraphResource,
+    pub stage: vk::PipelineStageFlags,
+    pub access: vk::AccessFlags,
+}
+
+// What a pass gets to record the current frame
+pub struct FrameContext<'a> {
+    pub camera: &'a Camera,
+    pub game_objects: &'a HashMap<u32, Rc<RefCell<GameObject>>>,
+    pub command_buffer: vk::CommandBuffer,
+    pub image_index: u32,
+}
+
+pub trait RenderGraphPass {
+    fn name(&self) -> &'static str;
+
+    fn reads(&self) -> Vec<ResourceUse> {
+        vec![]
+    }
+
+    fn writes(&self) -> Vec<ResourceUse>;
+
+    // Whether the last recording stays valid while the scene does not change.
+    // Passes recording anything else, like per frame uploads, keep the default.
+    fn reusable(&self) -> bool {
+        false
+    }
+
+    // The command buffer is recording, outside of any render pass
+    fn record(&mut self, renderer: &mut Renderer, frame: &FrameContext) -> Result<()>;
+}
+
+// Passes kept from one frame to the next. They run in dependency order, a
+// pass reading a resource comes after the passes writing it, and otherwise
+// in the order they were added.
+#[derive(Default)]
+pub struct RenderGraph {
+    passes: Vec<Box<dyn RenderGraphPass>>,
+    // Pass indices in execution order with the barrier each one waits on,
+    // computed again when passes change
+    compiled: Option<Vec<(usize, Option<Barrier>)>>,
+    // Bumped when passes are added or removed
+    revision: u64,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+struct Barrier {
+    src_stage: vk::PipelineStageFlags,
+    src_access: vk::AccessFlags,
+    dst_stage: vk::PipelineStageFlags,
+    dst_access: vk::AccessFlags,
+}
+
+impl ResourceUse {
+    pub fn color_attachment(resource: GraphResource) -> Self {
+        Self {
+            resource,
+            stage: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            access: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        }
+    }
+
+    pub fn depth_attachment(resource: GraphResource) -> Self {
+        Self {
+            resource,
+            stage: vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            access: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        }
+    }
+
+    pub fn sampled(resource: GraphResource) -> Self {
+        Self {
+            resource,
+            stage: vk::PipelineStageFlags::FRAGMENT_SHADER,
+            access: vk::AccessFlags::SHADER_READ,
+        }
+    }
+}
+
+impl RenderGraph {
+    pub fn add_pass(&mut self, pass: Box<dyn RenderGraphPass>) {
+        self.passes.push(pass);
+        self.compiled = None;
+        self.revision += 1;
+    }
+
+    pub fn remove_pass(&mut self, name: &str) -> Option<Box<dyn RenderGraphPass>> {
+        let index = self.passes.iter().position(|pass| pass.name() == name)?;
+        self.compiled = None;
+        self.revision += 1;
+        Some(self.passes.remove(index))
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn is_reusable(&self) -> bool {
+        self.passes.iter().all(|pass| pass.reusable())
+    }
+
+    // Names in execution order
+    pub fn pass_names(&mut self) -> Result<Vec<&'static str>> {
+        let order: Vec<usize> = self.compile()?.iter().map(|(index, _)| *index).collect();
+        Ok(order
+            .into_iter()
+            .map(|index| self.passes[index].name())
+            .collect())
+    }
+
+    pub fn record(&mut self, renderer: &mut Renderer, frame: &FrameContext) -> Result<()> {
+        let compiled = self.compile()?.clone();
+        for (index, barrier) in compiled {
+            if let Some(barrier) = barrier {
+                barrier.record(renderer, frame.command_buffer);
+            }
+            self.passes[index].record(renderer, frame)?;
+        }
+        Ok(())
+    }
+
+    fn compile(&mut self) -> Result<&Vec<(usize, Option<Barrier>)>> {
+        if self.compiled.is_none() {
+            self.compiled = Some(Self::schedule(&self.passes)?);
+        }
+        Ok(self.compiled.as_ref().unwrap())
+    }
+
+    fn schedule(passes: &[Box<dyn RenderGraphPass>]) -> Result<Vec<(usize, Option<Barrier>)>> {
+        let reads: Vec<Vec<ResourceUse>> = passes.iter().map(|pass| pass.reads()).collect();
+        let writes: Vec<Vec<ResourceUse>> = passes.iter().map(|pass| pass.writes()).collect();
+        let writes_resource = |pass: usize, resource: GraphResource| {
+            writes[pass].iter().any(|w| w.resource == resource)
+        };
+
+        // A pass waits for the writers of what it reads, and for the earlier
+        // writers of what it writes so writes keep their order
+        let dependencies: Vec<Vec<usize>> = (0..passes.len())
+            .map(|pass| {
+                (0..passes.len())
+                    .filter(|&other| other != pass)
+                    .filter(|&other| {
+                        reads[pass]
+                            .iter()
+                            .any(|r| writes_resource(other, r.resource))
+                            || (other < pass
+                                && writes[pass]
+                                    .iter()
+                                    .any(|w| writes_resource(other, w.resource)))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut order: Vec<usize> = Vec::with_capacity(passes.len());
+        while order.len() < passes.len() {
+            let next = (0..passes.len()).find(|pass| {
+                !order.contains(pass)
+                    && dependencies[*pass]
+                        .iter()
+                        .all(|dependency| order.contains(dependency))
+            });
+            match next {
+                Some(pass) => order.push(pass),
+                None => {
+                    let names: Vec<&str> = (0..passes.len())
+                        .filter(|pass| !order.contains(pass))
+                        .map(|pass| passes[pass].name())
+                        .collect();
+                    bail!(format!("Render graph: cycle between {}", names.join(", ")));
+                }
+            }
+        }
+
+        // The last write of each resource so far
+        let mut last_writes: HashMap<GraphResource, ResourceUse> = HashMap::new();
+        let mut compiled = Vec::with_capacity(order.len());
+        for pass in order {
+            let mut barrier: Option<Barrier> = None;
+            for resource_use in reads[pass].iter().chain(&writes[pass]) {
+                if let Some(write) = last_writes.get(&resource_use.resource) {
+                    let b = barrier.get_or_insert(Barrier::default());
+                    b.src_stage |= write.stage;
+                    b.src_access |= write.access;
+                    b.dst_stage |= resource_use.stage;
+                    b.dst_access |= resource_use.access;
+                }
+            }
+            for write in &writes[pass] {
+                last_writes.insert(write.resource, *write);
+            }
+            compiled.push((pass, barrier));
+        }
+
+        Ok(compiled)
+    }
+}
+
+impl Barrier {
+    fn record(&self, renderer: &Renderer, command_buffer: vk::CommandBuffer) {
+        let memory_barrier = vk::MemoryBarrier::builder()
+            .src_access_mask(self.src_access)
+            .dst_access_mask(self.dst_access)
+            .build();
+        unsafe {
+            renderer.main_device.logical_device.cmd_pipeline_barrier(
+                command_buffer,
+                self.src_stage,
+                self.dst_stage,
+                vk::DependencyFlags::empty(),
+                &[memory_barrier],
+                &[],
+                &[],
+            );
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use std::rc::Rc;
+
+use ash::vk;
+
+use crate::{ensure, utils::Result};
+
+use super::{
+    RendererDevice, ScopFramebuffer, ScopImage, ScopRenderPass, ScopSwapchain, ScopTexture2D,
+};
+
+pub const MIN_RENDER_SCALE: f32 = 0.5;
+pub const MAX_RENDER_SCALE: f32 = 2.;
+
+// The scene of each swapchain image drawn at `scale` times its size, then
+// blitted with linear filtering. Below 1 heavy scenes cost less, above 1 the
+// image is supersampled.
+pub struct ScaledFrames {
+    device: Rc<RendererDevice>,
+    pub scale: f32,
+    pub extent: vk::Extent2D,
+    // Same subpasses as the swapchain pass, the pipelines draw in both
+    pub render_pass: ScopRenderPass,
+    frames: Vec<ScaledFrame>,
+}
+
+struct ScaledFrame {
+    texture: ScopTexture2D,
+    depth_image: ScopImage,
+    depth_image_view: vk::ImageView,
+    framebuffer: ScopFramebuffer,
+}
+
+impl ScaledFrames {
+    pub fn new(
+        device: Rc<RendererDevice>,
+        swapchain: &ScopSwapchain,
+        swapchain_render_pass: &ScopRenderPass,
+        scale: f32,
+    ) -> Result<Self> {
+        ensure!(
+            swapchain.blit_target,
+            "The swapchain images cannot be blitted to"
+        );
+        let format = swapchain.surface_format.format;
+        let features = unsafe {
+            device
+                .instance
+                .get_physical_device_format_properties(device.physical_device, format)
+        }
+        .optimal_tiling_features;
+        ensure!(
+            features.contains(
+                vk::FormatFeatureFlags::BLIT_SRC
+                    | vk::FormatFeatureFlags::BLIT_DST
+                    | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR
+            ),
+            "The swapchain format cannot be blitted with filtering"
+        );
+
+        let max_size = device.properties.limits.max_image_dimension2_d;
+        let scaled = |size: u32| ((size as f32 * scale).round() as u32).clamp(1, max_size);
+        let extent = vk::Extent2D {
+            width: scaled(swapchain.extent.width),
+            height: scaled(swapchain.extent.height),
+        };
+
+        let mut render_pass = ScopRenderPass::offscreen(
+            device.clone(),
+            format,
+            swapchain_render_pass.subpasses.clone(),
+        )?;
+        render_pass.clear_color = swapchain_render_pass.clear_color;
+        render_pass.clear_depth = swapchain_render_pass.clear_depth;
+
+        let mut scaled_frames = Self {
+            device,
+            scale,
+            extent,
+            render_pass,
+            frames: Vec::with_capacity(swapchain.image_count),
+        };
+        for _ in 0..swapchain.image_count {
+            match scaled_frames.create_frame(format) {
+                Ok(frame) => scaled_frames.frames.push(frame),
+                Err(e) => {
+                    scaled_frames.cleanup();
+                    return Err(e);
+                }
+            }
+        }
+        Ok(scaled_frames)
+    }
+
+    fn create_frame(&self, format: vk::Format) -> Result<ScaledFrame> {
+        let mut texture = ScopTexture2D::new_color_attachment(
+            self.device.clone(),
+            self.extent.width,
+            self.extent.height,
+            format,
+        )?;
+        let (mut depth_image, depth_image_view) =
+            match unsafe { ScopSwapchain::create_depth_resources(&self.device, self.extent) } {
+                Ok(depth) => depth,
+                Err(e) => {
+                    texture.cleanup();
+                    return Err(e);
+                }
+            };
+        let framebuffer = ScopFramebuffer::new(
+            self.device.clone(),
+            texture.image_view,
+            depth_image_view,
+            self.render_pass.render_pass,
+            self.extent,
+        );
+        match framebuffer {
+            Ok(framebuffer) => Ok(ScaledFrame {
+                texture,
+                depth_image,
+                depth_image_view,
+                framebuffer,
+            }),
+            Err(e) => {
+                depth_image.cleanup_image_view(depth_image_view);
+                depth_image.cleanup();
+                texture.cleanup();
+                Err(e)
+            }
+        }
+    }
+
+    pub fn begin(&self, command_buffer: vk::CommandBuffer, image_index: u32) {
+        self.render_pass.begin_framebuffer(
+            command_buffer,
+            &self.frames[image_index as usize].framebuffer,
+        );
+    }
+
+    pub fn depth_image(&self, image_index: u32) -> &ScopImage {
+        &self.frames[image_index as usize].depth_image
+    }
+
+    // After the render pass, leaves the swapchain image ready to present
+    pub fn record_blit(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        image_index: u32,
+        swapchain: &ScopSwapchain,
+    ) -> Result<()> {
+        let device = &self.device.logical_device;
+        let source = &mut self.frames[image_index as usize].texture.image;
+        let destination = swapchain.images[image_index as usize];
+        let color_range = vk::ImageSubresourceRange::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .level_count(1)
+            .layer_count(1)
+            .build();
+        let swapchain_barrier = |old_layout, new_layout, src_access_mask, dst_access_mask| {
+            vk::ImageMemoryBarrier::builder()
+                .old_layout(old_layout)
+                .new_layout(new_layout)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .src_access_mask(src_access_mask)
+                .dst_access_mask(dst_access_mask)
+                .image(destination)
+                .subresource_range(color_range)
+                .build()
+        };
+
+        source.record_change_layout(command_buffer, vk::ImageLayout::TRANSFER_SRC_OPTIMAL)?;
+        unsafe {
+            // The image is acquired once the submission waits at the color
+            // output stage, the blit waits for that stage
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[swapchain_barrier(
+                    vk::ImageLayout::UNDEFINED,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::AccessFlags::empty(),
+                    vk::AccessFlags::TRANSFER_WRITE,
+                )],
+            );
+
+            let layers = vk::ImageSubresourceLayers::builder()
+                .aspect_mask(vk::ImageAspectFlags::COLOR)
+                .layer_count(1)
+                .build();
+            let corner = |extent: vk::Extent2D| vk::Offset3D {
+                x: extent.width as i32,
+                y: extent.height as i32,
+                z: 1,
+            };
+            let region = vk::ImageBlit::builder()
+                .src_subresource(layers)
+                .src_offsets([vk::Offset3D::default(), corner(self.extent)])
+                .dst_subresource(layers)
+                .dst_offsets([vk::Offset3D::default(), corner(swapchain.extent)])
+                .build();
+            device.cmd_blit_image(
+                command_buffer,
+                source.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                destination,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[region],
+                vk::Filter::LINEAR,
+            );
+
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[swapchain_barrier(
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::ImageLayout::PRESENT_SRC_KHR,
+                    vk::AccessFlags::TRANSFER_WRITE,
+                    vk::AccessFlags::empty(),
+                )],
+            );
+        }
+        // The layout the render pass leaves, it starts from undefined anyway
+        source.record_change_layout(command_buffer, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+    }
+
+    pub fn cleanup(&mut self) {
+        for frame in &mut self.frames {
+            frame.framebuffer.cleanup();
+            frame.depth_image.cleanup_image_view(frame.depth_image_view);
+            frame.depth_image.cleanup();
+            frame.texture.cleanup();
+        }
+        self.frames.clear();
+        self.render_pass.cleanup();
+    }
+}
@@ -1,25 +1,197 @@
 use std::{
+    any::Any,
     cell::RefCell,
-    collections::HashMap,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
     ffi::{CStr, CString},
+    fmt::{self, Display, Formatter},
+    hash::{Hash, Hasher},
     mem::size_of,
     rc::Rc,
+    time::{Duration, Instant},
 };
 
 use ash::{
     extensions::ext,
     vk::{self, CommandPoolCreateFlags, PipelineStageFlags, QueueFlags, ShaderStageFlags},
 };
+use math::{Mat4, Vec2, Vec3, Vec4};
 use raw_window_handle::HasRawDisplayHandle;
 
-use crate::{engine::{camera::Camera, mesh::Mesh, GameObject}, utils::Result};
+use crate::{
+    engine::{
+        camera::{Camera, Frustum, Handedness},
+        jobs::Jobs,
+        mesh::{BoundingSphere, Mesh, VertexLayout},
+        traverse, update_local_matrices, GameObject, GameObjectId, SceneNode,
+    },
+    ensure,
+    utils::Result,
+};
 
 use super::{
-    Material, MaterialInstance, RendererDebug, RendererDevice, RendererWindow, ScopBuffer,
-    ScopCommandPool, ScopDescriptorPool, ScopDescriptorSetLayout, ScopDescriptorWriter,
-    ScopGpuCameraData, ScopRenderPass, ScopSwapchain, SimplePushConstantData,
+    pipeline::RenderState,
+    render_scale::{ScaledFrames, MAX_RENDER_SCALE, MIN_RENDER_SCALE},
+    Background, BackgroundPass, DebugLines, DistinctColors, FrameContext, GraphResource, Material,
+    MaterialInstance, PictureInPicture, PipClear, RenderGraph, RenderGraphPass, RendererDebug,
+    RendererDevice, RendererPipeline, RendererWindow, ResourceUse, ScopCommandPool,
+    ScopDescriptorPool, ScopDescriptorSetLayout, ScopDescriptorWriter, ScopGpuCameraData,
+    ScopRenderPass, ScopRenderTarget, ScopStagingRing, ScopSubpass, ScopSwapchain, ScopUniformRing,
+    SimplePushConstantData,
 };
 
+const MAX_RENDER_TARGETS: usize = 64;
+const MAX_SKINS: usize = 32;
+const MAX_PICTURES_IN_PICTURE: usize = 4;
+const FRAME_UNIFORMS_SIZE: vk::DeviceSize = 64 * 1024;
+// Smaller scenes are culled on the render thread, starting the workers would
+// cost more than the tests
+const PARALLEL_CULLING_MIN_OBJECTS: usize = 512;
+
+// Choices fixed for the lifetime of the renderer, materials are built for them
+#[derive(Copy, Clone, Default, Debug)]
+pub struct RendererConfig {
+    // Opaque geometry first writes its depth alone, then only the visible
+    // fragments are shaded. Skipped while a clip plane is set.
+    pub depth_prepass: bool,
+    // Depth goes from 1 at the near plane to 0 at the far plane, which spreads
+    // the float precision evenly with distance. Cameras are flipped to match.
+    pub reverse_z: bool,
+    // Left-handed world, cameras mirror their view to match and triangles
+    // are front facing when clockwise, see `Handedness`
+    pub left_handed: bool,
+    // Seconds added to the clock each frame instead of the real elapsed
+    // time, so recorded frames come out the same on any machine
+    pub fixed_delta_time: Option<f32>,
+    // Enables the GPU-assisted and synchronization checks of the validation
+    // layer, an error when the layer is missing
+    pub gpu_validation: bool,
+    // The window is never shown, for tools that only render to targets.
+    // Vulkan still needs its surface, a display connection is required.
+    pub hidden: bool,
+    // Initial `Renderer::set_render_scale`, native resolution when None
+    pub render_scale: Option<f32>,
+    // Of the imported models
+    pub vertex_layout: VertexLayout,
+}
+
+#[derive(Copy, Clone, Default, Debug)]
+pub struct FrameStats {
+    pub objects_drawn: usize,
+    pub objects_culled: usize,
+    pub triangles: usize,
+    pub draw_calls: usize,
+    // Pipeline changes, consecutive objects sharing a material are batched
+    pub batches: usize,
+    pub prepass_draw_calls: usize,
+    // Measured a few frames late, None when the device cannot count them
+    pub fragment_invocations: Option<u64>,
+    pub pixels: u64,
+    // The command buffer of the previous frame on this image was submitted again
+    pub reused_recording: bool,
+}
+
+// Rolling averages of the CPU time spent in each phase of a frame. A long
+// acquire means the CPU waits for the GPU, a long update or record means the
+// frame is CPU bound.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct FrameBreakdown {
+    // Waiting for the frame in flight to finish, then for a swapchain image
+    pub acquire: Duration,
+    // The app closure given to `Engine::run`
+    pub update: Duration,
+    pub record: Duration,
+    pub submit: Duration,
+    pub present: Duration,
+}
+
+// What the renderer picked for the swapchain and what else the surface and
+// device offer, queried by `Renderer::capabilities`
+#[derive(Clone, Debug)]
+pub struct RendererCapabilities {
+    pub surface_format: vk::Format,
+    pub color_space: vk::ColorSpaceKHR,
+    pub image_count: usize,
+    pub present_mode: vk::PresentModeKHR,
+    pub present_modes: Vec<vk::PresentModeKHR>,
+    // Wide gamut and HDR color spaces the surface can present, empty on SDR
+    // displays or without VK_EXT_swapchain_colorspace
+    pub hdr_color_spaces: Vec<vk::ColorSpaceKHR>,
+    // Highest sample count usable by both color and depth attachments
+    pub max_msaa: vk::SampleCountFlags,
+    // A dedicated compute queue exists, see
+    // `RendererDevice::async_compute_queue_family`
+    pub async_compute: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum DrawPass {
+    Color,
+    DepthPrepass,
+    AfterDepthPrepass,
+}
+
+// Where `record_draws` records to
+#[derive(Copy, Clone)]
+struct DrawTarget<'a> {
+    render_pass: &'a ScopRenderPass,
+    command_buffer: vk::CommandBuffer,
+    global_descriptor_set: vk::DescriptorSet,
+    image_index: u32,
+    // Only these game objects and their children, all of them when None
+    objects: Option<&'a [GameObjectId]>,
+}
+
+// The built-in pass of the render graph
+struct ScenePass;
+
+impl RenderGraphPass for ScenePass {
+    fn name(&self) -> &'static str {
+        "scene"
+    }
+
+    fn writes(&self) -> Vec<ResourceUse> {
+        vec![
+            ResourceUse::color_attachment(GraphResource::SwapchainColor),
+            ResourceUse::depth_attachment(GraphResource::SwapchainDepth),
+        ]
+    }
+
+    fn reusable(&self) -> bool {
+        true
+    }
+
+    fn record(&mut self, renderer: &mut Renderer, frame: &FrameContext) -> Result<()> {
+        renderer.record_scene(frame)
+    }
+}
+
+impl DrawPass {
+    // The depth prepass only writes the depth of opaque geometry, the color
+    // pass after it shades each pixel once by keeping fragments at that depth
+    fn render_state(self, material: &Material) -> RenderState {
+        match self {
+            DrawPass::Color => material.render_state,
+            DrawPass::DepthPrepass => RenderState {
+                color_write: false,
+                ..material.render_state
+            },
+            DrawPass::AfterDepthPrepass => RenderState {
+                depth_write: false,
+                depth_compare: vk::CompareOp::EQUAL,
+                ..material.render_state
+            },
+        }
+    }
+
+    // With a depth prepass the render pass has a depth only subpass first
+    fn subpass(self, render_pass: &ScopRenderPass) -> u32 {
+        match self {
+            DrawPass::DepthPrepass => 0,
+            _ => render_pass.main_subpass(),
+        }
+    }
+}
+
 pub struct Renderer {
     #[allow(
         dead_code,
@@ -27,22 +199,83 @@ pub struct Renderer {
     )]
     entry: ash::Entry,
     pub instance: Rc<ash::Instance>,
+    pub config: RendererConfig,
     pub debug: Option<RendererDebug>,
     pub window: RendererWindow,
     pub main_device: Rc<RendererDevice>,
     pub swapchain: ScopSwapchain,
     pub defaut_render_pass: ScopRenderPass,
+    // The scene drawn at another resolution than the swapchain, see
+    // `set_render_scale`
+    pub scaled: Option<ScaledFrames>,
+    // Image of the last frame drawn, its depth is the one read back
+    pub last_image_index: u32,
+    pub offscreen_render_pass: ScopRenderPass,
     pub global_descriptor_pool: ScopDescriptorPool,
     pub global_descriptor_set_layout: ScopDescriptorSetLayout,
     pub global_descriptor_sets: Vec<vk::DescriptorSet>,
+    // Joint matrices of skinned draws, the set after the material ones
+    pub skin_set_layout: ScopDescriptorSetLayout,
     pub graphic_command_pools: Vec<ScopCommandPool>,
-    pub camera_buffers: Vec<ScopBuffer>,
+    // Per frame in flight, the camera data lives in the reserved part
+    pub uniform_rings: Vec<ScopUniformRing>,
+    pub staging_ring: RefCell<ScopStagingRing>,
     pub frame_count: u32,
     pub flat_texture_interpolation: f32,
+    pub clip_plane: Vec4,
+    pub naive_gamma: bool,
+    // Illuminance of the lights, 1 brings a white surface facing the light to
+    // the display white at exposure 0. The sun only lights the faces turned
+    // to it, the ambient light every face.
+    pub sun_intensity: f32,
+    pub ambient_intensity: f32,
+    // Direction the sun light travels in, normalized when uploaded
+    pub sun_direction: Vec3,
+    pub texel_density_view: bool,
+    // Front faces in blue and back faces in red, nothing is culled
+    pub winding_view: bool,
+    // Flat color per object, see `DistinctColors`
+    pub distinct_colors: Option<DistinctColors>,
+    pub debug_lines: Option<DebugLines>,
+    // Lines given in normalized device coordinates, drawn last
+    pub overlay_lines: Option<DebugLines>,
+    // Drawn over the scene, before the overlay lines. Removed ones are given
+    // to `retire`, the frames in flight may still use them.
+    pub pictures_in_picture: Vec<PictureInPicture>,
+    // Worker threads for the scene preparation, also free for the apps
+    pub jobs: Jobs,
+    // See `set_background`
+    background: Background,
+    // Draws the backgrounds a clear color cannot, created by the engine
+    pub background_pass: Option<BackgroundPass>,
+    // Starts with a "scene" pass drawing to the swapchain
+    pub render_graph: RenderGraph,
+    // Submits the previous recording of an image again while the scene
+    // signature given by the engine stays the same
+    pub reuse_recordings: bool,
+    pub scene_signature: Option<u64>,
+    // Per image, what its command buffer was recorded with
+    recordings: Vec<Option<(u64, FrameStats)>>,
+    pub stats: FrameStats,
+    // Dropped once no frame in flight can use them, with the frame they were
+    // retired in
+    retired: Vec<(u32, Box<dyn Any>)>,
+    frame_breakdown: FrameBreakdown,
+    // One fragment shader invocations query per frame in flight
+    statistics_query_pool: Option<vk::QueryPool>,
+    statistics_pending: Vec<bool>,
+    // Clock of the frame being prepared, advanced by `handle_draw_request`
+    time: f32,
+    delta_time: f32,
+    last_frame_start: Instant,
 }
 
 impl Renderer {
-    fn try_add_layer(available_layers: &Vec<vk::LayerProperties>, layers_names: &mut Vec<CString>, layer: CString) -> bool {
+    fn try_add_layer(
+        available_layers: &Vec<vk::LayerProperties>,
+        layers_names: &mut Vec<CString>,
+        layer: CString,
+    ) -> bool {
         for layer_props in available_layers {
             if layer.as_c_str() == unsafe { CStr::from_ptr(layer_props.layer_name.as_ptr()) } {
                 layers_names.push(layer);
@@ -52,9 +285,15 @@ impl Renderer {
         return false;
     }
 
-    fn try_add_extension(available_extensions: &Vec<vk::ExtensionProperties>, extensions_names: &mut Vec<CString>, extension: CString) -> bool {
+    fn try_add_extension(
+        available_extensions: &Vec<vk::ExtensionProperties>,
+        extensions_names: &mut Vec<CString>,
+        extension: CString,
+    ) -> bool {
         for extension_props in available_extensions {
-            if extension.as_c_str() == unsafe { CStr::from_ptr(extension_props.extension_name.as_ptr()) } {
+            if extension.as_c_str()
+                == unsafe { CStr::from_ptr(extension_props.extension_name.as_ptr()) }
+            {
                 extensions_names.push(extension);
                 return true;
             }
@@ -62,8 +301,8 @@ impl Renderer {
         return false;
     }
 
-    pub fn new() -> Result<Self> {
-        let (event_loop, window) = RendererWindow::create_window()?;
+    pub fn new(config: RendererConfig) -> Result<Self> {
+        let (event_loop, window) = RendererWindow::create_window(!config.hidden)?;
 
         let entry = unsafe { ash::Entry::load() }?;
 
@@ -76,10 +315,11 @@ impl Renderer {
             extension_names.push(unsafe { CString::from(CStr::from_ptr(*extension)) });
         }
 
-        Self::try_add_layer(
+        let validation_layer = CString::new("VK_LAYER_KHRONOS_validation")?;
+        let validation_available = Self::try_add_layer(
             &available_layers,
             &mut layers_names,
-            CString::new("VK_LAYER_KHRONOS_validation")?,
+            validation_layer.clone(),
         );
         let debug_available = Self::try_add_extension(
             &available_extension,
@@ -87,38 +327,102 @@ impl Renderer {
             CString::from(ext::DebugUtils::name()),
         );
 
-        let instance = Self::create_instance(&entry, &layers_names, &extension_names)?;
+        let mut validation_features: &[vk::ValidationFeatureEnableEXT] = &[];
+        if config.gpu_validation {
+            ensure!(
+                validation_available && debug_available,
+                "GPU validation needs VK_LAYER_KHRONOS_validation and VK_EXT_debug_utils"
+            );
+            // Provided by the layer, not listed with the instance extensions
+            let layer_extensions =
+                entry.enumerate_instance_extension_properties(Some(&validation_layer))?;
+            ensure!(
+                Self::try_add_extension(
+                    &layer_extensions,
+                    &mut extension_names,
+                    CString::from(vk::ExtValidationFeaturesFn::name()),
+                ),
+                "GPU validation needs VK_EXT_validation_features"
+            );
+            validation_features = RendererDebug::gpu_validation_features();
+        }
+
+        let instance = Self::create_instance(
+            &entry,
+            &layers_names,
+            &extension_names,
+            debug_available,
+            validation_features,
+        )?;
         let instance = Rc::new(instance);
 
-        let debug = if debug_available { Some(RendererDebug::new(&entry, &instance)?) } else { None };
-        
+        let debug = if debug_available {
+            Some(RendererDebug::new(&entry, &instance)?)
+        } else {
+            None
+        };
+
         let main_device = Rc::new(RendererDevice::new(&instance)?);
 
         let window = RendererWindow::new(event_loop, window, &entry, &instance)?;
 
         let swapchain = ScopSwapchain::new(&entry, &instance, main_device.clone(), &window)?;
 
-        let defaut_render_pass = ScopRenderPass::new(main_device.clone(), &swapchain)?;
+        let subpasses = if config.depth_prepass {
+            ScopSubpass::depth_prepass()
+        } else {
+            vec![ScopSubpass::color()]
+        };
+        let mut defaut_render_pass =
+            ScopRenderPass::new(main_device.clone(), &swapchain, subpasses.clone())?;
+        let mut offscreen_render_pass = ScopRenderPass::offscreen(
+            main_device.clone(),
+            swapchain.surface_format.format,
+            subpasses,
+        )?;
+        if config.reverse_z {
+            defaut_render_pass.clear_depth = 0.;
+            offscreen_render_pass.clear_depth = 0.;
+
+            let depth_format = main_device.find_depth_format()?;
+            if depth_format == vk::Format::D24_UNORM_S8_UINT {
+                eprintln!("Reverse-Z: no floating point depth format, precision is not improved");
+            }
+        }
 
         let global_descriptor_pool = ScopDescriptorPool::builder(&main_device)
+            // Render targets each take one more camera set, pictures in
+            // picture one per image, material instances one parameter block
+            // and two textures per image
             .add_size(
                 vk::DescriptorType::UNIFORM_BUFFER,
-                swapchain.image_count as u32,
+                (swapchain.image_count * (65 + MAX_SKINS + MAX_PICTURES_IN_PICTURE)
+                    + MAX_RENDER_TARGETS) as u32,
             )
             .add_size(
                 vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                (swapchain.image_count * 10) as u32,
+                (swapchain.image_count * 128) as u32,
+            )
+            .max_sets(
+                (swapchain.image_count * (144 + MAX_SKINS + MAX_PICTURES_IN_PICTURE)
+                    + MAX_RENDER_TARGETS) as u32,
             )
-            .max_sets((swapchain.image_count * 20) as u32)
             .build()?;
 
         let global_descriptor_set_layout = ScopDescriptorSetLayout::builder(&main_device)
+            .add_buffer_binding(
+                0,
+                vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+            )
+            .build()?;
+
+        let skin_set_layout = ScopDescriptorSetLayout::builder(&main_device)
             .add_buffer_binding(0, vk::ShaderStageFlags::VERTEX)
             .build()?;
 
         let mut graphic_command_pools =
             Vec::<ScopCommandPool>::with_capacity(swapchain.image_count);
-        let mut camera_buffers = Vec::<ScopBuffer>::with_capacity(swapchain.image_count);
+        let mut uniform_rings = Vec::<ScopUniformRing>::with_capacity(swapchain.image_count);
         for _ in 0..swapchain.image_count {
             let mut graphic_command_pool = ScopCommandPool::new(
                 main_device.clone(),
@@ -130,54 +434,104 @@ impl Renderer {
             graphic_command_pool.create_command_buffers(1 as u32)?;
             graphic_command_pools.push(graphic_command_pool);
 
-            camera_buffers.push(ScopBuffer::new(
+            uniform_rings.push(ScopUniformRing::new(
                 main_device.clone(),
-                1,
-                size_of::<ScopGpuCameraData>() as u64,
-                vk::BufferUsageFlags::UNIFORM_BUFFER,
-                vk::MemoryPropertyFlags::HOST_VISIBLE,
-                1,
+                FRAME_UNIFORMS_SIZE,
+                size_of::<ScopGpuCameraData>() as vk::DeviceSize,
             )?);
         }
 
         let mut global_descriptor_sets =
             Vec::<vk::DescriptorSet>::with_capacity(swapchain.image_count);
-        for i in 0..swapchain.image_count {
+        for uniform_ring in &uniform_rings {
             let allocate_info = vk::DescriptorSetAllocateInfo::builder()
                 .descriptor_pool(global_descriptor_pool.descriptor_pool)
                 .set_layouts(&[global_descriptor_set_layout.set_layout])
                 .build();
 
-            let set = unsafe {
-                main_device
-                    .logical_device
-                    .allocate_descriptor_sets(&allocate_info)?[0]
-            };
+            let set = main_device.allocate_descriptor_sets(&allocate_info)?[0];
 
             ScopDescriptorWriter::new(&main_device, &global_descriptor_set_layout)
                 .descriptors(&[set])
-                .set_buffer(0, &camera_buffers[i])
-                .write();
+                .set_buffer_info(0, uniform_ring.reserved().descriptor_info())
+                .write()?;
 
             global_descriptor_sets.push(set);
         }
 
-        Ok(Self {
+        let staging_ring = ScopStagingRing::new(main_device.clone(), 32 * 1024 * 1024)?;
+
+        let statistics_query_pool = if main_device.features.pipeline_statistics_query != 0 {
+            let create_info = vk::QueryPoolCreateInfo::builder()
+                .query_type(vk::QueryType::PIPELINE_STATISTICS)
+                .pipeline_statistics(vk::QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS)
+                .query_count(swapchain.image_count as u32);
+            Some(unsafe {
+                main_device
+                    .logical_device
+                    .create_query_pool(&create_info, None)?
+            })
+        } else {
+            None
+        };
+        let statistics_pending = vec![false; swapchain.image_count];
+        let recordings = vec![None; swapchain.image_count];
+
+        let mut render_graph = RenderGraph::default();
+        render_graph.add_pass(Box::new(ScenePass));
+
+        let mut renderer = Self {
             entry,
             instance,
+            config,
             debug,
             main_device,
             window,
             swapchain,
             defaut_render_pass,
+            scaled: None,
+            last_image_index: 0,
+            offscreen_render_pass,
             global_descriptor_pool,
             global_descriptor_set_layout,
             global_descriptor_sets,
+            skin_set_layout,
             graphic_command_pools,
-            camera_buffers,
+            uniform_rings,
+            staging_ring: RefCell::new(staging_ring),
             frame_count: 0,
             flat_texture_interpolation: 0.,
-        })
+            clip_plane: Vec4::default(),
+            naive_gamma: false,
+            sun_intensity: 0.6,
+            ambient_intensity: 0.4,
+            sun_direction: Vec3::new(-0.3, -1., -1.),
+            texel_density_view: false,
+            winding_view: false,
+            distinct_colors: None,
+            debug_lines: None,
+            overlay_lines: None,
+            pictures_in_picture: vec![],
+            jobs: Jobs::available(),
+            background: Background::default(),
+            background_pass: None,
+            render_graph,
+            reuse_recordings: true,
+            scene_signature: None,
+            recordings,
+            stats: FrameStats::default(),
+            retired: vec![],
+            frame_breakdown: FrameBreakdown::default(),
+            statistics_query_pool,
+            statistics_pending,
+            time: 0.,
+            delta_time: 0.,
+            last_frame_start: Instant::now(),
+        };
+        if let Some(scale) = config.render_scale {
+            renderer.set_render_scale(scale)?;
+        }
+        Ok(renderer)
     }
 
     pub fn recreate_swapchain(&mut self) -> Result<()> {
@@ -190,16 +544,180 @@ impl Renderer {
             &self.window,
         )?;
         self.defaut_render_pass.change_swapchain(&self.swapchain)?;
+        if let Some(scale) = self.scaled.as_ref().map(|scaled| scaled.scale) {
+            self.set_render_scale(scale)?;
+        }
+        self.invalidate_recordings();
+        Ok(())
+    }
+
+    // Factor applied to the swapchain size to get the size the scene is
+    // drawn at
+    pub fn render_scale(&self) -> f32 {
+        self.scaled.as_ref().map_or(1., |scaled| scaled.scale)
+    }
+
+    // The scene is drawn to images of the scaled size then stretched over the
+    // swapchain image. Waits for the GPU, the images are created again.
+    pub fn set_render_scale(&mut self, scale: f32) -> Result<()> {
+        ensure!(
+            (MIN_RENDER_SCALE..=MAX_RENDER_SCALE).contains(&scale),
+            "The render scale must be between 0.5 and 2"
+        );
+        self.wait_gpu();
+        if let Some(mut scaled) = self.scaled.take() {
+            scaled.cleanup();
+        }
+        if scale != 1. {
+            self.scaled = Some(ScaledFrames::new(
+                self.main_device.clone(),
+                &self.swapchain,
+                &self.defaut_render_pass,
+                scale,
+            )?);
+        }
+        self.invalidate_recordings();
         Ok(())
     }
 
+    // Size of the images the scene is drawn to
+    pub fn render_extent(&self) -> vk::Extent2D {
+        self.scaled
+            .as_ref()
+            .map_or(self.swapchain.extent, |scaled| scaled.extent)
+    }
+
+    pub fn background(&self) -> Background {
+        self.background
+    }
+
+    // Without background pass, only the clear color of other modes is shown
+    pub fn set_background(&mut self, background: Background) {
+        self.defaut_render_pass.clear_color = background.clear_value(self.swapchain.is_srgb());
+        if let Some(scaled) = &mut self.scaled {
+            scaled.render_pass.clear_color = self.defaut_render_pass.clear_color;
+        }
+        self.background = background;
+        self.invalidate_recordings();
+    }
+
+    // The next frames record their command buffers again, needed when
+    // something the scene signature does not see changes, like the shaders
+    // of a material
+    pub fn invalidate_recordings(&mut self) {
+        self.recordings.fill(None);
+    }
+
+    // Material parameters, skin poses and morph blends changed since the
+    // image was last drawn. Every enabled object is updated, culled ones too,
+    // so a reused recording reads the current values.
+    fn upload_object_data(
+        game_objects: &HashMap<u32, Rc<RefCell<GameObject>>>,
+        image_index: usize,
+    ) {
+        for go in game_objects.values() {
+            let go = go.borrow();
+            if !go.enabled {
+                continue;
+            }
+            if let Some(material) = &go.material {
+                if let Err(e) = material.upload_params(image_index) {
+                    eprintln!("Material parameters: {}", e);
+                }
+            }
+            if let Some(skin) = &go.skin {
+                if let Err(e) = skin.upload(image_index) {
+                    eprintln!("Skin joints: {}", e);
+                }
+            }
+            if let (Some(morph), Some(mesh)) = (&go.morph, &go.mesh) {
+                if morph.fits(mesh) {
+                    morph.upload(mesh, image_index);
+                }
+            }
+        }
+    }
+
+    // What the recording of this frame depends on besides the scene, None
+    // when it cannot be reused
+    fn recording_signature(&self) -> Option<u64> {
+        let scene_signature = self.scene_signature?;
+        if !self.reuse_recordings
+            || self.debug_lines.is_some()
+            || self.overlay_lines.is_some()
+            // Their camera is culled against when recording
+            || self.pictures_in_picture.iter().any(|picture| picture.enabled)
+            || !self.render_graph.is_reusable()
+        {
+            return None;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        scene_signature.hash(&mut hasher);
+        self.render_graph.revision().hash(&mut hasher);
+        self.flat_texture_interpolation.to_bits().hash(&mut hasher);
+        // The object colors are recorded in the push constants
+        self.distinct_colors.hash(&mut hasher);
+        self.winding_view.hash(&mut hasher);
+        [
+            self.clip_plane.x,
+            self.clip_plane.y,
+            self.clip_plane.z,
+            self.clip_plane.w,
+        ]
+        .map(f32::to_bits)
+        .hash(&mut hasher);
+        Some(hasher.finish())
+    }
+
+    // Recreates the swapchain when it no longer matches the surface size
+    pub fn handle_resize(&mut self) -> Result<()> {
+        let size = self.window.physical_size();
+        let extent = vk::Extent2D {
+            width: size.width,
+            height: size.height,
+        };
+        if extent.width == 0 || extent.height == 0 || extent == self.swapchain.extent {
+            return Ok(());
+        }
+        self.recreate_swapchain()
+    }
+
+    fn advance_clock(&mut self) {
+        let now = Instant::now();
+        self.delta_time = self
+            .config
+            .fixed_delta_time
+            .unwrap_or((now - self.last_frame_start).as_secs_f32());
+        self.last_frame_start = now;
+        self.time += self.delta_time;
+    }
+
+    // Seconds since the start, the sum of every frame delta time
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn delta_time(&self) -> f32 {
+        self.delta_time
+    }
+
     pub fn handle_draw_request(
         &mut self,
     ) -> Result<Option<(u32, vk::Semaphore, vk::Semaphore, vk::Fence)>> {
         self.frame_count += 1;
+        self.advance_clock();
 
-        let result = self.swapchain.next_image();
-        Ok(Some(result?))
+        let acquire_start = Instant::now();
+        let result = self.swapchain.next_image()?;
+        FrameBreakdown::add(&mut self.frame_breakdown.acquire, acquire_start.elapsed());
+        // The last frame drawn to the image is done, its uniforms are no longer read
+        self.uniform_rings[result.0 as usize].reset();
+        // Every image was drawn again since, without what was retired
+        let (frame_count, image_count) = (self.frame_count, self.swapchain.image_count as u32);
+        self.retired
+            .retain(|(retired_frame, _)| frame_count < retired_frame + image_count);
+        Ok(Some(result))
         // match result {
         //     Ok(e) => Ok(Some(e)),
         //     Err(e) => {
@@ -222,27 +740,54 @@ impl Renderer {
         rendering_finished: vk::Semaphore,
         may_begin_drawing: vk::Fence,
     ) -> Result<()> {
-        let camera_data = ScopGpuCameraData {
-            projection: *camera.get_projection(),
-            view: *camera.get_view(),
-        };
+        let now = Instant::now();
+        let camera_data = self.camera_data(camera, self.render_extent(), self.delta_time);
+        self.last_image_index = image_index;
 
-        let camera_buffer = &mut self.camera_buffers[image_index as usize];
-        camera_buffer.map(vk::WHOLE_SIZE, 0)?;
-        camera_buffer.write_to_buffer(&[camera_data], 0);
-        camera_buffer.flush(vk::WHOLE_SIZE, 0)?;
-        camera_buffer.unmap();
+        // Uploads recorded since the last frame
+        self.staging_ring.get_mut().flush()?;
+        Self::upload_object_data(game_objects, image_index as usize);
 
-        let command_pool = &self.graphic_command_pools[image_index as usize];
-        let command_buffer = command_pool.get_command_buffer(0);
+        self.uniform_rings[image_index as usize].write_reserved(&camera_data);
 
-        self.main_device.begin_command_buffer(command_buffer)?;
-        self.defaut_render_pass.begin(command_buffer, image_index);
+        let command_buffer = self.graphic_command_pools[image_index as usize].get_command_buffer(0);
+        let signature = self.recording_signature();
+        // The scene signature is given again for each frame
+        self.scene_signature = None;
 
-        self.draw_game_objects(game_objects, command_buffer, image_index);
+        match self.recordings[image_index as usize] {
+            Some((recorded, stats)) if Some(recorded) == signature => {
+                // Only the uniforms changed, they were written above
+                self.stats = stats;
+                self.stats.fragment_invocations = self.read_fragment_invocations(image_index);
+                self.stats.reused_recording = true;
+            }
+            _ => {
+                self.recordings[image_index as usize] = None;
+                self.main_device.begin_command_buffer(command_buffer)?;
 
-        self.defaut_render_pass.end(command_buffer);
-        self.main_device.end_command_buffer(command_buffer)?;
+                // Passes get the renderer itself, the graph is out of it meanwhile
+                let mut render_graph = std::mem::take(&mut self.render_graph);
+                let recorded = render_graph.record(
+                    self,
+                    &FrameContext {
+                        camera,
+                        game_objects,
+                        command_buffer,
+                        image_index,
+                    },
+                );
+                self.render_graph = render_graph;
+                recorded?;
+
+                self.main_device.end_command_buffer(command_buffer)?;
+                self.recordings[image_index as usize] =
+                    signature.map(|signature| (signature, self.stats));
+            }
+        }
+        let command_pool = &self.graphic_command_pools[image_index as usize];
+        let submit_start = Instant::now();
+        FrameBreakdown::add(&mut self.frame_breakdown.record, submit_start - now);
         command_pool.submit(
             &[command_buffer],
             &[image_available],
@@ -251,11 +796,17 @@ impl Renderer {
             may_begin_drawing,
         )?;
 
+        let present_start = Instant::now();
+        FrameBreakdown::add(
+            &mut self.frame_breakdown.submit,
+            present_start - submit_start,
+        );
         let result = self.swapchain.queue_present(
             command_pool.get_queue_family().queues[0],
             image_index,
             &[rendering_finished],
         );
+        FrameBreakdown::add(&mut self.frame_breakdown.present, present_start.elapsed());
         result
         // match result {
         //     Ok(()) => Ok(()),
@@ -270,82 +821,681 @@ impl Renderer {
         // }
     }
 
+    // Game objects, the background around them then debug lines, in the
+    // swapchain render pass
+    fn record_scene(&mut self, frame: &FrameContext) -> Result<()> {
+        let FrameContext {
+            command_buffer,
+            image_index,
+            ..
+        } = *frame;
+
+        let fragment_invocations = self.read_fragment_invocations(image_index);
+
+        if let Some(query_pool) = self.statistics_query_pool {
+            unsafe {
+                self.main_device.logical_device.cmd_reset_query_pool(
+                    command_buffer,
+                    query_pool,
+                    image_index,
+                    1,
+                );
+            }
+        }
+        match &self.scaled {
+            Some(scaled) => scaled.begin(command_buffer, image_index),
+            None => self.defaut_render_pass.begin(command_buffer, image_index),
+        }
+
+        if let Some(query_pool) = self.statistics_query_pool {
+            unsafe {
+                self.main_device.logical_device.cmd_begin_query(
+                    command_buffer,
+                    query_pool,
+                    image_index,
+                    vk::QueryControlFlags::empty(),
+                );
+            }
+        }
+        self.stats = self.draw_game_objects(
+            &frame.camera.get_frustum(),
+            frame.game_objects,
+            DrawTarget {
+                render_pass: self.scene_render_pass(),
+                command_buffer,
+                global_descriptor_set: self.global_descriptor_sets[image_index as usize],
+                image_index,
+                objects: None,
+            },
+        );
+        if let Some(query_pool) = self.statistics_query_pool {
+            unsafe {
+                self.main_device.logical_device.cmd_end_query(
+                    command_buffer,
+                    query_pool,
+                    image_index,
+                );
+            }
+            self.statistics_pending[image_index as usize] = true;
+        }
+        self.stats.fragment_invocations = fragment_invocations;
+        let extent = self.render_extent();
+        self.stats.pixels = extent.width as u64 * extent.height as u64;
+        if let Some(background_pass) = &self.background_pass {
+            if !matches!(self.background, Background::Solid(_)) {
+                background_pass.draw(
+                    &self.background,
+                    self.defaut_render_pass.clear_depth,
+                    self.global_descriptor_sets[image_index as usize],
+                    command_buffer,
+                );
+            }
+        }
+        if let Some(lines) = &mut self.debug_lines {
+            lines.draw(
+                self.global_descriptor_sets[image_index as usize],
+                command_buffer,
+                image_index,
+            )?;
+        }
+        self.record_pictures_in_picture(frame)?;
+        if let Some(lines) = &mut self.overlay_lines {
+            lines.draw(
+                self.global_descriptor_sets[image_index as usize],
+                command_buffer,
+                image_index,
+            )?;
+        }
+
+        self.defaut_render_pass.end(command_buffer);
+        if let Some(scaled) = &mut self.scaled {
+            scaled.record_blit(command_buffer, image_index, &self.swapchain)?;
+        }
+        Ok(())
+    }
+
+    // The swapchain pass, or the pass of the scaled images
+    fn scene_render_pass(&self) -> &ScopRenderPass {
+        self.scaled
+            .as_ref()
+            .map_or(&self.defaut_render_pass, |scaled| &scaled.render_pass)
+    }
+
+    // Each enabled picture in picture in its rectangle, cleared first as it
+    // asks. Recorded in the last subpass of the scene render pass.
+    fn record_pictures_in_picture(&mut self, frame: &FrameContext) -> Result<()> {
+        let FrameContext {
+            command_buffer,
+            image_index,
+            ..
+        } = *frame;
+        if !self
+            .pictures_in_picture
+            .iter()
+            .any(|picture| picture.enabled)
+        {
+            return Ok(());
+        }
+        let extent = self.render_extent();
+        let set_rect = |device: &ash::Device, rect: vk::Rect2D| unsafe {
+            let viewport = vk::Viewport {
+                x: rect.offset.x as f32,
+                y: rect.offset.y as f32,
+                width: rect.extent.width as f32,
+                height: rect.extent.height as f32,
+                min_depth: 0.0,
+                max_depth: 1.0,
+            };
+            device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+            device.cmd_set_scissor(command_buffer, 0, &[rect]);
+        };
+
+        // Out of the renderer while their camera data is written
+        let mut pictures = std::mem::take(&mut self.pictures_in_picture);
+        let mut stats = FrameStats::default();
+        let recorded = pictures
+            .iter_mut()
+            .filter(|picture| picture.enabled)
+            .try_for_each(|picture| {
+                ensure!(
+                    picture.camera.is_reverse_z() == self.config.reverse_z,
+                    "Picture in picture camera depth direction does not match the renderer"
+                );
+                ensure!(
+                    picture.camera.handedness() == self.handedness(),
+                    "Picture in picture camera handedness does not match the renderer"
+                );
+                let rect = picture.pixel_rect(extent);
+                let camera_data = self.camera_data(&picture.camera, rect.extent, self.delta_time);
+                picture.write_camera_data(image_index as usize, &camera_data)?;
+
+                let device = &self.main_device.logical_device;
+                set_rect(device, rect);
+                let render_pass = self.scene_render_pass();
+                let depth = vk::ClearAttachment {
+                    aspect_mask: vk::ImageAspectFlags::DEPTH,
+                    color_attachment: 0,
+                    clear_value: vk::ClearValue {
+                        depth_stencil: vk::ClearDepthStencilValue {
+                            depth: render_pass.clear_depth,
+                            stencil: 0,
+                        },
+                    },
+                };
+                let attachments = match picture.clear {
+                    PipClear::Color(color) => vec![
+                        vk::ClearAttachment {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            color_attachment: 0,
+                            clear_value: vk::ClearValue {
+                                color: vk::ClearColorValue {
+                                    float32: Background::Solid(color)
+                                        .clear_value(self.swapchain.is_srgb()),
+                                },
+                            },
+                        },
+                        depth,
+                    ],
+                    PipClear::Depth => vec![depth],
+                    PipClear::Keep => vec![],
+                };
+                if !attachments.is_empty() {
+                    let clear_rect = vk::ClearRect {
+                        rect,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    };
+                    unsafe {
+                        device.cmd_clear_attachments(command_buffer, &attachments, &[clear_rect])
+                    };
+                }
+
+                let drawn = self.record_draws(
+                    DrawPass::Color,
+                    &picture.camera.get_frustum(),
+                    frame.game_objects,
+                    DrawTarget {
+                        render_pass,
+                        command_buffer,
+                        global_descriptor_set: picture.frame_set(image_index as usize),
+                        image_index,
+                        objects: Some(&picture.objects),
+                    },
+                );
+                stats.objects_drawn += drawn.objects_drawn;
+                stats.objects_culled += drawn.objects_culled;
+                stats.triangles += drawn.triangles;
+                stats.draw_calls += drawn.draw_calls;
+                stats.batches += drawn.batches;
+                Ok(())
+            });
+        self.pictures_in_picture = pictures;
+
+        // The overlay covers the whole target again
+        set_rect(
+            &self.main_device.logical_device,
+            vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            },
+        );
+        self.stats.objects_drawn += stats.objects_drawn;
+        self.stats.objects_culled += stats.objects_culled;
+        self.stats.triangles += stats.triangles;
+        self.stats.draw_calls += stats.draw_calls;
+        self.stats.batches += stats.batches;
+        recorded
+    }
+
+    // Draws outside of the frame loop and waits for the result, meant for
+    // thumbnails and other images rendered once
+    pub fn render_to_target(
+        &mut self,
+        camera: &Camera,
+        game_objects: &HashMap<u32, Rc<RefCell<GameObject>>>,
+        target: &mut ScopRenderTarget,
+    ) -> Result<FrameStats> {
+        ensure!(
+            camera.is_reverse_z() == self.config.reverse_z,
+            "Camera depth direction does not match the renderer"
+        );
+        ensure!(
+            camera.handedness() == self.handedness(),
+            "Camera handedness does not match the renderer"
+        );
+        self.staging_ring.get_mut().flush()?;
+
+        let camera_data = self.camera_data(camera, target.extent(), 0.);
+        target.camera_buffer.map(vk::WHOLE_SIZE, 0)?;
+        target.camera_buffer.write_to_buffer(&[camera_data], 0);
+        target.camera_buffer.flush(vk::WHOLE_SIZE, 0)?;
+        target.camera_buffer.unmap();
+
+        let command_pool = &self.graphic_command_pools[0];
+        let command_buffer = command_pool.begin_single_time_commands()?;
+        self.offscreen_render_pass
+            .begin_framebuffer(command_buffer, &target.framebuffer);
+
+        // Material sets are identical for every image, any index works
+        let stats = self.draw_game_objects(
+            &camera.get_frustum(),
+            game_objects,
+            DrawTarget {
+                render_pass: &self.offscreen_render_pass,
+                command_buffer,
+                global_descriptor_set: target.global_descriptor_set,
+                image_index: 0,
+                objects: None,
+            },
+        );
+
+        self.offscreen_render_pass.end(command_buffer);
+        command_pool.end_single_time_commands(command_buffer)?;
+
+        Ok(stats)
+    }
+
+    // Uniform space for the frame being prepared, valid until it is drawn
+    pub fn frame_uniforms(&mut self, image_index: u32) -> &mut ScopUniformRing {
+        &mut self.uniform_rings[image_index as usize]
+    }
+
+    // Result of the last frame drawn to this image, its fence was waited on
+    fn read_fragment_invocations(&self, image_index: u32) -> Option<u64> {
+        let query_pool = self.statistics_query_pool?;
+        if !self.statistics_pending[image_index as usize] {
+            return None;
+        }
+
+        let mut invocations = [0u64];
+        unsafe {
+            self.main_device.logical_device.get_query_pool_results(
+                query_pool,
+                image_index,
+                1,
+                &mut invocations,
+                vk::QueryResultFlags::TYPE_64,
+            )
+        }
+        .ok()?;
+        Some(invocations[0])
+    }
+
+    pub fn capabilities(&self) -> Result<RendererCapabilities> {
+        let physical_device = self.main_device.physical_device;
+
+        let mut hdr_color_spaces: Vec<vk::ColorSpaceKHR> = self
+            .window
+            .formats(physical_device)?
+            .iter()
+            .map(|format| format.color_space)
+            .filter(|&color_space| color_space != vk::ColorSpaceKHR::SRGB_NONLINEAR)
+            .collect();
+        hdr_color_spaces.sort_by_key(|color_space| color_space.as_raw());
+        hdr_color_spaces.dedup();
+
+        let limits = &self.main_device.properties.limits;
+        let sample_counts =
+            limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+        let max_msaa = [
+            vk::SampleCountFlags::TYPE_64,
+            vk::SampleCountFlags::TYPE_32,
+            vk::SampleCountFlags::TYPE_16,
+            vk::SampleCountFlags::TYPE_8,
+            vk::SampleCountFlags::TYPE_4,
+            vk::SampleCountFlags::TYPE_2,
+        ]
+        .into_iter()
+        .find(|&count| sample_counts.contains(count))
+        .unwrap_or(vk::SampleCountFlags::TYPE_1);
+
+        Ok(RendererCapabilities {
+            surface_format: self.swapchain.surface_format.format,
+            color_space: self.swapchain.surface_format.color_space,
+            image_count: self.swapchain.image_count,
+            present_mode: self.swapchain.present_mode,
+            present_modes: self.window.present_modes(physical_device)?,
+            hdr_color_spaces,
+            max_msaa,
+            async_compute: self.main_device.async_compute_queue_family().is_some(),
+        })
+    }
+
+    pub fn frame_breakdown(&self) -> FrameBreakdown {
+        self.frame_breakdown
+    }
+
+    // The update runs outside of the renderer, the engine reports its duration
+    pub fn add_update_time(&mut self, duration: Duration) {
+        FrameBreakdown::add(&mut self.frame_breakdown.update, duration);
+    }
+
+    // Closest depth wins
+    pub fn depth_compare_op(&self) -> vk::CompareOp {
+        if self.config.reverse_z {
+            vk::CompareOp::GREATER
+        } else {
+            vk::CompareOp::LESS
+        }
+    }
+
+    pub fn handedness(&self) -> Handedness {
+        if self.config.left_handed {
+            Handedness::Left
+        } else {
+            Handedness::Right
+        }
+    }
+
+    // Winding of the triangles facing the camera, as authored in the world
+    pub fn front_face(&self) -> vk::FrontFace {
+        if self.config.left_handed {
+            vk::FrontFace::CLOCKWISE
+        } else {
+            vk::FrontFace::COUNTER_CLOCKWISE
+        }
+    }
+
+    // Creates the variants the configured passes draw `material` with
+    pub fn warm_pipelines(&self, material: &Material) -> Result<()> {
+        let passes: &[DrawPass] = if self.config.depth_prepass {
+            &[
+                DrawPass::Color,
+                DrawPass::DepthPrepass,
+                DrawPass::AfterDepthPrepass,
+            ]
+        } else {
+            &[DrawPass::Color]
+        };
+        for pass in passes {
+            material.pipeline(
+                &self.defaut_render_pass,
+                pass.subpass(&self.defaut_render_pass),
+                pass.render_state(material),
+            )?;
+        }
+        Ok(())
+    }
+
+    // Keeps a resource the frames in flight may still use, like a replaced
+    // mesh, until they are done
+    pub fn retire(&mut self, resource: impl Any) {
+        self.retired.push((self.frame_count, Box::new(resource)));
+    }
+
     pub fn wait_gpu(&self) {
         let _ = unsafe { self.main_device.logical_device.device_wait_idle() };
     }
 
     fn draw_game_objects(
         &self,
+        frustum: &Frustum,
         game_objects: &HashMap<u32, Rc<RefCell<GameObject>>>,
-        command_buffer: vk::CommandBuffer,
-        image_index: u32,
-    ) {
+        draw_target: DrawTarget,
+    ) -> FrameStats {
+        let render_pass = draw_target.render_pass;
+        if render_pass.subpasses.len() == 1 {
+            return self.record_draws(DrawPass::Color, frustum, game_objects, draw_target);
+        }
+
+        // The prepass has no fragment shader to discard clipped fragments, its
+        // subpass stays empty and the color subpass writes the depth itself
+        if self.clip_plane != Vec4::default() {
+            render_pass.next_subpass(draw_target.command_buffer);
+            return self.record_draws(DrawPass::Color, frustum, game_objects, draw_target);
+        }
+
+        let prepass_stats =
+            self.record_draws(DrawPass::DepthPrepass, frustum, game_objects, draw_target);
+        render_pass.next_subpass(draw_target.command_buffer);
+        let mut stats = self.record_draws(
+            DrawPass::AfterDepthPrepass,
+            frustum,
+            game_objects,
+            draw_target,
+        );
+        stats.prepass_draw_calls = prepass_stats.draw_calls;
+        stats
+    }
+
+    // The nodes of these game objects and of their descendants. Parents come
+    // first in the traversal, a child is kept when its parent was.
+    fn keep_subtrees(nodes: Vec<SceneNode>, roots: &[GameObjectId]) -> Vec<SceneNode> {
+        let mut kept = HashSet::<*const RefCell<GameObject>>::new();
+        nodes
+            .into_iter()
+            .filter(|node| {
+                let keep = roots.contains(&node.id)
+                    || node
+                        .game_object
+                        .borrow()
+                        .parent()
+                        .is_some_and(|parent| kept.contains(&Rc::as_ptr(&parent)));
+                if keep {
+                    kept.insert(Rc::as_ptr(&node.game_object));
+                }
+                keep
+            })
+            .collect()
+    }
+
+    fn record_draws(
+        &self,
+        pass: DrawPass,
+        frustum: &Frustum,
+        game_objects: &HashMap<u32, Rc<RefCell<GameObject>>>,
+        draw_target: DrawTarget,
+    ) -> FrameStats {
+        let DrawTarget {
+            render_pass,
+            command_buffer,
+            global_descriptor_set,
+            image_index,
+            objects,
+        } = draw_target;
+        let mut stats = FrameStats::default();
         let mut previous_mesh_ptr: *const Mesh = std::ptr::null();
         let mut previous_material_ptr: *const Material = std::ptr::null();
         let mut previous_material_instance_ptr: *const MaterialInstance = std::ptr::null();
+        let mut previous_skinned = false;
+        let mut previous_vertex_layout = VertexLayout::Interleaved;
+        let mut pipeline: Option<Rc<RendererPipeline>> = None;
 
-        for go in game_objects.values() {
-            let game_object = go.borrow();
+        update_local_matrices(game_objects, &self.jobs);
+        let mut nodes: Vec<SceneNode> = traverse(game_objects).filter(|node| node.active).collect();
+        if let Some(objects) = objects {
+            nodes = Self::keep_subtrees(nodes, objects);
+        }
+        let visible = self.cull(frustum, &nodes);
+        for (node, visible) in nodes.iter().zip(visible) {
+            let game_object = node.game_object.borrow();
 
             if let Some(mesh) = &game_object.mesh {
-                let material_instance = game_object.material.as_ref().unwrap();
+                let model_matrix = node.world_transform;
+                if !visible {
+                    stats.objects_culled += 1;
+                    continue;
+                }
+
+                let Some(material_instance) = &game_object.material else {
+                    continue;
+                };
+                let material = &material_instance.material;
+                // Without a skinned shader the mesh is drawn in its bind pose
+                let skin = game_object
+                    .skin
+                    .as_ref()
+                    .filter(|_| material.is_skinnable());
 
-                if previous_material_ptr != Rc::as_ptr(&material_instance.material) {
-                    previous_material_ptr = Rc::as_ptr(&material_instance.material);
+                if previous_material_ptr != Rc::as_ptr(material)
+                    || previous_skinned != skin.is_some()
+                    || previous_vertex_layout != mesh.vertex_layout
+                {
+                    previous_material_ptr = Rc::as_ptr(material);
+                    previous_material_instance_ptr = std::ptr::null();
+                    previous_skinned = skin.is_some();
+                    previous_vertex_layout = mesh.vertex_layout;
 
-                    material_instance
-                        .material
-                        .pipeline
-                        .bind(command_buffer, vk::PipelineBindPoint::GRAPHICS);
+                    pipeline = material
+                        .pipeline(
+                            render_pass,
+                            pass.subpass(render_pass),
+                            RenderState {
+                                skinned: skin.is_some(),
+                                vertex_layout: mesh.vertex_layout,
+                                cull_mode: if self.winding_view {
+                                    vk::CullModeFlags::NONE
+                                } else {
+                                    material.render_state.cull_mode
+                                },
+                                ..pass.render_state(material)
+                            },
+                        )
+                        .inspect_err(|e| eprintln!("Material pipeline: {}", e))
+                        .ok();
+                    if let Some(pipeline) = &pipeline {
+                        stats.batches += 1;
+                        pipeline.bind(command_buffer, vk::PipelineBindPoint::GRAPHICS);
+                    }
                 }
+                let Some(pipeline) = &pipeline else {
+                    continue;
+                };
 
                 if previous_material_instance_ptr != Rc::as_ptr(material_instance) {
                     previous_material_instance_ptr = Rc::as_ptr(material_instance);
-
-                    material_instance.material.pipeline.bind_descriptor_sets(
+                    pipeline.bind_descriptor_sets(
                         command_buffer,
                         vk::PipelineBindPoint::GRAPHICS,
                         &[
-                            self.global_descriptor_sets[image_index as usize],
-                            material_instance.material_sets[image_index as usize],
-                        ],
+                            &[global_descriptor_set],
+                            material_instance.frame_sets(image_index as usize),
+                        ]
+                        .concat(),
+                    );
+                }
+                if let Some(skin) = skin {
+                    pipeline.bind_descriptor_sets_from(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        material.skin_set_index(),
+                        &[skin.frame_set(image_index as usize)],
                     );
                 }
 
                 let push = SimplePushConstantData {
-                    model_matrix: game_object.transform.mat(),
-                    normal_matrix: game_object.transform.normal_matrix(),
-                    dummy0: 0.0,
-                    dummy1: 0.0,
-                    dummy2: 0.0,
+                    model_matrix: model_matrix.into(),
+                    normal_matrix: model_matrix.normal_matrix().into(),
                     flat_texture_interpolation: self.flat_texture_interpolation,
+                    debug_color: self
+                        .distinct_colors
+                        .map_or(0, |colors| colors.packed(node.id)),
                 };
 
                 unsafe {
                     self.main_device.logical_device.cmd_push_constants(
                         command_buffer,
-                        material_instance.material.pipeline.pipeline_layout,
+                        pipeline.pipeline_layout,
                         ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT,
                         0,
                         crate::utils::any_as_u8_slice(&push),
                     );
                 }
 
-                if previous_mesh_ptr != Rc::as_ptr(mesh) {
-                    previous_mesh_ptr = Rc::as_ptr(mesh);
+                match game_object.morph.as_ref().filter(|morph| morph.fits(mesh)) {
+                    Some(morph) => {
+                        // The next object sharing the mesh binds it again
+                        previous_mesh_ptr = std::ptr::null();
+                        morph.bind(mesh, command_buffer, image_index as usize);
+                    }
+                    None if previous_mesh_ptr != Rc::as_ptr(mesh) => {
+                        previous_mesh_ptr = Rc::as_ptr(mesh);
 
-                    mesh.bind(command_buffer);
+                        mesh.bind(command_buffer);
+                    }
+                    None => (),
                 }
 
                 mesh.draw(command_buffer);
+                stats.objects_drawn += 1;
+                stats.draw_calls += 1;
+                stats.triangles += mesh.triangle_count();
             }
         }
+
+        stats
+    }
+
+    // Whether the mesh bounds of each node are in the frustum, true without a
+    // mesh. Game objects stay on this thread, the jobs get plain copies.
+    fn cull(&self, frustum: &Frustum, nodes: &[SceneNode]) -> Vec<bool> {
+        let bounds: Vec<(Option<BoundingSphere>, Mat4)> = nodes
+            .iter()
+            .map(|node| {
+                let game_object = node.game_object.borrow();
+                let sphere = game_object.mesh.as_ref().map(|mesh| mesh.bounding_sphere);
+                (sphere, node.world_transform)
+            })
+            .collect();
+        let test = |(sphere, world_transform): &(Option<BoundingSphere>, Mat4)| {
+            sphere.is_none_or(|sphere| {
+                let sphere = sphere.transform(world_transform);
+                frustum.intersects_sphere(sphere.center, sphere.radius)
+            })
+        };
+
+        if bounds.len() < PARALLEL_CULLING_MIN_OBJECTS {
+            bounds.iter().map(test).collect()
+        } else {
+            self.jobs.map(&bounds, test)
+        }
+    }
+
+    fn camera_data(
+        &self,
+        camera: &Camera,
+        extent: vk::Extent2D,
+        delta_time: f32,
+    ) -> ScopGpuCameraData {
+        let mut color_flags = 0;
+        if !self.swapchain.is_srgb() {
+            color_flags |= ScopGpuCameraData::COLOR_ENCODE_SRGB;
+        }
+        if self.naive_gamma {
+            color_flags |= ScopGpuCameraData::COLOR_NAIVE_GAMMA;
+        }
+        if self.texel_density_view {
+            color_flags |= ScopGpuCameraData::COLOR_TEXEL_DENSITY;
+        }
+        if self.distinct_colors.is_some() {
+            color_flags |= ScopGpuCameraData::COLOR_DISTINCT;
+        }
+        if self.winding_view {
+            color_flags |= ScopGpuCameraData::COLOR_WINDING;
+        }
+
+        ScopGpuCameraData {
+            projection: (*camera.get_projection()).into(),
+            view: (*camera.get_view()).into(),
+            inverse_view: (*camera.get_inverse_view()).into(),
+            clip_plane: self.clip_plane,
+            viewport_size: Vec2::new(extent.width as f32, extent.height as f32),
+            time: self.time,
+            delta_time,
+            color_flags,
+            exposure: camera.exposure.exp2(),
+            sun_intensity: self.sun_intensity,
+            ambient_intensity: self.ambient_intensity,
+            sun_direction: Vec4::from(self.sun_direction.normalized()),
+        }
     }
 
     fn create_instance(
         entry: &ash::Entry,
         layers: &Vec<CString>,
         extensions: &Vec<CString>,
+        debug_available: bool,
+        validation_features: &[vk::ValidationFeatureEnableEXT],
     ) -> Result<ash::Instance> {
         let app_name = CString::new("Vulkan App")?;
         let engine_name = CString::new("Vulkan Engine")?;
@@ -357,13 +1507,29 @@ impl Renderer {
             .engine_version(vk::make_api_version(0, 1, 0, 0))
             .api_version(vk::API_VERSION_1_3);
 
-        let layer_names = layers.iter().map(|e| e.as_ptr()).collect::<Vec<*const i8>>();
-        let extension_names = extensions.iter().map(|e| e.as_ptr()).collect::<Vec<*const i8>>();
+        let layer_names = layers
+            .iter()
+            .map(|e| e.as_ptr())
+            .collect::<Vec<*const i8>>();
+        let extension_names = extensions
+            .iter()
+            .map(|e| e.as_ptr())
+            .collect::<Vec<*const i8>>();
+
+        let mut messenger_info = RendererDebug::messenger_info();
+        let mut features_info =
+            vk::ValidationFeaturesEXT::builder().enabled_validation_features(validation_features);
 
-        let instance_info = vk::InstanceCreateInfo::builder()
+        let mut instance_info = vk::InstanceCreateInfo::builder()
             .application_info(&app_info)
             .enabled_layer_names(&layer_names)
             .enabled_extension_names(&extension_names);
+        if debug_available {
+            instance_info = instance_info.push_next(&mut messenger_info);
+        }
+        if !validation_features.is_empty() {
+            instance_info = instance_info.push_next(&mut features_info);
+        }
 
         let instance = unsafe { entry.create_instance(&instance_info, None)? };
 
@@ -371,18 +1537,139 @@ impl Renderer {
     }
 }
 
+impl Display for RendererCapabilities {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Surface:    {:?} {:?}, {} images",
+            self.surface_format, self.color_space, self.image_count
+        )?;
+        writeln!(
+            f,
+            "Present:    {:?} (available: {:?})",
+            self.present_mode, self.present_modes
+        )?;
+        if self.hdr_color_spaces.is_empty() {
+            writeln!(f, "HDR:        no")?;
+        } else {
+            writeln!(f, "HDR:        {:?}", self.hdr_color_spaces)?;
+        }
+        writeln!(f, "Max MSAA:   {}x", self.max_msaa.as_raw())?;
+        write!(
+            f,
+            "Compute:    {}",
+            if self.async_compute {
+                "dedicated queue"
+            } else {
+                "graphics queue"
+            }
+        )
+    }
+}
+
+impl Display for FrameStats {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Objects:    {} drawn, {} culled",
+            self.objects_drawn, self.objects_culled
+        )?;
+        writeln!(f, "Triangles:  {}", self.triangles)?;
+        if self.prepass_draw_calls > 0 {
+            writeln!(
+                f,
+                "Draw calls: {} (+{} depth prepass)",
+                self.draw_calls, self.prepass_draw_calls
+            )?;
+        } else {
+            writeln!(f, "Draw calls: {}", self.draw_calls)?;
+        }
+        write!(f, "Batches:    {}", self.batches)?;
+        if self.reused_recording {
+            write!(f, " (reused recording)")?;
+        }
+        if let Some(fragment_invocations) = self.fragment_invocations {
+            // Shaded fragments per pixel, what the depth prepass brings down
+            write!(
+                f,
+                "\nOverdraw:   {:.2}x ({} fragments)",
+                fragment_invocations as f64 / self.pixels.max(1) as f64,
+                fragment_invocations
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl FrameBreakdown {
+    // Weight of the newest sample, roughly the last 20 frames count
+    const SMOOTHING: f32 = 0.05;
+
+    fn add(average: &mut Duration, sample: Duration) {
+        *average = average.mul_f32(1. - Self::SMOOTHING) + sample.mul_f32(Self::SMOOTHING);
+    }
+
+    pub fn total(&self) -> Duration {
+        self.acquire + self.update + self.record + self.submit + self.present
+    }
+}
+
+impl Display for FrameBreakdown {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.;
+        write!(
+            f,
+            "CPU:        {:.2} ms (acquire {:.2}, update {:.2}, record {:.2}, submit {:.2}, present {:.2})",
+            ms(self.total()),
+            ms(self.acquire),
+            ms(self.update),
+            ms(self.record),
+            ms(self.submit),
+            ms(self.present)
+        )
+    }
+}
+
 impl Drop for Renderer {
     fn drop(&mut self) {
         self.wait_gpu();
 
-        self.camera_buffers.iter_mut().for_each(ScopBuffer::cleanup);
+        self.retired.clear();
+        self.pictures_in_picture.clear();
+
+        for mut lines in [self.debug_lines.take(), self.overlay_lines.take()]
+            .into_iter()
+            .flatten()
+        {
+            lines.cleanup();
+        }
+        self.background_pass = None;
+        self.staging_ring.get_mut().cleanup();
+        if let Some(query_pool) = self.statistics_query_pool {
+            unsafe {
+                self.main_device
+                    .logical_device
+                    .destroy_query_pool(query_pool, None)
+            };
+        }
+        self.uniform_rings
+            .iter_mut()
+            .for_each(ScopUniformRing::cleanup);
         self.graphic_command_pools
             .iter_mut()
             .for_each(ScopCommandPool::cleanup);
+        self.main_device
+            .release_descriptor_sets(&self.global_descriptor_sets);
         self.global_descriptor_pool.cleanup();
         self.global_descriptor_set_layout.cleanup(&self.main_device);
+        self.skin_set_layout.cleanup(&self.main_device);
+        if let Some(scaled) = &mut self.scaled {
+            scaled.cleanup();
+        }
         self.swapchain.cleanup();
         self.defaut_render_pass.cleanup();
+        self.offscreen_render_pass.cleanup();
+        self.main_device.leaks.report();
         self.main_device.cleanup();
         if let Some(debug) = &mut self.debug {
             debug.cleanup();
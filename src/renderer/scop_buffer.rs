@@ -1,14 +1,17 @@
-use std::{ffi::c_void, ptr::null_mut, rc::Rc};
+use std::{ffi::c_void, mem::size_of, ptr::null_mut, rc::Rc};
 
 use ash::{util::Align, vk};
 
 use crate::utils::{Context, Result};
 
-use super::{RendererDevice, ScopCommandPool, ScopImage};
+use super::{RendererDevice, ScopCommandPool, ScopImage, ScopStagingRing, TrackedKind};
 
 pub struct ScopBuffer {
     device: Rc<RendererDevice>,
     mapped: *mut c_void,
+    mapped_offset: vk::DeviceSize,
+    // Bytes written since the last `flush_dirty`, relative to the mapping
+    dirty: Option<(vk::DeviceSize, vk::DeviceSize)>,
     pub buffer: vk::Buffer,
     device_memory: vk::DeviceMemory,
     pub buffer_size: vk::DeviceSize,
@@ -37,6 +40,8 @@ impl ScopBuffer {
         Ok(Self {
             device,
             mapped: null_mut(),
+            mapped_offset: 0,
+            dirty: None,
             buffer,
             device_memory,
             buffer_size,
@@ -48,6 +53,72 @@ impl ScopBuffer {
         })
     }
 
+    // Host visible and mapped until `cleanup`. Without HOST_COHERENT in
+    // `memory_property_flags`, writes reach the device with `flush_dirty`.
+    pub fn new_mapped(
+        device: Rc<RendererDevice>,
+        instance_count: usize,
+        instance_size: vk::DeviceSize,
+        usage_flags: vk::BufferUsageFlags,
+        memory_property_flags: vk::MemoryPropertyFlags,
+        min_offset_alignment: vk::DeviceSize,
+    ) -> Result<Self> {
+        let mut buffer = Self::new(
+            device,
+            instance_count,
+            instance_size,
+            usage_flags,
+            memory_property_flags | vk::MemoryPropertyFlags::HOST_VISIBLE,
+            min_offset_alignment,
+        )?;
+        if let Err(e) = buffer.map(vk::WHOLE_SIZE, 0) {
+            buffer.cleanup();
+            return Err(e);
+        }
+        Ok(buffer)
+    }
+
+    // Host visible copy of `data`, to be copied in device local memory
+    pub fn new_staging<T: Copy>(device: Rc<RendererDevice>, data: &[T]) -> Result<Self> {
+        let mut staging_buffer = Self::new(
+            device,
+            data.len(),
+            size_of::<T>() as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            1,
+        )?;
+
+        staging_buffer.map(vk::WHOLE_SIZE, 0)?;
+        staging_buffer.write_to_buffer(data, 0);
+        staging_buffer.unmap();
+
+        Ok(staging_buffer)
+    }
+
+    // The copy is only submitted with the next flush of `staging_ring`
+    pub fn new_device_local<T: Copy>(
+        device: Rc<RendererDevice>,
+        staging_ring: &mut ScopStagingRing,
+        data: &[T],
+        usage_flags: vk::BufferUsageFlags,
+    ) -> Result<Self> {
+        let mut buffer = Self::new(
+            device,
+            data.len(),
+            size_of::<T>() as vk::DeviceSize,
+            usage_flags | vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            1,
+        )?;
+
+        if let Err(e) = staging_ring.upload_buffer(data, buffer.buffer) {
+            buffer.cleanup();
+            return Err(e);
+        }
+        Ok(buffer)
+    }
+
     pub fn is_mapped(&self) -> bool {
         !self.mapped.is_null()
     }
@@ -62,6 +133,7 @@ impl ScopBuffer {
                 vk::MemoryMapFlags::empty(),
             )?
         };
+        self.mapped_offset = offset;
         Ok(())
     }
 
@@ -69,9 +141,60 @@ impl ScopBuffer {
         if self.is_mapped() {
             unsafe { self.device.logical_device.unmap_memory(self.device_memory) };
             self.mapped = null_mut();
+            self.dirty = None;
         }
     }
 
+    // Start of the instance `index`, relative to the buffer
+    pub fn offset_of(&self, index: usize) -> vk::DeviceSize {
+        self.alignment_size * index as vk::DeviceSize
+    }
+
+    // Flushes the bytes written since the last call, widened to the
+    // non-coherent atom size. Nothing to do for coherent memory.
+    pub fn flush_dirty(&mut self) -> Result<()> {
+        let Some((start, end)) = self.dirty.take() else {
+            return Ok(());
+        };
+        if self
+            .memory_property_flags
+            .contains(vk::MemoryPropertyFlags::HOST_COHERENT)
+        {
+            return Ok(());
+        }
+
+        let atom = self.device.properties.limits.non_coherent_atom_size.max(1);
+        let offset = (self.mapped_offset + start) / atom * atom;
+        let end = (self.mapped_offset + end).next_multiple_of(atom);
+        // The allocation may end before the rounded range
+        let size = if end >= self.mapped_offset + self.buffer_size {
+            vk::WHOLE_SIZE
+        } else {
+            end - offset
+        };
+
+        let range = vk::MappedMemoryRange::builder()
+            .memory(self.device_memory)
+            .offset(offset)
+            .size(size);
+
+        unsafe {
+            self.device
+                .logical_device
+                .flush_mapped_memory_ranges(&[*range])?
+        };
+
+        Ok(())
+    }
+
+    fn mark_dirty(&mut self, offset: vk::DeviceSize, size: vk::DeviceSize) {
+        let (start, end) = (offset, offset + size);
+        self.dirty = Some(match self.dirty {
+            Some((dirty_start, dirty_end)) => (dirty_start.min(start), dirty_end.max(end)),
+            None => (start, end),
+        });
+    }
+
     pub fn flush(&self, size: vk::DeviceSize, offset: vk::DeviceSize) -> Result<()> {
         assert!(self.is_mapped());
 
@@ -96,6 +219,45 @@ impl ScopBuffer {
         let mut align =
             unsafe { Align::new(self.mapped.add(offset as usize), self.alignment_size, size) };
         align.copy_from_slice(data);
+        self.mark_dirty(offset, size);
+    }
+
+    // Only the instance `index` is written and later flushed
+    pub fn write_at<T: Copy>(&mut self, index: usize, value: &T) {
+        assert!(index < self.instance_count);
+        assert!(size_of::<T>() as vk::DeviceSize <= self.instance_size);
+
+        let bytes = unsafe { crate::utils::any_as_u8_slice(value) };
+        self.write_bytes(bytes, self.offset_of(index));
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8], offset: vk::DeviceSize) {
+        assert!(self.is_mapped());
+        assert!(offset + bytes.len() as vk::DeviceSize <= self.buffer_size);
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                (self.mapped as *mut u8).add(offset as usize),
+                bytes.len(),
+            )
+        };
+        self.mark_dirty(offset, bytes.len() as vk::DeviceSize);
+    }
+
+    pub fn read_bytes(&self, size: usize, offset: vk::DeviceSize) -> Vec<u8> {
+        assert!(self.is_mapped());
+        assert!(offset + size as vk::DeviceSize <= self.buffer_size);
+
+        let mut bytes = vec![0u8; size];
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                (self.mapped as *const u8).add(offset as usize),
+                bytes.as_mut_ptr(),
+                size,
+            )
+        };
+        bytes
     }
 
     pub fn copy_to_buffer(
@@ -126,13 +288,22 @@ impl ScopBuffer {
         command_pool: &ScopCommandPool,
         dst_image: &ScopImage,
     ) -> Result<()> {
+        let command_buffer = command_pool.begin_single_time_commands()?;
+        self.record_copy_to_image(command_buffer, 0, dst_image);
+        command_pool.end_single_time_commands(command_buffer)
+    }
+
+    pub fn record_copy_to_image(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        offset: vk::DeviceSize,
+        dst_image: &ScopImage,
+    ) {
         assert!(
             dst_image.layout == vk::ImageLayout::TRANSFER_DST_OPTIMAL,
             "Image layout should be TRANSFER_DST_OPTIMAL"
         );
 
-        let command_buffer = command_pool.begin_single_time_commands()?;
-
         let image_subresource = vk::ImageSubresourceLayers::builder()
             .aspect_mask(vk::ImageAspectFlags::COLOR)
             .mip_level(0)
@@ -140,7 +311,7 @@ impl ScopBuffer {
             .layer_count(1);
 
         let region = vk::BufferImageCopy::builder()
-            .buffer_offset(0)
+            .buffer_offset(offset)
             .buffer_row_length(0)
             .buffer_image_height(0)
             .image_offset(*vk::Offset3D::builder().x(0).y(0).z(0))
@@ -162,9 +333,44 @@ impl ScopBuffer {
                 &[region],
             )
         };
+    }
 
-        command_pool.end_single_time_commands(command_buffer)?;
-        Ok(())
+    // Tightly packed rows, from top to bottom
+    pub fn record_copy_from_image(&self, command_buffer: vk::CommandBuffer, src_image: &ScopImage) {
+        assert!(
+            src_image.layout == vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            "Image layout should be TRANSFER_SRC_OPTIMAL"
+        );
+
+        let image_subresource = vk::ImageSubresourceLayers::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .mip_level(0)
+            .base_array_layer(0)
+            .layer_count(1);
+
+        let region = vk::BufferImageCopy::builder()
+            .buffer_offset(0)
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_offset(*vk::Offset3D::builder().x(0).y(0).z(0))
+            .image_extent(
+                *vk::Extent3D::builder()
+                    .width(src_image.width)
+                    .height(src_image.height)
+                    .depth(1),
+            )
+            .image_subresource(*image_subresource)
+            .build();
+
+        unsafe {
+            self.device.logical_device.cmd_copy_image_to_buffer(
+                command_buffer,
+                src_image.image,
+                src_image.layout,
+                self.buffer,
+                &[region],
+            )
+        };
     }
 
     pub fn descriptor_info(
@@ -183,10 +389,9 @@ impl ScopBuffer {
         self.unmap();
         unsafe {
             self.device.logical_device.destroy_buffer(self.buffer, None);
-            self.device
-                .logical_device
-                .free_memory(self.device_memory, None);
         }
+        self.device.leaks.untrack(TrackedKind::Buffer, self.buffer);
+        self.device.free_memory(self.device_memory);
     }
 
     fn get_alignment(
@@ -210,6 +415,7 @@ impl ScopBuffer {
                 .build();
             device.logical_device.create_buffer(&create_info, None)?
         };
+        device.leaks.track(TrackedKind::Buffer, buffer);
 
         let memory_req = device.logical_device.get_buffer_memory_requirements(buffer);
 
@@ -225,7 +431,7 @@ impl ScopBuffer {
                 .allocation_size(memory_req.size)
                 .memory_type_index(buffer_memory_index);
 
-            device.logical_device.allocate_memory(&allocate_info, None)
+            device.allocate_memory(&allocate_info)
         }?;
 
         device
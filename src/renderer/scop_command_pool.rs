@@ -59,18 +59,30 @@ impl ScopCommandPool {
         self.device.get_queue_family(self.queue_family)
     }
 
-    pub fn begin_single_time_commands(&self) -> Result<vk::CommandBuffer> {
+    pub fn allocate_command_buffer(&self) -> Result<vk::CommandBuffer> {
         let alloc_info = vk::CommandBufferAllocateInfo::builder()
             .level(vk::CommandBufferLevel::PRIMARY)
             .command_pool(self.command_pool)
             .command_buffer_count(1)
             .build();
 
-        let command_buffer = unsafe {
+        Ok(unsafe {
             self.device
                 .logical_device
                 .allocate_command_buffers(&alloc_info)?[0]
+        })
+    }
+
+    pub fn free_command_buffer(&self, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            self.device
+                .logical_device
+                .free_command_buffers(self.command_pool, std::slice::from_ref(&command_buffer))
         };
+    }
+
+    pub fn begin_single_time_commands(&self) -> Result<vk::CommandBuffer> {
+        let command_buffer = self.allocate_command_buffer()?;
 
         let begin_info = vk::CommandBufferBeginInfo::builder()
             .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
@@ -106,11 +118,7 @@ impl ScopCommandPool {
         };
 
         unsafe { self.device.logical_device.queue_wait_idle(queue)? };
-        unsafe {
-            self.device
-                .logical_device
-                .free_command_buffers(self.command_pool, std::slice::from_ref(&command_buffer))
-        };
+        self.free_command_buffer(command_buffer);
 
         Ok(())
     }
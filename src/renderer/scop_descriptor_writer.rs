@@ -1,15 +1,22 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use ash::vk;
 
+use crate::{bail, utils::Result};
+
 use super::{RendererDevice, ScopBuffer, ScopDescriptorSetLayout, ScopTexture2D};
 
+// Writes the same layout into one or more descriptor sets, usually one per
+// swapchain image. Bindings are either shared by every set or given one info
+// per set, in the order of the sets. A writer can be kept to change some
+// bindings later, `write` only emits the bindings set since the last one.
 pub struct ScopDescriptorWriter<'a> {
     device: &'a RendererDevice,
-    descriptor_sets: Option<&'a [vk::DescriptorSet]>,
+    descriptor_sets: Vec<vk::DescriptorSet>,
     set_layout: &'a ScopDescriptorSetLayout,
-    buffer_infos: HashMap<u32, vk::DescriptorBufferInfo>,
-    image_infos: HashMap<u32, vk::DescriptorImageInfo>,
+    buffer_infos: HashMap<u32, Vec<vk::DescriptorBufferInfo>>,
+    image_infos: HashMap<u32, Vec<vk::DescriptorImageInfo>>,
+    dirty: BTreeSet<u32>,
 }
 
 impl<'a> ScopDescriptorWriter<'a> {
@@ -20,65 +27,170 @@ impl<'a> ScopDescriptorWriter<'a> {
         Self {
             device,
             set_layout,
-            descriptor_sets: None,
+            descriptor_sets: vec![],
             buffer_infos: HashMap::new(),
             image_infos: HashMap::new(),
+            dirty: BTreeSet::new(),
         }
     }
 
-    pub fn descriptors(&mut self, descriptor_sets: &'a [vk::DescriptorSet]) -> &mut Self {
-        self.descriptor_sets = Some(descriptor_sets);
+    // Other sets get every binding at the next write
+    pub fn descriptors(&mut self, descriptor_sets: &[vk::DescriptorSet]) -> &mut Self {
+        self.descriptor_sets = descriptor_sets.to_vec();
+        self.dirty.extend(self.buffer_infos.keys());
+        self.dirty.extend(self.image_infos.keys());
         self
     }
 
     pub fn set_buffer(&mut self, binding: u32, buffer: &ScopBuffer) -> &mut Self {
-        self.buffer_infos
-            .insert(binding, buffer.descriptor_info(buffer.instance_size, 0));
+        self.set_buffer_info(binding, buffer.descriptor_info(buffer.instance_size, 0))
+    }
+
+    pub fn set_buffer_info(
+        &mut self,
+        binding: u32,
+        buffer_info: vk::DescriptorBufferInfo,
+    ) -> &mut Self {
+        self.buffer_infos.insert(binding, vec![buffer_info]);
+        self.dirty.insert(binding);
+        self
+    }
+
+    // `buffers[i]` goes to the i-th descriptor set
+    pub fn set_buffer_per_frame(&mut self, binding: u32, buffers: &[&ScopBuffer]) -> &mut Self {
+        self.buffer_infos.insert(
+            binding,
+            buffers
+                .iter()
+                .map(|buffer| buffer.descriptor_info(buffer.instance_size, 0))
+                .collect(),
+        );
+        self.dirty.insert(binding);
         self
     }
 
-    pub fn set_texture2d(&mut self, binding: u32, texture2d: &ScopTexture2D) -> &mut Self {
+    pub fn set_texture_all_frames(&mut self, binding: u32, texture2d: &ScopTexture2D) -> &mut Self {
         self.image_infos
-            .insert(binding, texture2d.descriptor_info());
+            .insert(binding, vec![texture2d.descriptor_info()]);
+        self.dirty.insert(binding);
         self
     }
 
-    pub fn write(&self) {
-        if self.descriptor_sets.is_none() {
-            return;
+    // Info of `binding` for the i-th set, a single info is shared by all sets
+    fn info_for<T>(infos: &[T], set_index: usize) -> &T {
+        if infos.len() == 1 {
+            &infos[0]
+        } else {
+            &infos[set_index]
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.descriptor_sets.is_empty() {
+            bail!("Descriptor writer: no descriptor set to write");
+        }
+
+        let set_count = self.descriptor_sets.len();
+        let bindings = self
+            .buffer_infos
+            .iter()
+            .map(|(binding, infos)| (*binding, infos.len(), true))
+            .chain(
+                self.image_infos
+                    .iter()
+                    .map(|(binding, infos)| (*binding, infos.len(), false)),
+            );
+
+        for (binding, info_count, is_buffer) in bindings {
+            let layout_binding = self.set_layout.bindings.get(&binding).ok_or_else(|| {
+                format!(
+                    "Descriptor writer: binding {} does not exist in the layout",
+                    binding
+                )
+            })?;
+
+            let descriptor_type = layout_binding.descriptor_type;
+            if is_buffer && !is_buffer_type(descriptor_type) {
+                bail!(format!(
+                    "Descriptor writer: binding {} is a {:?}, not a buffer",
+                    binding, descriptor_type
+                ));
+            }
+            if !is_buffer && !is_image_type(descriptor_type) {
+                bail!(format!(
+                    "Descriptor writer: binding {} is a {:?}, not a texture",
+                    binding, descriptor_type
+                ));
+            }
+
+            if info_count != 1 && info_count != set_count {
+                bail!(format!(
+                    "Descriptor writer: binding {} has {} descriptors for {} sets",
+                    binding, info_count, set_count
+                ));
+            }
         }
 
-        let descriptor_sets = self.descriptor_sets.unwrap();
+        Ok(())
+    }
+
+    pub fn write(&mut self) -> Result<()> {
+        self.validate()?;
 
         let mut write_descriptor_sets =
-            Vec::with_capacity(self.set_layout.bindings.len() * descriptor_sets.len());
+            Vec::with_capacity(self.dirty.len() * self.descriptor_sets.len());
 
-        for set in descriptor_sets {
-            for (binding, buffer) in &self.buffer_infos {
-                assert!(
-                    (*binding as usize) < self.set_layout.bindings.len(),
-                    "This binding does not exist !"
-                );
-                write_descriptor_sets.push(
-                    *vk::WriteDescriptorSet::builder()
-                        .dst_binding(*binding)
-                        .dst_set(*set)
-                        .descriptor_type(self.set_layout.bindings[binding].descriptor_type)
-                        .buffer_info(std::slice::from_ref(buffer)),
-                );
+        for (set_index, set) in self.descriptor_sets.iter().enumerate() {
+            for binding in &self.dirty {
+                let write = vk::WriteDescriptorSet::builder()
+                    .dst_binding(*binding)
+                    .dst_set(*set)
+                    .descriptor_type(self.set_layout.bindings[binding].descriptor_type);
+                let write = if let Some(infos) = self.buffer_infos.get(binding) {
+                    write.buffer_info(std::slice::from_ref(Self::info_for(infos, set_index)))
+                } else {
+                    let infos = &self.image_infos[binding];
+                    write.image_info(std::slice::from_ref(Self::info_for(infos, set_index)))
+                };
+                write_descriptor_sets.push(*write);
             }
+        }
 
-            for (binding, image) in &self.image_infos {
-                assert!(
-                    (*binding as usize) < self.set_layout.bindings.len(),
-                    "This binding does not exist !"
-                );
-                write_descriptor_sets.push(
-                    *vk::WriteDescriptorSet::builder()
-                        .dst_binding(*binding)
+        unsafe {
+            self.device
+                .logical_device
+                .update_descriptor_sets(write_descriptor_sets.as_slice(), &[])
+        }
+        self.dirty.clear();
+
+        Ok(())
+    }
+
+    // Duplicates `bindings` of `source` into the other sets, the device does
+    // not go through the descriptor infos again
+    pub fn copy_from(&self, source: vk::DescriptorSet, bindings: &[u32]) -> Result<()> {
+        if self.descriptor_sets.is_empty() {
+            bail!("Descriptor writer: no descriptor set to copy to");
+        }
+
+        let mut copy_descriptor_sets =
+            Vec::with_capacity(bindings.len() * self.descriptor_sets.len());
+        for binding in bindings {
+            let layout_binding = self.set_layout.bindings.get(binding).ok_or_else(|| {
+                format!(
+                    "Descriptor writer: binding {} does not exist in the layout",
+                    binding
+                )
+            })?;
+
+            for set in self.descriptor_sets.iter().filter(|set| **set != source) {
+                copy_descriptor_sets.push(
+                    *vk::CopyDescriptorSet::builder()
+                        .src_set(source)
+                        .src_binding(*binding)
                         .dst_set(*set)
-                        .descriptor_type(self.set_layout.bindings[binding].descriptor_type)
-                        .image_info(std::slice::from_ref(image)),
+                        .dst_binding(*binding)
+                        .descriptor_count(layout_binding.descriptor_count),
                 );
             }
         }
@@ -86,7 +198,28 @@ impl<'a> ScopDescriptorWriter<'a> {
         unsafe {
             self.device
                 .logical_device
-                .update_descriptor_sets(write_descriptor_sets.as_slice(), &[])
+                .update_descriptor_sets(&[], copy_descriptor_sets.as_slice())
         }
+
+        Ok(())
     }
 }
+
+fn is_buffer_type(descriptor_type: vk::DescriptorType) -> bool {
+    matches!(
+        descriptor_type,
+        vk::DescriptorType::UNIFORM_BUFFER
+            | vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC
+            | vk::DescriptorType::STORAGE_BUFFER
+            | vk::DescriptorType::STORAGE_BUFFER_DYNAMIC
+    )
+}
+
+fn is_image_type(descriptor_type: vk::DescriptorType) -> bool {
+    matches!(
+        descriptor_type,
+        vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+            | vk::DescriptorType::SAMPLED_IMAGE
+            | vk::DescriptorType::STORAGE_IMAGE
+    )
+}
@@ -4,7 +4,7 @@ use ash::vk;
 
 use crate::{bail, utils::{Context, Result}};
 
-use super::{RendererDevice, ScopCommandPool};
+use super::{RendererDevice, ScopCommandPool, TrackedKind};
 
 pub struct ScopImage {
     device: Rc<RendererDevice>,
@@ -44,6 +44,7 @@ impl ScopImage {
                 .sharing_mode(vk::SharingMode::EXCLUSIVE);
             unsafe { device.logical_device.create_image(&create_info, None)? }
         };
+        device.leaks.track(TrackedKind::Image, image);
 
         let device_memory = {
             let memory_requirements =
@@ -57,11 +58,7 @@ impl ScopImage {
             let allocate_info = vk::MemoryAllocateInfo::builder()
                 .allocation_size(memory_requirements.size)
                 .memory_type_index(memory_type_index);
-            unsafe {
-                device
-                    .logical_device
-                    .allocate_memory(&allocate_info, None)?
-            }
+            device.allocate_memory(&allocate_info)?
         };
 
         unsafe {
@@ -88,53 +85,79 @@ impl ScopImage {
         command_pool: &ScopCommandPool,
         new_layout: vk::ImageLayout,
     ) -> Result<()> {
-        unsafe {
-            let command_buffer = command_pool.begin_single_time_commands()?;
-
-            let subresource_range = vk::ImageSubresourceRange::builder()
-                .aspect_mask(vk::ImageAspectFlags::COLOR)
-                .base_mip_level(0)
-                .level_count(1)
-                .base_array_layer(0)
-                .layer_count(1);
-
-            let (src_access_mask, dst_access_mask, src_stage_mask, dst_stage_mask) =
-                match (self.layout, new_layout) {
-                    (vk::ImageLayout::UNDEFINED, vk::ImageLayout::TRANSFER_DST_OPTIMAL) => (
-                        vk::AccessFlags::empty(),
-                        vk::AccessFlags::TRANSFER_WRITE,
-                        vk::PipelineStageFlags::TOP_OF_PIPE,
-                        vk::PipelineStageFlags::TRANSFER,
-                    ),
-                    (vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::ImageLayout::READ_ONLY_OPTIMAL) => {
-                        (
-                            vk::AccessFlags::TRANSFER_WRITE,
-                            vk::AccessFlags::SHADER_READ,
-                            vk::PipelineStageFlags::TRANSFER,
-                            vk::PipelineStageFlags::FRAGMENT_SHADER,
-                        )
-                    },
-                    (vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL) => {
-                        (
-                            vk::AccessFlags::TRANSFER_WRITE,
-                            vk::AccessFlags::SHADER_READ,
-                            vk::PipelineStageFlags::TRANSFER,
-                            vk::PipelineStageFlags::FRAGMENT_SHADER,
-                        )
-                    }
-                    _ => bail!("Image transition unsupported"),
-                };
-
-            let image_memory_barrier = vk::ImageMemoryBarrier::builder()
-                .old_layout(self.layout)
-                .new_layout(new_layout)
-                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-                .src_access_mask(src_access_mask)
-                .dst_access_mask(dst_access_mask)
-                .image(self.image)
-                .subresource_range(*subresource_range);
+        let command_buffer = command_pool.begin_single_time_commands()?;
+        self.record_change_layout(command_buffer, new_layout)?;
+        command_pool.end_single_time_commands(command_buffer)
+    }
+
+    pub fn record_change_layout(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        new_layout: vk::ImageLayout,
+    ) -> Result<()> {
+        let subresource_range = vk::ImageSubresourceRange::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(1);
+
+        let (src_access_mask, dst_access_mask, src_stage_mask, dst_stage_mask) =
+            match (self.layout, new_layout) {
+                (vk::ImageLayout::UNDEFINED, vk::ImageLayout::TRANSFER_DST_OPTIMAL) => (
+                    vk::AccessFlags::empty(),
+                    vk::AccessFlags::TRANSFER_WRITE,
+                    vk::PipelineStageFlags::TOP_OF_PIPE,
+                    vk::PipelineStageFlags::TRANSFER,
+                ),
+                (vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::ImageLayout::READ_ONLY_OPTIMAL) => (
+                    vk::AccessFlags::TRANSFER_WRITE,
+                    vk::AccessFlags::SHADER_READ,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::FRAGMENT_SHADER,
+                ),
+                (
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                ) => (
+                    vk::AccessFlags::TRANSFER_WRITE,
+                    vk::AccessFlags::SHADER_READ,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::FRAGMENT_SHADER,
+                ),
+                // Read back after a render pass, see `ScopRenderTarget::read_pixels`
+                (
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                ) => (
+                    vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                    vk::AccessFlags::TRANSFER_READ,
+                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                    vk::PipelineStageFlags::TRANSFER,
+                ),
+                (
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                ) => (
+                    vk::AccessFlags::empty(),
+                    vk::AccessFlags::SHADER_READ,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::FRAGMENT_SHADER,
+                ),
+                _ => bail!("Image transition unsupported"),
+            };
+
+        let image_memory_barrier = vk::ImageMemoryBarrier::builder()
+            .old_layout(self.layout)
+            .new_layout(new_layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .src_access_mask(src_access_mask)
+            .dst_access_mask(dst_access_mask)
+            .image(self.image)
+            .subresource_range(*subresource_range);
 
+        unsafe {
             self.device.logical_device.cmd_pipeline_barrier(
                 command_buffer,
                 src_stage_mask,
@@ -143,10 +166,8 @@ impl ScopImage {
                 &[],
                 &[],
                 &[*image_memory_barrier],
-            );
-
-            command_pool.end_single_time_commands(command_buffer)?;
-        }
+            )
+        };
 
         self.layout = new_layout;
         Ok(())
@@ -187,9 +208,8 @@ impl ScopImage {
     pub fn cleanup(&mut self) {
         unsafe {
             self.device.logical_device.destroy_image(self.image, None);
-            self.device
-                .logical_device
-                .free_memory(self.device_memory, None);
         }
+        self.device.leaks.untrack(TrackedKind::Image, self.image);
+        self.device.free_memory(self.device_memory);
     }
 }
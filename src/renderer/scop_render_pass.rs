@@ -2,47 +2,153 @@ use std::rc::Rc;
 
 use ash::vk;
 
-use crate::utils::Result;
+use crate::{ensure, utils::Result};
 
 use super::{RendererDevice, ScopFramebuffer, ScopSwapchain};
 
+// Attachment 0 is the color, attachment 1 the depth
+const COLOR_ATTACHMENT: u32 = 0;
+const DEPTH_ATTACHMENT: u32 = 1;
+
+// One subpass of a render pass, the depth attachment is bound to all of them.
+// On tiled GPUs attachments stay in tile memory between subpasses, unlike
+// between render passes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScopSubpass {
+    pub color: bool,
+    // Read only otherwise, needed to read the depth as an input attachment
+    pub depth_write: bool,
+    // Attachments written by a previous subpass and read with subpassLoad
+    pub input_attachments: Vec<u32>,
+}
+
 pub struct ScopRenderPass {
     device: Rc<RendererDevice>,
     pub render_pass: vk::RenderPass,
+    pub subpasses: Vec<ScopSubpass>,
     pub framebuffers: Vec<ScopFramebuffer>,
+    pub clear_color: [f32; 4],
+    // 0 with a reversed depth buffer
+    pub clear_depth: f32,
+}
+
+impl ScopSubpass {
+    pub fn color() -> Self {
+        Self {
+            color: true,
+            depth_write: true,
+            input_attachments: vec![],
+        }
+    }
+
+    pub fn depth_only() -> Self {
+        Self {
+            color: false,
+            depth_write: true,
+            input_attachments: vec![],
+        }
+    }
+
+    // Only fills the depth buffer, the color subpass then shades each pixel once
+    pub fn depth_prepass() -> Vec<Self> {
+        vec![Self::depth_only(), Self::color()]
+    }
 }
 
 impl ScopRenderPass {
     pub fn new(
         device: Rc<RendererDevice>,
         swapchain: &ScopSwapchain,
+        subpasses: Vec<ScopSubpass>,
     ) -> Result<Self> {
-        let depth_format = device.find_supported_format(
-            vec![
-                vk::Format::D32_SFLOAT,
-                vk::Format::D32_SFLOAT_S8_UINT,
-                vk::Format::D24_UNORM_S8_UINT,
-            ],
-            vk::ImageTiling::OPTIMAL,
-            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+        let render_pass = ScopRenderPass::create_render_pass(
+            &device,
+            swapchain.surface_format.format,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+            &subpasses,
         )?;
 
+        let framebuffers = ScopRenderPass::create_framebuffers(&device, render_pass, swapchain)?;
+
+        Ok(Self {
+            device,
+            render_pass,
+            subpasses,
+            framebuffers,
+            clear_color: [1.0, 1.0, 1.0, 1.0],
+            clear_depth: 1.0,
+        })
+    }
+
+    // Compatible with the swapchain pass so the same pipelines draw in it, the
+    // color attachment ends up ready to be sampled. Framebuffers are owned by
+    // the render targets.
+    pub fn offscreen(
+        device: Rc<RendererDevice>,
+        color_format: vk::Format,
+        subpasses: Vec<ScopSubpass>,
+    ) -> Result<Self> {
+        let render_pass = ScopRenderPass::create_render_pass(
+            &device,
+            color_format,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            &subpasses,
+        )?;
+
+        Ok(Self {
+            device,
+            render_pass,
+            subpasses,
+            framebuffers: vec![],
+            clear_color: [0.85, 0.85, 0.85, 1.0],
+            clear_depth: 1.0,
+        })
+    }
+
+    fn create_render_pass(
+        device: &RendererDevice,
+        color_format: vk::Format,
+        color_final_layout: vk::ImageLayout,
+        subpasses: &[ScopSubpass],
+    ) -> Result<vk::RenderPass> {
+        ensure!(!subpasses.is_empty(), "ScopRenderPass: No subpass");
+        ensure!(
+            subpasses.last().is_some_and(|subpass| subpass.color),
+            "ScopRenderPass: The last subpass must write the color"
+        );
+        for (index, subpass) in subpasses.iter().enumerate() {
+            ensure!(
+                subpass.input_attachments.iter().all(|&attachment| {
+                    (attachment == COLOR_ATTACHMENT && !subpass.color)
+                        || (attachment == DEPTH_ATTACHMENT && !subpass.depth_write)
+                }),
+                "ScopRenderPass: Input attachments must not be written by their subpass"
+            );
+            ensure!(
+                index > 0 || subpass.input_attachments.is_empty(),
+                "ScopRenderPass: The first subpass has nothing to read"
+            );
+        }
+
+        let depth_format = device.find_depth_format()?;
+
         let attachments = [
             vk::AttachmentDescription::builder()
-                .format(swapchain.surface_format.format)
+                .format(color_format)
                 .samples(vk::SampleCountFlags::TYPE_1)
                 .load_op(vk::AttachmentLoadOp::CLEAR)
                 .store_op(vk::AttachmentStoreOp::STORE)
                 .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
                 .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
                 .initial_layout(vk::ImageLayout::UNDEFINED)
-                .final_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+                .final_layout(color_final_layout)
                 .build(),
             vk::AttachmentDescription::builder()
                 .format(depth_format)
                 .samples(vk::SampleCountFlags::TYPE_1)
                 .load_op(vk::AttachmentLoadOp::CLEAR)
-                .store_op(vk::AttachmentStoreOp::DONT_CARE)
+                // Kept for `Renderer::read_depth_at`
+                .store_op(vk::AttachmentStoreOp::STORE)
                 .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
                 .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
                 .initial_layout(vk::ImageLayout::UNDEFINED)
@@ -51,22 +157,63 @@ impl ScopRenderPass {
         ];
 
         let color_attachment_references = [vk::AttachmentReference::builder()
-            .attachment(0)
+            .attachment(COLOR_ATTACHMENT)
             .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
             .build()];
 
-        let depth_attachment_references = vk::AttachmentReference::builder()
-            .attachment(1)
-            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
-            .build();
+        let depth_attachment_references: Vec<vk::AttachmentReference> = subpasses
+            .iter()
+            .map(|subpass| {
+                vk::AttachmentReference::builder()
+                    .attachment(DEPTH_ATTACHMENT)
+                    .layout(if subpass.depth_write {
+                        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+                    } else {
+                        vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+                    })
+                    .build()
+            })
+            .collect();
 
-        let subpasses = [vk::SubpassDescription::builder()
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .color_attachments(&color_attachment_references)
-            .depth_stencil_attachment(&depth_attachment_references)
-            .build()];
+        let input_attachment_references: Vec<Vec<vk::AttachmentReference>> = subpasses
+            .iter()
+            .map(|subpass| {
+                subpass
+                    .input_attachments
+                    .iter()
+                    .map(|&attachment| {
+                        vk::AttachmentReference::builder()
+                            .attachment(attachment)
+                            .layout(if attachment == DEPTH_ATTACHMENT {
+                                vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+                            } else {
+                                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+                            })
+                            .build()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let subpass_descriptions: Vec<vk::SubpassDescription> = subpasses
+            .iter()
+            .enumerate()
+            .map(|(index, subpass)| {
+                let color_attachments: &[vk::AttachmentReference] = if subpass.color {
+                    &color_attachment_references
+                } else {
+                    &[]
+                };
+                vk::SubpassDescription::builder()
+                    .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+                    .color_attachments(color_attachments)
+                    .depth_stencil_attachment(&depth_attachment_references[index])
+                    .input_attachments(&input_attachment_references[index])
+                    .build()
+            })
+            .collect();
 
-        let subpass_dependencies = [vk::SubpassDependency::builder()
+        let mut subpass_dependencies = vec![vk::SubpassDependency::builder()
             .src_subpass(vk::SUBPASS_EXTERNAL)
             .src_stage_mask(
                 vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
@@ -81,10 +228,51 @@ impl ScopRenderPass {
                     | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
             )
             .build()];
+        // Each subpass waits for the attachment writes of the previous one
+        for index in 1..subpasses.len() as u32 {
+            subpass_dependencies.push(
+                vk::SubpassDependency::builder()
+                    .src_subpass(index - 1)
+                    .dst_subpass(index)
+                    .src_stage_mask(
+                        vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                            | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                    )
+                    .src_access_mask(
+                        vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                    )
+                    .dst_stage_mask(
+                        vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                            | vk::PipelineStageFlags::FRAGMENT_SHADER
+                            | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                    )
+                    .dst_access_mask(
+                        vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+                            | vk::AccessFlags::INPUT_ATTACHMENT_READ
+                            | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                    )
+                    .dependency_flags(vk::DependencyFlags::BY_REGION)
+                    .build(),
+            );
+        }
+        if color_final_layout == vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL {
+            subpass_dependencies.push(
+                vk::SubpassDependency::builder()
+                    .src_subpass(subpasses.len() as u32 - 1)
+                    .dst_subpass(vk::SUBPASS_EXTERNAL)
+                    .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                    .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                    .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                    .build(),
+            );
+        }
 
         let render_pass_info = vk::RenderPassCreateInfo::builder()
             .attachments(&attachments)
-            .subpasses(&subpasses)
+            .subpasses(&subpass_descriptions)
             .dependencies(&subpass_dependencies);
 
         let render_pass = unsafe {
@@ -93,13 +281,7 @@ impl ScopRenderPass {
                 .create_render_pass(&render_pass_info, None)
         }?;
 
-        let framebuffers = ScopRenderPass::create_framebuffers(&device, render_pass, swapchain)?;
-
-        Ok(Self {
-            device,
-            render_pass,
-            framebuffers,
-        })
+        Ok(render_pass)
     }
 
     pub fn change_swapchain(&mut self, swapchain: &ScopSwapchain) -> Result<()> {
@@ -111,17 +293,23 @@ impl ScopRenderPass {
     }
 
     pub fn begin(&self, command_buffer: vk::CommandBuffer, image_index: u32) {
-        let framebuffer = &self.framebuffers[image_index as usize];
+        self.begin_framebuffer(command_buffer, &self.framebuffers[image_index as usize]);
+    }
 
+    pub fn begin_framebuffer(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        framebuffer: &ScopFramebuffer,
+    ) {
         let clear_values = [
             vk::ClearValue {
                 color: vk::ClearColorValue {
-                    float32: [1.0, 1.0, 1.0, 1.0],
+                    float32: self.clear_color,
                 },
             },
             vk::ClearValue {
                 depth_stencil: vk::ClearDepthStencilValue {
-                    depth: 1f32,
+                    depth: self.clear_depth,
                     stencil: 0,
                 },
             },
@@ -138,15 +326,40 @@ impl ScopRenderPass {
             .render_area(render_area)
             .clear_values(&clear_values);
 
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: framebuffer.extent.width as f32,
+            height: framebuffer.extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+
         unsafe {
-            self.device.logical_device.cmd_begin_render_pass(
+            let device = &self.device.logical_device;
+            device.cmd_begin_render_pass(
                 command_buffer,
                 &render_pass_begin,
                 vk::SubpassContents::INLINE,
-            )
+            );
+            device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+            device.cmd_set_scissor(command_buffer, 0, &[render_area]);
         };
     }
 
+    // The subpass drawing the color, the last one
+    pub fn main_subpass(&self) -> u32 {
+        self.subpasses.len() as u32 - 1
+    }
+
+    pub fn next_subpass(&self, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            self.device
+                .logical_device
+                .cmd_next_subpass(command_buffer, vk::SubpassContents::INLINE);
+        }
+    }
+
     pub fn end(&self, command_buffer: vk::CommandBuffer) {
         unsafe {
             self.device
@@ -0,0 +1,146 @@
+use std::{mem::size_of, rc::Rc};
+
+use ash::vk;
+
+use crate::{bail, utils::Result};
+
+use super::{
+    Renderer, RendererDevice, ScopBuffer, ScopDescriptorWriter, ScopFramebuffer, ScopGpuCameraData,
+    ScopImage, ScopSwapchain, ScopTexture2D,
+};
+
+// Offscreen color and depth images, drawn with `Renderer::render_to_target`.
+// The color is then bound like any other texture.
+pub struct ScopRenderTarget {
+    device: Rc<RendererDevice>,
+    pub texture: ScopTexture2D,
+    depth_image: ScopImage,
+    depth_image_view: vk::ImageView,
+    pub framebuffer: ScopFramebuffer,
+    // Own camera data, the per-frame ones may still be read by the GPU
+    pub camera_buffer: ScopBuffer,
+    pub global_descriptor_set: vk::DescriptorSet,
+}
+
+impl ScopRenderTarget {
+    pub fn new(renderer: &Renderer, width: u32, height: u32) -> Result<Self> {
+        let device = renderer.main_device.clone();
+        let extent = vk::Extent2D { width, height };
+
+        let mut texture = ScopTexture2D::new_color_attachment(
+            device.clone(),
+            width,
+            height,
+            renderer.swapchain.surface_format.format,
+        )?;
+
+        let (depth_image, depth_image_view) =
+            match unsafe { ScopSwapchain::create_depth_resources(&device, extent) } {
+                Ok(depth) => depth,
+                Err(e) => {
+                    texture.cleanup();
+                    return Err(e);
+                }
+            };
+
+        let framebuffer = ScopFramebuffer::new(
+            device.clone(),
+            texture.image_view,
+            depth_image_view,
+            renderer.offscreen_render_pass.render_pass,
+            extent,
+        )?;
+
+        let camera_buffer = ScopBuffer::new(
+            device.clone(),
+            1,
+            size_of::<ScopGpuCameraData>() as u64,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+            1,
+        )?;
+
+        let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(renderer.global_descriptor_pool.descriptor_pool)
+            .set_layouts(&[renderer.global_descriptor_set_layout.set_layout])
+            .build();
+        let global_descriptor_set = device.allocate_descriptor_sets(&allocate_info)?[0];
+
+        ScopDescriptorWriter::new(&device, &renderer.global_descriptor_set_layout)
+            .descriptors(&[global_descriptor_set])
+            .set_buffer(0, &camera_buffer)
+            .write()?;
+
+        Ok(Self {
+            device,
+            texture,
+            depth_image,
+            depth_image_view,
+            framebuffer,
+            camera_buffer,
+            global_descriptor_set,
+        })
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.framebuffer.extent
+    }
+
+    // RGBA8 pixels of the last render, rows from top to bottom
+    pub fn read_pixels(&mut self, renderer: &Renderer) -> Result<Vec<u8>> {
+        let image = &mut self.texture.image;
+        let swap_red_blue = match image.format {
+            vk::Format::B8G8R8A8_SRGB | vk::Format::B8G8R8A8_UNORM => true,
+            vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM => false,
+            format => bail!(format!("Cannot read back {:?} pixels", format)),
+        };
+        let size = image.width as usize * image.height as usize * 4;
+
+        let mut buffer = ScopBuffer::new(
+            renderer.main_device.clone(),
+            1,
+            size as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            1,
+        )?;
+
+        let command_pool = &renderer.graphic_command_pools[0];
+        let copied = command_pool
+            .begin_single_time_commands()
+            .and_then(|command_buffer| {
+                image
+                    .record_change_layout(command_buffer, vk::ImageLayout::TRANSFER_SRC_OPTIMAL)?;
+                buffer.record_copy_from_image(command_buffer, image);
+                image.record_change_layout(
+                    command_buffer,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                )?;
+                command_pool.end_single_time_commands(command_buffer)
+            })
+            .and_then(|()| buffer.map(vk::WHOLE_SIZE, 0));
+        if let Err(e) = copied {
+            buffer.cleanup();
+            return Err(e);
+        }
+
+        let mut pixels = buffer.read_bytes(size, 0);
+        buffer.cleanup();
+        if swap_red_blue {
+            pixels
+                .chunks_exact_mut(4)
+                .for_each(|pixel| pixel.swap(0, 2));
+        }
+        Ok(pixels)
+    }
+
+    pub fn cleanup(&mut self) {
+        self.framebuffer.cleanup();
+        self.camera_buffer.cleanup();
+        self.device
+            .release_descriptor_sets(&[self.global_descriptor_set]);
+        self.depth_image.cleanup_image_view(self.depth_image_view);
+        self.depth_image.cleanup();
+        self.texture.cleanup();
+    }
+}
@@ -0,0 +1,283 @@
+use std::{collections::VecDeque, mem::size_of_val, rc::Rc, slice};
+
+use ash::vk;
+
+use crate::{bail, utils::Result};
+
+use super::{RendererDevice, ScopBuffer, ScopCommandPool, ScopImage};
+
+struct StagingBatch {
+    command_buffer: vk::CommandBuffer,
+    fence: vk::Fence,
+    // Bytes reserved by the batch, including the space skipped when wrapping
+    size: vk::DeviceSize,
+    end: vk::DeviceSize,
+}
+
+// Persistently mapped staging memory shared by every upload. Copies are
+// recorded in a single command buffer and submitted together by `flush`, the
+// space is reclaimed once the fence of their batch is signaled.
+pub struct ScopStagingRing {
+    device: Rc<RendererDevice>,
+    command_pool: ScopCommandPool,
+    buffer: ScopBuffer,
+    head: vk::DeviceSize,
+    tail: vk::DeviceSize,
+    used: vk::DeviceSize,
+    recording: Option<vk::CommandBuffer>,
+    recording_size: vk::DeviceSize,
+    in_flight: VecDeque<StagingBatch>,
+}
+
+impl ScopStagingRing {
+    // Satisfies optimalBufferCopyOffsetAlignment on common hardware
+    const ALIGNMENT: vk::DeviceSize = 256;
+
+    pub fn new(device: Rc<RendererDevice>, size: vk::DeviceSize) -> Result<Self> {
+        let command_pool = ScopCommandPool::new(
+            device.clone(),
+            device
+                .get_queue_family_with(vk::QueueFlags::GRAPHICS)
+                .unwrap(),
+            vk::CommandPoolCreateFlags::TRANSIENT,
+        )?;
+
+        let mut buffer = ScopBuffer::new(
+            device.clone(),
+            size as usize,
+            1,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            1,
+        )?;
+        buffer.map(vk::WHOLE_SIZE, 0)?;
+
+        Ok(Self {
+            device,
+            command_pool,
+            buffer,
+            head: 0,
+            tail: 0,
+            used: 0,
+            recording: None,
+            recording_size: 0,
+            in_flight: VecDeque::new(),
+        })
+    }
+
+    pub fn upload_buffer<T: Copy>(&mut self, data: &[T], dst_buffer: vk::Buffer) -> Result<()> {
+        let bytes = unsafe { slice::from_raw_parts(data.as_ptr() as *const u8, size_of_val(data)) };
+
+        if bytes.len() as vk::DeviceSize > self.buffer.buffer_size {
+            // Too big to ever fit, fall back on a dedicated staging buffer
+            let mut staging_buffer = ScopBuffer::new_staging(self.device.clone(), bytes)?;
+            let result = staging_buffer.copy_to_buffer(
+                &self.command_pool,
+                dst_buffer,
+                staging_buffer.buffer_size,
+            );
+            staging_buffer.cleanup();
+            return result;
+        }
+
+        let offset = self.write(bytes)?;
+        let command_buffer = self.command_buffer()?;
+        let region = vk::BufferCopy::builder()
+            .src_offset(offset)
+            .size(bytes.len() as vk::DeviceSize);
+        unsafe {
+            self.device.logical_device.cmd_copy_buffer(
+                command_buffer,
+                self.buffer.buffer,
+                dst_buffer,
+                &[*region],
+            )
+        };
+
+        Ok(())
+    }
+
+    // Leaves the image in SHADER_READ_ONLY_OPTIMAL
+    pub fn upload_image(&mut self, data: &[u8], dst_image: &mut ScopImage) -> Result<()> {
+        if data.len() as vk::DeviceSize > self.buffer.buffer_size {
+            let mut staging_buffer = ScopBuffer::new_staging(self.device.clone(), data)?;
+            let result = (|| {
+                dst_image
+                    .change_layout(&self.command_pool, vk::ImageLayout::TRANSFER_DST_OPTIMAL)?;
+                staging_buffer.copy_to_image(&self.command_pool, dst_image)?;
+                dst_image.change_layout(
+                    &self.command_pool,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                )
+            })();
+            staging_buffer.cleanup();
+            return result;
+        }
+
+        let offset = self.write(data)?;
+        let command_buffer = self.command_buffer()?;
+        dst_image.record_change_layout(command_buffer, vk::ImageLayout::TRANSFER_DST_OPTIMAL)?;
+        self.buffer
+            .record_copy_to_image(command_buffer, offset, dst_image);
+        dst_image
+            .record_change_layout(command_buffer, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)?;
+
+        Ok(())
+    }
+
+    // Submits the recorded copies, they complete before any later submission
+    // on the graphics queue reads the uploaded resources
+    pub fn flush(&mut self) -> Result<()> {
+        let Some(command_buffer) = self.recording.take() else {
+            return Ok(());
+        };
+
+        let barrier = vk::MemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(
+                vk::AccessFlags::VERTEX_ATTRIBUTE_READ
+                    | vk::AccessFlags::INDEX_READ
+                    | vk::AccessFlags::UNIFORM_READ
+                    | vk::AccessFlags::SHADER_READ,
+            );
+
+        let fence = unsafe {
+            let device = &self.device.logical_device;
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::VERTEX_INPUT
+                    | vk::PipelineStageFlags::VERTEX_SHADER
+                    | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[*barrier],
+                &[],
+                &[],
+            );
+            device.end_command_buffer(command_buffer)?;
+            device.create_fence(&vk::FenceCreateInfo::default(), None)?
+        };
+
+        self.command_pool
+            .submit(&[command_buffer], &[], &[], &[], fence)?;
+        self.in_flight.push_back(StagingBatch {
+            command_buffer,
+            fence,
+            size: self.recording_size,
+            end: self.head,
+        });
+        self.recording_size = 0;
+
+        Ok(())
+    }
+
+    // Flushes and blocks until every upload is done
+    pub fn wait(&mut self) -> Result<()> {
+        self.flush()?;
+        while !self.in_flight.is_empty() {
+            self.release_oldest(true)?;
+        }
+        Ok(())
+    }
+
+    pub fn cleanup(&mut self) {
+        if let Err(e) = self.wait() {
+            eprintln!("Staging uploads failed: {}", e);
+        }
+        if let Some(command_buffer) = self.recording.take() {
+            self.command_pool.free_command_buffer(command_buffer);
+        }
+        self.buffer.cleanup();
+        self.command_pool.cleanup();
+    }
+
+    fn command_buffer(&mut self) -> Result<vk::CommandBuffer> {
+        if let Some(command_buffer) = self.recording {
+            return Ok(command_buffer);
+        }
+
+        let command_buffer = self.command_pool.allocate_command_buffer()?;
+        let begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        unsafe {
+            self.device
+                .logical_device
+                .begin_command_buffer(command_buffer, &begin_info)?
+        };
+
+        self.recording = Some(command_buffer);
+        Ok(command_buffer)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<vk::DeviceSize> {
+        let size = (bytes.len() as vk::DeviceSize).next_multiple_of(Self::ALIGNMENT);
+
+        let offset = loop {
+            while self.release_oldest(false)? {}
+
+            if let Some(offset) = self.reserve(size) {
+                break offset;
+            }
+            if self.in_flight.is_empty() {
+                // The space is held by the copies not submitted yet
+                self.flush()?;
+            }
+            if !self.release_oldest(true)? {
+                bail!("Staging ring is too small for this upload");
+            }
+        };
+
+        self.buffer.write_bytes(bytes, offset);
+        Ok(offset)
+    }
+
+    fn reserve(&mut self, size: vk::DeviceSize) -> Option<vk::DeviceSize> {
+        let capacity = self.buffer.buffer_size;
+        if self.used == 0 {
+            self.head = 0;
+            self.tail = 0;
+        }
+
+        let (offset, reserved) = if self.used == 0 || self.head > self.tail {
+            if self.head + size <= capacity {
+                (self.head, size)
+            } else if size <= self.tail {
+                (0, capacity - self.head + size)
+            } else {
+                return None;
+            }
+        } else if self.head < self.tail && self.head + size <= self.tail {
+            (self.head, size)
+        } else {
+            return None;
+        };
+
+        self.head = offset + size;
+        self.used += reserved;
+        self.recording_size += reserved;
+        Some(offset)
+    }
+
+    // Returns whether a batch was released
+    fn release_oldest(&mut self, block: bool) -> Result<bool> {
+        let Some(batch) = self.in_flight.front() else {
+            return Ok(false);
+        };
+
+        let device = &self.device.logical_device;
+        unsafe {
+            if block {
+                device.wait_for_fences(&[batch.fence], true, u64::MAX)?;
+            } else if !device.get_fence_status(batch.fence)? {
+                return Ok(false);
+            }
+            device.destroy_fence(batch.fence, None);
+        }
+        self.command_pool.free_command_buffer(batch.command_buffer);
+
+        self.used -= batch.size;
+        self.tail = batch.end;
+        self.in_flight.pop_front();
+        Ok(true)
+    }
+}
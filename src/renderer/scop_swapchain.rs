@@ -3,10 +3,10 @@ use std::rc::Rc;
 
 use ash::{
     extensions,
-    vk::{self, FormatFeatureFlags, QueueFlags},
+    vk::{self, QueueFlags},
 };
 
-use crate::utils::Result;
+use crate::utils::{Context, Result};
 
 use super::{RendererDevice, RendererWindow, ScopImage};
 
@@ -14,15 +14,21 @@ pub struct ScopSwapchain {
     device: Rc<RendererDevice>,
     pub swapchain: vk::SwapchainKHR,
     pub swapchain_loader: extensions::khr::Swapchain,
+    pub images: Vec<vk::Image>,
     pub image_views: Vec<vk::ImageView>,
     pub extent: vk::Extent2D,
     pub image_count: usize,
     pub depth_image: ScopImage,
     pub depth_image_view: vk::ImageView,
     pub surface_format: vk::SurfaceFormatKHR,
+    pub present_mode: vk::PresentModeKHR,
+    // The images can be the destination of a transfer, see `ScaledFrames`
+    pub blit_target: bool,
     image_available: Vec<vk::Semaphore>,
     rendering_finished: Vec<vk::Semaphore>,
     may_begin_drawing: Vec<vk::Fence>,
+    // Fence of the frame that last drew to each image, null before its first use
+    image_fences: Vec<vk::Fence>,
     current_image: usize,
 }
 
@@ -37,15 +43,48 @@ impl ScopSwapchain {
 
         let capabilities = window.capabilities(device.physical_device)?;
 
-        let extent = capabilities.current_extent;
+        // u32::MAX means the surface takes the swapchain size (Wayland), use the
+        // window size in physical pixels so hiDPI screens are not upscaled
+        let extent = if capabilities.current_extent.width != u32::MAX {
+            capabilities.current_extent
+        } else {
+            let size = window.physical_size();
+            vk::Extent2D {
+                width: size.width.clamp(
+                    capabilities.min_image_extent.width,
+                    capabilities.max_image_extent.width,
+                ),
+                height: size.height.clamp(
+                    capabilities.min_image_extent.height,
+                    capabilities.max_image_extent.height,
+                ),
+            }
+        };
 
         let surface_formats = window.formats(device.physical_device)?;
-        let surface_format = surface_formats.into_iter().find(|s| s.format == vk::Format::B8G8R8A8_SRGB).expect("B8G8R8A8_SRGB not available");
+        // sRGB formats encode on write, otherwise the fragment shaders encode manually
+        let surface_format = [vk::Format::B8G8R8A8_SRGB, vk::Format::R8G8B8A8_SRGB]
+            .iter()
+            .find_map(|format| surface_formats.iter().find(|s| s.format == *format))
+            .or(surface_formats.first())
+            .copied()
+            .context("No surface format available")?;
 
         let swapchain_loader = extensions::khr::Swapchain::new_from_instance(entry, instance, device.logical_device.handle());
 
         let queue_family_indicies = [graphics_queue_family.index];
 
+        let present_mode = vk::PresentModeKHR::FIFO;
+
+        let blit_target = capabilities
+            .supported_usage_flags
+            .contains(vk::ImageUsageFlags::TRANSFER_DST);
+        let image_usage = if blit_target {
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST
+        } else {
+            vk::ImageUsageFlags::COLOR_ATTACHMENT
+        };
+
         let swapchain = {
             let min_image_count = if capabilities.max_image_count > 0 {
                 3.min(capabilities.max_image_count)
@@ -60,12 +99,12 @@ impl ScopSwapchain {
                 .image_color_space(surface_format.color_space)
                 .image_extent(extent)
                 .image_array_layers(1)
-                .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+                .image_usage(image_usage)
                 .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
                 .queue_family_indices(&queue_family_indicies)
                 .pre_transform(capabilities.current_transform)
                 .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
-                .present_mode(vk::PresentModeKHR::FIFO);
+                .present_mode(present_mode);
 
             unsafe { swapchain_loader.create_swapchain(&swapchain_info, None) }?
         };
@@ -74,7 +113,7 @@ impl ScopSwapchain {
 
         let mut image_views = Vec::with_capacity(images.len());
 
-        for image in images {
+        for &image in &images {
             let image_view = {
                 let subresource_range = vk::ImageSubresourceRange::builder()
                     .aspect_mask(vk::ImageAspectFlags::COLOR)
@@ -109,15 +148,19 @@ impl ScopSwapchain {
             device,
             swapchain,
             swapchain_loader,
+            images,
             image_views,
             extent,
             image_available: vec![],
             rendering_finished: vec![],
             may_begin_drawing: vec![],
             surface_format,
+            present_mode,
+            blit_target,
             image_count,
             depth_image,
             depth_image_view,
+            image_fences: vec![],
             current_image: 0,
         };
 
@@ -126,6 +169,13 @@ impl ScopSwapchain {
         Ok(swapchain)
     }
 
+    pub fn is_srgb(&self) -> bool {
+        matches!(
+            self.surface_format.format,
+            vk::Format::B8G8R8A8_SRGB | vk::Format::R8G8B8A8_SRGB
+        )
+    }
+
     pub fn next_image(&mut self) -> Result<(u32, vk::Semaphore, vk::Semaphore, vk::Fence)> {
         self.current_image = (self.current_image + 1) % self.image_count;
 
@@ -153,6 +203,21 @@ impl ScopSwapchain {
             )?
         };
 
+        // Images are not always acquired in the order of the frames, the
+        // previous frame drawn to this one may come from another slot and
+        // still be running. Its per image resources are reused right after.
+        let image_fence = &mut self.image_fences[image_index as usize];
+        if *image_fence != vk::Fence::null() && *image_fence != *may_begin_drawing {
+            unsafe {
+                self.device.logical_device.wait_for_fences(
+                    slice::from_ref(image_fence),
+                    true,
+                    std::u64::MAX,
+                )?;
+            }
+        }
+        *image_fence = *may_begin_drawing;
+
         Ok((
             image_index,
             *image_available,
@@ -221,25 +286,18 @@ impl ScopSwapchain {
         };
     }
 
-    unsafe fn create_depth_resources(
+    pub(crate) unsafe fn create_depth_resources(
         device: &Rc<RendererDevice>,
         extent: vk::Extent2D,
     ) -> Result<(ScopImage, vk::ImageView)> {
-        let depth_format = device.find_supported_format(
-            vec![
-                vk::Format::D32_SFLOAT,
-                vk::Format::D32_SFLOAT_S8_UINT,
-                vk::Format::D24_UNORM_S8_UINT,
-            ],
-            vk::ImageTiling::OPTIMAL,
-            FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
-        )?;
+        let depth_format = device.find_depth_format()?;
 
         let depth_image = ScopImage::new(
             device.clone(),
             depth_format,
             vk::ImageTiling::OPTIMAL,
-            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            // Copied from by `Renderer::read_depth_at`
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
             extent.width,
             extent.height,
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
@@ -274,6 +332,7 @@ impl ScopSwapchain {
             let fence = unsafe { self.device.logical_device.create_fence(&fence_info, None) }?;
 
             self.may_begin_drawing.push(fence);
+            self.image_fences.push(vk::Fence::null());
         }
 
         Ok(())
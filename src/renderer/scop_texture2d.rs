@@ -2,9 +2,11 @@ use std::rc::Rc;
 
 use ash::vk;
 
+use math::Vec3;
+
 use crate::{ensure, utils::Result};
 
-use super::{RendererDevice, ScopBuffer, ScopCommandPool, ScopImage};
+use super::{RendererDevice, ScopImage, ScopStagingRing};
 
 pub struct ScopTexture2D {
     device: Rc<RendererDevice>,
@@ -16,7 +18,7 @@ pub struct ScopTexture2D {
 impl ScopTexture2D {
     pub fn new(
         device: Rc<RendererDevice>,
-        command_pool: &ScopCommandPool,
+        staging_ring: &mut ScopStagingRing,
         data: &[u8],
         width: u32,
         height: u32,
@@ -32,37 +34,148 @@ impl ScopTexture2D {
 
         ensure!(data.len() == size as usize, "data is not the write size");
 
-        let mut staging_buffer = ScopBuffer::new(
+        let mut image = ScopImage::new(
             device.clone(),
-            size,
-            1,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-            1,
+            image_format,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+            width,
+            height,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
         )?;
 
-        staging_buffer.map(vk::WHOLE_SIZE, 0)?;
-        staging_buffer.write_to_buffer(data, 0);
-        staging_buffer.unmap();
+        if let Err(e) = staging_ring.upload_image(data, &mut image) {
+            image.cleanup();
+            return Err(e);
+        }
+
+        let image_view = image.create_image_view(vk::ImageAspectFlags::COLOR)?;
+
+        let sampler = ScopTexture2D::create_sampler(&device)?;
+
+        Ok(Self {
+            device,
+            image,
+            image_view,
+            sampler,
+        })
+    }
+
+    pub fn checkerboard(
+        device: Rc<RendererDevice>,
+        staging_ring: &mut ScopStagingRing,
+        size: u32,
+        colors: [Vec3; 2],
+    ) -> Result<Self> {
+        let cell = (size / 8).max(1);
+        let pixels = generate_pixels(size, |x, y| colors[((x / cell + y / cell) % 2) as usize]);
+        Self::from_generated(device, staging_ring, size, &pixels)
+    }
+
+    // Red follows u and green follows v, with a line every eighth
+    pub fn uv_grid(
+        device: Rc<RendererDevice>,
+        staging_ring: &mut ScopStagingRing,
+        size: u32,
+    ) -> Result<Self> {
+        let cell = (size / 8).max(1);
+        let pixels = generate_pixels(size, |x, y| {
+            let u = x as f32 / size as f32;
+            let v = y as f32 / size as f32;
+            let shade = if x % cell == 0 || y % cell == 0 {
+                0.1
+            } else if (x / cell + y / cell).is_multiple_of(2) {
+                1.
+            } else {
+                0.75
+            };
+            Vec3::new(u, v, 0.5) * shade
+        });
+        Self::from_generated(device, staging_ring, size, &pixels)
+    }
 
+    // Four octaves of gradient noise, tiles when repeated
+    pub fn perlin_noise(
+        device: Rc<RendererDevice>,
+        staging_ring: &mut ScopStagingRing,
+        size: u32,
+        seed: u32,
+    ) -> Result<Self> {
+        let permutation = noise_permutation(seed);
+        let pixels = generate_pixels(size, |x, y| {
+            let mut value = 0.;
+            let mut amplitude = 0.5;
+            let mut period = 4;
+            for _ in 0..4 {
+                let px = x as f32 * period as f32 / size as f32;
+                let py = y as f32 * period as f32 / size as f32;
+                value += amplitude * gradient_noise(&permutation, px, py, period);
+                amplitude /= 2.;
+                period *= 2;
+            }
+            Vec3::one() * (0.5 + value).clamp(0., 1.)
+        });
+        Self::from_generated(device, staging_ring, size, &pixels)
+    }
+
+    // Drawn into by a render pass, sampled afterwards
+    pub fn new_color_attachment(
+        device: Rc<RendererDevice>,
+        width: u32,
+        height: u32,
+        image_format: vk::Format,
+    ) -> Result<Self> {
         let mut image = ScopImage::new(
             device.clone(),
             image_format,
             vk::ImageTiling::OPTIMAL,
-            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT
+                | vk::ImageUsageFlags::SAMPLED
+                | vk::ImageUsageFlags::TRANSFER_SRC,
             width,
             height,
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
         )?;
+        // The layout left by the render pass, nothing samples it before
+        image.layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
 
-        image.change_layout(command_pool, vk::ImageLayout::TRANSFER_DST_OPTIMAL)?;
-        staging_buffer.copy_to_image(command_pool, &image)?;
-        image.change_layout(command_pool, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)?;
+        let image_view = image.create_image_view(vk::ImageAspectFlags::COLOR)?;
+        let sampler = ScopTexture2D::create_sampler(&device)?;
 
-        staging_buffer.cleanup();
+        Ok(Self {
+            device,
+            image,
+            image_view,
+            sampler,
+        })
+    }
 
-        let image_view = image.create_image_view(vk::ImageAspectFlags::COLOR)?;
+    pub fn descriptor_info(&self) -> vk::DescriptorImageInfo {
+        vk::DescriptorImageInfo::builder()
+            .image_layout(self.image.layout)
+            .image_view(self.image_view)
+            .sampler(self.sampler)
+            .build()
+    }
+
+    fn from_generated(
+        device: Rc<RendererDevice>,
+        staging_ring: &mut ScopStagingRing,
+        size: u32,
+        pixels: &[u8],
+    ) -> Result<Self> {
+        Self::new(
+            device,
+            staging_ring,
+            pixels,
+            size,
+            size,
+            vk::Format::B8G8R8A8_SRGB,
+            32,
+        )
+    }
 
+    fn create_sampler(device: &RendererDevice) -> Result<vk::Sampler> {
         let sampler_create_info = vk::SamplerCreateInfo::builder()
             .mag_filter(vk::Filter::LINEAR)
             .min_filter(vk::Filter::LINEAR)
@@ -78,20 +191,7 @@ impl ScopTexture2D {
                 .create_sampler(&sampler_create_info, None)?
         };
 
-        Ok(Self {
-            device,
-            image,
-            image_view,
-            sampler,
-        })
-    }
-
-    pub fn descriptor_info(&self) -> vk::DescriptorImageInfo {
-        vk::DescriptorImageInfo::builder()
-            .image_layout(self.image.layout)
-            .image_view(self.image_view)
-            .sampler(self.sampler)
-            .build()
+        Ok(sampler)
     }
 
     pub fn cleanup(&mut self) {
@@ -104,3 +204,64 @@ impl ScopTexture2D {
         self.image.cleanup();
     }
 }
+
+// B8G8R8A8 pixels from sRGB colors, rows in texture coordinates order
+fn generate_pixels(size: u32, color: impl Fn(u32, u32) -> Vec3) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(size as usize * size as usize * 4);
+    for y in 0..size {
+        for x in 0..size {
+            let color = color(x, y);
+            pixels.extend_from_slice(&[
+                (color.z.clamp(0., 1.) * 255.) as u8,
+                (color.y.clamp(0., 1.) * 255.) as u8,
+                (color.x.clamp(0., 1.) * 255.) as u8,
+                255,
+            ]);
+        }
+    }
+    pixels
+}
+
+fn noise_permutation(seed: u32) -> [u8; 256] {
+    let mut permutation = [0u8; 256];
+    for (i, p) in permutation.iter_mut().enumerate() {
+        *p = i as u8;
+    }
+
+    // xorshift32, zero would stay zero
+    let mut state = seed.max(1);
+    for i in (1..256).rev() {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        permutation.swap(i, state as usize % (i + 1));
+    }
+    permutation
+}
+
+// Lattice coordinates wrap at `period` so the noise tiles
+fn gradient_noise(permutation: &[u8; 256], x: f32, y: f32, period: u32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (x0, y0) = (x0 as u32 % period, y0 as u32 % period);
+    let (x1, y1) = ((x0 + 1) % period, (y0 + 1) % period);
+
+    let gradient = |ix: u32, iy: u32, dx: f32, dy: f32| {
+        let hash = permutation[((permutation[(ix & 255) as usize] as u32 + iy) & 255) as usize];
+        let angle = hash as f32 / 256. * std::f32::consts::TAU;
+        angle.cos() * dx + angle.sin() * dy
+    };
+    let fade = |t: f32| t * t * t * (t * (t * 6. - 15.) + 10.);
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+    let (u, v) = (fade(fx), fade(fy));
+    lerp(
+        lerp(gradient(x0, y0, fx, fy), gradient(x1, y0, fx - 1., fy), u),
+        lerp(
+            gradient(x0, y1, fx, fy - 1.),
+            gradient(x1, y1, fx - 1., fy - 1.),
+            u,
+        ),
+        v,
+    )
+}
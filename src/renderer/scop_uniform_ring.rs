@@ -0,0 +1,107 @@
+use std::{mem::size_of, rc::Rc};
+
+use ash::vk;
+
+use crate::{bail, utils::Result};
+
+use super::{RendererDevice, ScopBuffer};
+
+#[derive(Copy, Clone, Debug)]
+pub struct UniformAllocation {
+    pub buffer: vk::Buffer,
+    pub offset: vk::DeviceSize,
+    pub range: vk::DeviceSize,
+}
+
+// Persistently mapped uniform memory of one swapchain image. Allocations stay
+// valid until the ring is reset, when the image is acquired again, after
+// `ScopSwapchain::next_image` waited on the fence of the last frame drawn to
+// it. The first `reserved` bytes are kept for the renderer's own data.
+pub struct ScopUniformRing {
+    buffer: ScopBuffer,
+    alignment: vk::DeviceSize,
+    reserved: vk::DeviceSize,
+    head: vk::DeviceSize,
+}
+
+impl UniformAllocation {
+    pub fn descriptor_info(&self) -> vk::DescriptorBufferInfo {
+        vk::DescriptorBufferInfo::builder()
+            .buffer(self.buffer)
+            .offset(self.offset)
+            .range(self.range)
+            .build()
+    }
+}
+
+impl ScopUniformRing {
+    pub fn new(
+        device: Rc<RendererDevice>,
+        size: vk::DeviceSize,
+        reserved: vk::DeviceSize,
+    ) -> Result<Self> {
+        let alignment = device
+            .properties
+            .limits
+            .min_uniform_buffer_offset_alignment
+            .max(1);
+        let reserved = reserved.next_multiple_of(alignment);
+
+        let mut buffer = ScopBuffer::new(
+            device,
+            1,
+            size.max(reserved),
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            1,
+        )?;
+        buffer.map(vk::WHOLE_SIZE, 0)?;
+
+        Ok(Self {
+            buffer,
+            alignment,
+            reserved,
+            head: reserved,
+        })
+    }
+
+    pub fn reset(&mut self) {
+        self.head = self.reserved;
+    }
+
+    pub fn push<T: Copy>(&mut self, data: &T) -> Result<UniformAllocation> {
+        let range = size_of::<T>() as vk::DeviceSize;
+        if self.head + range > self.buffer.buffer_size {
+            bail!("Frame uniform ring is full");
+        }
+
+        let allocation = UniformAllocation {
+            buffer: self.buffer.buffer,
+            offset: self.head,
+            range,
+        };
+        self.buffer
+            .write_bytes(unsafe { crate::utils::any_as_u8_slice(data) }, self.head);
+        self.head = (self.head + range).next_multiple_of(self.alignment);
+
+        Ok(allocation)
+    }
+
+    pub fn write_reserved<T: Copy>(&mut self, data: &T) {
+        assert!(size_of::<T>() as vk::DeviceSize <= self.reserved);
+        self.buffer
+            .write_bytes(unsafe { crate::utils::any_as_u8_slice(data) }, 0);
+    }
+
+    pub fn reserved(&self) -> UniformAllocation {
+        UniformAllocation {
+            buffer: self.buffer.buffer,
+            offset: 0,
+            range: self.reserved,
+        }
+    }
+
+    pub fn cleanup(&mut self) {
+        self.buffer.cleanup();
+    }
+}
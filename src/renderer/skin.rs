@@ -0,0 +1,126 @@
+use std::{cell::RefCell, mem::size_of, rc::Rc};
+
+use ash::vk;
+
+use crate::{
+    engine::skeleton::{Skeleton, MAX_JOINTS},
+    utils::Result,
+};
+
+use super::{GpuMat4, Renderer, RendererDevice, ScopBuffer, ScopDescriptorWriter};
+
+// Joint matrices of one skinned object. Like material parameters, `buffer`
+// has one instance per swapchain image, and a pose change is copied to an
+// instance when its image is drawn.
+pub struct Skin {
+    device: Rc<RendererDevice>,
+    skeleton: Skeleton,
+    sets: Vec<vk::DescriptorSet>,
+    joints: RefCell<SkinJoints>,
+}
+
+struct SkinJoints {
+    buffer: ScopBuffer,
+    stale: Vec<bool>,
+}
+
+impl Skin {
+    pub fn new(renderer: &Renderer, skeleton: Skeleton) -> Result<Self> {
+        let block_size = (size_of::<GpuMat4>() * MAX_JOINTS) as vk::DeviceSize;
+        let image_count = renderer.swapchain.image_count;
+
+        // Flushed after each upload, so coherent memory is not needed
+        let mut buffer = ScopBuffer::new_mapped(
+            renderer.main_device.clone(),
+            image_count,
+            block_size,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+            renderer
+                .main_device
+                .properties
+                .limits
+                .min_uniform_buffer_offset_alignment
+                .max(1),
+        )?;
+
+        let set_layouts = vec![renderer.skin_set_layout.set_layout; image_count];
+        let allocate_info = *vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(renderer.global_descriptor_pool.descriptor_pool)
+            .set_layouts(&set_layouts);
+        let sets = match renderer
+            .main_device
+            .allocate_descriptor_sets(&allocate_info)
+        {
+            Ok(sets) => sets,
+            Err(e) => {
+                buffer.cleanup();
+                return Err(e.into());
+            }
+        };
+
+        for (image_index, set) in sets.iter().enumerate() {
+            let written =
+                ScopDescriptorWriter::new(&renderer.main_device, &renderer.skin_set_layout)
+                    .descriptors(&[*set])
+                    .set_buffer_info(
+                        0,
+                        buffer.descriptor_info(block_size, buffer.offset_of(image_index)),
+                    )
+                    .write();
+            if let Err(e) = written {
+                buffer.cleanup();
+                renderer.main_device.release_descriptor_sets(&sets);
+                return Err(e);
+            }
+        }
+
+        Ok(Self {
+            device: renderer.main_device.clone(),
+            skeleton,
+            sets,
+            joints: RefCell::new(SkinJoints {
+                buffer,
+                stale: vec![true; image_count],
+            }),
+        })
+    }
+
+    pub fn skeleton(&self) -> &Skeleton {
+        &self.skeleton
+    }
+
+    // Every image gets the new pose the next time it is drawn
+    pub fn skeleton_mut(&mut self) -> &mut Skeleton {
+        self.joints.get_mut().stale.fill(true);
+        &mut self.skeleton
+    }
+
+    // Called by the renderer before drawing the skinned object
+    pub fn upload(&self, image_index: usize) -> Result<()> {
+        let mut joints = self.joints.borrow_mut();
+        if !joints.stale[image_index] {
+            return Ok(());
+        }
+
+        let mut matrices = [GpuMat4::default(); MAX_JOINTS];
+        for (gpu_matrix, matrix) in matrices.iter_mut().zip(self.skeleton.joint_matrices()) {
+            *gpu_matrix = matrix.into();
+        }
+        joints.buffer.write_at(image_index, &matrices);
+        joints.buffer.flush_dirty()?;
+        joints.stale[image_index] = false;
+        Ok(())
+    }
+
+    pub fn frame_set(&self, image_index: usize) -> vk::DescriptorSet {
+        self.sets[image_index]
+    }
+}
+
+impl Drop for Skin {
+    fn drop(&mut self) {
+        self.joints.get_mut().buffer.cleanup();
+        self.device.release_descriptor_sets(&self.sets);
+    }
+}
@@ -1,14 +1,18 @@
+use std::ops::ControlFlow;
+
 use ash::{extensions::khr, vk};
+use math::Vec2;
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 use winit::{
-    dpi::LogicalSize,
+    dpi::{LogicalSize, PhysicalSize},
+    event::{ElementState, Event, MouseScrollDelta, WindowEvent},
     event_loop::EventLoop,
-    keyboard::{Key, NamedKey},
-    window::{Window, WindowBuilder},
+    keyboard::{Key, NamedKey, PhysicalKey},
+    window::{Icon, Window, WindowBuilder},
 };
 use winit_input_helper::WinitInputHelper;
 
-use crate::{bail, utils::Result};
+use crate::{bail, engine::input::InputEvent, utils::Result};
 
 pub struct RendererWindow {
     pub event_loop: Option<EventLoop<()>>,
@@ -18,12 +22,14 @@ pub struct RendererWindow {
 }
 
 impl RendererWindow {
-    pub fn create_window() -> Result<(EventLoop<()>, Window)> {
+    pub fn create_window(visible: bool) -> Result<(EventLoop<()>, Window)> {
         let event_loop = EventLoop::new()?;
         let window = WindowBuilder::new()
             .with_title("scop")
+            .with_visible(visible)
             .with_inner_size(LogicalSize::new(1000, 1000))
             .with_resizable(false)
+            .with_window_icon(Some(Self::create_icon()?))
             .build(&event_loop)?;
 
         Ok((event_loop, window))
@@ -75,6 +81,72 @@ impl RendererWindow {
         }
     }
 
+    pub fn present_modes(
+        &self,
+        physical_device: vk::PhysicalDevice,
+    ) -> core::result::Result<Vec<vk::PresentModeKHR>, vk::Result> {
+        unsafe {
+            self.surface_loader
+                .get_physical_device_surface_present_modes(physical_device, self.surface)
+        }
+    }
+
+    // Pixels of the surface, the unit of the swapchain, the viewports and the
+    // cursor position given by WinitInputHelper
+    pub fn physical_size(&self) -> PhysicalSize<u32> {
+        self.window.inner_size()
+    }
+
+    // The window was created with this size, on hiDPI screens the physical
+    // size is larger by `scale_factor`
+    pub fn logical_size(&self) -> LogicalSize<f64> {
+        self.physical_size().to_logical(self.scale_factor())
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.window.scale_factor()
+    }
+
+    // Physical size, for screen space math next to the cursor
+    pub fn viewport_size(&self) -> Vec2 {
+        let size = self.physical_size();
+        Vec2::new(size.width as f32, size.height as f32)
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        let size = self.viewport_size();
+        size.x / size.y
+    }
+
+    pub fn set_title(&self, title: &str) {
+        self.window.set_title(title);
+    }
+
+    // Shaded disc, generated so the icon does not depend on the working directory
+    fn create_icon() -> Result<Icon> {
+        const SIZE: u32 = 32;
+
+        let mut rgba = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let dx = (x as f32 + 0.5) / SIZE as f32 * 2. - 1.;
+                let dy = (y as f32 + 0.5) / SIZE as f32 * 2. - 1.;
+                let distance = (dx * dx + dy * dy).sqrt();
+                let light = (1. - ((dx + 0.4).powi(2) + (dy + 0.4).powi(2)).sqrt() / 2.).max(0.);
+                let alpha = ((1. - distance) * SIZE as f32).clamp(0., 1.);
+
+                rgba.extend_from_slice(&[
+                    (60. + 180. * light) as u8,
+                    (80. + 140. * light) as u8,
+                    (200. + 55. * light) as u8,
+                    (255. * alpha) as u8,
+                ]);
+            }
+        }
+
+        Ok(Icon::from_rgba(rgba, SIZE, SIZE)?)
+    }
+
     pub fn acquire_event_loop(&mut self) -> Result<winit::event_loop::EventLoop<()>> {
         match self.event_loop.take() {
             None => bail!("EventLoop was acquired before"),
@@ -82,14 +154,21 @@ impl RendererWindow {
         }
     }
 
-    pub fn run<F: FnMut(&WinitInputHelper) -> Result<()>>(
+    // `draw_request` also gets the keyboard and mouse events since the
+    // previous call, and breaks to close the window
+    pub fn run<F: FnMut(&WinitInputHelper, &[InputEvent]) -> Result<ControlFlow<()>>>(
         event_loop: EventLoop<()>,
         mut draw_request: F,
     ) -> Result<()> {
         let mut input = WinitInputHelper::new();
+        let mut events: Vec<InputEvent> = vec![];
 
         event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
         event_loop.run(move |event, elwt| {
+            if let Event::WindowEvent { event, .. } = &event {
+                Self::collect_input_event(event, &mut events);
+            }
+
             // Pass every event to the WinitInputHelper.
             // It will return true when the last event has been processed and it is time to run your application logic.
             if input.update(&event) {
@@ -101,19 +180,65 @@ impl RendererWindow {
                     return;
                 }
 
-                match draw_request(&input) {
-                    Ok(_) => (),
+                match draw_request(&input, &events) {
+                    Ok(ControlFlow::Continue(())) => (),
+                    Ok(ControlFlow::Break(())) => elwt.exit(),
                     Err(e) => {
                         eprintln!("{}", e);
                         elwt.exit();
                     }
                 }
+                events.clear();
             }
         })?;
 
         Ok(())
     }
 
+    fn collect_input_event(event: &WindowEvent, events: &mut Vec<InputEvent>) {
+        match event {
+            WindowEvent::KeyboardInput { event, .. } if !event.repeat => {
+                let PhysicalKey::Code(key) = event.physical_key else {
+                    return;
+                };
+                events.push(match event.state {
+                    ElementState::Pressed => InputEvent::KeyDown {
+                        key,
+                        text: match &event.logical_key {
+                            Key::Character(text) => Some(text.to_string()),
+                            _ => None,
+                        },
+                    },
+                    ElementState::Released => InputEvent::KeyUp(key),
+                });
+            }
+            WindowEvent::MouseInput { state, button, .. } => events.push(match state {
+                ElementState::Pressed => InputEvent::MouseDown(*button),
+                ElementState::Released => InputEvent::MouseUp(*button),
+            }),
+            WindowEvent::CursorMoved { position, .. } => {
+                let moved = InputEvent::CursorMoved(position.x as f32, position.y as f32);
+                match events.last_mut() {
+                    Some(last @ InputEvent::CursorMoved(..)) => *last = moved,
+                    _ => events.push(moved),
+                }
+            }
+            WindowEvent::CursorLeft { .. } => events.push(InputEvent::CursorLeft),
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    // Touchpads, about one line every 40 pixels
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 40.,
+                };
+                match events.last_mut() {
+                    Some(InputEvent::Scroll(scrolled)) => *scrolled += lines,
+                    _ => events.push(InputEvent::Scroll(lines)),
+                }
+            }
+            _ => (),
+        }
+    }
+
     pub fn cleanup(&self) {
         unsafe { self.surface_loader.destroy_surface(self.surface, None) };
     }